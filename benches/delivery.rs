@@ -0,0 +1,112 @@
+//! Cost of moving packets from the capture thread to the consumer: draining
+//! a full queue one packet at a time against `next_packets()`, and
+//! capturing with per-packet sends against `with_send_batch()`.
+
+use std::hint::black_box;
+use std::time::{Duration, UNIX_EPOCH};
+
+use async_pcap::{AsyncCapture, IterSource, Packet};
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use tokio::runtime::Runtime;
+
+const PACKETS: usize = 10_000;
+
+fn packets() -> Vec<Packet> {
+    (0..PACKETS)
+        .map(|_| Packet::new(vec![0; 64], UNIX_EPOCH))
+        .collect()
+}
+
+fn source(
+    packets: Vec<Packet>,
+) -> IterSource<impl Iterator<Item = Result<Packet, async_pcap::Error>> + Send> {
+    IterSource::new(packets.into_iter().map(Ok))
+}
+
+async fn drain_one_by_one(capture: AsyncCapture) -> usize {
+    let mut received = 0;
+    while let Some(packet) = capture.next_packet().await {
+        black_box(packet.unwrap());
+        received += 1;
+    }
+    received
+}
+
+async fn drain_many(capture: AsyncCapture, limit: usize) -> usize {
+    let mut buf = Vec::with_capacity(limit);
+    let mut received = 0;
+    loop {
+        let n = capture.next_packets(&mut buf, limit).await;
+        if n == 0 {
+            return received;
+        }
+        received += n;
+        black_box(buf.drain(..).count());
+    }
+}
+
+/// A capture whose packets are all queued, so that only draining is timed.
+fn queued(runtime: &Runtime) -> AsyncCapture {
+    let (capture, handle) = AsyncCapture::new(source(packets()));
+    runtime.block_on(handle.wait_stopped());
+    capture
+}
+
+fn receive(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("receive");
+    group.throughput(Throughput::Elements(PACKETS as u64));
+    group.bench_function("next_packet", |b| {
+        b.iter_batched(
+            || queued(&runtime),
+            |capture| runtime.block_on(drain_one_by_one(capture)),
+            BatchSize::LargeInput,
+        )
+    });
+    for limit in [16, 256] {
+        group.bench_function(format!("next_packets/{limit}"), |b| {
+            b.iter_batched(
+                || queued(&runtime),
+                |capture| runtime.block_on(drain_many(capture, limit)),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn send(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("send");
+    group.throughput(Throughput::Elements(PACKETS as u64));
+    group.bench_function("per_packet", |b| {
+        b.iter_batched(
+            packets,
+            |packets| {
+                let (capture, _handle) = AsyncCapture::new(source(packets));
+                runtime.block_on(drain_many(capture, 256))
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    for max_packets in [16, 256] {
+        group.bench_function(format!("send_batch/{max_packets}"), |b| {
+            b.iter_batched(
+                packets,
+                |packets| {
+                    let (capture, _handle) = AsyncCapture::with_send_batch(
+                        source(packets),
+                        max_packets,
+                        Duration::from_millis(1),
+                    );
+                    runtime.block_on(drain_many(capture, 256))
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, receive, send);
+criterion_main!(benches);
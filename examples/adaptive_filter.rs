@@ -0,0 +1,46 @@
+use std::net::Ipv4Addr;
+
+use async_pcap::{AsyncCaptureBuilder, Device};
+use tokio::runtime::Runtime;
+
+fn main() {
+    let rt = Runtime::new().unwrap();
+
+    rt.block_on(async {
+        // Start with a coarse filter: every TCP SYN towards port 22
+        let device = Device::lookup().unwrap().unwrap();
+        let (async_cap, handle) = AsyncCaptureBuilder::new(device)
+            .filter("tcp dst port 22 and tcp[tcpflags] & tcp-syn != 0")
+            .open()
+            .unwrap();
+
+        let mut suspect = None;
+        while let Some(packet) = async_cap.next_packet().await {
+            let Ok(packet) = packet else {
+                continue;
+            };
+            // Ethernet + IPv4: the source address is at bytes 26..30
+            let data = &packet.data;
+            if data.len() < 34 || data[12..14] != [0x08, 0x00] {
+                continue;
+            }
+            let src = Ipv4Addr::new(data[26], data[27], data[28], data[29]);
+
+            match suspect {
+                None => {
+                    // First SSH attempt: narrow the capture to that host
+                    println!("Narrowing capture to {src}");
+                    let swap = handle.set_filter(format!("host {src}")).await.unwrap();
+                    println!(
+                        "Filter swapped in {:?}, {:?} packets dropped by the kernel meanwhile",
+                        swap.took, swap.kernel_drops
+                    );
+                    suspect = Some(src);
+                }
+                Some(host) => {
+                    println!("Packet from {host}: {} bytes", packet.data.len());
+                }
+            }
+        }
+    });
+}
@@ -1,35 +1,210 @@
+use std::fmt;
+use std::path::Path;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use pcap::{Active, Capture, Error, PacketHeader};
+use pcap::{Active, Capture, Direction, Linktype, PacketHeader, Precision, Stat};
 use tokio::sync::Mutex;
-use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+use tokio::sync::mpsc::{Sender, unbounded_channel};
+use tokio::sync::oneshot;
+#[cfg(feature = "rt-tokio")]
+use tokio::time::error::Elapsed;
+
+use crate::clock::Clock;
+use crate::config::CaptureConfig;
+use crate::drop_monitor::DropReasons;
+use crate::error::Error;
+use crate::filter::FilterSwap;
+use crate::info::CaptureInfo;
+use crate::local::LocalAddresses;
+#[cfg(feature = "rt-tokio")]
+use crate::logging::warn;
+use crate::meta::PacketMeta;
+use crate::nonblock;
+use crate::probe::ReadBehavior;
+use crate::reconnect::LinkEvent;
+use crate::source::{IterSource, PacketSource};
+use crate::stats::CaptureStats;
+use crate::summary::CaptureSummary;
+use crate::throughput::Throughput;
+use crate::timing::{PacketTiming, QueueDwell};
+use crate::worker::{
+    CaptureOptions, Output, PacketOrStop, PacketReceiver, SendBatch, Shared, Worker,
+};
 
 /// Represents a network packet with its header and raw data.
 #[derive(Debug, Clone)]
 pub struct Packet {
-    /// Packet header information provided by pcap
+    /// Packet header information provided by pcap; the clock behind its
+    /// timestamp is given by [`CaptureInfo::timestamp_source`], and its
+    /// precision by [`CaptureInfo::precision`]
     pub header: PacketHeader,
     /// Raw packet data
     pub data: Vec<u8>,
 }
 
+impl Packet {
+    /// Creates a packet holding `data`, captured in full at `timestamp`.
+    ///
+    /// Both the captured and the original length are set to the length of
+    /// `data`. Timestamps before the Unix epoch are clamped to it. Useful
+    /// to synthesize packets for an [`IterSource`](crate::IterSource) or a
+    /// savefile.
+    pub fn new(data: Vec<u8>, timestamp: SystemTime) -> Self {
+        let since_epoch = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let len = u32::try_from(data.len()).unwrap_or(u32::MAX);
+        let header = PacketHeader {
+            ts: libc::timeval {
+                tv_sec: since_epoch.as_secs() as _,
+                tv_usec: since_epoch.subsec_micros() as _,
+            },
+            caplen: len,
+            len,
+        };
+        Self { header, data }
+    }
+
+    /// Creates a packet from a header and its data, as given.
+    ///
+    /// Use this to model truncated captures, where `header.len` exceeds
+    /// the captured `header.caplen`.
+    pub fn with_header(header: PacketHeader, data: Vec<u8>) -> Self {
+        Self { header, data }
+    }
+
+    /// Capture timestamp, as a point in wall-clock time.
+    ///
+    /// Reads `header.ts` as microseconds, the default precision; see
+    /// [`timestamp_with()`](Self::timestamp_with) for packets captured
+    /// with nanosecond precision.
+    pub fn timestamp(&self) -> SystemTime {
+        UNIX_EPOCH + self.since_epoch()
+    }
+
+    /// Capture timestamp, as a point in wall-clock time, for a packet
+    /// captured with `precision`.
+    pub fn timestamp_with(&self, precision: Precision) -> SystemTime {
+        UNIX_EPOCH + self.since_epoch_with(precision)
+    }
+
+    /// Capture timestamp as a duration since the Unix epoch, reading
+    /// `header.ts` as microseconds.
+    pub fn since_epoch(&self) -> Duration {
+        self.since_epoch_with(Precision::Micro)
+    }
+
+    /// Capture timestamp as a duration since the Unix epoch, for a packet
+    /// captured with `precision`.
+    ///
+    /// With [`Precision::Nano`] libpcap stores nanoseconds in `ts.tv_usec`;
+    /// the precision of a capture is given by [`CaptureInfo::precision`].
+    /// Timestamps before the Unix epoch are clamped to it.
+    pub fn since_epoch_with(&self, precision: Precision) -> Duration {
+        header_since_epoch(&self.header, precision)
+    }
+}
+
+/// Timestamp of `header` as a duration since the Unix epoch, for a packet
+/// captured with `precision`.
+pub(crate) fn header_since_epoch(header: &PacketHeader, precision: Precision) -> Duration {
+    let secs = Duration::from_secs(u64::try_from(header.ts.tv_sec).unwrap_or(0));
+    let fraction = u64::try_from(header.ts.tv_usec).unwrap_or(0);
+    secs + match precision {
+        Precision::Micro => Duration::from_micros(fraction),
+        Precision::Nano => Duration::from_nanos(fraction),
+    }
+}
+
+/// Item returned by [`AsyncCapture::next_item()`] and
+/// [`AsyncCapture::next_event()`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum StreamItem {
+    /// A captured packet, or the error that took its place
+    Packet(Result<Packet, Error>),
+    /// Capture statistics, queued every
+    /// [`stats_interval()`](crate::AsyncCaptureBuilder::stats_interval)
+    Stats(Stat),
+    /// The device was lost, with the error that reported it; the capture
+    /// thread is trying to reopen it under the
+    /// [`ReconnectPolicy`](crate::ReconnectPolicy)
+    Disconnected(Error),
+    /// The device was reopened after a [`Disconnected`](Self::Disconnected)
+    Reconnected,
+    /// The kernel dropped this many more packets since the previous
+    /// sample; only queued once [`next_event()`](AsyncCapture::next_event)
+    /// was called, and sampled once a second
+    KernelDropIncrease(u64),
+    /// A filter installed by
+    /// [`set_filter()`](crate::AsyncCaptureHandle::set_filter) applies to
+    /// the packets that follow; only queued once
+    /// [`next_event()`](AsyncCapture::next_event) was called
+    FilterChanged(String),
+    /// The capture was paused by
+    /// [`pause()`](crate::AsyncCaptureHandle::pause); only queued once
+    /// [`next_event()`](AsyncCapture::next_event) was called
+    Paused,
+    /// The capture was resumed after a [`Paused`](Self::Paused)
+    Resumed,
+    /// The capture has ended; this is the last item
+    Stopped(CaptureSummary),
+}
+
 /// An asynchronous wrapper around a `pcap::Capture`.
 ///  
 /// `AsyncCapture` owns the receiver side of a channel that receives
 /// captured packets or a stop signal. It allows async code to
 /// `await` new packets without blocking a thread.
+///
+/// # Threading model
+///
+/// - Each capture is read by exactly one dedicated OS thread, which owns the
+///   [`PacketSource`] and is the only producer on an unbounded channel.
+///   Packets are therefore delivered in the order they were read.
+/// - The receiving end is guarded by a Tokio `Mutex`, so `AsyncCapture` is
+///   `Send + Sync` and can be shared, but every packet is delivered to
+///   exactly one caller of [`next_packet()`](Self::next_packet); see
+///   its documentation for the exact contract.
+/// - [`AsyncCaptureHandle::stop()`] raises a flag with `Release` ordering;
+///   the thread reads it with `Acquire` after each packet (and while
+///   throttled or parked), so everything the stopping task did before the
+///   call is visible to the thread once it observes the stop. It also
+///   interrupts a read of a live capture that is blocked inside libpcap
+///   with `pcap_breakloop`; where that has no effect, the thread notices
+///   the stop when the read returns, at the latest after the read timeout.
+/// - When the thread exits it clears the running flag with `Release` and
+///   then sends a final stop message, so by the time `next_packet()`
+///   returns `None`, [`AsyncCaptureHandle::is_running()`] returns `false`.
+/// - Dropping the `AsyncCapture` closes the channel; the thread exits after
+///   its next read, when the send fails, with
+///   [`EndReason::ReceiverDropped`](crate::EndReason::ReceiverDropped). It
+///   logs this once at debug level and sends no stop message.
+///
+/// # Memory
+///
+/// The channel is unbounded unless
+/// [`AsyncCaptureBuilder::bounded()`](crate::AsyncCaptureBuilder::bounded)
+/// sets a capacity: if the consumer falls behind, queued packets
+/// accumulate without limit. The memory is returned as the backlog drains,
+/// though, and is not held after a burst:
+///
+/// - Each packet owns its bytes, which are freed when the consumer drops
+///   the [`Packet`].
+/// - Tokio stores queued messages in fixed-size blocks that are released
+///   (or recycled, a few at most) as they are consumed, so an empty channel
+///   holds only a small constant amount of memory regardless of the largest
+///   backlog it has seen.
+/// - The capture thread keeps no growing buffers of its own; the batch
+///   buffer of [`next_packets()`](Self::next_packets) is allocated per
+///   call and the caller's `buf` is left for the caller to shrink. With
+///   [`send_batch()`](crate::AsyncCaptureBuilder::send_batch), at most one
+///   batch is held on either side of the channel besides the backlog.
 pub struct AsyncCapture {
-    rx: Mutex<UnboundedReceiver<PacketOrStop>>,
-}
-
-/// Enum used internally to represent either a captured packet
-/// or a stop signal to terminate the capture.
-enum PacketOrStop {
-    /// A captured packet
-    Packet(Result<Packet, Error>),
-    /// Signal that capture has stopped
-    Stop,
+    pub(crate) rx: Mutex<PacketReceiver>,
+    info: CaptureInfo,
+    pub(crate) shared: Arc<Shared>,
 }
 
 /// Handle to control the asynchronous capture.
@@ -38,57 +213,471 @@ enum PacketOrStop {
 /// thread or async task.
 #[derive(Clone)]
 pub struct AsyncCaptureHandle {
-    stop_flag: Arc<AtomicBool>,
+    pub(crate) shared: Arc<Shared>,
 }
 
 impl AsyncCapture {
-    /// Creates a new asynchronous capture from a `pcap::Capture<Active>`.
+    /// Creates a new asynchronous capture from a `pcap::Capture<Active>`
+    /// or any other [`PacketSource`].
     ///
     /// Spawns a background thread that reads packets and sends them
     /// through a channel for async consumption.
     ///
     /// Returns a tuple of `(AsyncCapture, AsyncCaptureHandle)`.
-    pub fn new(mut cap: Capture<Active>) -> (Self, AsyncCaptureHandle) {
+    pub fn new<S: PacketSource>(source: S) -> (Self, AsyncCaptureHandle) {
+        let (capture, handle, _) = Self::with_options(source, CaptureOptions::default());
+        (capture, handle)
+    }
+
+    /// Creates a capture that delivers `packets`, in order, and then ends
+    /// with [`EndReason::SourceEnded`](crate::EndReason::SourceEnded).
+    ///
+    /// Shorthand for an [`IterSource`](crate::IterSource) of Ethernet
+    /// packets, to unit test code that consumes an `AsyncCapture` without
+    /// privileges or a real interface. Use `IterSource` directly to inject
+    /// errors or report another link type.
+    pub fn from_packets<I>(packets: I) -> (Self, AsyncCaptureHandle)
+    where
+        I: IntoIterator<Item = Packet>,
+        I::IntoIter: Send + 'static,
+    {
+        Self::new(IterSource::new(packets.into_iter().map(Ok)))
+    }
+
+    /// Opens a savefile and delivers its packets as fast as they can be
+    /// read.
+    ///
+    /// Classic pcap and pcapng files are supported, as by libpcap. The
+    /// capture ends, and [`next_packet()`](Self::next_packet) returns
+    /// `None`, at the end of the file; the summary then reports
+    /// [`EndReason::SourceEnded`](crate::EndReason::SourceEnded).
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<(Self, AsyncCaptureHandle), Error> {
+        Ok(Self::new(Capture::from_file(path)?))
+    }
+
+    /// Opens a savefile and delivers its packets with their recorded
+    /// inter-packet gaps, divided by `speed`.
+    ///
+    /// A `speed` of 1.0 replays at the original timing, 2.0 twice as fast;
+    /// `f64::INFINITY` or 0 is the same as [`from_file()`](Self::from_file).
+    /// See [`Paced`](crate::Paced) for how the gaps are computed.
+    pub fn from_file_timed<P: AsRef<Path>>(
+        path: P,
+        speed: f64,
+    ) -> Result<(Self, AsyncCaptureHandle), Error> {
+        Ok(Self::new(Capture::from_file(path)?.replay_speed(speed)))
+    }
+
+    /// Creates a new asynchronous capture from `source` whose
+    /// timing-dependent features read the time from `clock`.
+    ///
+    /// Pair it with an [`IterSource`](crate::IterSource) and a
+    /// [`ManualClock`](crate::ManualClock) to test code that depends on
+    /// throughput, batching or other timing without sleeping; see
+    /// [`Clock`] for what the clock covers.
+    pub fn new_with_clock<S: PacketSource, C: Clock>(
+        source: S,
+        clock: C,
+    ) -> (Self, AsyncCaptureHandle) {
+        let options = CaptureOptions {
+            clock: Some(Arc::new(clock)),
+            ..CaptureOptions::default()
+        };
+        let (capture, handle, _) = Self::with_options(source, options);
+        (capture, handle)
+    }
+
+    /// Creates a new asynchronous capture from `source` whose capture
+    /// thread sends packets in batches of up to `max_packets`.
+    ///
+    /// See [`AsyncCaptureBuilder::send_batch()`](crate::AsyncCaptureBuilder::send_batch)
+    /// for how the batches are formed.
+    pub fn with_send_batch<S: PacketSource>(
+        source: S,
+        max_packets: usize,
+        max_delay: Duration,
+    ) -> (Self, AsyncCaptureHandle) {
+        let options = CaptureOptions {
+            send_batch: Some(SendBatch {
+                max_packets,
+                max_delay,
+            }),
+            ..CaptureOptions::default()
+        };
+        let (capture, handle, _) = Self::with_options(source, options);
+        (capture, handle)
+    }
+
+    /// Drives packets from `source` into a channel supplied by the caller.
+    ///
+    /// Use this to feed an existing packet-processing task directly instead
+    /// of receiving through an [`AsyncCapture`]. Only the control handle is
+    /// returned; the end of the capture is signalled by the channel closing,
+    /// i.e. `recv()` returning `None`.
+    ///
+    /// # Notes
+    ///
+    /// - `tx` is bounded, so a full channel applies backpressure: the
+    ///   background thread blocks until there is room, and on a live
+    ///   capture the kernel drops packets once its buffer fills.
+    /// - A stop request is noticed after the pending send completes.
+    pub fn new_into_sender<S: PacketSource>(
+        source: S,
+        tx: Sender<Result<Packet, Error>>,
+    ) -> AsyncCaptureHandle {
+        let options = CaptureOptions::default();
+        let shared = Shared::new(&options);
+        let handle = AsyncCaptureHandle {
+            shared: shared.clone(),
+        };
+        let (worker, _) = Worker::new(source, Output::External(tx), shared, options);
+        handle
+            .shared
+            .set_thread(std::thread::spawn(move || worker.run()));
+        handle
+    }
+
+    /// Creates a new asynchronous capture whose background thread applies
+    /// the given options.
+    ///
+    /// Also returns the channel on which the thread reports a startup
+    /// failure when a startup grace window is configured.
+    pub(crate) fn with_options<S: PacketSource>(
+        source: S,
+        options: CaptureOptions,
+    ) -> (Self, AsyncCaptureHandle, Option<Receiver<Error>>) {
+        let mut info = source.info();
+        if let Some(timestamp_source) = options.timestamp_source {
+            info.timestamp_source = timestamp_source;
+        }
         let (tx, rx) = unbounded_channel::<PacketOrStop>();
-        let stop_flag = Arc::new(AtomicBool::new(false));
+        let shared = Shared::new(&options);
         let handle = AsyncCaptureHandle {
-            stop_flag: stop_flag.clone(),
+            shared: shared.clone(),
         };
 
-        std::thread::spawn(move || {
-            loop {
-                let res = cap.next_packet();
-                let owned = res.map(|packet| Packet {
-                    header: *packet.header,
-                    data: packet.data.to_vec(),
-                });
-                if let Err(e) = tx.send(PacketOrStop::Packet(owned)) {
-                    // Receiver dropped, exit thread
-                    log::warn!("{e}");
-                    break;
-                }
-                if stop_flag.load(Ordering::Relaxed) {
-                    log::warn!("AsyncCapture thread is aborted.");
-                    break;
-                }
-            }
-            // Send a Stop message when capture thread ends
-            let _ = tx.send(PacketOrStop::Stop);
-        });
+        if let Some(gate) = &options.start_gate {
+            gate.register();
+        }
+        let (worker, startup) = Worker::new(
+            source,
+            Output::internal(tx, options.send_batch, handle.shared.clock.clone()),
+            shared,
+            options,
+        );
+        handle
+            .shared
+            .set_thread(std::thread::spawn(move || worker.run()));
+
+        (
+            Self {
+                rx: Mutex::new(PacketReceiver::new(rx, handle.shared.clone())),
+                info,
+                shared: handle.shared.clone(),
+            },
+            handle,
+            startup,
+        )
+    }
+
+    /// Splits the capture into its receiver, info and shared state.
+    pub(crate) fn into_parts(self) -> (PacketReceiver, CaptureInfo, Arc<Shared>) {
+        (self.rx.into_inner(), self.info, self.shared)
+    }
+
+    /// Returns the parameters the capture is actually running with.
+    pub fn info(&self) -> &CaptureInfo {
+        &self.info
+    }
 
-        (Self { rx: Mutex::new(rx) }, handle)
+    /// Returns the snaplen granted by libpcap, which may be lower than
+    /// the one that was requested.
+    pub fn effective_snaplen(&self) -> i32 {
+        self.info.snaplen
+    }
+
+    /// Returns the link-layer header type of the captured packets, which
+    /// tells how to decode [`Packet::data`].
+    pub fn datalink(&self) -> Linktype {
+        self.info.datalink
+    }
+
+    /// Returns the addresses of the capturing interface, recorded when it
+    /// was opened by [`AsyncCaptureBuilder`](crate::AsyncCaptureBuilder).
+    ///
+    /// Empty for captures created from any other source.
+    pub fn local_addresses(&self) -> &LocalAddresses {
+        &self.shared.local
+    }
+
+    /// Returns when the background thread started reading packets, as an
+    /// [`Instant`] and the matching wall-clock time.
+    ///
+    /// # Notes
+    ///
+    /// - `None` until the read loop is entered, which happens after the
+    ///   source was opened and, with a [`StartGate`](crate::StartGate),
+    ///   once the gate is opened.
+    /// - Subtracting the wall-clock time from a packet's timestamp gives
+    ///   its offset into the capture.
+    pub fn started_at(&self) -> Option<(Instant, SystemTime)> {
+        self.shared.started.get().copied()
     }
 
     /// Waits for the next packet asynchronously.
     ///
     /// Returns `Some(Result<Packet, Error>)` if a packet is received,
     /// or `None` if the capture has stopped.
+    ///
+    /// # Concurrent callers
+    ///
+    /// The capture is a single queue, not a broadcast: when several tasks
+    /// call this method on a shared `AsyncCapture`, each packet is returned
+    /// to exactly one of them and no caller sees another caller's packets.
+    ///
+    /// - Callers wait on a fair (FIFO) Tokio `Mutex`, so they take turns in
+    ///   the order they started waiting and each receives the next packet
+    ///   in capture order. Which task gets which packet is therefore
+    ///   deterministic only relative to that order.
+    /// - Once the capture has stopped, every caller, current and future,
+    ///   gets `None`.
+    /// - The method is cancellation safe: dropping the future (e.g. in
+    ///   `tokio::select!`) never loses a packet; it stays queued for the
+    ///   next caller.
+    ///
+    /// [`next_packets()`](Self::next_packets) and
+    /// [`next_packet_timed()`](Self::next_packet_timed) share the same
+    /// queue and follow the same rules. To hand every packet to several
+    /// consumers, use [`broadcast()`](Self::broadcast); to give the queue
+    /// to a single owner, use [`into_split()`](Self::into_split).
     pub async fn next_packet(&self) -> Option<Result<Packet, Error>> {
-        let mut rx = self.rx.lock().await;
-        match rx.recv().await {
-            Some(PacketOrStop::Packet(pkt)) => Some(pkt),
-            Some(PacketOrStop::Stop) | None => None,
+        self.next_packet_timed().await.map(|(pkt, _)| pkt)
+    }
+
+    /// Waits up to `timeout` for the next packet.
+    ///
+    /// Returns `Err(Elapsed)` if neither a packet nor the end of the capture
+    /// came in time, and what [`next_packet()`](Self::next_packet) returns
+    /// otherwise. Since `next_packet()` is cancellation safe, a packet that
+    /// arrives just as the timeout expires is not lost; it is returned by
+    /// the next call.
+    ///
+    /// # Notes
+    ///
+    /// - Time spent waiting for concurrent callers counts towards the
+    ///   timeout.
+    /// - Read timeouts of libpcap are still delivered as errors, unless a
+    ///   [`RetryPolicy`](crate::RetryPolicy) skips them, so with a
+    ///   `timeout` longer than the read timeout they are usually returned
+    ///   before it elapses.
+    /// - Requires the `rt-tokio` feature.
+    #[cfg(feature = "rt-tokio")]
+    pub async fn next_packet_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<Option<Result<Packet, Error>>, Elapsed> {
+        tokio::time::timeout(timeout, self.next_packet()).await
+    }
+
+    /// Waits for the first packet for which `pred` returns `true`.
+    ///
+    /// Packets that do not match, and capture errors, are consumed and
+    /// discarded; they are not left in the queue for later calls. Packets
+    /// after the match stay queued. Returns `None` if the capture stops
+    /// before a packet matches. The capture itself keeps running; call
+    /// [`AsyncCaptureHandle::stop()`] to end it.
+    pub async fn capture_until<F>(&self, pred: F) -> Option<Packet>
+    where
+        F: Fn(&Packet) -> bool,
+    {
+        while let Some(item) = self.next_packet().await {
+            if let Ok(packet) = item
+                && pred(&packet)
+            {
+                return Some(packet);
+            }
+        }
+        None
+    }
+
+    /// Like [`capture_until()`](Self::capture_until), but gives up after
+    /// `timeout`.
+    ///
+    /// Returns `None` on timeout as well; packets discarded before the
+    /// timeout are not restored. Requires the `rt-tokio` feature.
+    #[cfg(feature = "rt-tokio")]
+    pub async fn capture_until_timeout<F>(&self, pred: F, timeout: Duration) -> Option<Packet>
+    where
+        F: Fn(&Packet) -> bool,
+    {
+        tokio::time::timeout(timeout, self.capture_until(pred))
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// Receives up to `n` packets, then stops the capture and waits for its
+    /// background thread to exit.
+    ///
+    /// Returns fewer packets if the capture ends first, e.g. at the end of
+    /// a file. The device is closed by the time this returns, whatever the
+    /// outcome, so it can be reopened right away.
+    ///
+    /// # Notes
+    ///
+    /// - Read timeouts are skipped. Any other error stops the capture and
+    ///   is returned instead of the packets received before it.
+    /// - Closing waits for the pending read, which
+    ///   [`AsyncCaptureHandle::stop()`] interrupts where the platform
+    ///   allows, and otherwise returns at the read timeout.
+    pub async fn collect(self, n: usize) -> Result<Vec<Packet>, Error> {
+        let mut packets = Vec::new();
+        let result = self.collect_into(&mut packets, n).await;
+        self.finish().await;
+        result.map(|()| packets)
+    }
+
+    /// Receives packets for `duration`, then stops the capture and waits
+    /// for its background thread to exit.
+    ///
+    /// Returns early if the capture ends first. Otherwise behaves like
+    /// [`collect()`](Self::collect). Requires the `rt-tokio` feature.
+    #[cfg(feature = "rt-tokio")]
+    pub async fn collect_for(self, duration: Duration) -> Result<Vec<Packet>, Error> {
+        let mut packets = Vec::new();
+        let result = tokio::time::timeout(duration, self.collect_into(&mut packets, usize::MAX))
+            .await
+            .unwrap_or(Ok(()));
+        self.finish().await;
+        result.map(|()| packets)
+    }
+
+    /// Appends packets to `packets` until it holds `n` of them or the
+    /// capture ends, skipping read timeouts.
+    async fn collect_into(&self, packets: &mut Vec<Packet>, n: usize) -> Result<(), Error> {
+        while packets.len() < n {
+            match self.next_packet().await {
+                Some(Ok(packet)) => packets.push(packet),
+                Some(Err(Error::Timeout)) => continue,
+                Some(Err(e)) => return Err(e),
+                None => break,
+            }
         }
+        Ok(())
+    }
+
+    /// Stops the capture, closes the channel and waits for the thread to
+    /// publish its summary.
+    async fn finish(self) {
+        let handle = AsyncCaptureHandle {
+            shared: Arc::clone(&self.shared),
+        };
+        handle.stop();
+        drop(self);
+        handle.wait_stopped().await;
+    }
+
+    /// Waits for packets and appends up to `limit` of them to `buf`.
+    ///
+    /// Waits until at least one packet is available, then takes every
+    /// packet already queued up to `limit` in a single channel operation,
+    /// which is cheaper than calling [`next_packet()`](Self::next_packet)
+    /// in a loop at high packet rates. Returns the number of items
+    /// appended, or `0` once the capture has stopped (or if `limit` is 0).
+    pub async fn next_packets(&self, buf: &mut Vec<Result<Packet, Error>>, limit: usize) -> usize {
+        self.rx.lock().await.next_packets(buf, limit).await
+    }
+
+    /// Collects up to `max` packets, waiting at most `timeout` for them.
+    ///
+    /// Returns as soon as `max` packets are collected, or with whatever
+    /// arrived once `timeout` expires, taking every already queued packet
+    /// at once like [`next_packets()`](Self::next_packets). Consumers that
+    /// process packets in chunks can so bound both the batch size and its
+    /// latency.
+    ///
+    /// # Notes
+    ///
+    /// - An empty batch means that no packet arrived in time or that the
+    ///   capture has stopped; tell them apart with
+    ///   [`AsyncCaptureHandle::is_running()`].
+    /// - Cancellation safe: packets not yet returned stay queued, except
+    ///   those already collected into the dropped batch.
+    /// - Requires the `rt-tokio` feature.
+    #[cfg(feature = "rt-tokio")]
+    pub async fn next_batch(&self, max: usize, timeout: Duration) -> Vec<Result<Packet, Error>> {
+        self.rx.lock().await.next_batch(max, timeout).await
+    }
+
+    /// Waits for the next packet, statistics or reconnection marker.
+    ///
+    /// With [`AsyncCaptureBuilder::stats_interval()`](crate::AsyncCaptureBuilder::stats_interval)
+    /// set, the kernel statistics are queued in order with the packets, so
+    /// a single loop can handle both; so are the losses and reopenings of
+    /// the device under
+    /// [`AsyncCaptureBuilder::reconnect()`](crate::AsyncCaptureBuilder::reconnect).
+    /// Ends with a [`StreamItem::Stopped`] carrying the summary, after
+    /// which `None` is returned.
+    ///
+    /// # Notes
+    ///
+    /// - [`next_packet()`](Self::next_packet) and the other packet methods
+    ///   share the same queue and skip the markers, so mixing them with
+    ///   this method loses the markers they pass over. The end of the
+    ///   capture is not lost: it is sticky, and `Stopped` is returned by
+    ///   the first call to this method after it, whichever method saw it.
+    /// - Without a configured interval or reconnection this behaves like
+    ///   [`next_packet()`](Self::next_packet), apart from `Stopped`.
+    pub async fn next_item(&self) -> Option<StreamItem> {
+        self.rx.lock().await.next_item().await
+    }
+
+    /// Waits for the next packet or change of the capture's state, in the
+    /// order they happened.
+    ///
+    /// Like [`next_item()`](Self::next_item), but also has the capture
+    /// thread queue pauses, filter changes and increases of the kernel
+    /// drops.
+    ///
+    /// # Notes
+    ///
+    /// - Those changes are only queued from the first call on; call it
+    ///   before pausing or changing the filter to see them.
+    /// - Kernel drops are sampled by the capture thread once a second while
+    ///   events are wanted, and only for sources with statistics.
+    pub async fn next_event(&self) -> Option<StreamItem> {
+        self.shared.events_wanted.store(true, Ordering::Relaxed);
+        self.next_item().await
+    }
+
+    /// Waits for the next packet along with its queue timing.
+    ///
+    /// The timing is `None` unless
+    /// [`AsyncCaptureBuilder::timing_instrumentation()`](crate::AsyncCaptureBuilder::timing_instrumentation)
+    /// is enabled. Compare [`PacketTiming::dwell()`] with the capture
+    /// timestamp to tell whether packets are delayed by the consumer or
+    /// already arrived late from the kernel.
+    pub async fn next_packet_timed(&self) -> Option<(Result<Packet, Error>, Option<PacketTiming>)> {
+        self.next_packet_meta()
+            .await
+            .map(|(pkt, meta)| (pkt, meta.timing))
+    }
+
+    /// Waits for the next packet along with the information gathered for
+    /// it on the capture thread.
+    ///
+    /// See [`PacketMeta`] for the options that fill it in.
+    pub async fn next_packet_meta(&self) -> Option<(Result<Packet, Error>, PacketMeta)> {
+        self.rx.lock().await.next_meta().await
+    }
+}
+
+impl fmt::Debug for AsyncCapture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncCapture")
+            .field("snaplen", &self.info.snaplen)
+            .field("datalink", &self.info.datalink)
+            .finish_non_exhaustive()
     }
 }
 
@@ -96,17 +685,534 @@ impl AsyncCaptureHandle {
     /// Stops the capture from another thread or asynchronous task.
     ///
     /// This method sets the internal stop flag, signaling the background
-    /// capture thread to terminate gracefully. It also sends a `Stop`
-    /// message through the internal channel to ensure that any awaiting
-    /// calls to [`AsyncCapture::next_packet()`] will return `None`.
+    /// capture thread to terminate gracefully, and interrupts a blocking
+    /// read of a live capture with `pcap_breakloop` so that the flag is
+    /// observed promptly. Once the thread observes the flag it sends a
+    /// `Stop` message through the internal channel, so that awaiting calls
+    /// to [`AsyncCapture::next_packet()`] return `None`.
     ///
     /// # Notes
     ///
     /// - Calling this method multiple times is safe and idempotent.
     /// - Once stopped, the background thread will no longer produce packets.
-    /// - After calling `stop`, any future calls to
-    ///   [`AsyncCapture::next_packet()`] will immediately return `None`.
+    /// - Packets read before the flag was observed are still delivered;
+    ///   after them, [`AsyncCapture::next_packet()`] returns `None`.
+    /// - Whether `pcap_breakloop` wakes a blocked read depends on the
+    ///   platform and libpcap version; where it does not, the stop is
+    ///   noticed when the read returns, at the latest after the read
+    ///   timeout.
     pub fn stop(&self) {
-        self.stop_flag.store(true, Ordering::Relaxed);
+        self.shared.stop_flag.store(true, Ordering::Release);
+        self.shared.interrupt.interrupt();
+    }
+
+    /// Suspends the capture without closing it.
+    ///
+    /// The capture thread stops reading, so the device, its filter and
+    /// datalink stay set up while the kernel buffer fills and then
+    /// discards the traffic. [`resume()`](Self::resume) picks up where it
+    /// left off.
+    ///
+    /// # Notes
+    ///
+    /// - Takes effect at the next check of the stop flag, i.e. after the
+    ///   read in progress; packets read until then are still delivered.
+    /// - Packets the kernel buffered during the pause are dropped when
+    ///   reading resumes, judged by their timestamps, and counted by
+    ///   [`paused_drops()`](Self::paused_drops).
+    /// - [`stop()`](Self::stop), [`with_capture()`](Self::with_capture)
+    ///   and filter updates still work while paused.
+    pub fn pause(&self) {
+        self.shared.pause.set(true);
+    }
+
+    /// Resumes reading after [`pause()`](Self::pause).
+    pub fn resume(&self) {
+        self.shared.pause.set(false);
+    }
+
+    /// Returns `true` between [`pause()`](Self::pause) and
+    /// [`resume()`](Self::resume).
+    pub fn is_paused(&self) -> bool {
+        self.shared.pause.is_paused()
+    }
+
+    /// Returns how many packets buffered by the kernel during a
+    /// [`pause()`](Self::pause) were dropped when reading resumed.
+    pub fn paused_drops(&self) -> u64 {
+        self.shared.pause.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` while the background capture thread is running.
+    pub fn is_running(&self) -> bool {
+        self.shared.running.load(Ordering::Acquire)
+    }
+
+    /// Runs `f` on the background thread with mutable access to the
+    /// underlying live capture, and returns its result.
+    ///
+    /// An escape hatch for pcap operations the wrapper does not expose.
+    /// The closure is queued and runs between two reads, so it waits for
+    /// the read in progress to return (at most the read timeout on an idle
+    /// interface).
+    ///
+    /// # Notes
+    ///
+    /// - The closure runs on the capture thread and holds up packet
+    ///   delivery while it runs; it must not block for long.
+    /// - Changing how packets are read (e.g. switching to non-blocking
+    ///   mode) affects the capture thread as well.
+    /// - Fails with [`Error::Unsupported`] if the capture was not created
+    ///   from a `pcap::Capture<Active>`, and with [`Error::CaptureStopped`]
+    ///   once the background thread has exited.
+    pub async fn with_capture<F, R>(&self, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&mut Capture<Active>) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let command = Box::new(move |cap: Option<&mut Capture<Active>>| {
+            let result = match cap {
+                Some(cap) => Ok(f(cap)),
+                None => Err(Error::Unsupported(
+                    "the capture source is not a live pcap capture".into(),
+                )),
+            };
+            let _ = tx.send(result);
+        });
+        if !self.shared.commands.push(command) {
+            return Err(Error::CaptureStopped);
+        }
+        rx.await.unwrap_or(Err(Error::CaptureStopped))
+    }
+
+    /// Puts the live capture in or out of non-blocking mode.
+    ///
+    /// Runs `pcap_setnonblock` on the capture thread through
+    /// [`with_capture()`](Self::with_capture). The capture thread keeps
+    /// working in either mode: in non-blocking mode a read that finds no
+    /// packet returns at once, and instead of retrying in a busy loop the
+    /// thread waits on the capture's descriptor for up to 50 ms before
+    /// reading again, still checking for stop requests and queued commands
+    /// between waits.
+    ///
+    /// # Notes
+    ///
+    /// - In non-blocking mode read timeouts are not delivered to the
+    ///   consumer, as no read waits for the timeout.
+    /// - `pcap::Capture::is_nonblock()` does not reflect a change made
+    ///   here; use [`is_nonblocking()`](Self::is_nonblocking).
+    /// - Fails like `with_capture()` if the source is not a live capture
+    ///   or the capture has stopped.
+    pub async fn set_nonblocking(&self, nonblocking: bool) -> Result<(), Error> {
+        let shared = self.shared.clone();
+        self.with_capture(move |cap| {
+            nonblock::set(cap, nonblocking)?;
+            shared.nonblocking.store(nonblocking, Ordering::Release);
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Returns whether the live capture is in non-blocking mode, as
+    /// reported by `pcap_getnonblock`.
+    pub async fn is_nonblocking(&self) -> Result<bool, Error> {
+        self.with_capture(nonblock::get).await?
+    }
+
+    /// Replaces the kernel BPF filter of the running capture.
+    ///
+    /// The program, in `tcpdump` syntax, is compiled and installed on the
+    /// capture thread through [`with_capture()`](Self::with_capture), so the
+    /// capture keeps running and no packets are read concurrently with the
+    /// swap. This makes it suitable for adaptive captures that narrow or
+    /// widen the filter in response to the traffic they see.
+    ///
+    /// # Notes
+    ///
+    /// - Updates are safe to issue rapidly. They are applied in order, and
+    ///   when several are queued before the thread gets to them only the
+    ///   latest is installed; the others resolve immediately with
+    ///   [`FilterSwap::superseded`](crate::FilterSwap::superseded) set.
+    /// - An update waits for the read in progress to return, i.e. up to the
+    ///   read timeout on an idle interface.
+    /// - The swap itself costs a BPF compilation (microseconds for typical
+    ///   programs) and one system call. On Linux, libpcap briefly installs
+    ///   a reject-all filter and drains the socket buffer, so packets
+    ///   already queued in the kernel when the swap happens are discarded.
+    ///   Packets arriving during the swap may be dropped if the buffer fills;
+    ///   [`FilterSwap::kernel_drops`](crate::FilterSwap::kernel_drops)
+    ///   reports how many drops the kernel counted meanwhile.
+    /// - Fails with the pcap error if the program does not compile, in which
+    ///   case the previous filter stays installed.
+    /// - Under a [`ReconnectPolicy`](crate::ReconnectPolicy) the filter is
+    ///   installed again on the reopened device, replacing the one it was
+    ///   opened with.
+    pub async fn set_filter<S: Into<String>>(&self, program: S) -> Result<FilterSwap, Error> {
+        self.set_filter_with(program, true).await
+    }
+
+    /// Like [`set_filter()`](Self::set_filter), but chooses whether the
+    /// BPF optimizer runs on the compiled program.
+    ///
+    /// The optimizer shrinks most programs, but some filters compile to
+    /// wrong code with it on older libpcap versions; disabling it is the
+    /// usual workaround.
+    pub async fn set_filter_with<S: Into<String>>(
+        &self,
+        program: S,
+        optimize: bool,
+    ) -> Result<FilterSwap, Error> {
+        let program = program.into();
+        let generation = self.shared.filter_generation.fetch_add(1, Ordering::AcqRel) + 1;
+        let shared = self.shared.clone();
+        self.with_capture(move |cap| {
+            if shared.filter_generation.load(Ordering::Acquire) != generation {
+                return Ok(FilterSwap {
+                    superseded: true,
+                    took: Duration::ZERO,
+                    kernel_drops: None,
+                });
+            }
+            let start = Instant::now();
+            let before = cap.stats().ok();
+            cap.filter(&program, optimize)?;
+            let after = cap.stats().ok();
+            *shared.live_filter.lock().unwrap_or_else(|e| e.into_inner()) =
+                Some((program.clone(), optimize));
+            #[cfg(feature = "tracing")]
+            tracing::Span::current().record("filter", program.as_str());
+            shared.raise(LinkEvent::FilterChanged(program));
+            Ok(FilterSwap {
+                superseded: false,
+                took: start.elapsed(),
+                kernel_drops: before
+                    .zip(after)
+                    .map(|(before, after)| after.dropped.wrapping_sub(before.dropped)),
+            })
+        })
+        .await?
+    }
+
+    /// Changes which way the packets captured from now on travel, as
+    /// [`AsyncCaptureBuilder::direction()`](crate::AsyncCaptureBuilder::direction)
+    /// does when opening.
+    ///
+    /// Runs `pcap_setdirection` on the capture thread through
+    /// [`with_capture()`](Self::with_capture), so it waits for the read in
+    /// progress to return.
+    ///
+    /// # Notes
+    ///
+    /// - Packets already queued, in the kernel or towards the consumer,
+    ///   are still delivered.
+    /// - A device reopened by
+    ///   [`reconnect()`](crate::AsyncCaptureBuilder::reconnect) gets the
+    ///   direction set on the builder again.
+    /// - Fails like `with_capture()` if the source is not a live capture
+    ///   or the capture has stopped, and with the pcap error if the
+    ///   platform does not support choosing a direction.
+    pub async fn set_direction(&self, direction: Direction) -> Result<(), Error> {
+        self.with_capture(move |cap| {
+            cap.direction(direction)?;
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Returns the settings the capture was opened with, or `None` unless
+    /// it was opened by an [`AsyncCaptureBuilder`](crate::AsyncCaptureBuilder).
+    ///
+    /// The snaplen, datalink and timestamp precision are the effective ones
+    /// reported by libpcap, which may differ from those requested; the
+    /// other values are as requested, since libpcap does not report them.
+    /// Changes made while running, e.g. by
+    /// [`set_filter()`](Self::set_filter), are not reflected.
+    pub fn config(&self) -> Option<CaptureConfig> {
+        self.shared.config.clone()
+    }
+
+    /// Returns the final report of the capture once its thread has exited.
+    pub fn summary(&self) -> Option<CaptureSummary> {
+        self.shared.summary.lock().unwrap().clone()
+    }
+
+    /// Waits until the background thread has exited and returns its final
+    /// report.
+    ///
+    /// The summary is recorded before the end of the capture is signalled,
+    /// so it is available as soon as [`AsyncCapture::next_packet()`]
+    /// returns `None`. The source is dropped before that, so a live device
+    /// is already closed by then. This does not stop the capture; call
+    /// [`stop()`](Self::stop) first to end it, or
+    /// [`stop_timeout()`](Self::stop_timeout) to bound the wait.
+    pub async fn wait_stopped(&self) -> CaptureSummary {
+        loop {
+            let ended = self.shared.ended.notified();
+            tokio::pin!(ended);
+            ended.as_mut().enable();
+            if let Some(summary) = self.summary() {
+                return summary;
+            }
+            ended.await;
+        }
+    }
+
+    /// Stops the capture and waits up to `timeout` for the background
+    /// thread to exit, returning its final report.
+    ///
+    /// A safety valve for drivers whose reads never return: if the thread
+    /// is still running after `timeout`, it is left behind and
+    /// [`Error::StopTimedOut`] is returned, so the application regains
+    /// control instead of hanging.
+    ///
+    /// # Notes
+    ///
+    /// - The capture thread is never joined, so a thread left behind keeps
+    ///   running, and holds the device open, until its read returns; it
+    ///   then observes the stop flag and exits on its own.
+    /// - Until then [`AsyncCapture::next_packet()`] keeps waiting; drop the
+    ///   [`AsyncCapture`] rather than waiting on it.
+    /// - The same as [`stop()`](Self::stop) followed by
+    ///   [`wait_stopped()`](Self::wait_stopped) under a timeout.
+    /// - Requires the `rt-tokio` feature.
+    #[cfg(feature = "rt-tokio")]
+    pub async fn stop_timeout(&self, timeout: Duration) -> Result<CaptureSummary, Error> {
+        self.stop();
+        match tokio::time::timeout(timeout, self.wait_stopped()).await {
+            Ok(summary) => Ok(summary),
+            Err(_) => {
+                warn!("AsyncCapture thread did not stop within {timeout:?}, leaving it behind.");
+                Err(Error::StopTimedOut(timeout))
+            }
+        }
+    }
+
+    /// Stops the capture and resolves once its background thread has
+    /// exited, returning the final report.
+    ///
+    /// Unlike [`stop()`](Self::stop), which returns as soon as the thread
+    /// has been asked to stop, this also joins the thread. By the time it
+    /// resolves the device is closed and the thread is gone.
+    ///
+    /// # Notes
+    ///
+    /// - Whether `pcap_breakloop` wakes a blocked read depends on the
+    ///   platform and libpcap version; where it does not, the read still
+    ///   returns at the read timeout. Use
+    ///   [`stop_timeout()`](Self::stop_timeout) to bound the wait for
+    ///   drivers whose reads never return.
+    /// - Calling it again, or from another handle, waits for the same
+    ///   thread and returns the same report.
+    /// - Requires the `rt-tokio` feature.
+    #[cfg(feature = "rt-tokio")]
+    pub async fn shutdown(&self) -> CaptureSummary {
+        self.stop();
+        let summary = self.wait_stopped().await;
+        let thread = self.shared.thread.lock().unwrap().take();
+        if let Some(thread) = thread {
+            // The thread has published its summary and is about to return
+            let _ = tokio::task::spawn_blocking(move || thread.join()).await;
+        }
+        summary
+    }
+
+    /// Returns the requested read timeout and immediate mode, along with
+    /// what the capture thread observed about them.
+    ///
+    /// See [`ReadBehavior`](crate::ReadBehavior) for what can and cannot be
+    /// determined.
+    pub fn read_behavior(&self) -> ReadBehavior {
+        self.shared.read_probe.current()
+    }
+
+    /// Returns how many reads were retried under the
+    /// [`RetryPolicy`](crate::RetryPolicy).
+    pub fn read_retries(&self) -> u64 {
+        self.shared.retries.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many times the device was reopened under the
+    /// [`ReconnectPolicy`](crate::ReconnectPolicy).
+    pub fn reconnects(&self) -> u64 {
+        self.shared.reconnects.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many errors the capture thread recovered from, the
+    /// errors reported to
+    /// [`AsyncCaptureBuilder::on_error()`](crate::AsyncCaptureBuilder::on_error).
+    pub fn soft_errors(&self) -> u64 {
+        self.shared.soft_errors.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many packets each predicate of the
+    /// [`FilterPipeline`](crate::FilterPipeline) dropped, in stage order.
+    ///
+    /// Empty if no pipeline is configured. Packets dropped by the BPF
+    /// stage never reach user space and are not counted.
+    pub fn pipeline_rejections(&self) -> Vec<u64> {
+        self.shared
+            .rejections
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    /// Returns how many packets were dropped as duplicates of the packet
+    /// before them.
+    ///
+    /// Always zero unless [`AsyncCaptureBuilder::dedup()`](crate::AsyncCaptureBuilder::dedup)
+    /// is configured.
+    pub fn duplicates_suppressed(&self) -> u64 {
+        self.shared.duplicates.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many packets were dropped because they arrived outside
+    /// the windows of the [`CaptureSchedule`](crate::CaptureSchedule).
+    ///
+    /// Always zero unless [`AsyncCaptureBuilder::schedule()`](crate::AsyncCaptureBuilder::schedule)
+    /// is configured.
+    pub fn unscheduled_drops(&self) -> u64 {
+        self.shared.unscheduled.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many packets were skipped by
+    /// [`min_len()`](crate::AsyncCaptureBuilder::min_len) or
+    /// [`max_len()`](crate::AsyncCaptureBuilder::max_len).
+    pub fn length_filtered(&self) -> u64 {
+        self.shared.length_filtered.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many packets were skipped by the
+    /// [`prefilter()`](crate::AsyncCaptureBuilder::prefilter).
+    pub fn prefiltered(&self) -> u64 {
+        self.shared.prefiltered.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many packets were skipped by
+    /// [`sample_ratio()`](crate::AsyncCaptureBuilder::sample_ratio),
+    /// [`sample_rate()`](crate::AsyncCaptureBuilder::sample_rate) or
+    /// [`max_bytes_per_sec()`](crate::AsyncCaptureBuilder::max_bytes_per_sec).
+    pub fn sampled_out(&self) -> u64 {
+        self.shared.sampled_out.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many packets were dropped because the bounded queue
+    /// towards the consumer was full.
+    ///
+    /// Always zero unless [`AsyncCaptureBuilder::bounded()`](crate::AsyncCaptureBuilder::bounded)
+    /// is configured with a dropping [`OverflowPolicy`](crate::OverflowPolicy).
+    pub fn overflow_drops(&self) -> u64 {
+        self.shared
+            .queue_bound
+            .as_ref()
+            .map_or(0, |bound| bound.dropped.load(Ordering::Relaxed))
+    }
+
+    /// Returns how many packets were flagged as coalesced by receive
+    /// offload.
+    ///
+    /// Always zero unless [`AsyncCaptureBuilder::detect_offload()`](crate::AsyncCaptureBuilder::detect_offload)
+    /// is enabled.
+    pub fn coalesced_packets(&self) -> u64 {
+        self.shared.coalesced.load(Ordering::Relaxed)
+    }
+
+    /// Returns the packets lost so far, broken down by where they were
+    /// dropped.
+    ///
+    /// The kernel counters are read with `pcap_stats` on the capture thread
+    /// through [`with_capture()`](Self::with_capture), so this waits for the
+    /// read in progress to return. See [`DropReasons`]
+    /// for the categories.
+    ///
+    /// # Notes
+    ///
+    /// - If the source is not a live capture, or does not report
+    ///   statistics, the kernel categories are `None`.
+    /// - Fails with [`Error::CaptureStopped`] once the background thread
+    ///   has exited.
+    pub async fn drop_reasons(&self) -> Result<DropReasons, Error> {
+        let stats = match self.with_capture(|cap| cap.stats().ok()).await {
+            Ok(stats) => stats,
+            Err(Error::Unsupported(_)) => None,
+            Err(e) => return Err(e),
+        };
+        Ok(DropReasons::new(
+            stats,
+            self.filtered(),
+            self.overflow_drops(),
+        ))
+    }
+
+    /// Returns the kernel statistics of the capture together with the
+    /// counters of the capture thread.
+    ///
+    /// The kernel counters are read with `pcap_stats` on the capture thread
+    /// through [`with_capture()`](Self::with_capture), so this waits for the
+    /// read in progress to return. See [`CaptureStats`] for the counters.
+    ///
+    /// # Notes
+    ///
+    /// - If the source is not a live capture, or does not report
+    ///   statistics, the kernel counters are `None`.
+    /// - Fails with [`Error::CaptureStopped`] once the background thread
+    ///   has exited; the [`summary()`](Self::summary) then holds the final
+    ///   counts.
+    pub async fn stats(&self) -> Result<CaptureStats, Error> {
+        let kernel = match self.with_capture(|cap| cap.stats().ok()).await {
+            Ok(stats) => stats,
+            Err(Error::Unsupported(_)) => None,
+            Err(e) => return Err(e),
+        };
+        let shared = &self.shared;
+        let evicted = shared
+            .queue_bound
+            .as_ref()
+            .map_or(0, |bound| bound.evicted.load(Ordering::Relaxed));
+        let stats = CaptureStats {
+            received: None,
+            dropped: None,
+            if_dropped: None,
+            read: shared.received.load(Ordering::Relaxed),
+            queued: shared.enqueued.load(Ordering::Relaxed),
+            delivered: shared
+                .dequeued
+                .load(Ordering::Relaxed)
+                .saturating_sub(evicted),
+            filtered: self.filtered(),
+            overflow_dropped: self.overflow_drops(),
+        };
+        Ok(stats.with_kernel(kernel))
+    }
+
+    /// Packets discarded on the capture thread by its filters.
+    pub(crate) fn filtered(&self) -> u64 {
+        self.length_filtered()
+            + self.prefiltered()
+            + self.sampled_out()
+            + self.duplicates_suppressed()
+            + self.unscheduled_drops()
+            + self.paused_drops()
+            + self.pipeline_rejections().iter().sum::<u64>()
+    }
+
+    /// Returns the queue dwell time of the packets delivered so far.
+    ///
+    /// Empty unless
+    /// [`AsyncCaptureBuilder::timing_instrumentation()`](crate::AsyncCaptureBuilder::timing_instrumentation)
+    /// is enabled.
+    pub fn queue_dwell(&self) -> QueueDwell {
+        self.shared.dwell.current()
+    }
+
+    /// Returns the current packet and bit rates of the capture.
+    ///
+    /// Rates are computed from the packets read by the background thread
+    /// over a rolling window (one second unless configured with
+    /// [`AsyncCaptureBuilder::throughput_window()`](crate::AsyncCaptureBuilder::throughput_window)),
+    /// so they decay to zero while the interface is idle.
+    pub fn throughput(&self) -> Throughput {
+        self.shared.throughput.current()
     }
 }
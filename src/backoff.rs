@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+/// How long the capture thread waits between polls of an idle
+/// non-blocking capture.
+///
+/// With [`AsyncCaptureHandle::set_nonblocking()`](crate::AsyncCaptureHandle::set_nonblocking)
+/// enabled, a read on an idle interface returns at once. The thread then
+/// waits for the descriptor to become readable, or sleeps where it cannot
+/// wait on one, before polling again. The wait starts at `initial` and is
+/// multiplied by `multiplier` after every further empty poll, up to `max`;
+/// the first packet resets it to `initial`. Short waits keep the latency
+/// low under load, long ones cut the wakeups on an idle link.
+///
+/// Set with
+/// [`AsyncCaptureBuilder::idle_backoff()`](crate::AsyncCaptureBuilder::idle_backoff).
+/// `max` is capped at 50 ms so that a stop request is still noticed
+/// promptly. A `multiplier` of 1 keeps the wait constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdleBackoff {
+    /// Wait after the first empty poll
+    pub initial: Duration,
+    /// Longest wait
+    pub max: Duration,
+    /// Factor by which the wait grows after each empty poll
+    pub multiplier: u32,
+}
+
+impl Default for IdleBackoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_micros(100),
+            max: Duration::from_millis(50),
+            multiplier: 2,
+        }
+    }
+}
+
+/// Current position on an [`IdleBackoff`] curve.
+#[derive(Debug)]
+pub(crate) struct Backoff {
+    config: IdleBackoff,
+    current: Duration,
+}
+
+impl Backoff {
+    pub(crate) fn new(config: IdleBackoff, cap: Duration) -> Self {
+        let config = IdleBackoff {
+            max: config.max.min(cap),
+            ..config
+        };
+        Self {
+            config,
+            current: config.initial.min(config.max),
+        }
+    }
+
+    /// Returns the wait for this empty poll and grows the next one.
+    pub(crate) fn next_wait(&mut self) -> Duration {
+        let wait = self.current;
+        // A zero initial wait still grows
+        self.current = self
+            .current
+            .max(Duration::from_micros(1))
+            .saturating_mul(self.config.multiplier.max(1))
+            .min(self.config.max);
+        wait
+    }
+
+    /// Drops back to the initial wait once packets flow again.
+    pub(crate) fn reset(&mut self) {
+        self.current = self.config.initial.min(self.config.max);
+    }
+}
@@ -0,0 +1,189 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::{self, Receiver, Sender, WeakSender};
+
+use crate::async_pcap::{AsyncCapture, Packet};
+use crate::error::Error;
+
+/// Item handed to every subscriber. Errors are shared as well, since
+/// [`Error`] cannot be cloned.
+type Item = Result<Arc<Packet>, Arc<Error>>;
+
+/// A capture whose packets are handed to every subscriber.
+///
+/// Returned by [`AsyncCapture::broadcast()`]. Each
+/// [`PacketSubscriber`] receives its own copy of the stream, so a logger,
+/// an analyzer and a metrics task can consume the same capture.
+pub struct PacketBroadcast {
+    /// Weak, so that subscribers see the end of the stream once the
+    /// forwarding task is done
+    tx: WeakSender<Item>,
+    hub: Arc<Hub>,
+}
+
+/// State shared by a broadcast and its subscribers.
+struct Hub {
+    /// Taken by the first subscriber to wait for a packet, which starts
+    /// the forwarding task
+    pending: Mutex<Option<(AsyncCapture, Sender<Item>)>>,
+    /// Packets read while no subscriber was listening
+    unheard: AtomicU64,
+}
+
+impl Hub {
+    /// Starts forwarding the capture, unless it is already.
+    fn start(self: &Arc<Self>) {
+        let pending = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        if let Some((capture, tx)) = pending {
+            tokio::spawn(forward(capture, tx, self.clone()));
+        }
+    }
+}
+
+impl PacketBroadcast {
+    /// Creates a new subscriber, which receives every packet read from
+    /// now on.
+    ///
+    /// The capture is read from once a subscriber first waits for a
+    /// packet, so the subscribers created before that all see the stream
+    /// from its first packet; later ones miss the packets delivered before
+    /// they subscribed.
+    pub fn subscribe(&self) -> PacketSubscriber {
+        let rx = match self.tx.upgrade() {
+            Some(tx) => tx.subscribe(),
+            // The capture has ended: hand out a subscriber that is closed
+            None => broadcast::channel(1).1,
+        };
+        PacketSubscriber {
+            rx,
+            hub: self.hub.clone(),
+            lagged: 0,
+        }
+    }
+
+    /// Number of subscribers currently alive.
+    pub fn subscriber_count(&self) -> usize {
+        self.tx.upgrade().map_or(0, |tx| tx.receiver_count())
+    }
+
+    /// Number of packets read while every subscriber had been dropped.
+    pub fn unheard(&self) -> u64 {
+        self.hub.unheard.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Debug for PacketBroadcast {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PacketBroadcast")
+            .field("subscribers", &self.subscriber_count())
+            .field("unheard", &self.unheard())
+            .finish_non_exhaustive()
+    }
+}
+
+/// One consumer of a [`PacketBroadcast`].
+///
+/// Subscribers read at their own pace from a shared ring of packets. A
+/// subscriber that falls more than the ring's capacity behind skips the
+/// packets it missed, instead of stalling the others; the skipped packets
+/// are counted by [`lagged()`](Self::lagged).
+pub struct PacketSubscriber {
+    rx: Receiver<Item>,
+    hub: Arc<Hub>,
+    lagged: u64,
+}
+
+impl PacketSubscriber {
+    /// Waits for the next packet.
+    ///
+    /// Returns `None` once the capture has stopped and every packet before
+    /// the stop has been received. Must be called from within a Tokio
+    /// runtime.
+    pub async fn next_packet(&mut self) -> Option<Result<Arc<Packet>, Arc<Error>>> {
+        self.hub.start();
+        loop {
+            match self.rx.recv().await {
+                Ok(item) => return Some(item),
+                Err(RecvError::Lagged(skipped)) => self.lagged += skipped,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Number of packets this subscriber skipped because it fell behind.
+    pub fn lagged(&self) -> u64 {
+        self.lagged
+    }
+
+    /// Number of packets queued for this subscriber.
+    pub fn backlog(&self) -> usize {
+        self.rx.len()
+    }
+}
+
+impl Clone for PacketSubscriber {
+    /// Creates a subscriber at the same position, with its own lag count.
+    fn clone(&self) -> Self {
+        Self {
+            rx: self.rx.resubscribe(),
+            hub: self.hub.clone(),
+            lagged: 0,
+        }
+    }
+}
+
+impl fmt::Debug for PacketSubscriber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PacketSubscriber")
+            .field("lagged", &self.lagged)
+            .field("backlog", &self.backlog())
+            .finish_non_exhaustive()
+    }
+}
+
+impl AsyncCapture {
+    /// Turns the capture into a [`PacketBroadcast`], which hands every
+    /// packet to each of its subscribers.
+    ///
+    /// Packets are shared behind an `Arc` rather than copied per
+    /// subscriber. Each subscriber can fall up to `capacity` packets
+    /// behind the newest one before it starts skipping packets; a slow
+    /// subscriber never holds up the capture or the other subscribers.
+    ///
+    /// # Notes
+    ///
+    /// - The capture is read by a Tokio task started the first time a
+    ///   subscriber waits for a packet. Once every subscriber
+    ///   is dropped, packets are discarded and counted by
+    ///   [`unheard()`](PacketBroadcast::unheard); stop the capture through
+    ///   its handle to end it.
+    /// - The ring holds `capacity` packets at all times once full, even
+    ///   after every subscriber has received them.
+    /// - A `capacity` of 0 is treated as 1.
+    pub fn broadcast(self, capacity: usize) -> PacketBroadcast {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        PacketBroadcast {
+            tx: tx.downgrade(),
+            hub: Arc::new(Hub {
+                pending: Mutex::new(Some((self, tx))),
+                unheard: AtomicU64::new(0),
+            }),
+        }
+    }
+}
+
+async fn forward(capture: AsyncCapture, tx: Sender<Item>, hub: Arc<Hub>) {
+    while let Some(item) = capture.next_packet().await {
+        let item = item.map(Arc::new).map_err(Arc::new);
+        if tx.send(item).is_err() {
+            hub.unheard.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,1259 @@
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+
+use std::sync::Arc;
+#[cfg(feature = "ring-file")]
+use std::sync::Mutex;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+use pcap::{Active, Capture, Device, Direction, Linktype, PacketHeader, Precision};
+#[cfg(feature = "cancellation")]
+use tokio_util::sync::CancellationToken;
+
+use crate::async_pcap::{AsyncCapture, AsyncCaptureHandle};
+use crate::backoff::IdleBackoff;
+use crate::clock::Clock;
+use crate::config::CaptureConfig;
+use crate::dedup::DedupMode;
+use crate::drop_monitor::{DropAlert, DropThreshold};
+use crate::error::Error;
+#[cfg(target_os = "linux")]
+use crate::fanout::{self, FanoutMode};
+use crate::filter::{FilterPipeline, Prefilter, compile_filter};
+use crate::gate::StartGate;
+use crate::info::CaptureInfo;
+use crate::local::LocalAddresses;
+use crate::logging::{ErrorHook, warn};
+use crate::offload;
+use crate::pool::BufferPool;
+use crate::probe::ReadRequest;
+use crate::queue::OverflowPolicy;
+use crate::rate_limit::RateLimit;
+use crate::reconnect::{Reconnect, ReconnectPolicy};
+use crate::recorder::RingRecorder;
+#[cfg(feature = "remote")]
+use crate::remote::RemoteSource;
+use crate::retry::RetryPolicy;
+#[cfg(feature = "ring-file")]
+use crate::ring_file::{RingFile, RingFileConfig};
+use crate::schedule::CaptureSchedule;
+use crate::summary::{CaptureSummary, StopHook};
+use crate::sync_capture::SyncCapture;
+use crate::tstamp::{self, TimestampSource};
+use crate::worker::{self, CaptureOptions, SendBatch};
+
+/// Kernel buffer size used by [`AsyncCaptureBuilder::low_latency()`], in bytes.
+const LOW_LATENCY_BUFFER_SIZE: i32 = 64 * 1024;
+/// Largest snaplen libpcap accepts, `MAXIMUM_SNAPLEN`.
+const MAX_SNAPLEN: i32 = 262_144;
+
+/// Builder that configures a device and opens it as an [`AsyncCapture`].
+///
+/// Replaces the `pcap::Capture::from_device(...).open()` boilerplate with a
+/// single chain that returns the async capture and its handle directly.
+#[derive(Debug, Clone)]
+pub struct AsyncCaptureBuilder {
+    device: Device,
+    promisc: bool,
+    snaplen: i32,
+    timeout: i32,
+    immediate_mode: bool,
+    buffer_size: Option<i32>,
+    datalink: Option<Linktype>,
+    direction: Option<Direction>,
+    filter: Option<String>,
+    host_mac: Option<[u8; 6]>,
+    hardware_timestamps: bool,
+    precision: Precision,
+    pool_size: Option<usize>,
+    reconnect: Option<ReconnectPolicy>,
+    options: CaptureOptions,
+    #[cfg(feature = "ring-file")]
+    ring_file: Option<RingFileConfig>,
+    #[cfg(feature = "cancellation")]
+    cancel: Option<CancellationToken>,
+    #[cfg(feature = "remote")]
+    pub(crate) remote: Option<RemoteSource>,
+    #[cfg(windows)]
+    min_to_copy: Option<i32>,
+    #[cfg(not(windows))]
+    rfmon: bool,
+    #[cfg(target_os = "linux")]
+    pub(crate) fanout_group: Option<u16>,
+    #[cfg(target_os = "linux")]
+    fanout_mode: FanoutMode,
+}
+
+impl AsyncCaptureBuilder {
+    /// Creates a builder for the given device.
+    ///
+    /// Defaults: promiscuous mode off, a snaplen of 65535 bytes, a 500 ms
+    /// read timeout, immediate mode on and the platform default buffer size.
+    pub fn new<D: Into<Device>>(device: D) -> Self {
+        Self {
+            device: device.into(),
+            promisc: false,
+            snaplen: 65535,
+            timeout: 500,
+            immediate_mode: true,
+            buffer_size: None,
+            datalink: None,
+            direction: None,
+            filter: None,
+            host_mac: None,
+            hardware_timestamps: false,
+            precision: Precision::Micro,
+            pool_size: None,
+            reconnect: None,
+            options: CaptureOptions::default(),
+            #[cfg(feature = "ring-file")]
+            ring_file: None,
+            #[cfg(feature = "cancellation")]
+            cancel: None,
+            #[cfg(feature = "remote")]
+            remote: None,
+            #[cfg(windows)]
+            min_to_copy: None,
+            #[cfg(not(windows))]
+            rfmon: false,
+            #[cfg(target_os = "linux")]
+            fanout_group: None,
+            #[cfg(target_os = "linux")]
+            fanout_mode: FanoutMode::default(),
+        }
+    }
+
+    /// Creates a builder for the default capture device, as picked by
+    /// libpcap.
+    ///
+    /// Fails with [`Error::NoDefaultDevice`] if libpcap finds no device.
+    pub fn default_device() -> Result<Self, Error> {
+        let device = Device::lookup()?.ok_or(Error::NoDefaultDevice)?;
+        Ok(Self::new(device))
+    }
+
+    /// Enables or disables promiscuous mode.
+    pub fn promisc(mut self, on: bool) -> Self {
+        self.promisc = on;
+        self
+    }
+
+    /// Sets the maximum number of bytes captured per packet.
+    pub fn snaplen(mut self, bytes: i32) -> Self {
+        self.snaplen = bytes;
+        self
+    }
+
+    /// Sets the read timeout in milliseconds.
+    pub fn timeout(mut self, ms: i32) -> Self {
+        self.timeout = ms;
+        self
+    }
+
+    /// Enables or disables immediate mode, which delivers packets as soon
+    /// as they arrive instead of buffering them.
+    pub fn immediate_mode(mut self, on: bool) -> Self {
+        self.immediate_mode = on;
+        self
+    }
+
+    /// Sets the kernel capture buffer size in bytes.
+    pub fn buffer_size(mut self, bytes: i32) -> Self {
+        self.buffer_size = Some(bytes);
+        self
+    }
+
+    /// Puts a wireless device in monitor mode, so that it captures every
+    /// 802.11 frame on its channel instead of the traffic of the network it
+    /// is associated with.
+    ///
+    /// # Notes
+    ///
+    /// - Opening fails if the device or its driver does not support monitor
+    ///   mode, or without the privileges to enable it.
+    /// - Monitor mode usually disassociates the device from its network.
+    /// - The frames come with an 802.11 or radiotap header. Which ones the
+    ///   device offers is listed by
+    ///   [`list_datalinks()`](Self::list_datalinks), and one is picked with
+    ///   [`datalink()`](Self::datalink); the `radiotap` feature decodes
+    ///   radiotap headers.
+    /// - Not available on Windows, where libpcap cannot set monitor mode.
+    #[cfg(not(windows))]
+    pub fn rfmon(mut self, rfmon: bool) -> Self {
+        self.rfmon = rfmon;
+        self
+    }
+
+    /// Selects the link-layer header type to capture with, e.g.
+    /// [`Linktype::IEEE802_11_RADIOTAP`] for a device in monitor mode.
+    ///
+    /// Opening fails if the device does not support it; the supported
+    /// types are returned by [`list_datalinks()`](Self::list_datalinks).
+    pub fn datalink(mut self, linktype: Linktype) -> Self {
+        self.datalink = Some(linktype);
+        self
+    }
+
+    /// Captures only the packets received by the device, only those it
+    /// sent, or both, as `pcap_setdirection` does; both by default.
+    ///
+    /// Packets going the other way are discarded by the kernel, before
+    /// they are copied to user space. Opening fails if the platform does
+    /// not support choosing a direction, as on Windows.
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.direction = Some(direction);
+        self
+    }
+
+    /// Sets a BPF filter, in `tcpdump` syntax, compiled and applied when the
+    /// device is opened.
+    ///
+    /// Opening fails with [`Error::Pcap`] if the program does not compile.
+    pub fn filter<S: Into<String>>(mut self, program: S) -> Self {
+        self.filter = Some(program.into());
+        self
+    }
+
+    /// Filters packets with a kernel BPF program followed by Rust
+    /// predicates on the capture thread.
+    ///
+    /// The pipeline's BPF program replaces any [`filter`](Self::filter)
+    /// set earlier; one set later replaces the pipeline's. See
+    /// [`FilterPipeline`] for how the stages are applied.
+    pub fn filter_pipeline(mut self, mut pipeline: FilterPipeline) -> Self {
+        if let Some(bpf) = pipeline.bpf.take() {
+            self.filter = Some(bpf);
+        }
+        self.options.pipeline = Some(pipeline);
+        self
+    }
+
+    /// Only captures frames sent from or to the given MAC address.
+    ///
+    /// Compiles an `ether host` filter, so that a capture of the host's own
+    /// traffic does not require writing BPF by hand. The address is given
+    /// as six hex octets separated by `:` or `-`, e.g. `00:1a:2b:3c:4d:5e`.
+    /// If a [`filter`](Self::filter) is also set, a frame must match both.
+    ///
+    /// Returns [`Error::Setup`] if `mac` is not a valid MAC address.
+    pub fn only_host_mac(mut self, mac: &str) -> Result<Self, Error> {
+        self.host_mac = Some(parse_mac(mac)?);
+        Ok(self)
+    }
+
+    /// Sets the minimum amount of data the kernel copies in a single read.
+    ///
+    /// Only available on Windows (Npcap/WinPcap).
+    #[cfg(windows)]
+    pub fn min_to_copy(mut self, bytes: i32) -> Self {
+        self.min_to_copy = Some(bytes);
+        self
+    }
+
+    /// Joins the capture to a Linux `PACKET_FANOUT` group.
+    ///
+    /// Every capture opened on the same interface with the same group id
+    /// receives a disjoint subset of the traffic, so several
+    /// [`AsyncCapture`]s (each with its own background thread) can share the
+    /// load across cores. The distribution is chosen with
+    /// [`fanout_mode`](Self::fanout_mode) and defaults to [`FanoutMode::Hash`].
+    ///
+    /// # Notes
+    ///
+    /// - Linux only; the group is joined with `setsockopt(PACKET_FANOUT)` on
+    ///   the socket libpcap opened, right after activation.
+    /// - All members of a group must use the same mode and live in the same
+    ///   network namespace, otherwise opening fails with [`Error::Setup`].
+    /// - Packets already queued before the group is joined are still read by
+    ///   the capture that queued them.
+    #[cfg(target_os = "linux")]
+    pub fn fanout_group(mut self, id: u16) -> Self {
+        self.fanout_group = Some(id);
+        self
+    }
+
+    /// Sets how packets are distributed within the fanout group.
+    ///
+    /// Has no effect unless [`fanout_group`](Self::fanout_group) is set.
+    #[cfg(target_os = "linux")]
+    pub fn fanout_mode(mut self, mode: FanoutMode) -> Self {
+        self.fanout_mode = mode;
+        self
+    }
+
+    /// Caps the rate at which packets are forwarded to the consumer.
+    ///
+    /// See [`RateLimit`] for how the ceiling is enforced.
+    pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.options.rate_limit = Some(limit);
+        self
+    }
+
+    /// Holds the read loop back until `gate` is opened with
+    /// [`StartGate::start_all()`], so that several captures begin reading
+    /// together.
+    pub fn start_gate(mut self, gate: &StartGate) -> Self {
+        self.options.start_gate = Some(gate.clone());
+        self
+    }
+
+    /// Treats read errors during the first `grace` of the capture as a
+    /// failure to open.
+    ///
+    /// With a grace window, [`open`](Self::open) and
+    /// [`open_async`](Self::open_async) wait for up to `grace` after the
+    /// device is activated. If the capture thread hits an error in that
+    /// window (e.g. the device went down right after opening) the capture is
+    /// torn down and the error is returned from the constructor instead of
+    /// being delivered by [`AsyncCapture::next_packet()`]. Packets read
+    /// during the window are delivered normally, read timeouts are ignored,
+    /// and errors after the window are delivered as stream items.
+    pub fn startup_grace(mut self, grace: Duration) -> Self {
+        self.options.startup_grace = Some(grace);
+        self
+    }
+
+    /// Sets the rolling window used by [`AsyncCaptureHandle::throughput()`].
+    ///
+    /// Defaults to one second.
+    pub fn throughput_window(mut self, window: Duration) -> Self {
+        self.options.throughput_window = Some(window);
+        self
+    }
+
+    /// Queues the capture statistics every `interval`, in order with the
+    /// packets, for [`AsyncCapture::next_item()`] to return as
+    /// [`StreamItem::Stats`](crate::StreamItem::Stats).
+    ///
+    /// # Notes
+    ///
+    /// - The statistics are sampled between reads, so a marker can be late
+    ///   by up to the read timeout while the interface is idle.
+    /// - Sources without statistics, such as savefiles, queue no markers.
+    pub fn stats_interval(mut self, interval: Duration) -> Self {
+        self.options.stats_interval = Some(interval);
+        self
+    }
+
+    /// Drops packets that duplicate the packet immediately before them.
+    ///
+    /// Applied on the capture thread; see [`DedupMode`] for how packets are
+    /// compared. The number of suppressed packets is reported by
+    /// [`AsyncCaptureHandle::duplicates_suppressed()`].
+    pub fn dedup(mut self, mode: DedupMode) -> Self {
+        self.options.dedup = Some(mode);
+        self
+    }
+
+    /// Skips packets whose original length is below `bytes`.
+    ///
+    /// The length is checked on the capture thread before the packet is
+    /// copied out of the libpcap buffer, so rejected packets cost no
+    /// allocation, and before the [`filter_pipeline`](Self::filter_pipeline)
+    /// predicates run. It is the wire length (`header.len`), not the
+    /// captured one. Skipped packets are counted by
+    /// [`AsyncCaptureHandle::length_filtered()`].
+    pub fn min_len(mut self, bytes: u32) -> Self {
+        self.options.length.get_or_insert_default().min = Some(bytes);
+        self
+    }
+
+    /// Skips packets whose original length is above `bytes`.
+    ///
+    /// See [`min_len()`](Self::min_len); both bounds can be combined.
+    pub fn max_len(mut self, bytes: u32) -> Self {
+        self.options.length.get_or_insert_default().max = Some(bytes);
+        self
+    }
+
+    /// Skips packets for which `predicate` returns `false`, before they are
+    /// copied out of the libpcap buffer.
+    ///
+    /// The predicate gets the header and the captured bytes of every packet
+    /// on the capture thread, so it can make the decisions a BPF filter
+    /// cannot express, such as matching the payload against a pattern,
+    /// without the allocation and channel traffic of a packet that is then
+    /// dropped. Skipped packets are counted by
+    /// [`AsyncCaptureHandle::prefiltered()`].
+    ///
+    /// # Notes
+    ///
+    /// - It runs after the [`min_len()`](Self::min_len) and
+    ///   [`max_len()`](Self::max_len) bounds, and before the
+    ///   [`filter_pipeline`](Self::filter_pipeline) predicates.
+    /// - It runs for every packet the kernel delivers, so keep it cheap; a
+    ///   slow predicate holds up the capture thread and leads to kernel
+    ///   drops.
+    /// - It must be `Sync` because the builder can be cloned; keep its
+    ///   state in atomics or a `Mutex`.
+    pub fn prefilter<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&PacketHeader, &[u8]) -> bool + Send + Sync + 'static,
+    {
+        self.options.prefilter = Some(Prefilter::new(predicate));
+        self
+    }
+
+    /// Keeps one packet in every `n`, e.g. 100 for a 1% sample.
+    ///
+    /// Sampling is decided on the capture thread before the packet is
+    /// copied out of the libpcap buffer, so skipped packets cost no
+    /// allocation and never reach the channel. It is deterministic: the
+    /// first packet is kept, then every `n`th. Skipped packets are counted
+    /// by [`AsyncCaptureHandle::sampled_out()`].
+    ///
+    /// # Notes
+    ///
+    /// - It counts the packets past the [`min_len()`](Self::min_len) and
+    ///   [`max_len()`](Self::max_len) bounds and the
+    ///   [`prefilter()`](Self::prefilter), and runs before the
+    ///   [`filter_pipeline`](Self::filter_pipeline) predicates.
+    /// - A ratio of 0 or 1 keeps every packet.
+    /// - It can be combined with [`sample_rate()`](Self::sample_rate) and
+    ///   [`max_bytes_per_sec()`](Self::max_bytes_per_sec), which then cap
+    ///   the sampled packets.
+    pub fn sample_ratio(mut self, n: u64) -> Self {
+        self.options.sampling.one_in = Some(n);
+        self
+    }
+
+    /// Keeps at most `pps` packets per second and skips the rest.
+    ///
+    /// Unlike [`rate_limit()`](Self::rate_limit), which delays the packets
+    /// over the ceiling, the excess is discarded before it is copied, so
+    /// the capture thread keeps up with a saturated link. Bursts of up to
+    /// 100 ms worth of packets are kept whole. Skipped packets are counted
+    /// by [`AsyncCaptureHandle::sampled_out()`]; see
+    /// [`sample_ratio()`](Self::sample_ratio) for where sampling runs. A
+    /// rate of zero keeps nothing.
+    pub fn sample_rate(mut self, pps: u64) -> Self {
+        self.options.sampling.packets_per_sec = Some(pps);
+        self
+    }
+
+    /// Keeps at most `bytes` captured bytes (`caplen`) per second and skips
+    /// the packets over it.
+    ///
+    /// Behaves like [`sample_rate()`](Self::sample_rate), with a byte
+    /// budget instead of a packet count; both can be combined, and a packet
+    /// is kept only if it fits both. A packet larger than 100 ms worth of
+    /// the budget is kept once the budget is full.
+    pub fn max_bytes_per_sec(mut self, bytes: u64) -> Self {
+        self.options.sampling.bytes_per_sec = Some(bytes);
+        self
+    }
+
+    /// Keeps only the headers of each packet and drops its payload.
+    ///
+    /// Unlike [`snaplen()`](Self::snaplen), which keeps a fixed number of
+    /// bytes, every packet is decoded on the capture thread and cut right
+    /// after its transport header, as by
+    /// [`Packet::strip_payload()`](crate::Packet::strip_payload). Use
+    /// it for privacy-preserving captures, or to save memory when only the
+    /// headers matter. `header.len` still holds the original length.
+    ///
+    /// # Notes
+    ///
+    /// - Packets that are not IP keep their first 64 bytes, or as many as
+    ///   set with [`headers_only_fallback()`](Self::headers_only_fallback).
+    /// - The payload is dropped before the packet is recorded, checksummed
+    ///   or delivered, so checksum verification cannot cover it.
+    /// - The full packet is still copied from libpcap; combine with a
+    ///   `snaplen` to bound that copy as well.
+    pub fn headers_only(mut self, enabled: bool) -> Self {
+        self.options.headers_only = enabled;
+        self
+    }
+
+    /// Sets how many bytes [`headers_only()`](Self::headers_only) keeps of
+    /// packets whose headers cannot be decoded.
+    pub fn headers_only_fallback(mut self, bytes: usize) -> Self {
+        self.options.headers_fallback = Some(bytes);
+        self
+    }
+
+    /// Forwards packets only during the daily windows of `schedule`.
+    ///
+    /// Outside the windows packets are read and dropped; see
+    /// [`CaptureSchedule`].
+    pub fn schedule(mut self, schedule: CaptureSchedule) -> Self {
+        self.options.schedule = Some(schedule);
+        self
+    }
+
+    /// Drops the first `n` packets read from the device.
+    ///
+    /// Counting starts when the read loop starts, i.e. after the device is
+    /// opened and any [`start_gate()`](Self::start_gate) is released. Read
+    /// errors are always forwarded and do not count towards `n`. The
+    /// dropped packets still count towards
+    /// [`AsyncCaptureHandle::throughput()`].
+    ///
+    /// Packets dropped by [`warmup()`](Self::warmup) or
+    /// [`dedup()`](Self::dedup) do not count towards `n`, so that `n`
+    /// packets those would have forwarded are dropped by this option.
+    pub fn discard_first(mut self, n: u64) -> Self {
+        self.options.discard_first = n;
+        self
+    }
+
+    /// Drops every packet read within `period` of the read loop starting.
+    ///
+    /// The period starts after the device is opened and any
+    /// [`start_gate()`](Self::start_gate) is released, and a packet is
+    /// dropped if its read returns before the period ends; packets already
+    /// in the kernel buffer when the period ends are forwarded. Read errors
+    /// are always forwarded.
+    pub fn warmup(mut self, period: Duration) -> Self {
+        self.options.warmup = Some(period);
+        self
+    }
+
+    /// Stops the capture once the packets delivered would exceed `n`
+    /// captured bytes in total.
+    ///
+    /// Bytes are counted from `caplen`, the captured length, of every
+    /// packet that would be delivered; packets dropped on the capture
+    /// thread do not count. The packet that would push the total past `n`
+    /// is not delivered, so the consumer receives at most `n` bytes. The
+    /// capture then stops as if [`AsyncCaptureHandle::stop()`] had been
+    /// called and its [`CaptureSummary`](crate::CaptureSummary) reports
+    /// [`EndReason::LimitReached`](crate::EndReason::LimitReached). Combined
+    /// with other limits, whichever is reached first ends the capture.
+    pub fn max_bytes(mut self, n: u64) -> Self {
+        self.options.max_bytes = Some(n);
+        self
+    }
+
+    /// Records every delivered packet into a memory-mapped ring file.
+    ///
+    /// The file is created when the device is opened, so opening fails with
+    /// [`Error::Setup`] if it cannot be created. The capture thread copies
+    /// each packet into the mapping before delivering it, overwriting the
+    /// oldest records once the ring is full, and finalizes the file when the
+    /// capture ends. See [`RingFile`](crate::RingFile) for the file layout.
+    /// Requires the `ring-file` feature.
+    #[cfg(feature = "ring-file")]
+    pub fn ring_file(mut self, config: RingFileConfig) -> Self {
+        self.ring_file = Some(config);
+        self
+    }
+
+    /// Copies every delivered packet into `recorder`, which keeps the most
+    /// recent ones in memory.
+    ///
+    /// The recorder takes the link-layer type, snaplen and timestamp
+    /// precision of the capture when the device is opened. Keep a clone of
+    /// it to [`dump_to()`](RingRecorder::dump_to) a file on demand, e.g.
+    /// when an incident is detected.
+    pub fn record_to(mut self, recorder: RingRecorder) -> Self {
+        self.options.recorder = Some(recorder);
+        self
+    }
+
+    /// Stops the capture when `token` is cancelled.
+    ///
+    /// See [`AsyncCaptureHandle::cancel_on()`], which this calls once the
+    /// capture is open. Requires the `cancellation` feature.
+    #[cfg(feature = "cancellation")]
+    pub fn cancel_on(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Sets how long the capture thread waits between polls while a
+    /// non-blocking capture is idle.
+    ///
+    /// See [`IdleBackoff`] for the curve; only used after
+    /// [`AsyncCaptureHandle::set_nonblocking()`] is enabled.
+    pub fn idle_backoff(mut self, backoff: IdleBackoff) -> Self {
+        self.options.idle_backoff = Some(backoff);
+        self
+    }
+
+    /// Retries transient read errors on the capture thread instead of
+    /// delivering them.
+    ///
+    /// See [`RetryPolicy`] for how errors are classified and when the
+    /// capture gives up.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.options.retry = Some(policy);
+        self
+    }
+
+    /// Reopens the device when the capture loses it instead of ending the
+    /// capture.
+    ///
+    /// See [`ReconnectPolicy`] for when the device is reopened and how the
+    /// loss is reported on [`AsyncCapture::next_item()`]. The device is
+    /// reopened with every setting of this builder, as of the time the
+    /// capture was opened, and the filter last installed by
+    /// [`AsyncCaptureHandle::set_filter()`] is installed again.
+    pub fn reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = Some(policy);
+        self
+    }
+
+    /// Verifies the IP and transport checksums of every packet on the
+    /// capture thread.
+    ///
+    /// The results are returned with each packet by
+    /// [`AsyncCapture::next_packet_meta()`]. Outgoing packets commonly fail
+    /// verification because of checksum offload; see
+    /// [`Checksums`](crate::Checksums) before treating them as corrupt.
+    /// Requires the `checksums` feature.
+    #[cfg(feature = "checksums")]
+    pub fn validate_checksums(mut self, enabled: bool) -> Self {
+        self.options.checksums = enabled;
+        self
+    }
+
+    /// Records when each packet is queued and received, to measure how long
+    /// packets wait for the consumer.
+    ///
+    /// Per-packet timings are returned by
+    /// [`AsyncCapture::next_packet_timed()`] and aggregated by
+    /// [`AsyncCaptureHandle::queue_dwell()`]. Off by default, as it reads
+    /// the monotonic clock twice per packet.
+    pub fn timing_instrumentation(mut self, enabled: bool) -> Self {
+        self.options.timing = enabled;
+        self
+    }
+
+    /// Calls `callback` when the kernel drops more than `rate` (a fraction
+    /// from 0 to 1) of the packets in a sampling interval.
+    ///
+    /// The capture thread samples the drop counter once a second and
+    /// compares the packets dropped with those it read in the interval.
+    /// After an alert, the callback is not called again until the rate has
+    /// fallen below half of `rate`, so a sustained overload is reported
+    /// once. Use the alert to react, e.g. by narrowing the filter with
+    /// [`AsyncCaptureHandle::set_filter()`].
+    ///
+    /// # Notes
+    ///
+    /// - The callback runs on the capture thread, which reads no packets
+    ///   while it runs; keep it short or hand the alert to another task.
+    /// - Sources without statistics never raise an alert.
+    pub fn on_drop_threshold<F>(mut self, rate: f64, callback: F) -> Self
+    where
+        F: Fn(&DropAlert) + Send + Sync + 'static,
+    {
+        self.options.drop_threshold = Some(DropThreshold {
+            rate,
+            callback: Arc::new(callback),
+        });
+        self
+    }
+
+    /// Reads the time of the timing-dependent features from `clock`
+    /// instead of the system clocks.
+    ///
+    /// See [`Clock`] for the features it covers. Meant for tests, with a
+    /// [`ManualClock`](crate::ManualClock).
+    pub fn clock<C: Clock>(mut self, clock: C) -> Self {
+        self.options.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Calls `callback` with the final report once the capture has ended,
+    /// whatever ended it.
+    ///
+    /// Use it to flush files, close sockets or publish a final metric
+    /// without waiting on
+    /// [`AsyncCaptureHandle::wait_stopped()`]. The callback runs on the
+    /// capture thread after the read loop has exited, including after a
+    /// stop request, the end of the source, a read error or a panic in the
+    /// loop.
+    ///
+    /// # Notes
+    ///
+    /// - It runs before the summary is published and the end of the capture
+    ///   is signalled, so its work is done by the time
+    ///   [`AsyncCapture::next_packet()`] returns `None`; a slow callback
+    ///   delays both.
+    /// - It runs at most once, even if the builder is cloned and several
+    ///   captures are opened from it; the first one to end calls it.
+    /// - A panic in the callback is caught and logged.
+    pub fn on_stop<F>(mut self, callback: F) -> Self
+    where
+        F: FnOnce(CaptureSummary) + Send + 'static,
+    {
+        self.options.on_stop = Some(StopHook::new(Box::new(callback)));
+        self
+    }
+
+    /// Calls `callback` with each error the capture thread recovers from,
+    /// instead of only logging it.
+    ///
+    /// These errors do not reach the consumer as packets: transient read
+    /// errors retried under the [`retry_policy()`](Self::retry_policy), the
+    /// loss of the device and the failed attempts to reopen it under the
+    /// [`reconnect()`](Self::reconnect) policy, failures to restore
+    /// non-blocking mode on the reopened device, and failures to record a
+    /// packet to the ring file. Errors that end the capture are still
+    /// delivered as packets.
+    ///
+    /// # Notes
+    ///
+    /// - The callback runs on the capture thread, which does not read while
+    ///   it runs; keep it short.
+    /// - A panic in the callback ends the capture with
+    ///   [`Error::CapturePanicked`].
+    pub fn on_error<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Error) + Send + Sync + 'static,
+    {
+        self.options.on_error = Some(ErrorHook::new(Arc::new(callback)));
+        self
+    }
+
+    /// Sends packets to the consumer in batches of up to `max_packets`
+    /// instead of one channel operation per packet.
+    ///
+    /// A partial batch is sent once its first packet has waited
+    /// `max_delay`. Packets are still received one at a time; only the
+    /// channel traffic between the capture thread and the consumer is
+    /// reduced, which lowers contention at high packet rates.
+    ///
+    /// # Notes
+    ///
+    /// - The delay is checked after each read, so on a quiet link a partial
+    ///   batch can wait until the pending read returns, i.e. up to the read
+    ///   timeout; keep the timeout no longer than the latency you accept.
+    /// - A `max_packets` of 0 or 1 disables batching.
+    pub fn send_batch(mut self, max_packets: usize, max_delay: Duration) -> Self {
+        self.options.send_batch = Some(SendBatch {
+            max_packets,
+            max_delay,
+        });
+        self
+    }
+
+    /// Bounds the queue between the capture thread and the consumer to
+    /// `capacity` packets, applying `policy` once it is full.
+    ///
+    /// By default the queue is unbounded, so a consumer that falls behind
+    /// on a fast link lets memory grow without limit. With a bound, the
+    /// capture thread either waits for room
+    /// ([`OverflowPolicy::Block`]), or drops the packet just read or the
+    /// oldest queued one; drops are counted by
+    /// [`AsyncCaptureHandle::overflow_drops()`].
+    ///
+    /// # Notes
+    ///
+    /// - Packets waiting in a partial [`send_batch()`](Self::send_batch)
+    ///   count towards the capacity; the packets of a whole batch are
+    ///   evicted at once by [`OverflowPolicy::DropOldest`], and its errors
+    ///   are kept.
+    /// - While the consumer is taking a packet at the moment the queue is
+    ///   full, `DropOldest` drops the new packet instead.
+    /// - Packets the consumer has already taken, e.g. the rest of a batch
+    ///   it is working through, are not counted.
+    /// - A capacity of 0 is treated as 1.
+    pub fn bounded(mut self, capacity: usize, policy: OverflowPolicy) -> Self {
+        self.options.bounded = Some((capacity, policy));
+        self
+    }
+
+    /// Recycles packet buffers through a pool of up to `pool_size` free
+    /// buffers, as [`AsyncCapture::with_buffer_pool()`] does.
+    ///
+    /// Receive the packets with [`AsyncCapture::next_pooled_packet()`], or
+    /// with `next_bytes_packet()` under the `bytes` feature, so that their
+    /// buffers go back to the pool once dropped.
+    ///
+    /// Every buffer is as large as the snaplen, queued packets included;
+    /// see the notes of [`AsyncCapture::with_buffer_pool()`].
+    pub fn buffer_pool(mut self, pool_size: usize) -> Self {
+        self.pool_size = Some(pool_size);
+        self
+    }
+
+    /// Reads up to `packets` packets in a row on the capture thread before
+    /// it checks for stop requests, queued commands and the statistics
+    /// timers again.
+    ///
+    /// A batch ends early as soon as a read finds no packet or fails, so on
+    /// a quiet link the thread behaves as without batching. Under load,
+    /// larger batches save the per-packet housekeeping and raise the
+    /// throughput; smaller ones make the thread react sooner. This is
+    /// independent of [`send_batch()`](Self::send_batch), which groups the
+    /// channel traffic, and of the consumer's batch reads.
+    ///
+    /// # Notes
+    ///
+    /// - A stop request, a [`with_capture()`](crate::AsyncCaptureHandle::with_capture)
+    ///   command or a filter swap waits for the batch in progress, so under
+    ///   load the stop latency grows by up to `packets` reads.
+    /// - A value of 0 or 1 (the default) checks before every read.
+    pub fn read_batch(mut self, packets: usize) -> Self {
+        self.options.read_batch = packets;
+        self
+    }
+
+    /// Stamps packets with the network adapter's clock instead of the
+    /// host's, where the adapter supports it.
+    ///
+    /// Adapter timestamps are taken when the packet arrives at the NIC,
+    /// without the interrupt and scheduling delays included in host
+    /// timestamps. The timestamp type actually used is reported by
+    /// [`CaptureInfo::timestamp_source`]; the capture falls back to host
+    /// timestamps, with a warning, when the device offers none or enabling
+    /// them fails.
+    ///
+    /// The adapter timestamp takes the place of the host one in
+    /// `header.ts`. The host time of each packet is then reported apart, as
+    /// the [`PacketMeta::host_timestamp`](crate::PacketMeta::host_timestamp)
+    /// returned by [`AsyncCapture::next_packet_meta()`].
+    ///
+    /// # Notes
+    ///
+    /// - The host time is read when the capture thread reads the packet,
+    ///   not when the kernel received it, so it also includes the time the
+    ///   packet spent in the kernel buffer.
+    /// - The NIC and its driver must support hardware timestamping; on
+    ///   Linux, `ethtool -T <device>` lists the capabilities, and libpcap
+    ///   offers adapter timestamps only for devices that report
+    ///   `hardware-raw-clock` or `hardware-receive`.
+    /// - Enabling them on Linux needs `CAP_NET_ADMIN`, and affects every
+    ///   socket using the device.
+    /// - Timestamps keep microsecond precision unless
+    ///   [`timestamp_precision()`](Self::timestamp_precision) asks for
+    ///   nanoseconds.
+    pub fn hardware_timestamps(mut self, enabled: bool) -> Self {
+        self.hardware_timestamps = enabled;
+        self
+    }
+
+    /// Asks libpcap for timestamps with `precision`; microseconds by
+    /// default.
+    ///
+    /// With [`Precision::Nano`] the `ts.tv_usec` field of each packet
+    /// header holds nanoseconds, so read the timestamps with
+    /// [`Packet::since_epoch_with()`](crate::Packet::since_epoch_with) and
+    /// record them with a writer set to the same precision, e.g.
+    /// [`PcapNgWriter::with_precision()`](crate::PcapNgWriter::with_precision).
+    /// The precision actually used is reported by
+    /// [`CaptureInfo::precision`]; the capture falls back to microseconds,
+    /// with a warning, when the device does not offer nanoseconds.
+    ///
+    /// # Notes
+    ///
+    /// - Nanosecond precision is mostly useful with
+    ///   [`hardware_timestamps()`](Self::hardware_timestamps); host
+    ///   timestamps are rarely accurate to better than a microsecond.
+    /// - The crate's own timestamp-based features, such as
+    ///   [`AsyncCapture::merge_ordered()`], deduplication windows and
+    ///   reassembly timeouts, read timestamps as microseconds and are
+    ///   thrown off by nanosecond ones.
+    pub fn timestamp_precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Flags packets larger than the link MTU, which were coalesced by
+    /// receive offload (LRO/GRO) before they were captured.
+    ///
+    /// Flagged packets report [`PacketMeta::coalesced`](crate::PacketMeta::coalesced)
+    /// and are counted by [`AsyncCaptureHandle::coalesced_packets()`]; the
+    /// first one logs a warning suggesting how to disable the offload. The
+    /// MTU is read from the interface on Linux and assumed to be 1500
+    /// bytes elsewhere. Packets are flagged, not split.
+    pub fn detect_offload(mut self, enabled: bool) -> Self {
+        self.options.offload_mtu = enabled.then_some(offload::DEFAULT_MTU);
+        self
+    }
+
+    /// Attributes each TCP and UDP packet of the local host to the process
+    /// owning its socket, and attaches it as
+    /// [`PacketMeta::process`](crate::PacketMeta::process).
+    ///
+    /// Requires the `process-info` feature. The ports of each packet are
+    /// matched against the socket tables in `/proc/net`, and the socket
+    /// against the descriptors of the processes in `/proc/<pid>/fd`.
+    ///
+    /// # Notes
+    ///
+    /// - Best-effort and Linux-only; elsewhere no packet is attributed.
+    /// - The tables are cached, and rescanned on a thread of their own when
+    ///   a packet matches none of them, at most once a second; the capture
+    ///   thread does not wait for the scan. Packets of sockets opened since
+    ///   the last scan go unattributed until the next one completes, so
+    ///   sockets that live for less than a second may be missed.
+    /// - Other users' processes are only visible when running as root.
+    /// - Forwarded traffic and traffic of other hosts match no local
+    ///   socket and are left unattributed.
+    #[cfg(feature = "process-info")]
+    pub fn annotate_process(mut self, enabled: bool) -> Self {
+        self.options.annotate_process = enabled;
+        self
+    }
+
+    /// Applies a preset tuned for the lowest possible delivery latency.
+    ///
+    /// This enables immediate mode, uses a small 64 KiB kernel buffer, a
+    /// 1 ms read timeout and, on Windows, `setmintocopy(0)` so the driver
+    /// never holds back packets to fill a batch.
+    ///
+    /// # Notes
+    ///
+    /// - The preset removes the batching delays of the kernel and the
+    ///   driver; what remains is the hand-off from the capture thread to
+    ///   the task awaiting [`AsyncCapture::next_packet()`], typically well
+    ///   under a millisecond. Measure it on the target host with
+    ///   [`timing_instrumentation()`](Self::timing_instrumentation).
+    /// - The small buffer trades drop resistance for latency. Under sustained
+    ///   high packet rates prefer a larger [`buffer_size`](Self::buffer_size).
+    /// - Options set after this call override the preset.
+    pub fn low_latency(mut self) -> Self {
+        self.immediate_mode = true;
+        self.buffer_size = Some(LOW_LATENCY_BUFFER_SIZE);
+        self.timeout = 1;
+        #[cfg(windows)]
+        {
+            self.min_to_copy = Some(0);
+        }
+        self
+    }
+
+    /// Opens the device and starts the asynchronous capture.
+    ///
+    /// Blocks for the [`startup_grace`](Self::startup_grace) window, if one
+    /// is set; prefer [`open_async`](Self::open_async) from async code.
+    pub fn open(self) -> Result<(AsyncCapture, AsyncCaptureHandle), Error> {
+        let grace = self.options.startup_grace;
+        let (capture, handle, startup) = self.spawn()?;
+        if let (Some(rx), Some(grace)) = (startup, grace) {
+            worker::wait_startup(rx, grace).inspect_err(|_| handle.stop())?;
+        }
+        Ok((capture, handle))
+    }
+
+    /// Opens the device and starts the asynchronous capture, waiting out the
+    /// [`startup_grace`](Self::startup_grace) window without blocking the
+    /// runtime. Requires the `rt-tokio` feature.
+    #[cfg(feature = "rt-tokio")]
+    pub async fn open_async(self) -> Result<(AsyncCapture, AsyncCaptureHandle), Error> {
+        let grace = self.options.startup_grace;
+        let (capture, handle, startup) = self.spawn()?;
+        if let (Some(rx), Some(grace)) = (startup, grace) {
+            tokio::task::spawn_blocking(move || worker::wait_startup(rx, grace))
+                .await
+                .map_err(|e| Error::Setup(e.to_string()))?
+                .inspect_err(|_| handle.stop())?;
+        }
+        Ok((capture, handle))
+    }
+
+    /// Opens the device and starts a blocking [`SyncCapture`].
+    ///
+    /// Behaves like [`open`](Self::open), including the wait for the
+    /// [`startup_grace`](Self::startup_grace) window.
+    pub fn open_sync(self) -> Result<(SyncCapture, AsyncCaptureHandle), Error> {
+        let (capture, handle) = self.open()?;
+        Ok((capture.into_sync(), handle))
+    }
+
+    /// Configures and activates the device, asking for adapter timestamps
+    /// if `hardware_timestamps` is set.
+    fn activate(
+        &self,
+        hardware_timestamps: bool,
+    ) -> Result<(Capture<Active>, TimestampSource), Error> {
+        #[cfg(feature = "remote")]
+        if let Some(remote) = &self.remote {
+            let cap = remote.open(self.snaplen, self.promisc, self.timeout)?;
+            return Ok((cap, TimestampSource::Host));
+        }
+        let mut inactive = Capture::from_device(self.device.clone())?
+            .promisc(self.promisc)
+            .snaplen(self.snaplen)
+            .timeout(self.timeout)
+            .immediate_mode(self.immediate_mode);
+        if let Some(size) = self.buffer_size {
+            inactive = inactive.buffer_size(size);
+        }
+        #[cfg(not(windows))]
+        if self.rfmon {
+            inactive = inactive.rfmon(true);
+        }
+        if self.precision != Precision::Micro
+            && !tstamp::request_precision(&inactive, self.precision)
+        {
+            warn!(
+                "{} offers no {:?} timestamp precision, using microseconds.",
+                self.device.name, self.precision
+            );
+        }
+        let mut timestamp_source = TimestampSource::Host;
+        if hardware_timestamps {
+            match tstamp::request_adapter(&inactive) {
+                Some(source) => timestamp_source = source,
+                None => warn!(
+                    "{} offers no adapter timestamps, using host timestamps.",
+                    self.device.name
+                ),
+            }
+        }
+        Ok((inactive.open()?, timestamp_source))
+    }
+
+    /// Lists the link-layer header types the device offers with the
+    /// builder's settings, to choose one with [`datalink()`](Self::datalink).
+    ///
+    /// The device is opened and closed again to query it, so this needs the
+    /// same privileges as capturing, and the list depends on settings such
+    /// as [`rfmon()`](Self::rfmon). The first entry is the type the device
+    /// captures with by default.
+    pub fn list_datalinks(&self) -> Result<Vec<Linktype>, Error> {
+        let (cap, _) = self.activate(false)?;
+        let default = cap.get_datalink();
+        let mut linktypes = cap.list_datalinks()?;
+        if let Some(at) = linktypes.iter().position(|linktype| *linktype == default) {
+            linktypes[..=at].rotate_right(1);
+        }
+        Ok(linktypes)
+    }
+
+    /// Opens the device with every setting applied to the capture itself,
+    /// the filter, direction and datalink among them.
+    fn open_device(&self) -> Result<(Capture<Active>, TimestampSource), Error> {
+        let program = self.filter_program();
+        let (mut cap, timestamp_source) = match self.activate(self.hardware_timestamps) {
+            Err(e) if self.hardware_timestamps => {
+                warn!("Adapter timestamps failed ({e}), falling back to host timestamps.");
+                self.activate(false)?
+            }
+            opened => opened?,
+        };
+        if let Some(linktype) = self.datalink {
+            cap.set_datalink(linktype)?;
+        }
+        if let Some(direction) = self.direction {
+            cap.direction(direction)?;
+        }
+        if let Some(program) = &program {
+            cap.filter(program, true)?;
+        }
+        #[cfg(windows)]
+        let cap = match self.min_to_copy {
+            Some(bytes) => cap.min_to_copy(bytes),
+            None => cap,
+        };
+        #[cfg(target_os = "linux")]
+        if let Some(group) = self.fanout_group {
+            fanout::join(cap.as_raw_fd(), group, self.fanout_mode)?;
+        }
+        Ok((cap, timestamp_source))
+    }
+
+    fn spawn(self) -> Result<(AsyncCapture, AsyncCaptureHandle, Option<Receiver<Error>>), Error> {
+        self.validate()?;
+        let local = LocalAddresses::of(&self.device);
+        let (cap, timestamp_source) = self.open_device()?;
+        let mut config = self.config();
+        let info = CaptureInfo::query(&cap);
+        config.snaplen = info.snaplen;
+        config.datalink = Some(info.datalink.0);
+        config.nanosecond_timestamps = info.precision == Precision::Nano;
+        let reconnect = self.reconnect.map(|policy| {
+            let builder = self.clone();
+            Reconnect {
+                policy,
+                reopen: Arc::new(move || builder.open_device().map(|(cap, _)| cap)),
+            }
+        });
+
+        // The program installed, with the host MAC filter folded in
+        #[cfg(feature = "tracing")]
+        let program = self.filter_program();
+        let mut options = self.options;
+        options.reconnect = reconnect;
+        if options.offload_mtu.is_some() {
+            let mtu = offload::link_mtu(&self.device.name).unwrap_or(offload::DEFAULT_MTU);
+            options.offload_mtu = Some(mtu);
+        }
+        if let Some(pool_size) = self.pool_size {
+            let buffer_len = usize::try_from(CaptureInfo::query(&cap).snaplen).unwrap_or(0);
+            options.buffer_pool = Some(BufferPool::new(pool_size, buffer_len));
+        }
+        options.local_addresses = Some(local);
+        options.timestamp_source = Some(timestamp_source);
+        options.config = Some(config);
+        if let Some(recorder) = &options.recorder {
+            recorder.set_info(&CaptureInfo::query(&cap));
+        }
+        #[cfg(feature = "tracing")]
+        {
+            options.span = Some(crate::logging::capture_span(
+                Some(&self.device.name),
+                program.as_deref(),
+            ));
+        }
+        #[cfg(feature = "ring-file")]
+        if let Some(config) = &self.ring_file {
+            let info = CaptureInfo::query(&cap);
+            let ring = RingFile::create(
+                config,
+                info.datalink,
+                info.snaplen.max(0) as u32,
+                info.precision,
+            )
+            .map_err(|e| Error::Setup(format!("cannot create {}: {e}", config.path.display())))?;
+            options.ring = Some(Arc::new(Mutex::new(ring)));
+        }
+        options.read_request = Some(ReadRequest {
+            timeout: Duration::from_millis(self.timeout.max(0) as u64),
+            immediate_mode: self.immediate_mode,
+        });
+        let spawned = AsyncCapture::with_options(cap, options);
+        #[cfg(feature = "cancellation")]
+        if let Some(token) = self.cancel {
+            spawned.1.cancel_on(token);
+        }
+        Ok(spawned)
+    }
+
+    /// Returns the plain-value settings of the builder as a
+    /// [`CaptureConfig`], e.g. to save them to a file.
+    ///
+    /// Settings that are not plain values, such as callbacks and filter
+    /// pipelines, are left out; see [`CaptureConfig`].
+    pub fn config(&self) -> CaptureConfig {
+        let length = self.options.length.unwrap_or_default();
+        CaptureConfig {
+            device: Some(self.device.name.clone()),
+            promisc: self.promisc,
+            snaplen: self.snaplen,
+            timeout_ms: self.timeout,
+            immediate_mode: self.immediate_mode,
+            buffer_size: self.buffer_size,
+            datalink: self.datalink.map(|linktype| linktype.0),
+            filter: self.filter.clone(),
+            host_mac: self.host_mac.as_ref().map(format_mac),
+            hardware_timestamps: self.hardware_timestamps,
+            nanosecond_timestamps: self.precision == Precision::Nano,
+            #[cfg(not(windows))]
+            rfmon: self.rfmon,
+            #[cfg(windows)]
+            rfmon: false,
+            min_len: length.min,
+            max_len: length.max,
+            max_bytes: self.options.max_bytes,
+            discard_first: self.options.discard_first,
+            warmup: self.options.warmup,
+            startup_grace: self.options.startup_grace,
+            throughput_window: self.options.throughput_window,
+            stats_interval: self.options.stats_interval,
+            timing_instrumentation: self.options.timing,
+            detect_offload: self.options.offload_mtu.is_some(),
+            read_batch: self.options.read_batch,
+            sample_ratio: self.options.sampling.one_in,
+            sample_rate: self.options.sampling.packets_per_sec,
+            max_bytes_per_sec: self.options.sampling.bytes_per_sec,
+        }
+    }
+
+    /// Checks that the filter, combined with the
+    /// [`only_host_mac()`](Self::only_host_mac) filter, compiles, without
+    /// opening the device.
+    ///
+    /// The program is compiled for the [`datalink()`](Self::datalink) if
+    /// one is set, and for Ethernet otherwise, as by [`compile_filter()`];
+    /// a device with another default link type may still reject it when
+    /// opened. Succeeds if there is no filter.
+    pub fn check_filter(&self) -> Result<(), Error> {
+        match self.filter_program() {
+            Some(program) => {
+                compile_filter(&program, self.datalink.unwrap_or(Linktype::ETHERNET)).map(drop)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that the snaplen, read timeout and buffer size are usable,
+    /// without opening the device.
+    ///
+    /// Opening fails with the same [`Error::Setup`] when they are not:
+    ///
+    /// - The snaplen must be between 1 and 262144 bytes, libpcap's maximum.
+    /// - The read timeout must not be negative.
+    /// - The buffer size, if set, must hold at least one packet of the
+    ///   snaplen.
+    pub fn validate(&self) -> Result<(), Error> {
+        if !(1..=MAX_SNAPLEN).contains(&self.snaplen) {
+            return Err(Error::Setup(format!(
+                "snaplen {} is outside 1..={MAX_SNAPLEN}",
+                self.snaplen
+            )));
+        }
+        if self.timeout < 0 {
+            return Err(Error::Setup(format!(
+                "read timeout {} ms is negative",
+                self.timeout
+            )));
+        }
+        if let Some(size) = self.buffer_size
+            && size < self.snaplen
+        {
+            return Err(Error::Setup(format!(
+                "buffer size {size} is smaller than the snaplen {}",
+                self.snaplen
+            )));
+        }
+        Ok(())
+    }
+
+    /// Combines the user filter with the host MAC filter.
+    fn filter_program(&self) -> Option<String> {
+        let host = self
+            .host_mac
+            .map(|mac| format!("ether host {}", format_mac(&mac)));
+        match (&self.filter, host) {
+            (Some(filter), Some(host)) => Some(format!("({filter}) and {host}")),
+            (Some(filter), None) => Some(filter.clone()),
+            (None, host) => host,
+        }
+    }
+}
+
+impl AsyncCapture {
+    /// Returns an [`AsyncCaptureBuilder`] for the given device, e.g.
+    /// `AsyncCapture::builder("eth0")`.
+    pub fn builder<D: Into<Device>>(device: D) -> AsyncCaptureBuilder {
+        AsyncCaptureBuilder::new(device)
+    }
+
+    /// Returns an [`AsyncCaptureBuilder`] for the default capture device.
+    ///
+    /// See [`AsyncCaptureBuilder::default_device()`].
+    pub fn default_device() -> Result<AsyncCaptureBuilder, Error> {
+        AsyncCaptureBuilder::default_device()
+    }
+}
+
+/// Formats a MAC address as six hex octets separated by `:`.
+fn format_mac(mac: &[u8; 6]) -> String {
+    let octets: Vec<String> = mac.iter().map(|b| format!("{b:02x}")).collect();
+    octets.join(":")
+}
+
+/// Parses six hex octets separated by `:` or `-`.
+pub(crate) fn parse_mac(mac: &str) -> Result<[u8; 6], Error> {
+    let invalid = || Error::Setup(format!("invalid MAC address {mac:?}"));
+    let mut octets = [0u8; 6];
+    let mut parts = mac.split([':', '-']);
+    for octet in &mut octets {
+        let part = parts.next().ok_or_else(invalid)?;
+        if part.len() != 2 || !part.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        *octet = u8::from_str_radix(part, 16).map_err(|_| invalid())?;
+    }
+    if parts.next().is_some() {
+        return Err(invalid());
+    }
+    Ok(octets)
+}
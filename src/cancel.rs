@@ -0,0 +1,76 @@
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::Thread;
+
+use tokio_util::sync::CancellationToken;
+
+use crate::async_pcap::AsyncCaptureHandle;
+use crate::logging::debug;
+
+impl AsyncCaptureHandle {
+    /// Stops the capture when `token` is cancelled, as part of the
+    /// application's shutdown.
+    ///
+    /// Cancelling the token behaves like [`stop()`](Self::stop): a blocking
+    /// read is interrupted, the packets already queued are delivered, and
+    /// pending calls to [`AsyncCapture::next_packet()`](crate::AsyncCapture::next_packet)
+    /// then return `None`. Requires the `cancellation` feature.
+    ///
+    /// # Notes
+    ///
+    /// - The token is watched by a thread of its own, so no Tokio runtime
+    ///   is needed. The thread exits once the capture has ended, whether or
+    ///   not the token was cancelled.
+    /// - Pass a [`child_token()`](CancellationToken::child_token) to stop
+    ///   this capture on its own as well as with the rest of the
+    ///   application.
+    /// - A token that is already cancelled stops the capture right away.
+    pub fn cancel_on(&self, token: CancellationToken) {
+        let handle = self.clone();
+        std::thread::spawn(move || {
+            let cancelled = block_on(async {
+                let mut cancelled = pin!(token.cancelled());
+                let mut ended = pin!(handle.wait_stopped());
+                std::future::poll_fn(|cx| {
+                    if cancelled.as_mut().poll(cx).is_ready() {
+                        Poll::Ready(true)
+                    } else if ended.as_mut().poll(cx).is_ready() {
+                        Poll::Ready(false)
+                    } else {
+                        Poll::Pending
+                    }
+                })
+                .await
+            });
+            if cancelled {
+                debug!("AsyncCapture cancellation token cancelled, stopping.");
+                handle.stop();
+            }
+        });
+    }
+}
+
+/// Wakes the thread blocked in [`block_on()`].
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Runs `future` to completion on the calling thread, parking it while
+/// the future is pending.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        std::thread::park();
+    }
+}
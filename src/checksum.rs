@@ -0,0 +1,251 @@
+use std::net::IpAddr;
+
+use pcap::Linktype;
+
+use crate::async_pcap::Packet;
+use crate::parse::{self, IPPROTO_ICMP, IPPROTO_ICMPV6, IPPROTO_TCP, IPPROTO_UDP, IpLayer};
+
+/// Result of verifying one checksum of a packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChecksumStatus {
+    /// The checksum matches the packet contents
+    Valid,
+    /// The checksum does not match the packet contents
+    Invalid,
+    /// The checksum field is zero or holds only the pseudo-header sum,
+    /// which is what a sender that leaves the checksum to its NIC writes
+    Offloaded,
+    /// The layer carries no checksum, or the packet does not have the layer
+    NotApplicable,
+    /// The bytes needed were not captured, because of the snaplen or
+    /// because the datagram is an IP fragment
+    Incomplete,
+}
+
+/// Checksum verification results for the network and transport layers.
+///
+/// # Checksum offload
+///
+/// Packets *sent* by the capturing host are usually captured before the
+/// NIC fills in their TCP/UDP checksums, so their checksums are zero or
+/// partial and would not match. Such values are reported as
+/// [`ChecksumStatus::Offloaded`] rather than `Invalid` when they have the
+/// recognizable zero or pseudo-header-only form, but drivers are not
+/// consistent: an `Invalid` transport checksum on an outgoing packet is far
+/// more likely to be offload than corruption. Only received packets give a
+/// reliable signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Checksums {
+    /// IPv4 header checksum; IPv6 has none
+    pub ip: ChecksumStatus,
+    /// TCP, UDP, ICMP or ICMPv6 checksum
+    pub transport: ChecksumStatus,
+}
+
+impl Checksums {
+    /// Verifies the checksums of a packet of the given link type.
+    ///
+    /// Link types whose IP packet cannot be located, and packets that are
+    /// not IP, report [`ChecksumStatus::NotApplicable`] for both layers.
+    pub fn verify(datalink: Linktype, data: &[u8]) -> Self {
+        let not_applicable = Self {
+            ip: ChecksumStatus::NotApplicable,
+            transport: ChecksumStatus::NotApplicable,
+        };
+        let Some(ip) = parse::network_payload(datalink, data).and_then(parse::ip_layer) else {
+            return not_applicable;
+        };
+        let ip_status = match ip.src {
+            IpAddr::V4(_) if ip.header.len() >= 20 => {
+                if fold(sum(0, ip.header)) == 0xffff {
+                    ChecksumStatus::Valid
+                } else {
+                    ChecksumStatus::Invalid
+                }
+            }
+            _ => ChecksumStatus::NotApplicable,
+        };
+        Self {
+            ip: ip_status,
+            transport: transport(&ip),
+        }
+    }
+}
+
+impl Packet {
+    /// Verifies the IP and transport checksums of the packet.
+    ///
+    /// Requires the `checksums` feature; see [`Checksums`] for the offload
+    /// caveat.
+    pub fn checksums(&self, datalink: Linktype) -> Checksums {
+        Checksums::verify(datalink, &self.data)
+    }
+}
+
+fn transport(ip: &IpLayer<'_>) -> ChecksumStatus {
+    let (field, pseudo) = match ip.protocol {
+        IPPROTO_TCP => (16, true),
+        IPPROTO_UDP => (6, true),
+        IPPROTO_ICMPV6 => (2, true),
+        IPPROTO_ICMP => (2, false),
+        _ => return ChecksumStatus::NotApplicable,
+    };
+    if ip.fragmented || ip.truncated {
+        return ChecksumStatus::Incomplete;
+    }
+    let segment = ip.payload;
+    let Some(stored) = segment.get(field..field + 2) else {
+        return ChecksumStatus::Incomplete;
+    };
+    let stored = u16::from_be_bytes([stored[0], stored[1]]);
+
+    let initial = if pseudo {
+        pseudo_header(ip, segment.len())
+    } else {
+        0
+    };
+    if fold(sum(initial, segment)) == 0xffff {
+        return ChecksumStatus::Valid;
+    }
+    match ip.protocol {
+        // A zero UDP checksum over IPv4 means none was computed
+        IPPROTO_UDP if stored == 0 && ip.src.is_ipv4() => ChecksumStatus::NotApplicable,
+        IPPROTO_TCP | IPPROTO_UDP if stored == 0 || stored == fold(initial) => {
+            ChecksumStatus::Offloaded
+        }
+        _ => ChecksumStatus::Invalid,
+    }
+}
+
+/// Sums the pseudo-header of the TCP/UDP/ICMPv6 checksum.
+fn pseudo_header(ip: &IpLayer<'_>, len: usize) -> u32 {
+    let mut acc = match (ip.src, ip.dst) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => sum(sum(0, &src.octets()), &dst.octets()),
+        (IpAddr::V6(src), IpAddr::V6(dst)) => sum(sum(0, &src.octets()), &dst.octets()),
+        _ => 0,
+    };
+    acc += u32::from(ip.protocol);
+    acc += (len as u32) >> 16;
+    acc += (len as u32) & 0xffff;
+    acc
+}
+
+/// Adds `data` to a ones' complement sum of 16-bit big-endian words.
+fn sum(mut acc: u32, data: &[u8]) -> u32 {
+    let mut words = data.chunks_exact(2);
+    for word in &mut words {
+        acc += u32::from(u16::from_be_bytes([word[0], word[1]]));
+        acc = (acc & 0xffff) + (acc >> 16);
+    }
+    if let [last] = words.remainder() {
+        acc += u32::from(*last) << 8;
+    }
+    acc
+}
+
+/// Folds a running sum into 16 bits.
+fn fold(mut acc: u32) -> u16 {
+    while acc > 0xffff {
+        acc = (acc & 0xffff) + (acc >> 16);
+    }
+    acc as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ChecksumStatus::*;
+
+    // Raw IP packets from 10.0.0.1 to 10.0.0.2, or 2001:db8::1 to
+    // 2001:db8::2, with checksums computed independently
+    const V4_UDP: &str = "4500002100014000401126c90a0000010a00000204d20035000da2f868656c6c6f";
+    const V4_TCP: &str =
+        "4500002800014000400626cd0a0000010a0000029c40005000000001000000005002ffffff4e0000";
+    const V6_UDP: &str = concat!(
+        "60000000000d114020010db800000000000000000000000120010db8000000000000000000000002",
+        "04d20035000d5b8668656c6c6f"
+    );
+    const V6_TCP: &str = concat!(
+        "600000000014064020010db800000000000000000000000120010db8000000000000000000000002",
+        "9c40005000000001000000005002ffffb7dc0000"
+    );
+    /// `V4_UDP` without a UDP checksum
+    const V4_UDP_ZERO: &str = "4500002100014000401126c90a0000010a00000204d20035000d000068656c6c6f";
+    /// `V4_TCP` with only the pseudo-header sum in the checksum field
+    const V4_TCP_OFFLOADED: &str =
+        "4500002800014000400626cd0a0000010a0000029c40005000000001000000005002ffff141d0000";
+    /// `V4_TCP` with the low bit of the checksum flipped
+    const V4_TCP_CORRUPT: &str =
+        "4500002800014000400626cd0a0000010a0000029c40005000000001000000005002ffffff4f0000";
+    /// `V4_UDP` as the first fragment of a larger datagram
+    const V4_UDP_FRAGMENT: &str =
+        "4500002100012000401146c90a0000010a00000204d20035000da2f868656c6c6f";
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        hex.as_bytes()
+            .chunks(2)
+            .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap())
+            .collect()
+    }
+
+    fn verify(hex: &str) -> (ChecksumStatus, ChecksumStatus) {
+        let checksums = Checksums::verify(Linktype::RAW, &bytes(hex));
+        (checksums.ip, checksums.transport)
+    }
+
+    #[test]
+    fn ipv4_header_sum_matches_the_reference_value() {
+        // The textbook example header, whose checksum is b861
+        let mut header = bytes("450000730000400040110000c0a80001c0a800c7");
+        assert_eq!(!fold(sum(0, &header)), 0xb861);
+        header[10..12].copy_from_slice(&[0xb8, 0x61]);
+        assert_eq!(fold(sum(0, &header)), 0xffff);
+    }
+
+    #[test]
+    fn valid_packets_are_valid() {
+        assert_eq!(verify(V4_UDP), (Valid, Valid));
+        assert_eq!(verify(V4_TCP), (Valid, Valid));
+        assert_eq!(verify(V6_UDP), (NotApplicable, Valid));
+        assert_eq!(verify(V6_TCP), (NotApplicable, Valid));
+    }
+
+    #[test]
+    fn corrupted_checksums_are_invalid() {
+        let mut packet = bytes(V4_UDP);
+        // The TTL is not covered by the UDP checksum
+        packet[8] -= 1;
+        let checksums = Checksums::verify(Linktype::RAW, &packet);
+        assert_eq!((checksums.ip, checksums.transport), (Invalid, Valid));
+        assert_eq!(verify(V4_TCP_CORRUPT), (Valid, Invalid));
+    }
+
+    #[test]
+    fn offloaded_and_missing_checksums_are_recognized() {
+        assert_eq!(verify(V4_UDP_ZERO), (Valid, NotApplicable));
+        assert_eq!(verify(V4_TCP_OFFLOADED), (Valid, Offloaded));
+        // Over IPv6 a zero UDP checksum is not allowed, so it is offload
+        let mut packet = bytes(V6_UDP);
+        packet[46..48].fill(0);
+        let checksums = Checksums::verify(Linktype::RAW, &packet);
+        assert_eq!(checksums.transport, Offloaded);
+    }
+
+    #[test]
+    fn fragments_and_truncated_packets_are_incomplete() {
+        assert_eq!(verify(V4_UDP_FRAGMENT), (Valid, Incomplete));
+        let packet = bytes(V4_TCP);
+        let checksums = Checksums::verify(Linktype::RAW, &packet[..30]);
+        assert_eq!((checksums.ip, checksums.transport), (Valid, Incomplete));
+    }
+
+    #[test]
+    fn other_packets_are_not_applicable() {
+        let arp = [0xff; 14];
+        let checksums = Checksums::verify(Linktype::ETHERNET, &arp);
+        assert_eq!(
+            (checksums.ip, checksums.transport),
+            (NotApplicable, NotApplicable)
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Source of time for the timing-dependent parts of a capture.
+///
+/// The capture thread reads the time through a clock when it computes
+/// throughput, enforces a [`RateLimit`](crate::RateLimit), batches packets,
+/// checks a [`CaptureSchedule`](crate::CaptureSchedule), a warmup or a
+/// statistics interval, samples drops, and when it sleeps for any of them.
+/// The [`PacketTiming`](crate::PacketTiming) of each packet is taken from
+/// it too, on both sides of the queue, and so is the host time of packets
+/// stamped by the network adapter. [`SystemClock`] is used unless
+/// another clock is set with
+/// [`AsyncCaptureBuilder::clock()`](crate::AsyncCaptureBuilder::clock) or
+/// [`AsyncCapture::new_with_clock()`](crate::AsyncCapture::new_with_clock);
+/// [`ManualClock`] makes these features testable without sleeping.
+///
+/// Measurements of real delays, like the read probe and the startup grace
+/// window, always use the system clock.
+pub trait Clock: fmt::Debug + Send + Sync + 'static {
+    /// Current monotonic time.
+    fn now(&self) -> Instant;
+
+    /// Current wall-clock time.
+    fn system_now(&self) -> SystemTime;
+
+    /// Blocks the calling thread for `duration`.
+    ///
+    /// The capture thread sleeps in slices of at most 50 ms so that it
+    /// notices stop requests. The default implementation calls
+    /// `std::thread::sleep`.
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// The operating system's clocks.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to, for deterministic tests.
+///
+/// Clones share the same time, so a test keeps one clone and hands another
+/// to the capture. Sleeping on the clock advances it by the sleep duration
+/// and returns at once, so a rate-limited capture runs at full speed while
+/// observing the limit in clock time.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    /// Times at creation, and how far the clock was advanced since
+    state: Arc<Mutex<(Instant, SystemTime, Duration)>>,
+}
+
+impl ManualClock {
+    /// Creates a clock starting at the current time.
+    pub fn new() -> Self {
+        Self::starting_at(SystemTime::now())
+    }
+
+    /// Creates a clock whose wall-clock time starts at `time`.
+    pub fn starting_at(time: SystemTime) -> Self {
+        Self {
+            state: Arc::new(Mutex::new((Instant::now(), time, Duration::ZERO))),
+        }
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        self.state.lock().unwrap().2 += duration;
+    }
+
+    /// Time the clock was advanced by since it was created.
+    pub fn elapsed(&self) -> Duration {
+        self.state.lock().unwrap().2
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        let (start, _, offset) = *self.state.lock().unwrap();
+        start + offset
+    }
+
+    fn system_now(&self) -> SystemTime {
+        let (_, start, offset) = *self.state.lock().unwrap();
+        start + offset
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
@@ -0,0 +1,270 @@
+use std::time::Duration;
+
+use pcap::{Device, Linktype, Precision};
+
+use crate::async_pcap::{AsyncCapture, AsyncCaptureHandle};
+use crate::builder::AsyncCaptureBuilder;
+use crate::error::Error;
+
+/// Plain-data description of a capture, for saving setups to and loading
+/// them from configuration files.
+///
+/// Obtained from [`AsyncCaptureBuilder::config()`] or built by hand, and
+/// turned back into a builder with [`builder()`](Self::builder). With the
+/// `serde` feature it implements `Serialize` and `Deserialize`; missing
+/// fields take their [`Default`] values, which are those of
+/// [`AsyncCaptureBuilder::new()`].
+///
+/// # Notes
+///
+/// - Only the settings that are plain values are covered. Closures, start
+///   gates, filter pipelines, schedules, rate limits, retry policies and
+///   ring files must be added to the builder returned by
+///   [`builder()`](Self::builder).
+/// - Loading a configuration checks its values, as
+///   [`validate()`](Self::validate) does, but does not touch the system;
+///   the device is checked by [`builder()`](Self::builder), so a
+///   configuration written on one host can be read on another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, remote = "Self"))]
+pub struct CaptureConfig {
+    /// Name of the device; `None` captures on the default device
+    pub device: Option<String>,
+    /// Promiscuous mode
+    pub promisc: bool,
+    /// Maximum number of bytes captured per packet
+    pub snaplen: i32,
+    /// Read timeout in milliseconds
+    pub timeout_ms: i32,
+    /// Immediate mode
+    pub immediate_mode: bool,
+    /// Kernel capture buffer size in bytes; `None` keeps the platform
+    /// default
+    pub buffer_size: Option<i32>,
+    /// Link-layer header type, as a `DLT_` value
+    pub datalink: Option<i32>,
+    /// BPF filter, in `tcpdump` syntax
+    pub filter: Option<String>,
+    /// MAC address given to
+    /// [`only_host_mac()`](AsyncCaptureBuilder::only_host_mac)
+    pub host_mac: Option<String>,
+    /// Whether adapter timestamps are requested
+    pub hardware_timestamps: bool,
+    /// Whether nanosecond timestamps are requested
+    pub nanosecond_timestamps: bool,
+    /// Whether a wireless device is put in monitor mode
+    pub rfmon: bool,
+    /// Packets shorter than this on the wire are skipped
+    pub min_len: Option<u32>,
+    /// Packets longer than this on the wire are skipped
+    pub max_len: Option<u32>,
+    /// Budget of captured bytes after which the capture stops
+    pub max_bytes: Option<u64>,
+    /// Number of initial packets dropped
+    pub discard_first: u64,
+    /// Period after the start during which packets are dropped
+    pub warmup: Option<Duration>,
+    /// Window during which read errors fail the startup
+    pub startup_grace: Option<Duration>,
+    /// Rolling window of the throughput figures
+    pub throughput_window: Option<Duration>,
+    /// Period of the statistics markers returned by
+    /// [`AsyncCapture::next_item()`]
+    pub stats_interval: Option<Duration>,
+    /// Whether packets carry their queue timing
+    pub timing_instrumentation: bool,
+    /// Whether offload-coalesced packets are flagged
+    pub detect_offload: bool,
+    /// Packets read between two checks of the stop flag; 0 or 1 checks
+    /// before every read
+    pub read_batch: usize,
+    /// One packet in this many is kept
+    pub sample_ratio: Option<u64>,
+    /// Packets kept per second, at most
+    pub sample_rate: Option<u64>,
+    /// Captured bytes kept per second, at most
+    pub max_bytes_per_sec: Option<u64>,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            device: None,
+            promisc: false,
+            snaplen: 65535,
+            timeout_ms: 500,
+            immediate_mode: true,
+            buffer_size: None,
+            datalink: None,
+            filter: None,
+            host_mac: None,
+            hardware_timestamps: false,
+            nanosecond_timestamps: false,
+            rfmon: false,
+            min_len: None,
+            max_len: None,
+            max_bytes: None,
+            discard_first: 0,
+            warmup: None,
+            startup_grace: None,
+            throughput_window: None,
+            stats_interval: None,
+            timing_instrumentation: false,
+            detect_offload: false,
+            read_batch: 0,
+            sample_ratio: None,
+            sample_rate: None,
+            max_bytes_per_sec: None,
+        }
+    }
+}
+
+impl CaptureConfig {
+    /// Checks the values of the configuration, without looking up the
+    /// device.
+    ///
+    /// Fails with [`Error::Setup`] if the MAC address does not parse, a
+    /// value is out of range, as by [`AsyncCaptureBuilder::validate()`],
+    /// or monitor mode is requested on Windows.
+    pub fn validate(&self) -> Result<(), Error> {
+        let placeholder = Device::from(self.device.as_deref().unwrap_or_default());
+        self.apply(AsyncCaptureBuilder::new(placeholder))?
+            .validate()
+    }
+
+    /// Checks the configuration and returns a builder applying it.
+    ///
+    /// Fails as [`validate()`](Self::validate) does, with
+    /// [`Error::Setup`] if the device is not one of [`Device::list()`], and
+    /// with [`Error::NoDefaultDevice`] if no device is named and there is
+    /// no default one.
+    pub fn builder(&self) -> Result<AsyncCaptureBuilder, Error> {
+        self.validate()?;
+        let device = match &self.device {
+            Some(name) => Device::list()?
+                .into_iter()
+                .find(|device| &device.name == name)
+                .ok_or_else(|| Error::Setup(format!("unknown capture device {name:?}")))?,
+            None => Device::lookup()?.ok_or(Error::NoDefaultDevice)?,
+        };
+        self.apply(AsyncCaptureBuilder::new(device))
+    }
+
+    /// Applies the configuration to `builder`, checking the values the
+    /// builder does not.
+    fn apply(&self, builder: AsyncCaptureBuilder) -> Result<AsyncCaptureBuilder, Error> {
+        if let (Some(min), Some(max)) = (self.min_len, self.max_len)
+            && min > max
+        {
+            return Err(Error::Setup(format!(
+                "min_len {min} is above max_len {max}"
+            )));
+        }
+        if cfg!(windows) && self.rfmon {
+            return Err(Error::Setup(
+                "monitor mode is not supported on Windows".into(),
+            ));
+        }
+
+        let mut builder = builder
+            .promisc(self.promisc)
+            .snaplen(self.snaplen)
+            .timeout(self.timeout_ms)
+            .immediate_mode(self.immediate_mode)
+            .hardware_timestamps(self.hardware_timestamps)
+            .timestamp_precision(if self.nanosecond_timestamps {
+                Precision::Nano
+            } else {
+                Precision::Micro
+            })
+            .discard_first(self.discard_first)
+            .timing_instrumentation(self.timing_instrumentation)
+            .detect_offload(self.detect_offload)
+            .read_batch(self.read_batch);
+        if let Some(bytes) = self.buffer_size {
+            builder = builder.buffer_size(bytes);
+        }
+        #[cfg(not(windows))]
+        {
+            builder = builder.rfmon(self.rfmon);
+        }
+        if let Some(linktype) = self.datalink {
+            builder = builder.datalink(Linktype(linktype));
+        }
+        if let Some(filter) = &self.filter {
+            builder = builder.filter(filter.as_str());
+        }
+        if let Some(mac) = &self.host_mac {
+            builder = builder.only_host_mac(mac)?;
+        }
+        if let Some(bytes) = self.min_len {
+            builder = builder.min_len(bytes);
+        }
+        if let Some(bytes) = self.max_len {
+            builder = builder.max_len(bytes);
+        }
+        if let Some(n) = self.max_bytes {
+            builder = builder.max_bytes(n);
+        }
+        if let Some(period) = self.warmup {
+            builder = builder.warmup(period);
+        }
+        if let Some(grace) = self.startup_grace {
+            builder = builder.startup_grace(grace);
+        }
+        if let Some(window) = self.throughput_window {
+            builder = builder.throughput_window(window);
+        }
+        if let Some(interval) = self.stats_interval {
+            builder = builder.stats_interval(interval);
+        }
+        if let Some(n) = self.sample_ratio {
+            builder = builder.sample_ratio(n);
+        }
+        if let Some(pps) = self.sample_rate {
+            builder = builder.sample_rate(pps);
+        }
+        if let Some(bytes) = self.max_bytes_per_sec {
+            builder = builder.max_bytes_per_sec(bytes);
+        }
+        Ok(builder)
+    }
+
+    /// Checks the configuration and opens the capture it describes.
+    ///
+    /// Shorthand for [`builder()`](Self::builder) followed by
+    /// [`AsyncCaptureBuilder::open()`], so it blocks for the startup grace
+    /// window; prefer [`open_async()`](Self::open_async) from async code.
+    pub fn open(&self) -> Result<(AsyncCapture, AsyncCaptureHandle), Error> {
+        self.builder()?.open()
+    }
+
+    /// Checks the configuration and opens the capture it describes, without
+    /// blocking the runtime.
+    ///
+    /// Shorthand for [`builder()`](Self::builder) followed by
+    /// [`AsyncCaptureBuilder::open_async()`]. Requires the `rt-tokio`
+    /// feature.
+    #[cfg(feature = "rt-tokio")]
+    pub async fn open_async(&self) -> Result<(AsyncCapture, AsyncCaptureHandle), Error> {
+        self.builder()?.open_async().await
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for CaptureConfig {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        CaptureConfig::serialize(self, serializer)
+    }
+}
+
+/// Rejects configurations that [`CaptureConfig::validate()`] refuses.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for CaptureConfig {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let config = CaptureConfig::deserialize(deserializer)?;
+        config.validate().map_err(serde::de::Error::custom)?;
+        Ok(config)
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+use pcap::Precision;
+
+use crate::async_pcap::Packet;
+
+/// How consecutive packets are compared when suppressing duplicates.
+///
+/// A packet is dropped when it matches the packet immediately before it
+/// and their capture timestamps are at most `window` apart. Only the
+/// immediately preceding packet is considered, which is what SPAN/mirror
+/// setups that deliver every frame twice produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupMode {
+    /// Packets must be byte-for-byte identical, including their lengths
+    FullBytes {
+        /// Maximum capture-time distance between the two copies
+        window: Duration,
+    },
+    /// Only a hash of the first `header_len` bytes is compared
+    ///
+    /// Cheaper than [`DedupMode::FullBytes`] and tolerant to differences in
+    /// the payload, at the cost of a small chance of dropping a distinct
+    /// packet whose headers collide.
+    HeaderHash {
+        /// Number of leading bytes hashed
+        header_len: usize,
+        /// Maximum capture-time distance between the two copies
+        window: Duration,
+    },
+}
+
+impl DedupMode {
+    fn window(&self) -> Duration {
+        match self {
+            DedupMode::FullBytes { window } | DedupMode::HeaderHash { window, .. } => *window,
+        }
+    }
+
+    fn scope<'a>(&self, packet: &'a Packet) -> &'a [u8] {
+        match self {
+            DedupMode::FullBytes { .. } => &packet.data,
+            DedupMode::HeaderHash { header_len, .. } => {
+                &packet.data[..packet.data.len().min(*header_len)]
+            }
+        }
+    }
+}
+
+/// Remembers the previous packet and recognises its duplicate.
+#[derive(Debug)]
+pub(crate) struct Deduplicator {
+    mode: DedupMode,
+    /// Precision the packet timestamps are read with
+    precision: Precision,
+    last: Option<Previous>,
+}
+
+#[derive(Debug)]
+struct Previous {
+    hash: u64,
+    len: u32,
+    ts: Duration,
+    /// Copy of the previous packet, kept for full comparisons only
+    data: Option<Vec<u8>>,
+}
+
+impl Deduplicator {
+    pub(crate) fn new(mode: DedupMode, precision: Precision) -> Self {
+        Self {
+            mode,
+            precision,
+            last: None,
+        }
+    }
+
+    /// Returns `true` if `packet` duplicates the previous packet.
+    pub(crate) fn is_duplicate(&mut self, packet: &Packet) -> bool {
+        let scope = self.mode.scope(packet);
+        let mut hasher = DefaultHasher::new();
+        scope.hash(&mut hasher);
+        let hash = hasher.finish();
+        let ts = packet.since_epoch_with(self.precision);
+
+        let duplicate = self.last.as_ref().is_some_and(|prev| {
+            prev.hash == hash
+                && prev.len == packet.header.len
+                && ts.saturating_sub(prev.ts) <= self.mode.window()
+                && prev.data.as_deref().is_none_or(|data| data == scope)
+        });
+        if !duplicate {
+            self.last = Some(Previous {
+                hash,
+                len: packet.header.len,
+                ts,
+                data: matches!(self.mode, DedupMode::FullBytes { .. }).then(|| scope.to_vec()),
+            });
+        }
+        duplicate
+    }
+}
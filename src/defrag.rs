@@ -0,0 +1,701 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::time::Duration;
+
+use pcap::{Linktype, PacketHeader, Precision};
+
+use crate::async_pcap::{AsyncCapture, Packet};
+use crate::error::Error;
+use crate::parse::{self, MAX_EXTENSION_HEADERS};
+
+/// How often expired datagrams are swept, in capture time.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Largest IP datagram that can be reassembled, in bytes.
+const MAX_DATAGRAM_LEN: usize = 65535;
+
+/// Limits applied by an [`IpDefragmenter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DefragConfig {
+    /// Datagrams not completed this long after their first fragment (in
+    /// capture time) are dropped
+    pub timeout: Duration,
+    /// Maximum number of datagrams reassembled at the same time; the
+    /// oldest one is dropped to make room for a new one
+    pub max_datagrams: usize,
+}
+
+impl Default for DefragConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_datagrams: 256,
+        }
+    }
+}
+
+/// Reassembles fragmented IPv4 and IPv6 datagrams.
+///
+/// Feed it packets with [`push`](Self::push) or let it pull from a capture
+/// with [`next`](Self::next). Packets that are not fragments are passed
+/// through unchanged; fragments are held back until their datagram is
+/// complete, which is then returned as a single packet of the same link
+/// type. The output can be handed to parsers that ignore fragments, such
+/// as the [`TcpReassembler`](crate::TcpReassembler).
+///
+/// # Policy
+///
+/// - Fragments are matched on source, destination, protocol and
+///   identification.
+/// - The first copy of a byte wins: overlapping or duplicate fragments
+///   never replace bytes already received, as in current IP stacks.
+///   Fragments may arrive in any order.
+/// - The first fragment to end the datagram fixes its length; bytes past
+///   it are discarded.
+/// - The reassembled packet carries the link-layer and IP headers of the
+///   first fragment, with the length and fragment fields updated (and the
+///   IPv4 header checksum recomputed, the IPv6 fragment header removed),
+///   and the timestamp of the fragment that completed it.
+/// - A datagram is dropped [`DefragConfig::timeout`] after its first
+///   fragment arrived, or when [`DefragConfig::max_datagrams`] are already
+///   in flight and a new one starts; the oldest is dropped first.
+/// - Fragments cut short by the snaplen, and fragments reaching past
+///   65535 bytes of payload, cannot be reassembled: such fragments are
+///   passed through unchanged, as are all fragments if `max_datagrams` is
+///   0.
+/// - A completed datagram whose headers would push it past 65535 bytes is
+///   dropped, its fragments already consumed, and counted in
+///   [`oversized`](Self::oversized).
+/// - Memory is bounded by the number of datagrams in flight times their
+///   size, at most 64 KiB each.
+pub struct IpDefragmenter {
+    datalink: Linktype,
+    /// Precision the packet timestamps are read with
+    precision: Precision,
+    config: DefragConfig,
+    pending: HashMap<FragmentKey, Datagram>,
+    ready: VecDeque<Packet>,
+    last_sweep: Duration,
+    reassembled: u64,
+    timed_out: u64,
+    evicted: u64,
+    oversized: u64,
+}
+
+/// Fields identifying the fragments of one datagram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FragmentKey {
+    src: IpAddr,
+    dst: IpAddr,
+    protocol: u8,
+    id: u32,
+}
+
+/// One fragment, located in its packet.
+struct Fragment<'a> {
+    key: FragmentKey,
+    /// Byte offset of the payload in the datagram
+    offset: usize,
+    more: bool,
+    /// Link-layer header followed by the IP headers that precede the
+    /// fragmented part
+    prefix: &'a [u8],
+    /// Position in `prefix` of the IPv6 next-header field pointing at the
+    /// fragment header
+    next_header_at: Option<usize>,
+    payload: &'a [u8],
+}
+
+/// State of one datagram being reassembled.
+struct Datagram {
+    first_seen: Duration,
+    /// Headers of the first fragment, once it arrived
+    prefix: Option<(Vec<u8>, Option<usize>)>,
+    /// Length of the payload, once the last fragment arrived
+    total: Option<usize>,
+    data: Vec<u8>,
+    /// Sorted, disjoint ranges of `data` received so far
+    covered: Vec<(usize, usize)>,
+}
+
+impl IpDefragmenter {
+    /// Creates a defragmenter for packets of the given datalink with the
+    /// default limits.
+    pub fn new(datalink: Linktype) -> Self {
+        Self::with_config(datalink, DefragConfig::default())
+    }
+
+    /// Creates a defragmenter with custom limits.
+    pub fn with_config(datalink: Linktype, config: DefragConfig) -> Self {
+        Self {
+            datalink,
+            precision: Precision::Micro,
+            config,
+            pending: HashMap::new(),
+            ready: VecDeque::new(),
+            last_sweep: Duration::ZERO,
+            reassembled: 0,
+            timed_out: 0,
+            evicted: 0,
+            oversized: 0,
+        }
+    }
+
+    /// Reads the packet timestamps, which drive the timeouts, with
+    /// `precision`; microseconds by default.
+    ///
+    /// Set it to the [`CaptureInfo::precision`](crate::CaptureInfo::precision)
+    /// of a capture opened with nanosecond timestamps.
+    pub fn precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Number of datagrams currently being reassembled.
+    pub fn in_flight(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Number of datagrams reassembled so far.
+    pub fn reassembled(&self) -> u64 {
+        self.reassembled
+    }
+
+    /// Number of datagrams dropped because they were not completed within
+    /// [`DefragConfig::timeout`].
+    pub fn timed_out(&self) -> u64 {
+        self.timed_out
+    }
+
+    /// Number of datagrams dropped to stay within
+    /// [`DefragConfig::max_datagrams`].
+    pub fn evicted(&self) -> u64 {
+        self.evicted
+    }
+
+    /// Number of completed datagrams dropped because, with their headers,
+    /// they would exceed 65535 bytes.
+    pub fn oversized(&self) -> u64 {
+        self.oversized
+    }
+
+    /// Processes one packet and returns the packets that became available:
+    /// the packet itself if it is not a fragment, or the datagram it
+    /// completed.
+    pub fn push(&mut self, packet: Packet) -> impl Iterator<Item = Packet> + '_ {
+        self.process(packet);
+        self.ready.drain(..)
+    }
+
+    /// Pulls packets from `capture` until a packet or reassembled datagram
+    /// is available.
+    ///
+    /// Returns `None` once the capture has stopped; errors from the capture
+    /// are passed through.
+    pub async fn next(&mut self, capture: &AsyncCapture) -> Option<Result<Packet, Error>> {
+        loop {
+            if let Some(packet) = self.ready.pop_front() {
+                return Some(Ok(packet));
+            }
+            match capture.next_packet().await? {
+                Ok(packet) => self.process(packet),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+
+    fn process(&mut self, packet: Packet) {
+        let ts = packet.since_epoch_with(self.precision);
+        self.sweep(ts);
+
+        let truncated = packet.header.caplen < packet.header.len;
+        let Some(fragment) = fragment(self.datalink, &packet.data) else {
+            self.ready.push_back(packet);
+            return;
+        };
+        if truncated
+            || fragment.offset + fragment.payload.len() > MAX_DATAGRAM_LEN
+            || self.config.max_datagrams == 0
+        {
+            self.ready.push_back(packet);
+            return;
+        }
+
+        if !self.pending.contains_key(&fragment.key)
+            && self.pending.len() >= self.config.max_datagrams
+        {
+            let oldest = self
+                .pending
+                .iter()
+                .min_by_key(|(_, datagram)| datagram.first_seen)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                self.pending.remove(&oldest);
+                self.evicted += 1;
+            }
+        }
+        let datagram = self
+            .pending
+            .entry(fragment.key)
+            .or_insert_with(|| Datagram {
+                first_seen: ts,
+                prefix: None,
+                total: None,
+                data: Vec::new(),
+                covered: Vec::new(),
+            });
+        if fragment.offset == 0 && datagram.prefix.is_none() {
+            datagram.prefix = Some((fragment.prefix.to_vec(), fragment.next_header_at));
+        }
+        if !fragment.more && datagram.total.is_none() {
+            datagram.total = Some(fragment.offset + fragment.payload.len());
+        }
+        datagram.insert(fragment.offset, fragment.payload);
+
+        if datagram.is_complete() {
+            let key = fragment.key;
+            let datagram = self.pending.remove(&key).expect("datagram is pending");
+            match datagram.build(self.datalink, key.protocol, packet.header) {
+                Some(packet) => {
+                    self.reassembled += 1;
+                    self.ready.push_back(packet);
+                }
+                None => self.oversized += 1,
+            }
+        }
+    }
+
+    fn sweep(&mut self, now: Duration) {
+        if now.saturating_sub(self.last_sweep) < SWEEP_INTERVAL {
+            return;
+        }
+        self.last_sweep = now;
+        let timeout = self.config.timeout;
+        let before = self.pending.len();
+        self.pending
+            .retain(|_, datagram| now.saturating_sub(datagram.first_seen) <= timeout);
+        self.timed_out += (before - self.pending.len()) as u64;
+        if self.pending.is_empty() && self.pending.capacity() > self.config.max_datagrams {
+            self.pending.shrink_to(0);
+        }
+    }
+}
+
+impl Datagram {
+    /// Copies the bytes of `payload` at `offset` that were not received
+    /// yet.
+    fn insert(&mut self, offset: usize, payload: &[u8]) {
+        let end = match self.total {
+            Some(total) => (offset + payload.len()).min(total),
+            None => offset + payload.len(),
+        };
+        if offset >= end {
+            return;
+        }
+        if self.data.len() < end {
+            self.data.resize(end, 0);
+        }
+        let mut cursor = offset;
+        for &(start, stop) in &self.covered {
+            if stop <= cursor {
+                continue;
+            }
+            if start >= end {
+                break;
+            }
+            if start > cursor {
+                self.data[cursor..start].copy_from_slice(&payload[cursor - offset..start - offset]);
+            }
+            cursor = cursor.max(stop);
+            if cursor >= end {
+                break;
+            }
+        }
+        if cursor < end {
+            self.data[cursor..end].copy_from_slice(&payload[cursor - offset..end - offset]);
+        }
+
+        self.covered.push((offset, end));
+        self.covered.sort_unstable();
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(self.covered.len());
+        for &(start, stop) in &self.covered {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(stop),
+                _ => merged.push((start, stop)),
+            }
+        }
+        self.covered = merged;
+    }
+
+    fn is_complete(&self) -> bool {
+        match (self.total, &self.prefix, self.covered.first()) {
+            (Some(total), Some(_), Some(&(0, end))) => end >= total,
+            _ => false,
+        }
+    }
+
+    /// Builds the reassembled packet from the headers of the first
+    /// fragment.
+    fn build(self, datalink: Linktype, protocol: u8, last: PacketHeader) -> Option<Packet> {
+        let (mut prefix, next_header_at) = self.prefix?;
+        let total = self.total?;
+        let link_len = prefix.len() - parse::network_payload(datalink, &prefix)?.len();
+        let ip = &mut prefix[link_len..];
+        match next_header_at {
+            None => {
+                // IPv4: update the length, clear the fragment fields but DF
+                let len = u16::try_from(ip.len() + total).ok()?;
+                ip[2..4].copy_from_slice(&len.to_be_bytes());
+                ip[6] &= 0x40;
+                ip[7] = 0;
+                ip[10..12].fill(0);
+                let checksum = !ipv4_checksum(ip);
+                ip[10..12].copy_from_slice(&checksum.to_be_bytes());
+            }
+            Some(at) => {
+                let len = u16::try_from(ip.len() - 40 + total).ok()?;
+                ip[4..6].copy_from_slice(&len.to_be_bytes());
+                prefix[at] = protocol;
+            }
+        }
+
+        let mut data = prefix;
+        data.extend_from_slice(&self.data[..total]);
+        let len = u32::try_from(data.len()).ok()?;
+        let header = PacketHeader {
+            ts: last.ts,
+            caplen: len,
+            len,
+        };
+        Some(Packet::with_header(header, data))
+    }
+}
+
+/// Locates the fragment carried by a packet, if it is one.
+fn fragment(datalink: Linktype, data: &[u8]) -> Option<Fragment<'_>> {
+    let ip = parse::network_payload(datalink, data)?;
+    let link_len = data.len() - ip.len();
+    match ip.first()? >> 4 {
+        4 => {
+            let ihl = usize::from(ip.first()? & 0x0f) * 4;
+            let total = usize::from(parse::be16(ip, 2)?);
+            let frag = parse::be16(ip, 6)?;
+            if ihl < 20 || total < ihl || frag & 0x3fff == 0 {
+                return None;
+            }
+            let src: [u8; 4] = ip.get(12..16)?.try_into().ok()?;
+            let dst: [u8; 4] = ip.get(16..20)?.try_into().ok()?;
+            Some(Fragment {
+                key: FragmentKey {
+                    src: IpAddr::from(src),
+                    dst: IpAddr::from(dst),
+                    protocol: *ip.get(9)?,
+                    id: u32::from(parse::be16(ip, 4)?),
+                },
+                offset: usize::from(frag & 0x1fff) * 8,
+                more: frag & 0x2000 != 0,
+                prefix: data.get(..link_len + ihl)?,
+                next_header_at: None,
+                payload: ip.get(ihl..total.min(ip.len()))?,
+            })
+        }
+        6 => {
+            let end = (40 + usize::from(parse::be16(ip, 4)?)).min(ip.len());
+            let mut next_header_at = 6;
+            let mut offset = 40;
+            for _ in 0..MAX_EXTENSION_HEADERS {
+                match *ip.get(next_header_at)? {
+                    0 | 43 | 60 | 135 | 139 | 140 => {
+                        next_header_at = offset;
+                        offset += (usize::from(*ip.get(offset + 1)?) + 1) * 8;
+                    }
+                    51 => {
+                        next_header_at = offset;
+                        offset += (usize::from(*ip.get(offset + 1)?) + 2) * 4;
+                    }
+                    44 => {
+                        let frag = parse::be16(ip, offset + 2)?;
+                        if frag & 0xfff9 == 0 {
+                            // An atomic fragment is a complete datagram
+                            return None;
+                        }
+                        let src: [u8; 16] = ip.get(8..24)?.try_into().ok()?;
+                        let dst: [u8; 16] = ip.get(24..40)?.try_into().ok()?;
+                        return Some(Fragment {
+                            key: FragmentKey {
+                                src: IpAddr::from(src),
+                                dst: IpAddr::from(dst),
+                                protocol: *ip.get(offset)?,
+                                id: parse::be32(ip, offset + 4)?,
+                            },
+                            offset: usize::from(frag & 0xfff8),
+                            more: frag & 0x0001 != 0,
+                            prefix: data.get(..link_len + offset)?,
+                            next_header_at: Some(link_len + next_header_at),
+                            payload: ip.get(offset + 8..end)?,
+                        });
+                    }
+                    _ => return None,
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Ones' complement sum of an IPv4 header.
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut acc: u32 = header
+        .chunks(2)
+        .map(|word| u32::from(u16::from_be_bytes([word[0], *word.get(1).unwrap_or(&0)])))
+        .sum();
+    while acc > 0xffff {
+        acc = (acc & 0xffff) + (acc >> 16);
+    }
+    acc as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+
+    const UDP: u8 = 17;
+
+    /// A raw IPv4 UDP fragment of datagram `id` holding `payload` at
+    /// `offset` bytes, captured `millis` after the epoch.
+    fn v4_fragment(id: u16, offset: usize, more: bool, payload: &[u8], millis: u64) -> Packet {
+        let total = (20 + payload.len()) as u16;
+        let frag = (offset / 8) as u16 | if more { 0x2000 } else { 0 };
+        let mut data = vec![0x45, 0];
+        data.extend_from_slice(&total.to_be_bytes());
+        data.extend_from_slice(&id.to_be_bytes());
+        data.extend_from_slice(&frag.to_be_bytes());
+        data.extend_from_slice(&[64, UDP, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2]);
+        data.extend_from_slice(payload);
+        Packet::new(data, UNIX_EPOCH + Duration::from_millis(millis))
+    }
+
+    /// A raw IPv6 UDP fragment of datagram `id`, as [`v4_fragment`].
+    fn v6_fragment(id: u32, offset: usize, more: bool, payload: &[u8], millis: u64) -> Packet {
+        let mut data = vec![0x60, 0, 0, 0];
+        data.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+        data.extend_from_slice(&[44, 64]);
+        data.extend_from_slice(&[0; 15]);
+        data.push(1);
+        data.extend_from_slice(&[0; 15]);
+        data.push(2);
+        let frag = offset as u16 | u16::from(more);
+        data.extend_from_slice(&[UDP, 0]);
+        data.extend_from_slice(&frag.to_be_bytes());
+        data.extend_from_slice(&id.to_be_bytes());
+        data.extend_from_slice(payload);
+        Packet::new(data, UNIX_EPOCH + Duration::from_millis(millis))
+    }
+
+    fn push_all(defrag: &mut IpDefragmenter, packets: Vec<Packet>) -> Vec<Packet> {
+        packets
+            .into_iter()
+            .flat_map(|packet| defrag.push(packet).collect::<Vec<_>>())
+            .collect()
+    }
+
+    /// The payload of a reassembled IPv4 datagram, checking its header.
+    fn v4_payload(packet: &Packet) -> &[u8] {
+        let ip = &packet.data;
+        assert_eq!(usize::from(parse::be16(ip, 2).unwrap()), ip.len());
+        assert_eq!(
+            parse::be16(ip, 6).unwrap() & 0x3fff,
+            0,
+            "fragment fields cleared"
+        );
+        assert_eq!(ipv4_checksum(&ip[..20]), 0xffff, "valid header checksum");
+        &ip[20..]
+    }
+
+    #[test]
+    fn fragments_in_order_are_reassembled() {
+        let mut defrag = IpDefragmenter::new(Linktype::RAW);
+        let out = push_all(
+            &mut defrag,
+            vec![
+                v4_fragment(7, 0, true, &[1; 16], 0),
+                v4_fragment(7, 16, true, &[2; 16], 1),
+                v4_fragment(7, 32, false, &[3; 5], 2),
+            ],
+        );
+        assert_eq!(out.len(), 1);
+        let payload = v4_payload(&out[0]);
+        assert_eq!(payload, [[1; 16].as_slice(), &[2; 16], &[3; 5]].concat());
+        assert_eq!(out[0].since_epoch(), Duration::from_millis(2));
+        assert_eq!((defrag.reassembled(), defrag.in_flight()), (1, 0));
+    }
+
+    #[test]
+    fn fragments_out_of_order_are_reassembled() {
+        let mut defrag = IpDefragmenter::new(Linktype::RAW);
+        let out = push_all(
+            &mut defrag,
+            vec![
+                v4_fragment(7, 32, false, &[3; 5], 0),
+                v4_fragment(7, 0, true, &[1; 16], 1),
+                v4_fragment(7, 16, true, &[2; 16], 2),
+            ],
+        );
+        assert_eq!(out.len(), 1);
+        assert_eq!(
+            v4_payload(&out[0]),
+            [[1; 16].as_slice(), &[2; 16], &[3; 5]].concat()
+        );
+    }
+
+    #[test]
+    fn overlapping_fragments_keep_the_first_copy() {
+        let mut defrag = IpDefragmenter::new(Linktype::RAW);
+        let out = push_all(
+            &mut defrag,
+            vec![
+                v4_fragment(7, 8, true, &[b'b'; 16], 0),
+                // Overlaps the end of the first fragment and the start of
+                // the second one
+                v4_fragment(7, 0, true, &[b'a'; 16], 1),
+                v4_fragment(7, 16, false, &[b'c'; 16], 2),
+                // A duplicate has nothing left to complete
+                v4_fragment(7, 16, false, &[b'd'; 16], 3),
+            ],
+        );
+        assert_eq!(out.len(), 1);
+        let expected = [[b'a'; 8].as_slice(), &[b'b'; 16], &[b'c'; 8]].concat();
+        assert_eq!(v4_payload(&out[0]), expected);
+        // The duplicate starts a datagram of its own
+        assert_eq!(defrag.in_flight(), 1);
+    }
+
+    #[test]
+    fn bytes_past_the_end_are_discarded() {
+        let mut defrag = IpDefragmenter::new(Linktype::RAW);
+        let out = push_all(
+            &mut defrag,
+            vec![
+                v4_fragment(7, 8, false, &[2; 8], 0),
+                v4_fragment(7, 0, true, &[1; 24], 1),
+            ],
+        );
+        assert_eq!(v4_payload(&out[0]), [[1; 8], [2; 8]].concat());
+    }
+
+    #[test]
+    fn incomplete_datagram_times_out() {
+        let config = DefragConfig {
+            timeout: Duration::from_secs(5),
+            max_datagrams: 16,
+        };
+        let mut defrag = IpDefragmenter::with_config(Linktype::RAW, config);
+        let out = push_all(
+            &mut defrag,
+            vec![
+                v4_fragment(7, 0, true, &[1; 16], 0),
+                v4_fragment(8, 0, true, &[1; 16], 4_000),
+            ],
+        );
+        assert!(out.is_empty());
+        assert_eq!(defrag.in_flight(), 2);
+
+        // Completing datagram 8 sweeps datagram 7, now 6 s old
+        let out = push_all(&mut defrag, vec![v4_fragment(8, 16, false, &[2; 4], 6_000)]);
+        assert_eq!(out.len(), 1);
+        assert_eq!((defrag.timed_out(), defrag.in_flight()), (1, 0));
+
+        // The rest of datagram 7 cannot complete it any more
+        let out = push_all(&mut defrag, vec![v4_fragment(7, 16, false, &[2; 4], 6_500)]);
+        assert!(out.is_empty());
+        assert_eq!(defrag.in_flight(), 1);
+    }
+
+    #[test]
+    fn nanosecond_timestamps_drive_the_timeout() {
+        let config = DefragConfig {
+            timeout: Duration::from_secs(5),
+            max_datagrams: 16,
+        };
+        let mut defrag =
+            IpDefragmenter::with_config(Linktype::RAW, config).precision(Precision::Nano);
+        let first = v4_fragment(7, 0, true, &[1; 16], 0);
+        let mut last = v4_fragment(7, 16, false, &[2; 4], 0);
+        // 1 s after the first as nanoseconds, 1000 s as microseconds
+        last.header.ts.tv_usec = 999_999_999;
+        let out = push_all(&mut defrag, vec![first, last]);
+        assert_eq!(out.len(), 1);
+        assert_eq!(defrag.timed_out(), 0);
+    }
+
+    #[test]
+    fn oldest_datagram_is_evicted_at_the_limit() {
+        let config = DefragConfig {
+            timeout: Duration::from_secs(30),
+            max_datagrams: 2,
+        };
+        let mut defrag = IpDefragmenter::with_config(Linktype::RAW, config);
+        let out = push_all(
+            &mut defrag,
+            vec![
+                v4_fragment(1, 0, true, &[1; 8], 0),
+                v4_fragment(2, 0, true, &[2; 8], 1),
+                v4_fragment(3, 0, true, &[3; 8], 2),
+                v4_fragment(1, 8, false, &[1; 8], 3),
+                v4_fragment(3, 8, false, &[3; 8], 4),
+            ],
+        );
+        assert_eq!(out.len(), 1);
+        assert_eq!(v4_payload(&out[0]), [3; 16]);
+        assert_eq!(defrag.evicted(), 2);
+    }
+
+    #[test]
+    fn non_fragments_and_truncated_fragments_pass_through() {
+        let mut defrag = IpDefragmenter::new(Linktype::RAW);
+        let whole = v4_fragment(7, 0, false, &[9; 10], 0);
+        let mut truncated = v4_fragment(8, 0, true, &[1; 16], 1);
+        truncated.header.len += 100;
+        let out = push_all(&mut defrag, vec![whole.clone(), truncated.clone()]);
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0].data, whole.data);
+        assert_eq!(out[1].data, truncated.data);
+        assert_eq!(defrag.in_flight(), 0);
+    }
+
+    #[test]
+    fn ipv6_fragments_lose_their_fragment_header() {
+        let mut defrag = IpDefragmenter::new(Linktype::RAW);
+        let out = push_all(
+            &mut defrag,
+            vec![
+                v6_fragment(42, 8, false, &[2; 3], 0),
+                v6_fragment(42, 0, true, &[1; 8], 1),
+            ],
+        );
+        assert_eq!(out.len(), 1);
+        let ip = &out[0].data;
+        assert_eq!(ip[6], UDP, "next header of the fixed header");
+        assert_eq!(usize::from(parse::be16(ip, 4).unwrap()), 11);
+        assert_eq!(&ip[40..], [[1; 8].as_slice(), &[2; 3]].concat());
+    }
+
+    #[test]
+    fn datagrams_too_long_with_their_headers_are_counted() {
+        let mut defrag = IpDefragmenter::new(Linktype::RAW);
+        let out = push_all(
+            &mut defrag,
+            vec![
+                v4_fragment(7, 0, true, &[1; 8], 0),
+                v4_fragment(7, 8, true, &[2; 65504], 1),
+                v4_fragment(7, 65512, false, &[3; 20], 2),
+            ],
+        );
+        assert!(out.is_empty());
+        assert_eq!(defrag.oversized(), 1);
+        assert_eq!((defrag.reassembled(), defrag.in_flight()), (0, 0));
+    }
+}
@@ -0,0 +1,134 @@
+use std::net::IpAddr;
+
+use pcap::{Address, ConnectionStatus, Device, DeviceFlags};
+use tokio::sync::oneshot;
+
+use crate::builder::AsyncCaptureBuilder;
+use crate::error::Error;
+use crate::local::link_address;
+
+/// A capture device, as listed by [`devices()`].
+///
+/// Carries what a device picker needs to show, and turns back into a
+/// [`Device`] or a builder to open the chosen one.
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    /// Name to open the device with, e.g. `eth0`
+    pub name: String,
+    /// Human readable description, mostly provided on Windows
+    pub description: Option<String>,
+    /// IP addresses with their netmask, broadcast and point-to-point
+    /// destination addresses, as reported by libpcap
+    pub addresses: Vec<Address>,
+    /// Interface flags and connection status, as reported by libpcap
+    pub flags: DeviceFlags,
+    /// Link-layer (MAC) address, where the platform exposes it
+    pub link: Option<[u8; 6]>,
+}
+
+impl DeviceInfo {
+    /// Whether the interface is up.
+    pub fn is_up(&self) -> bool {
+        self.flags.is_up()
+    }
+
+    /// Whether the interface is running, i.e. has its resources allocated.
+    pub fn is_running(&self) -> bool {
+        self.flags.is_running()
+    }
+
+    /// Whether the interface is a loopback interface.
+    pub fn is_loopback(&self) -> bool {
+        self.flags.is_loopback()
+    }
+
+    /// Whether the interface is a wireless interface.
+    pub fn is_wireless(&self) -> bool {
+        self.flags.is_wireless()
+    }
+
+    /// Whether the interface has a link, where libpcap can tell.
+    pub fn connection_status(&self) -> ConnectionStatus {
+        self.flags.connection_status.clone()
+    }
+
+    /// IP addresses of the interface, without their netmasks.
+    pub fn ip_addrs(&self) -> impl Iterator<Item = IpAddr> + '_ {
+        self.addresses.iter().map(|address| address.addr)
+    }
+
+    /// Returns the device, to open it with [`pcap::Capture`].
+    pub fn device(&self) -> Device {
+        Device {
+            name: self.name.clone(),
+            desc: self.description.clone(),
+            addresses: self.addresses.clone(),
+            flags: self.flags.clone(),
+        }
+    }
+
+    /// Starts configuring a capture on the device.
+    pub fn builder(&self) -> AsyncCaptureBuilder {
+        AsyncCaptureBuilder::new(self.device())
+    }
+}
+
+impl From<Device> for DeviceInfo {
+    fn from(device: Device) -> Self {
+        Self {
+            link: link_address(&device.name),
+            name: device.name,
+            description: device.desc,
+            addresses: device.addresses,
+            flags: device.flags,
+        }
+    }
+}
+
+impl From<DeviceInfo> for Device {
+    fn from(info: DeviceInfo) -> Self {
+        Self {
+            name: info.name,
+            desc: info.description,
+            addresses: info.addresses,
+            flags: info.flags,
+        }
+    }
+}
+
+/// Lists the capture devices, without blocking the task.
+///
+/// `Device::list()` can take a while, e.g. while libpcap probes USB or
+/// Bluetooth adapters, so it runs on a thread of its own; no Tokio runtime
+/// is needed.
+pub async fn devices() -> Result<Vec<DeviceInfo>, Error> {
+    off_task(|| Ok(Device::list()?.into_iter().map(DeviceInfo::from).collect())).await
+}
+
+/// Looks up the capture device called `name`, without blocking the task.
+///
+/// Returns `None` if no device has that name.
+pub async fn device_by_name(name: &str) -> Result<Option<DeviceInfo>, Error> {
+    let name = name.to_string();
+    off_task(move || {
+        Ok(Device::list()?
+            .into_iter()
+            .find(|device| device.name == name)
+            .map(DeviceInfo::from))
+    })
+    .await
+}
+
+/// Runs `lookup` on a new thread and waits for its result.
+async fn off_task<T, F>(lookup: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Error> + Send + 'static,
+{
+    let (tx, rx) = oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(lookup());
+    });
+    rx.await
+        .map_err(|_| Error::CapturePanicked("device lookup panicked".to_string()))?
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use pcap::Stat;
+
+/// How often the kernel drop counter is sampled.
+pub(crate) const DROP_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Fraction of the threshold the drop rate must fall below before the
+/// alert can fire again.
+const REARM_FRACTION: f64 = 0.5;
+
+type Callback = Arc<dyn Fn(&DropAlert) + Send + Sync>;
+
+/// A sampling interval in which the kernel dropped too many packets.
+///
+/// Passed to the callback of
+/// [`AsyncCaptureBuilder::on_drop_threshold()`](crate::AsyncCaptureBuilder::on_drop_threshold).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DropAlert {
+    /// Fraction of the packets in the interval that were dropped, from 0
+    /// to 1
+    pub rate: f64,
+    /// Packets the kernel dropped during the interval
+    pub dropped: u64,
+    /// Packets read by the capture thread during the interval
+    pub received: u64,
+    /// Length of the interval
+    pub interval: Duration,
+}
+
+/// Packets lost so far, broken down by where they were dropped.
+///
+/// Returned by
+/// [`AsyncCaptureHandle::drop_reasons()`](crate::AsyncCaptureHandle::drop_reasons).
+/// Categories the platform or source cannot report are `None`, which means
+/// unknown rather than zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DropReasons {
+    /// Packets the kernel dropped because the capture buffer was full;
+    /// a growing count calls for a larger
+    /// [`buffer_size()`](crate::AsyncCaptureBuilder::buffer_size) or a
+    /// narrower filter. `None` unless the source is a live capture
+    pub buffer_full: Option<u64>,
+    /// Packets dropped by the interface or its driver before they reached
+    /// the capture. Only reported on Linux; other platforms' libpcap
+    /// always reports zero
+    pub interface: Option<u64>,
+    /// Packets read and then discarded on the capture thread by the length
+    /// bounds, deduplication, schedule, filter pipeline or a pause. Packets
+    /// rejected by the kernel BPF filter are not drops and are not counted
+    pub filtered: u64,
+    /// Packets dropped on the capture thread because the bounded queue
+    /// towards the consumer was full; see
+    /// [`AsyncCaptureBuilder::bounded()`](crate::AsyncCaptureBuilder::bounded)
+    pub queue_full: u64,
+}
+
+impl DropReasons {
+    /// Splits the source statistics, if any, into categories.
+    pub(crate) fn new(stats: Option<Stat>, filtered: u64, queue_full: u64) -> Self {
+        Self {
+            buffer_full: stats.map(|stats| u64::from(stats.dropped)),
+            interface: stats
+                .filter(|_| cfg!(target_os = "linux"))
+                .map(|stats| u64::from(stats.if_dropped)),
+            filtered,
+            queue_full,
+        }
+    }
+
+    /// Total of the known categories.
+    pub fn total(&self) -> u64 {
+        self.buffer_full.unwrap_or(0)
+            + self.interface.unwrap_or(0)
+            + self.filtered
+            + self.queue_full
+    }
+}
+
+/// Threshold and callback configured on the builder.
+#[derive(Clone)]
+pub(crate) struct DropThreshold {
+    pub(crate) rate: f64,
+    pub(crate) callback: Callback,
+}
+
+impl fmt::Debug for DropThreshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DropThreshold")
+            .field("rate", &self.rate)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Samples the drop counter on the capture thread and fires the callback
+/// when the rate crosses the threshold.
+///
+/// The alert is edge-triggered: after firing, it stays quiet until the
+/// rate falls below half the threshold, so a sustained overload produces
+/// one alert rather than one per interval.
+pub(crate) struct DropMonitor {
+    threshold: DropThreshold,
+    sampled_at: Instant,
+    dropped: Option<u32>,
+    received: u64,
+    alarmed: bool,
+}
+
+impl DropMonitor {
+    pub(crate) fn new(threshold: DropThreshold, now: Instant) -> Self {
+        Self {
+            threshold,
+            sampled_at: now,
+            dropped: None,
+            received: 0,
+            alarmed: false,
+        }
+    }
+
+    /// Whether the next sample is due at `now`.
+    pub(crate) fn is_due(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.sampled_at) >= DROP_SAMPLE_INTERVAL
+    }
+
+    /// Takes a sample from the source statistics and the number of packets
+    /// read so far, at `now`.
+    pub(crate) fn sample(&mut self, stats: Option<Stat>, received: u64, now: Instant) {
+        let interval = now.saturating_duration_since(self.sampled_at);
+        self.sampled_at = now;
+        let Some(stats) = stats else {
+            return;
+        };
+        let previous = self.dropped.replace(stats.dropped);
+        let delivered = received - std::mem::replace(&mut self.received, received);
+        let Some(previous) = previous else {
+            // The first sample only sets the baseline
+            return;
+        };
+        // The kernel counter is 32 bits and wraps
+        let dropped = u64::from(stats.dropped.wrapping_sub(previous));
+        let total = dropped + delivered;
+        let rate = if total == 0 {
+            0.0
+        } else {
+            dropped as f64 / total as f64
+        };
+
+        if self.alarmed {
+            if rate < self.threshold.rate * REARM_FRACTION {
+                self.alarmed = false;
+            }
+        } else if dropped > 0 && rate >= self.threshold.rate {
+            self.alarmed = true;
+            (self.threshold.callback)(&DropAlert {
+                rate,
+                dropped,
+                received: delivered,
+                interval,
+            });
+        }
+    }
+}
@@ -0,0 +1,558 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use pcap::{Linktype, Precision};
+use tokio::sync::mpsc::{Receiver, Sender, channel};
+use tokio::sync::oneshot;
+
+use crate::async_pcap::Packet;
+use crate::info::CaptureInfo;
+use crate::logging::{debug, warn};
+
+const PCAP_MAGIC_MICRO: u32 = 0xa1b2_c3d4;
+const PCAP_MAGIC_NANO: u32 = 0xa1b2_3c4d;
+const FILE_HEADER_LEN: u64 = 24;
+const RECORD_HEADER_LEN: u64 = 16;
+
+/// Packets that may wait for the writer thread before
+/// [`AsyncDumper::write()`] waits for room.
+const DEFAULT_QUEUE_CAPACITY: usize = 4096;
+/// Snaplen recorded when none is configured.
+const DEFAULT_SNAPLEN: u32 = 65535;
+
+enum Command {
+    Packet(Packet),
+    Flush(oneshot::Sender<io::Result<()>>),
+    Close(oneshot::Sender<io::Result<()>>),
+}
+
+/// First error of the writer thread, kept to be reported by every later
+/// call.
+type Failure = Arc<Mutex<Option<(io::ErrorKind, String)>>>;
+
+/// Settings of an [`AsyncDumper`], created by [`AsyncDumper::builder()`].
+///
+/// The path is a template in which the following placeholders are
+/// replaced each time a file is opened:
+///
+/// - `{n}`: sequence number of the file, starting at 0
+/// - `{ts}`: Unix time in seconds at which the file was opened
+/// - `{time}`: the same time in UTC, as `YYYYMMDD-HHMMSS`
+///
+/// When a rotation is configured and the template has no placeholder, the
+/// sequence number is appended to the file stem, so that `capture.pcap`
+/// becomes `capture-0.pcap`, `capture-1.pcap` and so on. A template whose
+/// placeholders can repeat, such as `{time}` alone with rotations less
+/// than a second apart, truncates the earlier file.
+#[derive(Debug, Clone)]
+pub struct AsyncDumperBuilder {
+    template: PathBuf,
+    linktype: Linktype,
+    snaplen: u32,
+    precision: Precision,
+    rotate_size: Option<u64>,
+    rotate_packets: Option<u64>,
+    rotate_every: Option<Duration>,
+    max_files: Option<usize>,
+    queue_capacity: usize,
+}
+
+impl AsyncDumperBuilder {
+    /// Sets the link-layer header type recorded in the files; Ethernet by
+    /// default.
+    pub fn linktype(mut self, linktype: Linktype) -> Self {
+        self.linktype = linktype;
+        self
+    }
+
+    /// Sets the snapshot length recorded in the files; 65535 by default.
+    pub fn snaplen(mut self, snaplen: u32) -> Self {
+        self.snaplen = snaplen;
+        self
+    }
+
+    /// Sets the timestamp precision of the packets written.
+    ///
+    /// Must match the capture the packets come from: with
+    /// [`Precision::Nano`] libpcap stores nanoseconds in `ts.tv_usec`.
+    /// Microseconds by default.
+    pub fn precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Takes the linktype and snaplen from the capture the packets come
+    /// from, e.g. [`AsyncCapture::info()`](crate::AsyncCapture::info).
+    pub fn capture_info(mut self, info: &CaptureInfo) -> Self {
+        self.linktype = info.datalink;
+        self.snaplen = u32::try_from(info.snaplen).unwrap_or(DEFAULT_SNAPLEN);
+        self
+    }
+
+    /// Starts a new file before one would grow beyond `bytes`.
+    ///
+    /// A packet larger than the limit still gets a file of its own.
+    pub fn rotate_size(mut self, bytes: u64) -> Self {
+        self.rotate_size = Some(bytes);
+        self
+    }
+
+    /// Starts a new file after every `packets` packets.
+    pub fn rotate_packets(mut self, packets: u64) -> Self {
+        self.rotate_packets = Some(packets.max(1));
+        self
+    }
+
+    /// Starts a new file once the current one has been open for `interval`.
+    ///
+    /// The interval is checked as packets arrive, so on an idle link the
+    /// current file stays open until the next packet.
+    pub fn rotate_every(mut self, interval: Duration) -> Self {
+        self.rotate_every = Some(interval);
+        self
+    }
+
+    /// Keeps at most `files` files, deleting the oldest one this dumper
+    /// wrote whenever a rotation would exceed the limit.
+    pub fn max_files(mut self, files: usize) -> Self {
+        self.max_files = Some(files.max(1));
+        self
+    }
+
+    /// Sets how many packets may wait for the writer thread; 4096 by
+    /// default.
+    pub fn queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = capacity.max(1);
+        self
+    }
+
+    /// Creates the first file and starts the writer thread.
+    ///
+    /// Fails if the first file cannot be created; errors on later files
+    /// are reported by the dumper's methods.
+    pub fn open(self) -> io::Result<AsyncDumper> {
+        let mut writer = Writer::open(self)?;
+        let (tx, mut rx) = channel(writer.config.queue_capacity);
+        let failure = Failure::default();
+        let failed = failure.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = writer.run(&mut rx) {
+                warn!("AsyncDumper stopped writing {}: {e}", writer.path.display());
+                *failed.lock().unwrap_or_else(|e| e.into_inner()) = Some((e.kind(), e.to_string()));
+            }
+        });
+        Ok(AsyncDumper { tx, failure })
+    }
+
+    fn rotates(&self) -> bool {
+        self.rotate_size.is_some() || self.rotate_packets.is_some() || self.rotate_every.is_some()
+    }
+
+    /// Expands the template for the file numbered `index`.
+    fn path_for(&self, index: u64, opened: SystemTime) -> PathBuf {
+        match self.template.to_str() {
+            Some(template)
+                if ["{n}", "{ts}", "{time}"]
+                    .iter()
+                    .any(|p| template.contains(p)) =>
+            {
+                let secs = opened
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                template
+                    .replace("{n}", &index.to_string())
+                    .replace("{ts}", &secs.to_string())
+                    .replace("{time}", &utc_stamp(secs))
+                    .into()
+            }
+            _ if self.rotates() => numbered(&self.template, index),
+            _ => self.template.clone(),
+        }
+    }
+}
+
+/// Records packets into pcap savefiles from a background thread.
+///
+/// [`write()`](Self::write) hands the packet to a thread that owns the
+/// file, so a slow disk does not stall the task consuming the capture; it
+/// only waits once the queue of pending packets is full. Files are
+/// written in the classic pcap format and rotated by size, packet count
+/// or age as configured on the [`AsyncDumperBuilder`].
+///
+/// # Notes
+///
+/// - Dropping the dumper lets the thread write the queued packets and
+///   close the file; [`close()`](Self::close) does the same and reports
+///   the outcome.
+/// - After a write error the thread stops; the packets still queued are
+///   lost and every later call returns the error.
+/// - No Tokio runtime is needed to open the dumper.
+pub struct AsyncDumper {
+    tx: Sender<Command>,
+    failure: Failure,
+}
+
+impl AsyncDumper {
+    /// Starts configuring a dumper writing to `path`, a template that may
+    /// contain placeholders; see [`AsyncDumperBuilder`].
+    pub fn builder<P: Into<PathBuf>>(path: P) -> AsyncDumperBuilder {
+        AsyncDumperBuilder {
+            template: path.into(),
+            linktype: Linktype::ETHERNET,
+            snaplen: DEFAULT_SNAPLEN,
+            precision: Precision::Micro,
+            rotate_size: None,
+            rotate_packets: None,
+            rotate_every: None,
+            max_files: None,
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+        }
+    }
+
+    /// Writes to a single file at `path`, without rotation, for packets of
+    /// the capture described by `info`.
+    pub fn create<P: Into<PathBuf>>(path: P, info: &CaptureInfo) -> io::Result<Self> {
+        Self::builder(path).capture_info(info).open()
+    }
+
+    /// Queues a packet, waiting only while the queue is full.
+    pub async fn write(&self, packet: Packet) -> io::Result<()> {
+        if self.tx.send(Command::Packet(packet)).await.is_err() {
+            return Err(self.error());
+        }
+        Ok(())
+    }
+
+    /// Waits until the packets queued so far are written and flushed to the
+    /// file.
+    pub async fn flush(&self) -> io::Result<()> {
+        self.request(Command::Flush).await
+    }
+
+    /// Writes the queued packets, closes the file and stops the thread.
+    pub async fn close(self) -> io::Result<()> {
+        self.request(Command::Close).await
+    }
+
+    async fn request(
+        &self,
+        command: impl FnOnce(oneshot::Sender<io::Result<()>>) -> Command,
+    ) -> io::Result<()> {
+        let (done, result) = oneshot::channel();
+        if self.tx.send(command(done)).await.is_err() {
+            return Err(self.error());
+        }
+        result.await.unwrap_or_else(|_| Err(self.error()))
+    }
+
+    /// Error to report once the thread is gone.
+    fn error(&self) -> io::Error {
+        match &*self.failure.lock().unwrap_or_else(|e| e.into_inner()) {
+            Some((kind, msg)) => io::Error::new(*kind, msg.clone()),
+            None => io::Error::new(io::ErrorKind::BrokenPipe, "dumper thread has exited"),
+        }
+    }
+}
+
+impl fmt::Debug for AsyncDumper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncDumper")
+            .field("queued", &(self.tx.max_capacity() - self.tx.capacity()))
+            .finish_non_exhaustive()
+    }
+}
+
+/// State of the writer thread.
+struct Writer {
+    config: AsyncDumperBuilder,
+    out: BufWriter<File>,
+    path: PathBuf,
+    index: u64,
+    bytes: u64,
+    packets: u64,
+    opened: Instant,
+    /// Files written so far, oldest first, when their number is limited
+    files: VecDeque<PathBuf>,
+}
+
+impl Writer {
+    fn open(config: AsyncDumperBuilder) -> io::Result<Self> {
+        let path = config.path_for(0, SystemTime::now());
+        let out = create_file(&path, &config)?;
+        Ok(Self {
+            config,
+            out,
+            files: VecDeque::from([path.clone()]),
+            path,
+            index: 0,
+            bytes: FILE_HEADER_LEN,
+            packets: 0,
+            opened: Instant::now(),
+        })
+    }
+
+    fn run(&mut self, rx: &mut Receiver<Command>) -> io::Result<()> {
+        while let Some(command) = rx.blocking_recv() {
+            match command {
+                Command::Packet(packet) => self.write(&packet)?,
+                Command::Flush(done) => {
+                    if let Err(e) = self.out.flush() {
+                        let _ = done.send(Err(io::Error::new(e.kind(), e.to_string())));
+                        return Err(e);
+                    }
+                    let _ = done.send(Ok(()));
+                }
+                Command::Close(done) => {
+                    let _ = done.send(self.out.flush());
+                    return Ok(());
+                }
+            }
+        }
+        self.out.flush()
+    }
+
+    fn write(&mut self, packet: &Packet) -> io::Result<()> {
+        let record = RECORD_HEADER_LEN + packet.data.len() as u64;
+        if self.due(record) {
+            self.rotate()?;
+        }
+        write_record(&mut self.out, packet)?;
+        self.bytes += record;
+        self.packets += 1;
+        Ok(())
+    }
+
+    /// Whether a new file must be started before writing a record of
+    /// `record` bytes.
+    fn due(&self, record: u64) -> bool {
+        let config = &self.config;
+        let full = config
+            .rotate_size
+            .is_some_and(|limit| self.packets > 0 && self.bytes + record > limit);
+        let counted = config
+            .rotate_packets
+            .is_some_and(|limit| self.packets >= limit);
+        let aged = config
+            .rotate_every
+            .is_some_and(|interval| self.opened.elapsed() >= interval);
+        full || counted || aged
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.out.flush()?;
+        self.index += 1;
+        let path = self.config.path_for(self.index, SystemTime::now());
+        self.out = create_file(&path, &self.config)?;
+        debug!("AsyncDumper rotated to {}", path.display());
+        self.path = path.clone();
+        self.bytes = FILE_HEADER_LEN;
+        self.packets = 0;
+        self.opened = Instant::now();
+
+        if let Some(max) = self.config.max_files {
+            self.files.retain(|old| *old != path);
+            self.files.push_back(path);
+            while self.files.len() > max {
+                let oldest = self.files.pop_front().expect("more files than the limit");
+                if let Err(e) = fs::remove_file(&oldest) {
+                    warn!("AsyncDumper could not remove {}: {e}", oldest.display());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Creates `path` and writes the pcap file header.
+fn create_file(path: &Path, config: &AsyncDumperBuilder) -> io::Result<BufWriter<File>> {
+    let magic = match config.precision {
+        Precision::Micro => PCAP_MAGIC_MICRO,
+        Precision::Nano => PCAP_MAGIC_NANO,
+    };
+    let mut out = BufWriter::new(File::create(path)?);
+    let mut header = [0; FILE_HEADER_LEN as usize];
+    header[0..4].copy_from_slice(&magic.to_le_bytes());
+    header[4..6].copy_from_slice(&2u16.to_le_bytes());
+    header[6..8].copy_from_slice(&4u16.to_le_bytes());
+    // Bytes 8..16, the timezone offset and accuracy, stay zero
+    header[16..20].copy_from_slice(&config.snaplen.to_le_bytes());
+    header[20..24].copy_from_slice(&(config.linktype.0 as u32).to_le_bytes());
+    out.write_all(&header)?;
+    Ok(out)
+}
+
+/// Writes the record header and bytes of `packet`.
+fn write_record<W: Write>(out: &mut W, packet: &Packet) -> io::Result<()> {
+    let ts = packet.header.ts;
+    let mut header = [0; RECORD_HEADER_LEN as usize];
+    header[0..4].copy_from_slice(&(ts.tv_sec as u32).to_le_bytes());
+    header[4..8].copy_from_slice(&(ts.tv_usec as u32).to_le_bytes());
+    header[8..12].copy_from_slice(&(packet.data.len() as u32).to_le_bytes());
+    header[12..16].copy_from_slice(&packet.header.len.to_le_bytes());
+    out.write_all(&header)?;
+    out.write_all(&packet.data)
+}
+
+/// Writes `packets` to a new pcap file at `path`, on the calling thread.
+pub(crate) fn write_savefile<'a>(
+    path: &Path,
+    linktype: Linktype,
+    snaplen: u32,
+    precision: Precision,
+    packets: impl IntoIterator<Item = &'a Packet>,
+) -> io::Result<()> {
+    let config = AsyncDumper::builder(path)
+        .linktype(linktype)
+        .snaplen(snaplen)
+        .precision(precision);
+    let mut out = create_file(path, &config)?;
+    for packet in packets {
+        write_record(&mut out, packet)?;
+    }
+    out.flush()
+}
+
+/// Appends `-index` to the file stem of `path`.
+fn numbered(path: &Path, index: u64) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!("-{index}"));
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+/// Formats Unix time `secs` in UTC as `YYYYMMDD-HHMMSS`.
+fn utc_stamp(secs: u64) -> String {
+    let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+    // Civil date from a day count, after Howard Hinnant's algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty directory unique to the test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("async-pcap-dumper-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Number of packet records in the pcap file at `path`.
+    fn records(path: &Path) -> usize {
+        let file = fs::read(path).unwrap();
+        let mut at = FILE_HEADER_LEN as usize;
+        let mut records = 0;
+        while at < file.len() {
+            let caplen = u32::from_le_bytes(file[at + 8..at + 12].try_into().unwrap());
+            at += RECORD_HEADER_LEN as usize + caplen as usize;
+            records += 1;
+        }
+        records
+    }
+
+    #[test]
+    fn utc_stamps_follow_the_civil_calendar() {
+        assert_eq!(utc_stamp(0), "19700101-000000");
+        assert_eq!(utc_stamp(951_782_400), "20000229-000000");
+        assert_eq!(utc_stamp(1_709_210_096), "20240229-123456");
+        // 2100 is not a leap year
+        assert_eq!(utc_stamp(4_107_542_399), "21000228-235959");
+        assert_eq!(utc_stamp(4_107_542_400), "21000301-000000");
+    }
+
+    #[test]
+    fn numbers_go_before_the_extension() {
+        assert_eq!(
+            numbered(Path::new("out/capture.pcap"), 3),
+            Path::new("out/capture-3.pcap")
+        );
+        assert_eq!(numbered(Path::new("capture"), 0), Path::new("capture-0"));
+    }
+
+    #[test]
+    fn templates_expand_their_placeholders() {
+        let opened = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        let config = AsyncDumper::builder("cap-{n}-{ts}-{time}.pcap");
+        assert_eq!(
+            config.path_for(2, opened),
+            Path::new("cap-2-1709210096-20240229-123456.pcap")
+        );
+        // Without placeholders, only a rotating dumper numbers its files
+        let config = AsyncDumper::builder("cap.pcap");
+        assert_eq!(config.path_for(2, opened), Path::new("cap.pcap"));
+        let config = config.rotate_packets(10);
+        assert_eq!(config.path_for(2, opened), Path::new("cap-2.pcap"));
+    }
+
+    #[test]
+    fn rotation_keeps_the_newest_files() {
+        let dir = temp_dir("rotate");
+        let config = AsyncDumper::builder(dir.join("cap.pcap"))
+            .rotate_packets(2)
+            .max_files(2);
+        let mut writer = Writer::open(config).unwrap();
+        for n in 0..5u8 {
+            writer.write(&Packet::new(vec![n; 10], UNIX_EPOCH)).unwrap();
+        }
+        writer.out.flush().unwrap();
+
+        let mut names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["cap-1.pcap", "cap-2.pcap"]);
+        assert_eq!(records(&dir.join("cap-1.pcap")), 2);
+        assert_eq!(records(&dir.join("cap-2.pcap")), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn size_rotation_leaves_room_for_the_file_header() {
+        let dir = temp_dir("size");
+        // The header and two records of 26 bytes
+        let config = AsyncDumper::builder(dir.join("cap.pcap")).rotate_size(24 + 2 * 26);
+        let mut writer = Writer::open(config).unwrap();
+        let small = Packet::new(vec![0; 10], UNIX_EPOCH);
+        assert!(!writer.due(26));
+        writer.write(&small).unwrap();
+        writer.write(&small).unwrap();
+        assert!(writer.due(26));
+        writer.write(&small).unwrap();
+        assert_eq!(writer.index, 1);
+        // A record larger than the limit still gets a file of its own
+        writer
+            .write(&Packet::new(vec![0; 100], UNIX_EPOCH))
+            .unwrap();
+        assert_eq!((writer.index, writer.packets), (2, 1));
+        writer.out.flush().unwrap();
+        assert_eq!(records(&dir.join("cap-0.pcap")), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
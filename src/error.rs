@@ -0,0 +1,297 @@
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+/// Fragments of libpcap messages reporting that the device went away.
+const GONE_MESSAGES: [&str; 4] = [
+    "went down",
+    "no such device",
+    "device not configured",
+    "not up",
+];
+
+/// Fragments of libpcap messages reporting missing privileges.
+const PERMISSION_MESSAGES: [&str; 3] =
+    ["permission", "operation not permitted", "access is denied"];
+
+/// Broad category of an [`Error`], returned by [`Error::kind()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The read timeout expired without a packet, or a non-blocking read
+    /// found none; harmless on a live capture
+    Timeout,
+    /// A system call was interrupted
+    Interrupted,
+    /// The process lacks the privileges to capture on the device
+    PermissionDenied,
+    /// The device went down or was removed while capturing
+    DeviceGone,
+    /// No capture device could be found
+    NoDevice,
+    /// The internal packet channel was closed unexpectedly
+    ChannelClosed,
+    /// The capture has stopped or its savefile has no more packets
+    Stopped,
+    /// Any other I/O error
+    Io,
+    /// The capture could not be configured or opened
+    Setup,
+    /// The operation is not supported by the capture's packet source
+    Unsupported,
+    /// The capture thread panicked
+    Panicked,
+    /// The capture thread did not exit in time after being asked to stop
+    StopTimedOut,
+    /// Any other error reported by libpcap
+    Pcap,
+}
+
+/// Errors produced by `async-pcap`.
+///
+/// The errors of libpcap that callers commonly need to tell apart, such as
+/// a read timeout or a device that went away, get variants of their own;
+/// the others are wrapped in [`Pcap`](Self::Pcap). [`kind()`](Self::kind)
+/// sorts every error into an [`ErrorKind`].
+///
+/// # Notes
+///
+/// - The enum is `non_exhaustive`: new failure modes may get their own
+///   variant, so matches need a wildcard arm.
+/// - Errors of libpcap are converted with `From`. Any [`PacketSource`]
+///   may still return [`Pcap`](Self::Pcap) directly; the capture thread
+///   converts those too before handing them out.
+///
+/// [`PacketSource`]: crate::PacketSource
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The read timeout expired without a packet, or a non-blocking read
+    /// found none
+    Timeout,
+    /// The process lacks the privileges to capture on the device; the
+    /// message of libpcap or the system is attached
+    PermissionDenied(String),
+    /// The device went down or was removed; the message of libpcap or the
+    /// system is attached
+    DeviceGone(String),
+    /// A savefile or a capture interrupted by a shutdown has no more
+    /// packets
+    NoMorePackets,
+    /// Any other I/O error
+    Io(io::ErrorKind),
+    /// Any other error reported by the underlying pcap library
+    Pcap(pcap::Error),
+    /// The internal packet channel was closed unexpectedly
+    ChannelClosed,
+    /// The capture has already been stopped
+    CaptureStopped,
+    /// No default capture device could be found
+    NoDefaultDevice,
+    /// The capture could not be configured or opened
+    Setup(String),
+    /// The operation is not supported by the capture's packet source
+    Unsupported(String),
+    /// The capture thread panicked, e.g. in a user-supplied predicate; the
+    /// panic message is attached
+    CapturePanicked(String),
+    /// The capture thread did not exit within the given time after being
+    /// asked to stop
+    StopTimedOut(Duration),
+}
+
+impl Error {
+    /// Returns the wrapped [`pcap::Error`] of a [`Pcap`](Self::Pcap) error.
+    ///
+    /// The errors of libpcap that have a variant of their own, e.g.
+    /// [`Timeout`](Self::Timeout), are not returned.
+    pub fn as_pcap(&self) -> Option<&pcap::Error> {
+        match self {
+            Error::Pcap(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Classifies the error, e.g. to tell a harmless read timeout from an
+    /// interface that went away without matching on libpcap's messages.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Timeout => ErrorKind::Timeout,
+            Error::PermissionDenied(_) => ErrorKind::PermissionDenied,
+            Error::DeviceGone(_) => ErrorKind::DeviceGone,
+            Error::NoMorePackets => ErrorKind::Stopped,
+            Error::Io(kind) => io_kind(*kind),
+            Error::Pcap(e) => pcap_kind(e),
+            Error::ChannelClosed => ErrorKind::ChannelClosed,
+            Error::CaptureStopped => ErrorKind::Stopped,
+            Error::NoDefaultDevice => ErrorKind::NoDevice,
+            Error::Setup(_) => ErrorKind::Setup,
+            Error::Unsupported(_) => ErrorKind::Unsupported,
+            Error::CapturePanicked(_) => ErrorKind::Panicked,
+            Error::StopTimedOut(_) => ErrorKind::StopTimedOut,
+        }
+    }
+
+    /// Returns `true` if the operation that failed may succeed when retried.
+    ///
+    /// Read timeouts, interrupted or would-block I/O and generic libpcap
+    /// read errors are transient. Errors reporting that the device went
+    /// away or that permission was denied, and every error that did not
+    /// come from a read, are fatal.
+    pub fn is_transient(&self) -> bool {
+        match self.kind() {
+            ErrorKind::Timeout | ErrorKind::Interrupted => true,
+            ErrorKind::Pcap => matches!(self, Error::Pcap(pcap::Error::PcapError(_))),
+            _ => false,
+        }
+    }
+
+    /// Converts a [`Pcap`](Self::Pcap) error into the wrapped
+    /// [`pcap::Error`].
+    ///
+    /// Returns `Err(self)` for every other variant, including the errors of
+    /// libpcap that have a variant of their own.
+    pub fn into_pcap(self) -> Result<pcap::Error, Self> {
+        match self {
+            Error::Pcap(e) => Ok(e),
+            other => Err(other),
+        }
+    }
+
+    /// Converts a [`Pcap`](Self::Pcap) error that has a variant of its own
+    /// into that variant.
+    pub(crate) fn classified(self) -> Self {
+        match self {
+            Error::Pcap(e) => Error::from(e),
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Timeout => write!(f, "read timeout expired"),
+            Error::PermissionDenied(msg) => write!(f, "permission denied: {msg}"),
+            Error::DeviceGone(msg) => write!(f, "capture device went away: {msg}"),
+            Error::NoMorePackets => write!(f, "no more packets to read"),
+            Error::Io(kind) => write!(f, "I/O error: {kind}"),
+            Error::Pcap(e) => write!(f, "pcap error: {e}"),
+            Error::ChannelClosed => write!(f, "packet channel closed"),
+            Error::CaptureStopped => write!(f, "capture has been stopped"),
+            Error::NoDefaultDevice => write!(f, "no default capture device found"),
+            Error::Setup(msg) => write!(f, "capture setup failed: {msg}"),
+            Error::Unsupported(msg) => write!(f, "unsupported operation: {msg}"),
+            Error::CapturePanicked(msg) => write!(f, "capture thread panicked: {msg}"),
+            Error::StopTimedOut(timeout) => {
+                write!(f, "capture thread did not stop within {timeout:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Pcap(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<pcap::Error> for Error {
+    fn from(e: pcap::Error) -> Self {
+        match e {
+            pcap::Error::TimeoutExpired => Error::Timeout,
+            pcap::Error::NoMorePackets => Error::NoMorePackets,
+            pcap::Error::IoError(kind) => io::Error::from(kind).into(),
+            pcap::Error::ErrnoError(errno) if pcap_kind(&e) != ErrorKind::DeviceGone => {
+                io::Error::from_raw_os_error(errno.0).into()
+            }
+            e => match pcap_kind(&e) {
+                ErrorKind::DeviceGone => Error::DeviceGone(e.to_string()),
+                ErrorKind::PermissionDenied => Error::PermissionDenied(e.to_string()),
+                _ => Error::Pcap(e),
+            },
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        match io_kind(e.kind()) {
+            ErrorKind::Timeout => Error::Timeout,
+            ErrorKind::PermissionDenied => Error::PermissionDenied(e.to_string()),
+            ErrorKind::DeviceGone => Error::DeviceGone(e.to_string()),
+            _ => Error::Io(e.kind()),
+        }
+    }
+}
+
+/// Classifies an error of libpcap, e.g. one wrapped by a [`PacketSource`]
+/// without conversion.
+///
+/// [`PacketSource`]: crate::PacketSource
+fn pcap_kind(e: &pcap::Error) -> ErrorKind {
+    match e {
+        pcap::Error::TimeoutExpired => ErrorKind::Timeout,
+        pcap::Error::NoMorePackets => ErrorKind::Stopped,
+        pcap::Error::IoError(kind) => io_kind(*kind),
+        pcap::Error::ErrnoError(errno) => match errno.0 {
+            libc::ENODEV | libc::ENXIO | libc::ENETDOWN => ErrorKind::DeviceGone,
+            code => io_kind(io::Error::from_raw_os_error(code).kind()),
+        },
+        pcap::Error::PcapError(msg) => {
+            let msg = msg.to_ascii_lowercase();
+            if GONE_MESSAGES.iter().any(|gone| msg.contains(gone)) {
+                ErrorKind::DeviceGone
+            } else if PERMISSION_MESSAGES
+                .iter()
+                .any(|denied| msg.contains(denied))
+            {
+                ErrorKind::PermissionDenied
+            } else {
+                ErrorKind::Pcap
+            }
+        }
+        _ => ErrorKind::Pcap,
+    }
+}
+
+/// Maps an I/O error kind of libpcap onto an [`ErrorKind`].
+fn io_kind(kind: io::ErrorKind) -> ErrorKind {
+    match kind {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => ErrorKind::Timeout,
+        io::ErrorKind::Interrupted => ErrorKind::Interrupted,
+        io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+        io::ErrorKind::NotFound => ErrorKind::DeviceGone,
+        _ => ErrorKind::Io,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn libpcap_errors_get_their_own_variant() {
+        assert_eq!(Error::from(pcap::Error::TimeoutExpired), Error::Timeout);
+        assert_eq!(
+            Error::from(pcap::Error::PcapError("eth0: interface went down".into())),
+            Error::DeviceGone("libpcap error: eth0: interface went down".into())
+        );
+        assert_eq!(
+            Error::from(pcap::Error::IoError(io::ErrorKind::Interrupted)),
+            Error::Io(io::ErrorKind::Interrupted)
+        );
+        let other = Error::from(pcap::Error::PcapError("bad filter".into()));
+        assert_eq!(other.kind(), ErrorKind::Pcap);
+        assert!(other.as_pcap().is_some());
+    }
+
+    #[test]
+    fn wrapped_libpcap_errors_are_classified_alike() {
+        let wrapped = Error::Pcap(pcap::Error::TimeoutExpired);
+        assert_eq!(wrapped.kind(), ErrorKind::Timeout);
+        assert_eq!(wrapped.classified(), Error::Timeout);
+    }
+}
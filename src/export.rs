@@ -0,0 +1,208 @@
+use std::fmt;
+use std::io;
+
+use pcap::{PacketHeader, Precision};
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::async_pcap::{AsyncCapture, Packet};
+use crate::error::Error;
+
+/// Encoding of the packets written by [`AsyncCapture::export()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    /// One JSON object per line
+    #[default]
+    Ndjson,
+    /// MessagePack maps, one after the other without a separator
+    MessagePack,
+}
+
+/// The serialized form of a [`Packet`].
+#[derive(Serialize)]
+struct PacketRef<'a> {
+    seconds: i64,
+    nanos: i64,
+    caplen: u32,
+    len: u32,
+    data: Bytes<'a>,
+}
+
+#[derive(Deserialize)]
+struct PacketOwned {
+    seconds: i64,
+    nanos: i64,
+    caplen: u32,
+    len: u32,
+    data: ByteBuf,
+}
+
+// `time_t` and `suseconds_t` are not 64 bits on every platform
+#[allow(clippy::unnecessary_cast)]
+impl<'a> PacketRef<'a> {
+    /// Record of `packet`, whose `ts.tv_usec` is in units of `precision`.
+    fn new(packet: &'a Packet, precision: Precision) -> Self {
+        let fraction = packet.header.ts.tv_usec as i64;
+        PacketRef {
+            seconds: packet.header.ts.tv_sec as i64,
+            nanos: match precision {
+                Precision::Micro => fraction.saturating_mul(1000),
+                Precision::Nano => fraction,
+            },
+            caplen: packet.header.caplen,
+            len: packet.header.len,
+            data: Bytes(&packet.data),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorRecord {
+    error: String,
+}
+
+/// Serializes a byte slice as bytes rather than a sequence, so binary
+/// formats store it compactly.
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+/// Deserializes bytes, or a sequence of numbers as JSON stores them.
+struct ByteBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for ByteBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ByteBufVisitor;
+
+        impl<'de> Visitor<'de> for ByteBufVisitor {
+            type Value = ByteBuf;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("packet bytes")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<ByteBuf, E> {
+                Ok(ByteBuf(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<ByteBuf, E> {
+                Ok(ByteBuf(v))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ByteBuf, A::Error> {
+                let mut data = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(65_536));
+                while let Some(byte) = seq.next_element()? {
+                    data.push(byte);
+                }
+                Ok(ByteBuf(data))
+            }
+        }
+
+        deserializer.deserialize_byte_buf(ByteBufVisitor)
+    }
+}
+
+/// With the `serde` feature, a packet is serialized as a map with the
+/// header flattened into `seconds`, `nanos`, `caplen` and `len`, followed
+/// by its `data`.
+///
+/// `nanos` is the fractional part of the timestamp in nanoseconds. A packet
+/// on its own does not know the precision it was captured with, so its
+/// `ts.tv_usec` is read as microseconds, like [`Packet::timestamp()`] does;
+/// [`AsyncCapture::export()`] uses the precision of the capture instead.
+/// Deserializing stores microseconds back into `ts.tv_usec`, dropping the
+/// digits below them. The data is written as bytes, which JSON stores as
+/// an array of numbers.
+impl Serialize for Packet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        PacketRef::new(self, Precision::Micro).serialize(serializer)
+    }
+}
+
+// `time_t` and `suseconds_t` are not 64 bits on every platform
+#[allow(clippy::useless_conversion)]
+impl<'de> Deserialize<'de> for Packet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let packet = PacketOwned::deserialize(deserializer)?;
+        let out_of_range = |field| de::Error::custom(format!("packet {field} out of range"));
+        let header = PacketHeader {
+            ts: libc::timeval {
+                tv_sec: packet
+                    .seconds
+                    .try_into()
+                    .map_err(|_| out_of_range("seconds"))?,
+                tv_usec: (packet.nanos / 1000)
+                    .try_into()
+                    .map_err(|_| out_of_range("nanos"))?,
+            },
+            caplen: packet.caplen,
+            len: packet.len,
+        };
+        Ok(Packet::with_header(header, packet.data.0))
+    }
+}
+
+impl AsyncCapture {
+    /// Writes every packet of the capture to `out` in its serialized form,
+    /// until the capture stops.
+    ///
+    /// Unlike [`into_jsonl_writer()`](Self::into_jsonl_writer), the records
+    /// are those of `Packet`'s `Serialize` implementation, so a collector
+    /// can deserialize them back into packets. Timestamps are converted to
+    /// nanoseconds from the [`CaptureInfo::precision`](crate::CaptureInfo::precision)
+    /// of the capture. Capture errors are written as `{"error":"..."}`
+    /// records, except read timeouts, which are skipped.
+    ///
+    /// Returns the number of packet records written. Requires the `serde`
+    /// feature.
+    pub async fn export<W>(self, mut out: W, format: ExportFormat) -> io::Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let precision = self.info().precision;
+        let mut written = 0;
+        let mut record = Vec::new();
+        while let Some(item) = self.next_packet().await {
+            record.clear();
+            match item {
+                Ok(packet) => {
+                    encode(&mut record, &PacketRef::new(&packet, precision), format)?;
+                    written += 1;
+                }
+                Err(Error::Timeout) => continue,
+                Err(e) => encode(
+                    &mut record,
+                    &ErrorRecord {
+                        error: e.to_string(),
+                    },
+                    format,
+                )?,
+            }
+            out.write_all(&record).await?;
+        }
+        out.flush().await?;
+        Ok(written)
+    }
+}
+
+/// Appends `value` to `buf` as one record of `format`.
+fn encode<T: Serialize>(buf: &mut Vec<u8>, value: &T, format: ExportFormat) -> io::Result<()> {
+    match format {
+        ExportFormat::Ndjson => {
+            serde_json::to_writer(&mut *buf, value)?;
+            buf.push(b'\n');
+        }
+        ExportFormat::MessagePack => {
+            value
+                .serialize(&mut rmp_serde::Serializer::new(&mut *buf).with_struct_map())
+                .map_err(io::Error::other)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use ::metrics::{Counter, Gauge, Label, Unit, counter, describe_counter, describe_gauge, gauge};
+
+use crate::async_pcap::AsyncCaptureHandle;
+use crate::snapshot::{Metric, MetricUnit, MetricValue, snapshot};
+
+/// Shortest interval between two samples of
+/// [`AsyncCaptureHandle::export_metrics()`].
+const MIN_EXPORT_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Handle of one metric, registered with the installed recorder.
+enum Recorded {
+    Counter(Counter),
+    Gauge(Gauge),
+}
+
+impl Recorded {
+    /// Describes `metric` to the installed recorder and registers it.
+    fn register(metric: &Metric, labels: &[Label]) -> Self {
+        let unit = match metric.unit {
+            MetricUnit::Count => Unit::Count,
+            MetricUnit::Bytes => Unit::Bytes,
+            MetricUnit::CountPerSecond => Unit::CountPerSecond,
+            MetricUnit::BitsPerSecond => Unit::BitsPerSecond,
+            MetricUnit::Seconds => Unit::Seconds,
+        };
+        let labels = labels.to_vec();
+        match metric.value {
+            MetricValue::Counter(_) => {
+                describe_counter!(metric.name, unit, metric.help);
+                Recorded::Counter(counter!(metric.name, labels))
+            }
+            MetricValue::Gauge(_) => {
+                describe_gauge!(metric.name, unit, metric.help);
+                Recorded::Gauge(gauge!(metric.name, labels))
+            }
+        }
+    }
+
+    fn update(&self, value: MetricValue) {
+        match (self, value) {
+            (Recorded::Counter(counter), MetricValue::Counter(value)) => counter.absolute(value),
+            (Recorded::Gauge(gauge), MetricValue::Gauge(value)) => gauge.set(value),
+            _ => unreachable!("snapshots list the metrics in a fixed order"),
+        }
+    }
+}
+
+impl AsyncCaptureHandle {
+    /// Publishes the metrics of the capture through the `metrics` facade
+    /// every `interval`, each carrying `labels`.
+    ///
+    /// Requires the `metrics` feature. The metrics are:
+    ///
+    /// - `pcap_packets_received_total` and `pcap_bytes_received_total`:
+    ///   packets read from the source, and their original length
+    /// - `pcap_packets_dropped_total` and `pcap_packets_if_dropped_total`:
+    ///   kernel and interface drops, as in
+    ///   [`DropReasons`](crate::DropReasons)
+    /// - `pcap_packets_overflow_dropped_total`: packets dropped by a full
+    ///   bounded queue, as counted by [`overflow_drops()`](Self::overflow_drops)
+    /// - `pcap_packets_filtered_total`: packets discarded on the capture
+    ///   thread, as in [`CaptureStats::filtered`](crate::CaptureStats::filtered)
+    /// - `pcap_read_retries_total` and `pcap_reconnects_total`: transient
+    ///   read errors retried, and devices reopened
+    /// - `pcap_soft_errors_total`: errors the capture thread recovered
+    ///   from, as counted by [`soft_errors()`](Self::soft_errors)
+    /// - `pcap_queue_depth`: packets read but not yet received by the
+    ///   consumer
+    /// - `pcap_packets_per_second` and `pcap_bits_per_second`: the
+    ///   [`throughput()`](Self::throughput)
+    /// - `pcap_uptime_seconds`: time since the read loop started, or how
+    ///   long it ran once the capture has ended
+    ///
+    /// # Notes
+    ///
+    /// - The values are sampled from the capture's own counters by a
+    ///   thread of its own, so the capture thread does no extra work per
+    ///   packet. The thread publishes a last sample and exits once the
+    ///   capture has ended.
+    /// - The metrics are registered with the recorder installed when this
+    ///   is called; install it first.
+    /// - Intervals shorter than 10 ms, including zero, are raised to
+    ///   10 ms.
+    /// - The drop counters are refreshed by the capture thread once a
+    ///   second, and stay at zero for sources without statistics.
+    /// - To export several captures, give each distinct labels, e.g. the
+    ///   device name.
+    pub fn export_metrics(&self, interval: Duration, labels: Vec<Label>) {
+        let recorded: Vec<_> = snapshot(self)
+            .iter()
+            .map(|metric| Recorded::register(metric, &labels))
+            .collect();
+        self.shared.publish_stats.store(true, Ordering::Relaxed);
+        let interval = interval.max(MIN_EXPORT_INTERVAL);
+        let handle = self.clone();
+        std::thread::spawn(move || {
+            loop {
+                let ended = handle.summary().is_some();
+                for (recorded, metric) in recorded.iter().zip(snapshot(&handle)) {
+                    recorded.update(metric.value);
+                }
+                if ended {
+                    break;
+                }
+                std::thread::sleep(interval);
+            }
+        });
+    }
+}
@@ -0,0 +1,155 @@
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicU16, Ordering};
+
+use pcap::Device;
+
+use crate::async_pcap::{AsyncCapture, AsyncCaptureHandle};
+use crate::builder::AsyncCaptureBuilder;
+use crate::error::Error;
+use crate::logging::debug;
+#[cfg(feature = "rt-tokio")]
+use crate::merge::{MergedCapture, MergedHandle};
+
+/// Fanout groups handed out by this process, mixed with its pid into the
+/// group id.
+static NEXT_GROUP: AtomicU16 = AtomicU16::new(0);
+
+/// Packet distribution algorithm for a Linux `PACKET_FANOUT` group.
+///
+/// All captures joining the same group must use the same mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FanoutMode {
+    /// Distribute by flow hash, keeping each flow on one member
+    #[default]
+    Hash,
+    /// Round-robin across members
+    LoadBalance,
+    /// Select the member by the CPU the packet arrived on
+    Cpu,
+    /// Fill one member before moving on to the next
+    Rollover,
+    /// Select a member at random
+    Random,
+    /// Select the member by the NIC receive queue
+    QueueMapping,
+}
+
+impl FanoutMode {
+    fn as_raw(self) -> libc::c_uint {
+        match self {
+            FanoutMode::Hash => libc::PACKET_FANOUT_HASH,
+            FanoutMode::LoadBalance => libc::PACKET_FANOUT_LB,
+            FanoutMode::Cpu => libc::PACKET_FANOUT_CPU,
+            FanoutMode::Rollover => libc::PACKET_FANOUT_ROLLOVER,
+            FanoutMode::Random => libc::PACKET_FANOUT_RND,
+            FanoutMode::QueueMapping => libc::PACKET_FANOUT_QM,
+        }
+    }
+}
+
+/// Joins the `AF_PACKET` socket behind `fd` to the given fanout group.
+pub(crate) fn join(fd: RawFd, group: u16, mode: FanoutMode) -> Result<(), Error> {
+    let arg: libc::c_int = (group as libc::c_int) | ((mode.as_raw() as libc::c_int) << 16);
+    // SAFETY: `fd` is the live socket owned by the capture and `arg` outlives the call.
+    let ret = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_PACKET,
+            libc::PACKET_FANOUT,
+            &arg as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(Error::Setup(format!(
+            "failed to join fanout group {group}: {}",
+            std::io::Error::last_os_error()
+        )))
+    }
+}
+
+/// Picks a fanout group id that other processes are unlikely to be using.
+fn unused_group() -> u16 {
+    (std::process::id() as u16).wrapping_add(NEXT_GROUP.fetch_add(1, Ordering::Relaxed))
+}
+
+impl AsyncCaptureBuilder {
+    /// Opens `workers` captures of the device, joined to one fanout group,
+    /// so that each gets a share of the traffic and its own thread.
+    ///
+    /// Every capture is opened with the builder's settings; the
+    /// [`fanout_mode`](Self::fanout_mode) decides how packets are spread,
+    /// and the default [`FanoutMode::Hash`] keeps each flow on one
+    /// worker. Hand each capture to its own task to process the traffic
+    /// on several cores, or use
+    /// [`open_fanout_merged()`](Self::open_fanout_merged) for a single
+    /// stream. Linux only.
+    ///
+    /// # Notes
+    ///
+    /// - The group id is the [`fanout_group`](Self::fanout_group) if one is
+    ///   set, and otherwise one derived from the process id; group ids are
+    ///   shared by every process of the network namespace.
+    /// - Fails with [`Error::Setup`] if `workers` is 0, and with the error
+    ///   of the first capture that cannot be opened, after stopping the
+    ///   ones already open.
+    pub fn open_fanout(
+        self,
+        workers: usize,
+    ) -> Result<Vec<(AsyncCapture, AsyncCaptureHandle)>, Error> {
+        let mut opened: Vec<(AsyncCapture, AsyncCaptureHandle)> = Vec::with_capacity(workers);
+        for member in self.fanout_members(workers)? {
+            match member.open() {
+                Ok(capture) => opened.push(capture),
+                Err(e) => {
+                    opened.iter().for_each(|(_, handle)| handle.stop());
+                    return Err(e);
+                }
+            }
+        }
+        Ok(opened)
+    }
+
+    /// Opens `workers` captures of the device in one fanout group, as
+    /// [`open_fanout()`](Self::open_fanout) does, and merges their packets
+    /// into one stream, as [`AsyncCapture::merge()`] does.
+    ///
+    /// The captures are still read by one thread each, but the packets are
+    /// handed out by a single [`MergedCapture`], tagged with the index of
+    /// the worker that read them. Requires the `rt-tokio` feature.
+    #[cfg(feature = "rt-tokio")]
+    pub fn open_fanout_merged(
+        self,
+        workers: usize,
+    ) -> Result<(MergedCapture, MergedHandle), Error> {
+        AsyncCapture::merge(self.fanout_members(workers)?)
+    }
+
+    /// Returns a builder per worker, all joined to the same group.
+    fn fanout_members(mut self, workers: usize) -> Result<Vec<AsyncCaptureBuilder>, Error> {
+        if workers == 0 {
+            return Err(Error::Setup("a fanout needs at least one worker".into()));
+        }
+        let group = *self.fanout_group.get_or_insert_with(unused_group);
+        debug!("AsyncCapture opening {workers} captures in fanout group {group}");
+        Ok(vec![self; workers])
+    }
+}
+
+impl AsyncCapture {
+    /// Opens `workers` captures of `device`, load-balanced by flow hash
+    /// with a Linux `PACKET_FANOUT` group.
+    ///
+    /// Shorthand for [`AsyncCaptureBuilder::open_fanout()`] with a default
+    /// builder; use the builder for other settings, or
+    /// [`AsyncCaptureBuilder::open_fanout_merged()`] for a single stream.
+    /// Linux only.
+    pub fn fanout<D: Into<Device>>(
+        device: D,
+        workers: usize,
+    ) -> Result<Vec<(AsyncCapture, AsyncCaptureHandle)>, Error> {
+        AsyncCaptureBuilder::new(device).open_fanout(workers)
+    }
+}
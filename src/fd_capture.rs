@@ -0,0 +1,221 @@
+use std::ffi::{c_int, c_void};
+use std::fmt;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use pcap::{Active, Capture, Linktype, Stat};
+use tokio::io::Interest;
+use tokio::io::unix::{AsyncFd, AsyncFdReadyMutGuard};
+use tokio::sync::Mutex;
+
+use crate::async_pcap::Packet;
+use crate::error::Error;
+use crate::info::CaptureInfo;
+use crate::nonblock;
+
+unsafe extern "C" {
+    fn pcap_get_selectable_fd(p: *mut c_void) -> c_int;
+}
+
+/// A live capture together with the descriptor polled for it.
+struct Selectable {
+    cap: Capture<Active>,
+    fd: RawFd,
+}
+
+impl AsRawFd for Selectable {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+/// Outcome of one non-blocking read.
+enum Read {
+    Packet(Packet),
+    Empty,
+    Ended,
+    Failed(Error),
+}
+
+/// A live capture read on the Tokio runtime itself, without a capture
+/// thread. Unix only.
+///
+/// The capture is put in non-blocking mode and its selectable descriptor is
+/// registered with the runtime's reactor through `AsyncFd`; packets are
+/// read by the task awaiting them once the descriptor becomes readable.
+/// This saves the OS thread per capture and the hop through a channel that
+/// [`AsyncCapture`](crate::AsyncCapture) costs, at the price of its
+/// capture-thread features: there is no handle, no filter pipeline, no
+/// rate limit and no queue in front of a slow consumer, which leaves
+/// packets in the kernel buffer instead.
+///
+/// Not every capture has a selectable descriptor; check with
+/// [`is_supported()`](Self::is_supported) and fall back to
+/// [`AsyncCapture::new()`](crate::AsyncCapture::new) otherwise.
+///
+/// # Notes
+///
+/// - Packets are copied out of the libpcap buffer by
+///   [`next_packet()`](Self::next_packet) on the runtime's worker thread,
+///   so a consumer that does heavy work per packet is better served by an
+///   `AsyncCapture`.
+/// - With the read timeout, libpcap may hold packets back until its buffer
+///   fills or the timeout expires before the descriptor turns readable;
+///   open the device with immediate mode for the lowest latency. On macOS
+///   `poll()` on a BPF device does not honor the timeout, so immediate mode
+///   is recommended there.
+/// - Must be created and used within a Tokio runtime with I/O enabled.
+pub struct AsyncFdCapture {
+    inner: Mutex<AsyncFd<Selectable>>,
+    info: CaptureInfo,
+}
+
+impl AsyncFdCapture {
+    /// Returns whether `cap` has a descriptor that can be polled, i.e.
+    /// whether [`new()`](Self::new) can succeed.
+    pub fn is_supported(cap: &Capture<Active>) -> bool {
+        selectable_fd(cap).is_some()
+    }
+
+    /// Switches `cap` to non-blocking mode and registers its descriptor
+    /// with the runtime.
+    ///
+    /// Fails with [`Error::Unsupported`] if the capture has no selectable
+    /// descriptor, and with [`Error::Setup`] if it cannot be registered,
+    /// e.g. outside of a Tokio runtime.
+    pub fn new(mut cap: Capture<Active>) -> Result<Self, Error> {
+        let fd = selectable_fd(&cap).ok_or_else(|| {
+            Error::Unsupported("the capture has no selectable file descriptor".into())
+        })?;
+        nonblock::set(&mut cap, true)?;
+        let info = CaptureInfo::query(&cap);
+        let inner = AsyncFd::with_interest(Selectable { cap, fd }, Interest::READABLE)
+            .map_err(|e| Error::Setup(format!("cannot register the capture: {e}")))?;
+        Ok(Self {
+            inner: Mutex::new(inner),
+            info,
+        })
+    }
+
+    /// Waits for the next packet.
+    ///
+    /// Returns `None` once the capture can deliver no more packets. Errors
+    /// are returned as they occur and do not end the capture.
+    pub async fn next_packet(&self) -> Option<Result<Packet, Error>> {
+        let mut inner = self.inner.lock().await;
+        loop {
+            let mut ready = match inner.readable_mut().await {
+                Ok(ready) => ready,
+                Err(e) => return Some(Err(e.into())),
+            };
+            match read(&mut ready) {
+                Read::Packet(packet) => return Some(Ok(packet)),
+                Read::Empty => ready.clear_ready(),
+                Read::Ended => return None,
+                Read::Failed(e) => return Some(Err(e)),
+            }
+        }
+    }
+
+    /// Waits for packets and appends up to `limit` of them to `buf`.
+    ///
+    /// Waits until the descriptor is readable, then reads every packet
+    /// libpcap has buffered up to `limit`. Returns the number of items
+    /// appended, or `0` once the capture can deliver no more packets (or
+    /// if `limit` is 0). A read error ends the batch, so a persistent error
+    /// is reported once per call.
+    pub async fn next_packets(&self, buf: &mut Vec<Result<Packet, Error>>, limit: usize) -> usize {
+        if limit == 0 {
+            return 0;
+        }
+        let mut inner = self.inner.lock().await;
+        loop {
+            let mut ready = match inner.readable_mut().await {
+                Ok(ready) => ready,
+                Err(e) => {
+                    buf.push(Err(e.into()));
+                    return 1;
+                }
+            };
+            let mut n = 0;
+            while n < limit {
+                match read(&mut ready) {
+                    Read::Packet(packet) => buf.push(Ok(packet)),
+                    Read::Empty => {
+                        ready.clear_ready();
+                        break;
+                    }
+                    Read::Ended => return n,
+                    Read::Failed(e) => {
+                        buf.push(Err(e));
+                        return n + 1;
+                    }
+                }
+                n += 1;
+            }
+            if n > 0 {
+                return n;
+            }
+        }
+    }
+
+    /// Runs `f` with exclusive access to the live capture, e.g. to replace
+    /// its filter or read its statistics.
+    ///
+    /// Waits for a concurrent [`next_packet()`](Self::next_packet) to
+    /// return. Leave the capture in non-blocking mode; in blocking mode a
+    /// read would stall the runtime's worker thread.
+    pub async fn with_capture<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Capture<Active>) -> R,
+    {
+        let mut inner = self.inner.lock().await;
+        f(&mut inner.get_mut().cap)
+    }
+
+    /// Returns the kernel statistics of the capture.
+    pub async fn stats(&self) -> Result<Stat, Error> {
+        self.with_capture(|cap| cap.stats().map_err(Error::from))
+            .await
+    }
+
+    /// Returns the parameters the capture is actually running with.
+    pub fn info(&self) -> &CaptureInfo {
+        &self.info
+    }
+
+    /// Returns the link-layer header type of the captured packets.
+    pub fn datalink(&self) -> Linktype {
+        self.info.datalink
+    }
+
+    /// Deregisters the descriptor and returns the capture, still in
+    /// non-blocking mode.
+    pub fn into_inner(self) -> Capture<Active> {
+        self.inner.into_inner().into_inner().cap
+    }
+}
+
+impl fmt::Debug for AsyncFdCapture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncFdCapture")
+            .field("info", &self.info)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Reads one packet without blocking.
+fn read(ready: &mut AsyncFdReadyMutGuard<'_, Selectable>) -> Read {
+    match ready.get_inner_mut().cap.next_packet() {
+        Ok(packet) => Read::Packet(Packet::with_header(*packet.header, packet.data.to_vec())),
+        Err(pcap::Error::TimeoutExpired) => Read::Empty,
+        Err(pcap::Error::NoMorePackets) => Read::Ended,
+        Err(e) => Read::Failed(e.into()),
+    }
+}
+
+/// Returns the descriptor to poll for `cap`, if it has one.
+fn selectable_fd(cap: &Capture<Active>) -> Option<RawFd> {
+    // SAFETY: the handle stays valid for as long as `cap` is borrowed.
+    let fd = unsafe { pcap_get_selectable_fd(cap.as_ptr().cast()) };
+    (fd >= 0).then_some(fd)
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use pcap::{BpfProgram, Capture, Linktype, PacketHeader};
+
+use crate::async_pcap::Packet;
+use crate::error::Error;
+
+type Stage = Arc<dyn Fn(&Packet) -> bool + Send + Sync>;
+type HeaderPredicate = dyn Fn(&PacketHeader, &[u8]) -> bool + Send + Sync;
+
+/// Two-stage packet filter: a BPF program run in the kernel, followed by
+/// Rust predicates run on the capture thread.
+///
+/// The BPF program discards most unwanted traffic before it is copied to
+/// user space; the predicates then make the decisions BPF cannot express,
+/// such as ones that depend on state kept across packets. Predicates run in
+/// the order they were added and a packet is dropped by the first one that
+/// returns `false`. The number of packets each predicate dropped is
+/// reported by
+/// [`AsyncCaptureHandle::pipeline_rejections()`](crate::AsyncCaptureHandle::pipeline_rejections).
+///
+/// Predicates are only ever called from the capture thread, but must be
+/// `Sync` because the builder can be cloned; keep their state in atomics or
+/// a `Mutex`.
+#[derive(Clone, Default)]
+pub struct FilterPipeline {
+    pub(crate) bpf: Option<String>,
+    pub(crate) stages: Vec<Stage>,
+}
+
+impl FilterPipeline {
+    /// Creates a pipeline without a kernel filter.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a pipeline whose first stage is the given BPF program, in
+    /// `tcpdump` syntax.
+    pub fn bpf<S: Into<String>>(program: S) -> Self {
+        Self {
+            bpf: Some(program.into()),
+            stages: Vec::new(),
+        }
+    }
+
+    /// Appends a predicate; packets for which it returns `false` are
+    /// dropped.
+    pub fn then<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&Packet) -> bool + Send + Sync + 'static,
+    {
+        self.stages.push(Arc::new(predicate));
+        self
+    }
+
+    /// Returns the index of the first predicate that rejects `packet`.
+    pub(crate) fn rejected_by(&self, packet: &Packet) -> Option<usize> {
+        self.stages.iter().position(|stage| !stage(packet))
+    }
+}
+
+impl fmt::Debug for FilterPipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FilterPipeline")
+            .field("bpf", &self.bpf)
+            .field("stages", &self.stages.len())
+            .finish()
+    }
+}
+
+/// Compiles the BPF `expression`, in `tcpdump` syntax, for packets of
+/// `linktype`, without opening a device.
+///
+/// The program is compiled on a dead capture, so this needs no privileges
+/// and no interface; use it to validate filters from user input or
+/// configuration files before capturing. The returned program can also
+/// test packets with [`BpfProgram::filter()`], e.g. those of an
+/// [`IterSource`](crate::IterSource) in a unit test.
+///
+/// Fails with the pcap error if the expression does not compile for the
+/// link type, e.g. `wlan host` on Ethernet.
+pub fn compile_filter(expression: &str, linktype: Linktype) -> Result<BpfProgram, Error> {
+    Ok(Capture::dead(linktype)?.compile(expression, true)?)
+}
+
+/// Outcome of
+/// [`AsyncCaptureHandle::set_filter()`](crate::AsyncCaptureHandle::set_filter).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilterSwap {
+    /// `true` if a later update was issued before this one ran, in which
+    /// case this program was neither compiled nor installed
+    pub superseded: bool,
+    /// Time the capture thread spent compiling and installing the program
+    pub took: Duration,
+    /// Packets the kernel reported dropped while the program was installed,
+    /// if the source keeps statistics
+    pub kernel_drops: Option<u32>,
+}
+
+/// Predicate run on the capture thread before a packet is copied.
+#[derive(Clone)]
+pub(crate) struct Prefilter(Arc<HeaderPredicate>);
+
+impl Prefilter {
+    pub(crate) fn new<F>(predicate: F) -> Self
+    where
+        F: Fn(&PacketHeader, &[u8]) -> bool + Send + Sync + 'static,
+    {
+        Self(Arc::new(predicate))
+    }
+
+    pub(crate) fn accepts(&self, header: &PacketHeader, data: &[u8]) -> bool {
+        (self.0)(header, data)
+    }
+}
+
+impl fmt::Debug for Prefilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Prefilter").finish_non_exhaustive()
+    }
+}
+
+/// Bounds on the original length of forwarded packets.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct LengthBounds {
+    pub(crate) min: Option<u32>,
+    pub(crate) max: Option<u32>,
+}
+
+impl LengthBounds {
+    pub(crate) fn accepts(&self, header: &PacketHeader) -> bool {
+        self.min.is_none_or(|min| header.len >= min) && self.max.is_none_or(|max| header.len <= max)
+    }
+}
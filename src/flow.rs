@@ -0,0 +1,45 @@
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+
+/// Directional 5-tuple identifying one side of a flow.
+///
+/// Packets travelling in opposite directions of the same connection have
+/// mirrored keys; use [`FlowKey::reversed()`] to map one onto the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FlowKey {
+    /// Source address
+    pub src_ip: IpAddr,
+    /// Destination address
+    pub dst_ip: IpAddr,
+    /// Source port, or 0 for protocols without ports
+    pub src_port: u16,
+    /// Destination port, or 0 for protocols without ports
+    pub dst_port: u16,
+    /// IP protocol number (6 for TCP, 17 for UDP, ...)
+    pub protocol: u8,
+}
+
+impl FlowKey {
+    /// Returns the key of the opposite direction.
+    pub fn reversed(&self) -> Self {
+        Self {
+            src_ip: self.dst_ip,
+            dst_ip: self.src_ip,
+            src_port: self.dst_port,
+            dst_port: self.src_port,
+            protocol: self.protocol,
+        }
+    }
+}
+
+impl fmt::Display for FlowKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} -> {}",
+            self.protocol,
+            SocketAddr::new(self.src_ip, self.src_port),
+            SocketAddr::new(self.dst_ip, self.dst_port)
+        )
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+
+/// How long a parked capture thread waits before re-checking its stop flag.
+const PARK_SLICE: Duration = Duration::from_millis(50);
+
+/// Gate that holds back the read loops of several captures until
+/// [`start_all()`](Self::start_all) is called.
+///
+/// Pass the same gate to each capture with
+/// [`AsyncCaptureBuilder::start_gate()`](crate::AsyncCaptureBuilder::start_gate).
+/// Their background threads open their devices as usual and then park
+/// before the first read. `start_all()` waits until every registered thread
+/// is parked and releases them all at once, so the captures begin reading
+/// within a thread wakeup of each other instead of being staggered by
+/// thread startup.
+///
+/// Once opened the gate stays open: captures registered afterwards start
+/// immediately.
+#[derive(Clone, Default)]
+pub struct StartGate {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    state: Mutex<State>,
+    released: Condvar,
+    parked: Notify,
+}
+
+#[derive(Default)]
+struct State {
+    registered: usize,
+    parked: usize,
+    open: bool,
+}
+
+impl StartGate {
+    /// Creates a closed gate with no registered captures.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits until every registered capture thread is parked, then
+    /// releases them together.
+    pub async fn start_all(&self) {
+        loop {
+            let parked = self.inner.parked.notified();
+            tokio::pin!(parked);
+            parked.as_mut().enable();
+            {
+                let mut state = self.inner.state.lock().unwrap();
+                if state.parked >= state.registered {
+                    state.open = true;
+                    self.inner.released.notify_all();
+                    return;
+                }
+            }
+            parked.await;
+        }
+    }
+
+    /// Returns `true` once [`start_all()`](Self::start_all) has released the gate.
+    pub fn is_open(&self) -> bool {
+        self.inner.state.lock().unwrap().open
+    }
+
+    /// Registers a capture that will later call [`wait`](Self::wait).
+    pub(crate) fn register(&self) {
+        self.inner.state.lock().unwrap().registered += 1;
+    }
+
+    /// Parks the calling capture thread until the gate opens.
+    ///
+    /// Returns `false` early if `stop_flag` is raised, unregistering the
+    /// capture so that `start_all()` does not wait for it.
+    pub(crate) fn wait(&self, stop_flag: &AtomicBool) -> bool {
+        let mut state = self.inner.state.lock().unwrap();
+        state.parked += 1;
+        self.inner.parked.notify_waiters();
+        while !state.open {
+            if stop_flag.load(Ordering::Acquire) {
+                state.parked -= 1;
+                state.registered -= 1;
+                self.inner.parked.notify_waiters();
+                return false;
+            }
+            state = self
+                .inner
+                .released
+                .wait_timeout(state, PARK_SLICE)
+                .unwrap()
+                .0;
+        }
+        true
+    }
+}
+
+impl fmt::Debug for StartGate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.inner.state.lock().unwrap();
+        f.debug_struct("StartGate")
+            .field("registered", &state.registered)
+            .field("parked", &state.parked)
+            .field("open", &state.open)
+            .finish()
+    }
+}
@@ -0,0 +1,173 @@
+use std::net::IpAddr;
+
+use pcap::Linktype;
+
+use crate::async_pcap::Packet;
+use crate::flow::FlowKey;
+use crate::parse::{self, IPPROTO_ICMP, IPPROTO_ICMPV6, IPPROTO_TCP, IPPROTO_UDP};
+
+/// Bytes kept of packets whose headers cannot be decoded in headers-only
+/// mode.
+pub(crate) const DEFAULT_HEADERS_FALLBACK: usize = 64;
+
+/// The commonly used header fields of a packet.
+///
+/// Returned by [`Packet::headers()`]. Every accessor returns `None` when
+/// the layer it reads is missing, e.g. the ports of a packet that is not
+/// IP, or of an ICMP message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PacketHeaders {
+    ip: Option<IpFields>,
+    ports: Option<(u16, u16)>,
+    icmp: Option<(u8, u8)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IpFields {
+    src: IpAddr,
+    dst: IpAddr,
+    protocol: u8,
+    ttl: u8,
+    fragmented: bool,
+}
+
+impl Packet {
+    /// Decodes the IP and transport headers of the packet.
+    ///
+    /// `datalink` is the link type of the capture the packet came from, as
+    /// returned by [`AsyncCapture::datalink()`](crate::AsyncCapture::datalink).
+    /// Ethernet (VLAN-tagged included), raw IP, BSD loopback (`NULL` and
+    /// `LOOP`), Linux cooked (`LINUX_SLL` and `LINUX_SLL2`), PPP and Cisco
+    /// HDLC packets are decoded, which covers loopback, `tun`/`utun` and
+    /// most tunnel interfaces; IPv4 and IPv6 are both handled, with IPv6 extension headers skipped
+    /// to find the transport protocol.
+    ///
+    /// # Notes
+    ///
+    /// - Ports are read from TCP and UDP headers, ICMP type and code from
+    ///   ICMP and ICMPv6 messages.
+    /// - Fragments carry no transport fields, as only the first one holds
+    ///   the transport header; see [`PacketHeaders::is_fragment()`].
+    pub fn headers(&self, datalink: Linktype) -> PacketHeaders {
+        let Some(ip) = parse::network_payload(datalink, &self.data).and_then(parse::ip_layer)
+        else {
+            return PacketHeaders::default();
+        };
+        let mut headers = PacketHeaders {
+            ip: Some(IpFields {
+                src: ip.src,
+                dst: ip.dst,
+                protocol: ip.protocol,
+                ttl: ip.ttl,
+                fragmented: ip.fragmented,
+            }),
+            ..PacketHeaders::default()
+        };
+        if ip.fragmented {
+            return headers;
+        }
+        match ip.protocol {
+            IPPROTO_TCP => {
+                headers.ports = parse::tcp(ip.payload).map(|tcp| (tcp.src_port, tcp.dst_port));
+            }
+            IPPROTO_UDP if ip.payload.len() >= 8 => {
+                headers.ports = parse::be16(ip.payload, 0).zip(parse::be16(ip.payload, 2));
+            }
+            IPPROTO_ICMP | IPPROTO_ICMPV6 => {
+                headers.icmp = ip.payload.first().copied().zip(ip.payload.get(1).copied());
+            }
+            _ => {}
+        }
+        headers
+    }
+}
+
+impl Packet {
+    /// Truncates the packet after its transport header, dropping the
+    /// payload.
+    ///
+    /// The header boundary is found with the same decoding as
+    /// [`headers()`](Self::headers): after the TCP header and its options,
+    /// the UDP header or the first 8 bytes of an ICMP message, or after the
+    /// IP header for fragments and other protocols. `header.caplen` is
+    /// updated, while `header.len` keeps the original length. Returns
+    /// `false`, leaving the packet untouched, if it is not an IP packet.
+    pub fn strip_payload(&mut self, datalink: Linktype) -> bool {
+        let Some(len) = parse::headers_len(datalink, &self.data) else {
+            return false;
+        };
+        self.truncate(len);
+        true
+    }
+
+    /// Truncates the captured data to at most `len` bytes.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        if len < self.data.len() {
+            self.data.truncate(len);
+            self.header.caplen = len as u32;
+        }
+    }
+}
+
+impl PacketHeaders {
+    /// Source address of the IPv4 or IPv6 header.
+    pub fn src_ip(&self) -> Option<IpAddr> {
+        self.ip.map(|ip| ip.src)
+    }
+
+    /// Destination address of the IPv4 or IPv6 header.
+    pub fn dst_ip(&self) -> Option<IpAddr> {
+        self.ip.map(|ip| ip.dst)
+    }
+
+    /// IP protocol number of the transport layer (6 for TCP, 17 for UDP,
+    /// ...), found past any IPv6 extension headers.
+    pub fn protocol(&self) -> Option<u8> {
+        self.ip.map(|ip| ip.protocol)
+    }
+
+    /// IPv4 time to live, or IPv6 hop limit.
+    pub fn ttl(&self) -> Option<u8> {
+        self.ip.map(|ip| ip.ttl)
+    }
+
+    /// Whether the packet is an IP fragment rather than a complete
+    /// datagram.
+    pub fn is_fragment(&self) -> bool {
+        self.ip.is_some_and(|ip| ip.fragmented)
+    }
+
+    /// Source port of a TCP or UDP packet.
+    pub fn src_port(&self) -> Option<u16> {
+        self.ports.map(|(src, _)| src)
+    }
+
+    /// Destination port of a TCP or UDP packet.
+    pub fn dst_port(&self) -> Option<u16> {
+        self.ports.map(|(_, dst)| dst)
+    }
+
+    /// Type of an ICMP or ICMPv6 message.
+    pub fn icmp_type(&self) -> Option<u8> {
+        self.icmp.map(|(kind, _)| kind)
+    }
+
+    /// Code of an ICMP or ICMPv6 message.
+    pub fn icmp_code(&self) -> Option<u8> {
+        self.icmp.map(|(_, code)| code)
+    }
+
+    /// Flow key of an IP packet, with ports set to 0 unless it is a TCP or
+    /// UDP packet.
+    pub fn flow_key(&self) -> Option<FlowKey> {
+        let ip = self.ip?;
+        let (src_port, dst_port) = self.ports.unwrap_or_default();
+        Some(FlowKey {
+            src_ip: ip.src,
+            dst_ip: ip.dst,
+            src_port,
+            dst_port,
+            protocol: ip.protocol,
+        })
+    }
+}
@@ -0,0 +1,49 @@
+use std::ffi::{c_int, c_void};
+
+use pcap::{Activated, Capture, Linktype, Precision};
+
+use crate::tstamp::TimestampSource;
+
+unsafe extern "C" {
+    fn pcap_snapshot(p: *mut c_void) -> c_int;
+    fn pcap_get_tstamp_precision(p: *mut c_void) -> c_int;
+}
+
+/// Parameters a capture actually runs with, queried right after it opened.
+///
+/// libpcap may clamp the requested snaplen and the datalink can differ from
+/// what was expected, so buffers should be sized from these values rather
+/// than from the requested ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureInfo {
+    /// Snapshot length granted by libpcap
+    pub snaplen: i32,
+    /// Link-layer header type of the captured packets
+    pub datalink: Linktype,
+    /// Clock that stamped the packets
+    pub timestamp_source: TimestampSource,
+    /// Unit of the `ts.tv_usec` field of the packet headers
+    pub precision: Precision,
+}
+
+impl CaptureInfo {
+    /// Queries the effective parameters of an opened capture.
+    pub(crate) fn query<T: Activated + ?Sized>(cap: &Capture<T>) -> Self {
+        // SAFETY: the handle stays valid for as long as `cap` is borrowed.
+        let (snaplen, precision) = unsafe {
+            (
+                pcap_snapshot(cap.as_ptr().cast()),
+                pcap_get_tstamp_precision(cap.as_ptr().cast()),
+            )
+        };
+        Self {
+            snaplen,
+            datalink: cap.get_datalink(),
+            timestamp_source: TimestampSource::Host,
+            precision: match precision {
+                1 => Precision::Nano,
+                _ => Precision::Micro,
+            },
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::io;
+
+use pcap::Precision;
+use serde::Serialize;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::async_pcap::{AsyncCapture, Packet};
+use crate::error::Error;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// How packet bytes are written by [`AsyncCapture::into_jsonl_writer()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PayloadEncoding {
+    /// Lowercase hex, two characters per byte
+    #[default]
+    Hex,
+    /// Standard base64 with padding, about a third smaller than hex
+    Base64,
+    /// Only metadata is written; the `payload` field is omitted
+    Omit,
+}
+
+#[derive(Serialize)]
+struct Record {
+    ts_sec: i64,
+    ts_usec: i64,
+    caplen: u32,
+    len: u32,
+    truncated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ErrorRecord {
+    error: String,
+}
+
+impl AsyncCapture {
+    /// Writes every packet of the capture to `out` as JSON Lines, until the
+    /// capture stops.
+    ///
+    /// Each packet becomes one object on its own line:
+    ///
+    /// ```text
+    /// {"ts_sec":1700000000,"ts_usec":123456,"caplen":60,"len":60,"truncated":false,"payload":"ffff..."}
+    /// ```
+    ///
+    /// `ts_usec` is in microseconds whatever the precision of the capture,
+    /// so nanosecond timestamps lose their last three digits. `truncated`
+    /// is set when the snaplen cut the packet short
+    /// (`caplen < len`). The packet bytes are written in `payload` with the
+    /// given encoding. Capture errors are written as `{"error":"..."}`
+    /// records, except read timeouts, which are skipped.
+    ///
+    /// Returns the number of packet records written. Requires the `serde`
+    /// feature.
+    pub async fn into_jsonl_writer<W>(
+        self,
+        mut out: W,
+        encoding: PayloadEncoding,
+    ) -> io::Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let precision = self.info().precision;
+        let mut written = 0;
+        let mut line = Vec::new();
+        while let Some(item) = self.next_packet().await {
+            line.clear();
+            match item {
+                Ok(packet) => {
+                    serde_json::to_writer(&mut line, &record(&packet, precision, encoding))?;
+                    written += 1;
+                }
+                Err(Error::Timeout) => continue,
+                Err(e) => serde_json::to_writer(
+                    &mut line,
+                    &ErrorRecord {
+                        error: e.to_string(),
+                    },
+                )?,
+            }
+            line.push(b'\n');
+            out.write_all(&line).await?;
+        }
+        out.flush().await?;
+        Ok(written)
+    }
+}
+
+// `time_t` and `suseconds_t` are not 64 bits on every platform
+#[allow(clippy::unnecessary_cast)]
+fn record(packet: &Packet, precision: Precision, encoding: PayloadEncoding) -> Record {
+    let payload = match encoding {
+        PayloadEncoding::Hex => Some(hex(&packet.data)),
+        PayloadEncoding::Base64 => Some(base64(&packet.data)),
+        PayloadEncoding::Omit => None,
+    };
+    Record {
+        ts_sec: packet.header.ts.tv_sec as i64,
+        ts_usec: match precision {
+            Precision::Micro => packet.header.ts.tv_usec as i64,
+            Precision::Nano => packet.header.ts.tv_usec as i64 / 1000,
+        },
+        caplen: packet.header.caplen,
+        len: packet.header.len,
+        truncated: packet.header.caplen < packet.header.len,
+        payload,
+    }
+}
+
+fn hex(data: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut out = String::with_capacity(data.len() * 2);
+    for byte in data {
+        out.push(DIGITS[usize::from(byte >> 4)] as char);
+        out.push(DIGITS[usize::from(byte & 0x0f)] as char);
+    }
+    out
+}
+
+fn base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -1,6 +1,175 @@
 mod async_pcap;
+mod backoff;
+#[cfg(feature = "rt-tokio")]
+mod broadcast;
+mod builder;
+#[cfg(feature = "cancellation")]
+mod cancel;
+#[cfg(feature = "checksums")]
+mod checksum;
+mod clock;
+mod config;
+mod dedup;
+#[cfg(feature = "ip-defrag")]
+mod defrag;
+mod device;
+mod drop_monitor;
+mod dumper;
+mod error;
+#[cfg(feature = "serde")]
+mod export;
+#[cfg(feature = "metrics")]
+mod facade;
+#[cfg(target_os = "linux")]
+mod fanout;
+#[cfg(all(unix, feature = "rt-tokio"))]
+mod fd_capture;
+mod filter;
+mod flow;
+mod gate;
+mod headers;
+mod info;
+#[cfg(feature = "serde")]
+mod jsonl;
+mod local;
+mod log_limit;
+mod logging;
+#[cfg(feature = "rt-tokio")]
+mod merge;
+mod meta;
+#[cfg(feature = "prometheus")]
+mod metrics;
+mod nonblock;
+mod offload;
+#[cfg(feature = "rt-tokio")]
+mod oneshot;
+mod parse;
+mod pause;
+mod pcapng;
+mod pool;
+mod probe;
+#[cfg(feature = "process-info")]
+mod process;
+mod profile;
+mod queue;
+#[cfg(feature = "radiotap")]
+mod radiotap;
+mod rate_limit;
+mod reader;
+#[cfg(feature = "tcp-reassembly")]
+mod reassembly;
+mod reconnect;
+mod recorder;
+#[cfg(feature = "remote")]
+mod remote;
+mod replay;
+mod replayer;
+mod retry;
+#[cfg(feature = "ring-file")]
+mod ring_file;
+mod sample;
+mod schedule;
+mod sender;
+#[cfg(feature = "sink")]
+mod sink;
+#[cfg(any(feature = "metrics", feature = "prometheus"))]
+mod snapshot;
+mod sniff;
+mod source;
+#[cfg(all(target_os = "linux", feature = "rt-tokio"))]
+mod split;
+mod stats;
+mod summary;
+mod sync_capture;
+mod throughput;
+mod timing;
+mod tstamp;
+#[cfg(feature = "parse")]
+mod views;
+mod window;
+mod worker;
 
-pub use async_pcap::{AsyncCapture, AsyncCaptureHandle, Packet};
+pub use async_pcap::{AsyncCapture, AsyncCaptureHandle, Packet, StreamItem};
+pub use backoff::IdleBackoff;
+#[cfg(feature = "rt-tokio")]
+pub use broadcast::{PacketBroadcast, PacketSubscriber};
+pub use builder::AsyncCaptureBuilder;
+#[cfg(feature = "checksums")]
+pub use checksum::{ChecksumStatus, Checksums};
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use config::CaptureConfig;
+pub use dedup::DedupMode;
+#[cfg(feature = "ip-defrag")]
+pub use defrag::{DefragConfig, IpDefragmenter};
+pub use device::{DeviceInfo, device_by_name, devices};
+pub use drop_monitor::{DropAlert, DropReasons};
+pub use dumper::{AsyncDumper, AsyncDumperBuilder};
+pub use error::{Error, ErrorKind};
+#[cfg(feature = "serde")]
+pub use export::ExportFormat;
+#[cfg(target_os = "linux")]
+pub use fanout::FanoutMode;
+#[cfg(all(unix, feature = "rt-tokio"))]
+pub use fd_capture::AsyncFdCapture;
+pub use filter::{FilterPipeline, FilterSwap, compile_filter};
+pub use flow::FlowKey;
+pub use gate::StartGate;
+pub use headers::PacketHeaders;
+pub use info::CaptureInfo;
+#[cfg(feature = "serde")]
+pub use jsonl::PayloadEncoding;
+pub use local::LocalAddresses;
+#[cfg(feature = "rt-tokio")]
+pub use merge::{MergedCapture, MergedHandle};
+pub use meta::PacketMeta;
+#[cfg(feature = "rt-tokio")]
+pub use oneshot::capture_one;
+pub use pcap::Error as PcapError;
 pub use pcap::{
-    Active, Capture, ConnectionStatus, Dead, Device, DeviceFlags, Error, IfFlags, Inactive, Offline,
+    Active, Address, BpfProgram, Capture, ConnectionStatus, Dead, Device, DeviceFlags, Direction,
+    IfFlags, Inactive, Linktype, Offline, Precision, Stat,
 };
+pub use pcapng::{InterfaceDescription, PcapNgWriter};
+#[cfg(feature = "bytes")]
+pub use pool::BytesPacket;
+pub use pool::PooledPacket;
+pub use probe::ReadBehavior;
+#[cfg(feature = "process-info")]
+pub use process::ProcessInfo;
+pub use profile::CaptureProfile;
+pub use queue::OverflowPolicy;
+#[cfg(feature = "radiotap")]
+pub use radiotap::{FrameType, Ieee80211Header, Radiotap, RadiotapChannel};
+pub use rate_limit::RateLimit;
+pub use reader::AsyncCaptureReader;
+#[cfg(feature = "tcp-reassembly")]
+pub use reassembly::{CloseReason, FlowEvent, ReassemblyConfig, TcpReassembler};
+pub use reconnect::ReconnectPolicy;
+pub use recorder::RingRecorder;
+#[cfg(feature = "remote")]
+pub use remote::RemoteAuth;
+pub use replay::Paced;
+pub use replayer::{Replay, ReplayEvent, ReplayProgress, Replayer};
+pub use retry::RetryPolicy;
+#[cfg(feature = "ring-file")]
+pub use ring_file::{
+    RING_FILE_HEADER_LEN, RING_FILE_MAGIC, RING_FILE_VERSION, RING_FLAG_FINALIZED, RING_FLAG_NANO,
+    RING_RECORD_HEADER_LEN, RingFile, RingFileConfig,
+};
+pub use schedule::CaptureSchedule;
+pub use sender::AsyncSender;
+#[cfg(feature = "sink")]
+pub use sink::ForwardError;
+pub use sniff::{SniffOptions, Sniffer, Verbosity, sniff, summarize};
+pub use source::{IterSource, PacketSource};
+#[cfg(all(target_os = "linux", feature = "rt-tokio"))]
+pub use split::{InterfaceSplit, InterfaceStream};
+pub use stats::CaptureStats;
+pub use summary::{CaptureSummary, EndReason};
+pub use sync_capture::SyncCapture;
+pub use throughput::Throughput;
+pub use timing::{PacketTiming, QueueDwell};
+pub use tstamp::TimestampSource;
+#[cfg(feature = "parse")]
+pub use views::{EthernetView, IpView, TcpView, UdpView};
+pub use window::{TimeWindow, TimeWindows};
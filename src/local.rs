@@ -0,0 +1,85 @@
+use std::net::IpAddr;
+
+use pcap::{Address, Device};
+
+#[cfg(target_os = "linux")]
+use crate::builder::parse_mac;
+
+/// Addresses of the capturing interface, recorded when it was opened.
+///
+/// Compare packet addresses against these to tell whether a packet was
+/// sent or received by the local host, without querying the system for
+/// each capture. Captures not opened from a device have no addresses.
+#[derive(Debug, Clone, Default)]
+pub struct LocalAddresses {
+    /// Name of the interface
+    pub device: Option<String>,
+    /// IP addresses with their netmask, broadcast and point-to-point
+    /// destination addresses, as reported by libpcap
+    pub ip: Vec<Address>,
+    /// Link-layer (MAC) address, where the platform exposes it
+    pub link: Option<[u8; 6]>,
+}
+
+impl LocalAddresses {
+    /// Records the addresses of `device`.
+    ///
+    /// A device built from a bare name carries no addresses, so they are
+    /// looked up in the device list by name in that case.
+    pub(crate) fn of(device: &Device) -> Self {
+        let ip = if device.addresses.is_empty() {
+            Device::list()
+                .ok()
+                .and_then(|list| list.into_iter().find(|d| d.name == device.name))
+                .map(|d| d.addresses)
+                .unwrap_or_default()
+        } else {
+            device.addresses.clone()
+        };
+        Self {
+            device: Some(device.name.clone()),
+            ip,
+            link: link_address(&device.name),
+        }
+    }
+
+    /// Whether `addr` is one of the interface's IP addresses.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        self.ip.iter().any(|a| a.addr == addr)
+    }
+
+    /// Whether `addr` is on one of the interface's networks, according to
+    /// the netmasks.
+    pub fn is_on_link(&self, addr: IpAddr) -> bool {
+        self.ip.iter().any(|a| match (a.addr, a.netmask, addr) {
+            (IpAddr::V4(local), Some(IpAddr::V4(mask)), IpAddr::V4(addr)) => {
+                u32::from(local) & u32::from(mask) == u32::from(addr) & u32::from(mask)
+            }
+            (IpAddr::V6(local), Some(IpAddr::V6(mask)), IpAddr::V6(addr)) => {
+                u128::from(local) & u128::from(mask) == u128::from(addr) & u128::from(mask)
+            }
+            _ => false,
+        })
+    }
+
+    /// Whether `mac` is the interface's link-layer address.
+    pub fn is_local_mac(&self, mac: [u8; 6]) -> bool {
+        self.link == Some(mac)
+    }
+}
+
+/// Reads the MAC address of an interface from sysfs.
+#[cfg(target_os = "linux")]
+pub(crate) fn link_address(name: &str) -> Option<[u8; 6]> {
+    if name.contains('/') {
+        return None;
+    }
+    let text = std::fs::read_to_string(format!("/sys/class/net/{name}/address")).ok()?;
+    // Interfaces without a link-layer address, like loopback, report zeros
+    parse_mac(text.trim()).ok().filter(|mac| *mac != [0; 6])
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn link_address(_name: &str) -> Option<[u8; 6]> {
+    None
+}
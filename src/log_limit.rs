@@ -0,0 +1,65 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::logging::warn;
+
+/// Period over which repeats of the same message are collapsed.
+const REPEAT_WINDOW: Duration = Duration::from_secs(1);
+
+/// Collapses repeated warnings of the capture thread.
+///
+/// The first occurrence of a message is logged; identical messages within
+/// the next second are only counted, and reported as one "repeated N
+/// times" line once the second is over, a different message is logged, or
+/// the capture ends. Messages are compared by a key, so that ones
+/// carrying counters, like retry attempts, still collapse.
+#[derive(Debug, Default)]
+pub(crate) struct LogLimiter {
+    last: Option<String>,
+    since: Option<Instant>,
+    repeats: u64,
+}
+
+impl LogLimiter {
+    /// Logs `args` at warn level unless a message with the same `key` was
+    /// logged within the last second.
+    pub(crate) fn warn(&mut self, key: &str, args: fmt::Arguments<'_>) {
+        let now = Instant::now();
+        let within = self
+            .since
+            .is_some_and(|since| now.duration_since(since) < REPEAT_WINDOW);
+        if within && self.last.as_deref() == Some(key) {
+            self.repeats += 1;
+            return;
+        }
+        self.flush();
+        warn!("{args}");
+        self.last = Some(key.to_owned());
+        self.since = Some(now);
+    }
+
+    /// Reports the repeats counted once their window is over.
+    pub(crate) fn tick(&mut self) {
+        if self.repeats > 0
+            && self
+                .since
+                .is_some_and(|since| since.elapsed() >= REPEAT_WINDOW)
+        {
+            self.flush();
+            self.last = None;
+        }
+    }
+
+    /// Reports the repeats counted so far.
+    pub(crate) fn flush(&mut self) {
+        if self.repeats == 0 {
+            return;
+        }
+        let elapsed = self.since.map(|since| since.elapsed()).unwrap_or_default();
+        warn!(
+            "AsyncCapture last message repeated {} times in {elapsed:.1?}.",
+            self.repeats
+        );
+        self.repeats = 0;
+    }
+}
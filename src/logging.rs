@@ -0,0 +1,36 @@
+use std::fmt;
+use std::sync::Arc;
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use log::{debug, error, info, warn};
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::{debug, error, info, warn};
+
+use crate::error::Error;
+
+/// Callback the capture thread reports the errors it recovers from to.
+#[derive(Clone)]
+pub(crate) struct ErrorHook(Arc<dyn Fn(&Error) + Send + Sync>);
+
+impl ErrorHook {
+    pub(crate) fn new(callback: Arc<dyn Fn(&Error) + Send + Sync>) -> Self {
+        Self(callback)
+    }
+
+    pub(crate) fn report(&self, error: &Error) {
+        (self.0)(error)
+    }
+}
+
+impl fmt::Debug for ErrorHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ErrorHook").finish_non_exhaustive()
+    }
+}
+
+/// Span the capture thread runs in, so that its events carry the device
+/// and the filter of the capture.
+#[cfg(feature = "tracing")]
+pub(crate) fn capture_span(device: Option<&str>, filter: Option<&str>) -> tracing::Span {
+    tracing::info_span!("capture", device, filter)
+}
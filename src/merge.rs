@@ -0,0 +1,393 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fmt;
+use std::time::Duration;
+
+use pcap::{Precision, Stat};
+use tokio::sync::Mutex;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::time::Instant;
+
+use crate::async_pcap::{AsyncCapture, AsyncCaptureHandle, Packet};
+use crate::builder::AsyncCaptureBuilder;
+use crate::error::Error;
+use crate::info::CaptureInfo;
+use crate::summary::CaptureSummary;
+use crate::throughput::Throughput;
+
+/// Packets of several captures merged into one stream.
+///
+/// Returned by [`AsyncCapture::merge()`] and
+/// [`AsyncCapture::merge_ordered()`]. Each packet is tagged with the index
+/// of the builder its capture was opened from.
+pub struct MergedCapture {
+    inbox: Mutex<Inbox>,
+    sources: Vec<(String, CaptureInfo)>,
+}
+
+/// Message of a forwarding task.
+enum Forwarded {
+    Item(usize, Result<Packet, Error>),
+    /// The capture of the given index has ended
+    Ended(usize),
+}
+
+struct Inbox {
+    rx: UnboundedReceiver<Forwarded>,
+    /// Set when packets are put in timestamp order
+    reorder: Option<Reorder>,
+}
+
+/// Packets held back to be handed out in timestamp order.
+struct Reorder {
+    window: Duration,
+    heap: BinaryHeap<Reverse<Held>>,
+    /// Packets held per capture
+    held: Vec<usize>,
+    /// Whether each capture may still deliver packets
+    open: Vec<bool>,
+    /// Timestamp precision of each capture
+    precisions: Vec<Precision>,
+    /// Arrival counter, which keeps equal timestamps in arrival order
+    seq: u64,
+}
+
+struct Held {
+    ts: Duration,
+    seq: u64,
+    arrived: Instant,
+    index: usize,
+    packet: Packet,
+}
+
+impl PartialEq for Held {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Held {}
+
+impl PartialOrd for Held {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Held {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.ts, self.seq).cmp(&(other.ts, other.seq))
+    }
+}
+
+impl Reorder {
+    fn new(window: Duration, precisions: Vec<Precision>) -> Self {
+        Self {
+            window,
+            heap: BinaryHeap::new(),
+            held: vec![0; precisions.len()],
+            open: vec![true; precisions.len()],
+            precisions,
+            seq: 0,
+        }
+    }
+
+    fn push(&mut self, index: usize, packet: Packet) {
+        self.seq += 1;
+        self.held[index] += 1;
+        self.heap.push(Reverse(Held {
+            ts: packet.since_epoch_with(self.precisions[index]),
+            seq: self.seq,
+            arrived: Instant::now(),
+            index,
+            packet,
+        }));
+    }
+
+    /// Takes the earliest packet once no capture can deliver an earlier
+    /// one: every open capture has a packet held, or the earliest has
+    /// waited for the whole window.
+    fn pop_ready(&mut self) -> Option<(usize, Result<Packet, Error>)> {
+        let Reverse(head) = self.heap.peek()?;
+        let complete = self
+            .open
+            .iter()
+            .zip(&self.held)
+            .all(|(open, held)| !open || *held > 0);
+        if !complete && head.arrived + self.window > Instant::now() {
+            return None;
+        }
+        let Reverse(head) = self.heap.pop()?;
+        self.held[head.index] -= 1;
+        Some((head.index, Ok(head.packet)))
+    }
+
+    /// When the earliest packet is released regardless of the others.
+    fn deadline(&self) -> Option<Instant> {
+        self.heap
+            .peek()
+            .map(|Reverse(head)| head.arrived + self.window)
+    }
+}
+
+/// Handle controlling all the captures of a [`MergedCapture`] at once.
+#[derive(Clone)]
+pub struct MergedHandle {
+    handles: Vec<AsyncCaptureHandle>,
+}
+
+impl AsyncCapture {
+    /// Opens one capture per builder and merges their packets into a
+    /// single tagged stream.
+    ///
+    /// Each builder carries its own device, filter and options, which are
+    /// applied independently on the capture's own thread; to capture DNS
+    /// on one interface and HTTP on another, give each builder its
+    /// [`filter()`](AsyncCaptureBuilder::filter). Packets are tagged with
+    /// the index of their builder.
+    ///
+    /// # Notes
+    ///
+    /// - If a capture fails to open, the ones already opened are stopped
+    ///   and the error is returned.
+    /// - Spawns one Tokio task per capture to forward its packets, so it
+    ///   must be called from within a Tokio runtime.
+    /// - Packets of different captures are interleaved in the order the
+    ///   tasks forward them, which is close to but not exactly timestamp
+    ///   order.
+    pub fn merge<I>(builders: I) -> Result<(MergedCapture, MergedHandle), Error>
+    where
+        I: IntoIterator<Item = AsyncCaptureBuilder>,
+    {
+        Self::open_merged(builders, None)
+    }
+
+    /// Like [`merge()`](Self::merge), but hands the packets out in
+    /// timestamp order across the captures.
+    ///
+    /// Each packet is held until every other capture has delivered a
+    /// later one, or for at most `window` after it arrived, which bounds
+    /// the latency added on idle interfaces. A packet that arrives more
+    /// than `window` after an earlier-stamped packet of another capture
+    /// still comes out of order; a window of a few read timeouts covers
+    /// the usual delays.
+    ///
+    /// # Notes
+    ///
+    /// - Timestamps of different interfaces are only comparable if they
+    ///   come from the same clock, e.g. host timestamps.
+    /// - Errors are handed out as soon as they arrive, ahead of the packets
+    ///   held back.
+    /// - Packets are held on the receiving side, so they are only ordered
+    ///   while [`MergedCapture::next_packet()`] is being called.
+    pub fn merge_ordered<I>(
+        builders: I,
+        window: Duration,
+    ) -> Result<(MergedCapture, MergedHandle), Error>
+    where
+        I: IntoIterator<Item = AsyncCaptureBuilder>,
+    {
+        Self::open_merged(builders, Some(window))
+    }
+
+    fn open_merged<I>(
+        builders: I,
+        window: Option<Duration>,
+    ) -> Result<(MergedCapture, MergedHandle), Error>
+    where
+        I: IntoIterator<Item = AsyncCaptureBuilder>,
+    {
+        let mut captures = Vec::new();
+        let mut handles = Vec::new();
+        for builder in builders {
+            match builder.open() {
+                Ok((capture, handle)) => {
+                    captures.push(capture);
+                    handles.push(handle);
+                }
+                Err(e) => {
+                    handles.iter().for_each(AsyncCaptureHandle::stop);
+                    return Err(e);
+                }
+            }
+        }
+
+        let (tx, rx) = unbounded_channel();
+        let sources: Vec<_> = captures
+            .iter()
+            .map(|capture| {
+                let device = capture.local_addresses().device.clone();
+                (device.unwrap_or_default(), capture.info().clone())
+            })
+            .collect();
+        for (index, capture) in captures.into_iter().enumerate() {
+            tokio::spawn(forward(index, capture, tx.clone()));
+        }
+        let reorder = window.map(|window| {
+            let precisions = sources.iter().map(|(_, info)| info.precision).collect();
+            Reorder::new(window, precisions)
+        });
+        let merged = MergedCapture {
+            inbox: Mutex::new(Inbox { rx, reorder }),
+            sources,
+        };
+        Ok((merged, MergedHandle { handles }))
+    }
+}
+
+async fn forward(index: usize, capture: AsyncCapture, tx: UnboundedSender<Forwarded>) {
+    while let Some(item) = capture.next_packet().await {
+        if tx.send(Forwarded::Item(index, item)).is_err() {
+            // Dropping the capture ends its thread
+            return;
+        }
+    }
+    let _ = tx.send(Forwarded::Ended(index));
+}
+
+impl MergedCapture {
+    /// Waits for the next packet of any capture, along with the index of
+    /// the builder it was opened from.
+    ///
+    /// Returns `None` once every capture has stopped.
+    pub async fn next_packet(&self) -> Option<(usize, Result<Packet, Error>)> {
+        let mut inbox = self.inbox.lock().await;
+        let Inbox { rx, reorder } = &mut *inbox;
+        let Some(reorder) = reorder else {
+            loop {
+                match rx.recv().await? {
+                    Forwarded::Item(index, item) => return Some((index, item)),
+                    Forwarded::Ended(_) => continue,
+                }
+            }
+        };
+        loop {
+            if let Some(item) = reorder.pop_ready() {
+                return Some(item);
+            }
+            let message = match reorder.deadline() {
+                Some(deadline) => match tokio::time::timeout_at(deadline, rx.recv()).await {
+                    Ok(message) => message,
+                    Err(_) => continue,
+                },
+                None => rx.recv().await,
+            };
+            match message {
+                Some(Forwarded::Item(index, Ok(packet))) => reorder.push(index, packet),
+                Some(Forwarded::Item(index, Err(e))) => return Some((index, Err(e))),
+                Some(Forwarded::Ended(index)) => reorder.open[index] = false,
+                None => {
+                    // Every capture has ended: release what is held
+                    reorder.open.fill(false);
+                    if reorder.heap.is_empty() {
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Number of merged captures.
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Whether no capture is merged.
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Name of the device of capture `index`.
+    pub fn device(&self, index: usize) -> Option<&str> {
+        self.sources.get(index).map(|(device, _)| device.as_str())
+    }
+
+    /// Properties of capture `index`, such as its datalink, which are
+    /// needed to parse its packets.
+    pub fn info(&self, index: usize) -> Option<&CaptureInfo> {
+        self.sources.get(index).map(|(_, info)| info)
+    }
+
+    /// A merged capture of `sources` that delivers no packets.
+    #[cfg(test)]
+    pub(crate) fn idle(sources: Vec<(String, CaptureInfo)>) -> Self {
+        let (_, rx) = unbounded_channel();
+        Self {
+            inbox: Mutex::new(Inbox { rx, reorder: None }),
+            sources,
+        }
+    }
+}
+
+impl fmt::Debug for MergedCapture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MergedCapture")
+            .field("sources", &self.sources)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MergedHandle {
+    /// Signals every capture to stop.
+    pub fn stop(&self) {
+        self.handles.iter().for_each(AsyncCaptureHandle::stop);
+    }
+
+    /// Returns `true` while any capture thread is running.
+    pub fn is_running(&self) -> bool {
+        self.handles.iter().any(AsyncCaptureHandle::is_running)
+    }
+
+    /// Handles of the individual captures, in builder order.
+    pub fn handles(&self) -> &[AsyncCaptureHandle] {
+        &self.handles
+    }
+
+    /// Returns the combined packet and bit rates of all captures.
+    pub fn throughput(&self) -> Throughput {
+        self.handles
+            .iter()
+            .map(AsyncCaptureHandle::throughput)
+            .fold(Throughput::default(), |total, rate| Throughput {
+                pps: total.pps + rate.pps,
+                bps: total.bps + rate.bps,
+            })
+    }
+
+    /// Returns the kernel statistics summed over all captures.
+    ///
+    /// Fails with the first error reported by a capture, e.g.
+    /// [`Error::CaptureStopped`] once one has stopped.
+    pub async fn stats(&self) -> Result<Stat, Error> {
+        let mut total = Stat {
+            received: 0,
+            dropped: 0,
+            if_dropped: 0,
+        };
+        for handle in &self.handles {
+            let stat = handle.with_capture(|cap| cap.stats()).await??;
+            total.received = total.received.wrapping_add(stat.received);
+            total.dropped = total.dropped.wrapping_add(stat.dropped);
+            total.if_dropped = total.if_dropped.wrapping_add(stat.if_dropped);
+        }
+        Ok(total)
+    }
+
+    /// Waits until every capture thread has exited and returns their
+    /// final reports, in builder order.
+    pub async fn wait_stopped(&self) -> Vec<CaptureSummary> {
+        let mut summaries = Vec::with_capacity(self.handles.len());
+        for handle in &self.handles {
+            summaries.push(handle.wait_stopped().await);
+        }
+        summaries
+    }
+}
+
+impl fmt::Debug for MergedHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MergedHandle")
+            .field("captures", &self.handles.len())
+            .finish_non_exhaustive()
+    }
+}
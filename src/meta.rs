@@ -0,0 +1,66 @@
+use std::time::{Instant, SystemTime};
+
+#[cfg(feature = "checksums")]
+use crate::checksum::Checksums;
+#[cfg(feature = "process-info")]
+use crate::process::ProcessInfo;
+use crate::timing::PacketTiming;
+
+/// Per-packet information gathered on the capture thread, returned by
+/// [`AsyncCapture::next_packet_meta()`](crate::AsyncCapture::next_packet_meta).
+///
+/// Each field is only filled in when the option that produces it is
+/// enabled on the builder.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PacketMeta {
+    /// Queue timing, with
+    /// [`AsyncCaptureBuilder::timing_instrumentation()`](crate::AsyncCaptureBuilder::timing_instrumentation)
+    pub timing: Option<PacketTiming>,
+    /// Host time at which the capture thread read the packet, when its
+    /// `header.ts` comes from the network adapter; see
+    /// [`AsyncCaptureBuilder::hardware_timestamps()`](crate::AsyncCaptureBuilder::hardware_timestamps)
+    pub host_timestamp: Option<SystemTime>,
+    /// Checksum verification results, with
+    /// [`AsyncCaptureBuilder::validate_checksums()`](crate::AsyncCaptureBuilder::validate_checksums)
+    #[cfg(feature = "checksums")]
+    pub checksums: Option<Checksums>,
+    /// Whether the packet exceeds the link MTU, with
+    /// [`AsyncCaptureBuilder::detect_offload()`](crate::AsyncCaptureBuilder::detect_offload);
+    /// see [`Packet::is_likely_coalesced()`](crate::Packet::is_likely_coalesced)
+    pub coalesced: bool,
+    /// Local process owning the packet's socket, with
+    /// [`AsyncCaptureBuilder::annotate_process()`](crate::AsyncCaptureBuilder::annotate_process)
+    #[cfg(feature = "process-info")]
+    pub process: Option<ProcessInfo>,
+}
+
+/// Side information sent along with a packet through the internal channel.
+#[derive(Debug, Default)]
+pub(crate) struct Annotations {
+    pub(crate) enqueued_at: Option<Instant>,
+    pub(crate) host_timestamp: Option<SystemTime>,
+    #[cfg(feature = "checksums")]
+    pub(crate) checksums: Option<Checksums>,
+    pub(crate) coalesced: bool,
+    #[cfg(feature = "process-info")]
+    pub(crate) process: Option<ProcessInfo>,
+}
+
+impl Annotations {
+    /// Completes the annotations of a packet received at `delivered_at`.
+    pub(crate) fn into_meta(self, delivered_at: Instant) -> PacketMeta {
+        PacketMeta {
+            timing: self.enqueued_at.map(|enqueued_at| PacketTiming {
+                enqueued_at,
+                delivered_at,
+            }),
+            host_timestamp: self.host_timestamp,
+            #[cfg(feature = "checksums")]
+            checksums: self.checksums,
+            coalesced: self.coalesced,
+            #[cfg(feature = "process-info")]
+            process: self.process,
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::MetricFamily;
+use prometheus::{Gauge, IntCounter, Opts, Registry};
+
+use crate::async_pcap::AsyncCaptureHandle;
+use crate::snapshot::{MetricValue, snapshot};
+
+/// One metric of the capture in the registry.
+enum Exported {
+    Counter(IntCounter),
+    Gauge(Gauge),
+}
+
+impl Exported {
+    fn collector(&self) -> &dyn Collector {
+        match self {
+            Exported::Counter(counter) => counter,
+            Exported::Gauge(gauge) => gauge,
+        }
+    }
+
+    fn update(&self, value: MetricValue) {
+        match (self, value) {
+            (Exported::Counter(counter), MetricValue::Counter(value)) => advance(counter, value),
+            (Exported::Gauge(gauge), MetricValue::Gauge(value)) => gauge.set(value),
+            _ => unreachable!("snapshots list the metrics in a fixed order"),
+        }
+    }
+}
+
+/// Prometheus collector reading the counters of one capture at scrape
+/// time.
+struct CaptureCollector {
+    handle: AsyncCaptureHandle,
+    metrics: Vec<Exported>,
+    descs: Vec<Desc>,
+}
+
+impl CaptureCollector {
+    fn new(
+        handle: AsyncCaptureHandle,
+        labels: HashMap<String, String>,
+    ) -> prometheus::Result<Self> {
+        let metrics = snapshot(&handle)
+            .iter()
+            .map(|metric| {
+                let opts = Opts::new(metric.name, metric.help).const_labels(labels.clone());
+                Ok(match metric.value {
+                    MetricValue::Counter(_) => Exported::Counter(IntCounter::with_opts(opts)?),
+                    MetricValue::Gauge(_) => Exported::Gauge(Gauge::with_opts(opts)?),
+                })
+            })
+            .collect::<prometheus::Result<Vec<_>>>()?;
+        let descs = metrics
+            .iter()
+            .flat_map(|metric| metric.collector().desc())
+            .cloned()
+            .collect();
+        Ok(Self {
+            handle,
+            metrics,
+            descs,
+        })
+    }
+}
+
+/// Raises `counter` to `value`; counters only go up, so a value that
+/// went backwards, like a wrapped kernel counter, is ignored.
+fn advance(counter: &IntCounter, value: u64) {
+    let current = counter.get();
+    if value > current {
+        counter.inc_by(value - current);
+    }
+}
+
+impl Collector for CaptureCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        self.descs.iter().collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let mut families = Vec::new();
+        for (exported, metric) in self.metrics.iter().zip(snapshot(&self.handle)) {
+            exported.update(metric.value);
+            families.extend(exported.collector().collect());
+        }
+        families
+    }
+}
+
+impl AsyncCaptureHandle {
+    /// Registers the metrics of the capture with a Prometheus `registry`,
+    /// each carrying the constant `labels`.
+    ///
+    /// Requires the `prometheus` feature. The metrics are:
+    ///
+    /// - `pcap_packets_received_total` and `pcap_bytes_received_total`:
+    ///   packets read from the source, and their original length
+    /// - `pcap_packets_dropped_total` and `pcap_packets_if_dropped_total`:
+    ///   kernel and interface drops, as in
+    ///   [`DropReasons`](crate::DropReasons)
+    /// - `pcap_packets_overflow_dropped_total`: packets dropped by a full
+    ///   bounded queue, as counted by [`overflow_drops()`](Self::overflow_drops)
+    /// - `pcap_packets_filtered_total`: packets discarded on the capture
+    ///   thread, as in [`CaptureStats::filtered`](crate::CaptureStats::filtered)
+    /// - `pcap_read_retries_total` and `pcap_reconnects_total`: transient
+    ///   read errors retried, and devices reopened
+    /// - `pcap_soft_errors_total`: errors the capture thread recovered
+    ///   from, as counted by [`soft_errors()`](Self::soft_errors)
+    /// - `pcap_queue_depth`: packets read but not yet received by the
+    ///   consumer
+    /// - `pcap_packets_per_second` and `pcap_bits_per_second`: the
+    ///   [`throughput()`](Self::throughput)
+    /// - `pcap_uptime_seconds`: time since the read loop started, or how
+    ///   long it ran once the capture has ended
+    ///
+    /// Values are read from the capture's own counters when the registry
+    /// is gathered, so the capture thread does no extra work per packet.
+    ///
+    /// # Notes
+    ///
+    /// - The drop counters are refreshed by the capture thread once a
+    ///   second after registration, and stay at zero for sources without
+    ///   statistics.
+    /// - The queue depth is always zero for captures created with
+    ///   [`AsyncCapture::new_into_sender()`](crate::AsyncCapture::new_into_sender).
+    /// - The metrics keep their last values after the capture ends. To
+    ///   register several captures with one registry, give each distinct
+    ///   labels, e.g. the device name; registering the same names and
+    ///   labels twice fails with `AlreadyReg`.
+    pub fn register_metrics(
+        &self,
+        registry: &Registry,
+        labels: HashMap<String, String>,
+    ) -> prometheus::Result<()> {
+        let collector = CaptureCollector::new(self.clone(), labels)?;
+        registry.register(Box::new(collector))?;
+        self.shared.publish_stats.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
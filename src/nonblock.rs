@@ -0,0 +1,92 @@
+use std::ffi::{CStr, c_char, c_int, c_void};
+use std::time::Duration;
+
+use pcap::{Active, Capture};
+
+use crate::error::Error;
+
+/// `PCAP_ERRBUF_SIZE`
+const ERRBUF_SIZE: usize = 256;
+
+unsafe extern "C" {
+    fn pcap_setnonblock(p: *mut c_void, nonblock: c_int, errbuf: *mut c_char) -> c_int;
+    fn pcap_getnonblock(p: *mut c_void, errbuf: *mut c_char) -> c_int;
+    #[cfg(unix)]
+    fn pcap_get_selectable_fd(p: *mut c_void) -> c_int;
+}
+
+/// Puts the capture in or out of non-blocking mode.
+pub(crate) fn set(cap: &mut Capture<Active>, nonblocking: bool) -> Result<(), Error> {
+    let mut errbuf = [0 as c_char; ERRBUF_SIZE];
+    // SAFETY: the handle is valid while `cap` is borrowed and `errbuf` has
+    // the size libpcap writes error messages up to.
+    let ret = unsafe {
+        pcap_setnonblock(
+            cap.as_ptr().cast(),
+            c_int::from(nonblocking),
+            errbuf.as_mut_ptr(),
+        )
+    };
+    if ret < 0 {
+        return Err(error(&errbuf));
+    }
+    Ok(())
+}
+
+/// Returns whether the capture is in non-blocking mode.
+pub(crate) fn get(cap: &mut Capture<Active>) -> Result<bool, Error> {
+    let mut errbuf = [0 as c_char; ERRBUF_SIZE];
+    // SAFETY: as in `set`
+    let ret = unsafe { pcap_getnonblock(cap.as_ptr().cast(), errbuf.as_mut_ptr()) };
+    if ret < 0 {
+        return Err(error(&errbuf));
+    }
+    Ok(ret != 0)
+}
+
+fn error(errbuf: &[c_char; ERRBUF_SIZE]) -> Error {
+    // SAFETY: libpcap NUL-terminates the message, and the buffer was zeroed
+    let msg = unsafe { CStr::from_ptr(errbuf.as_ptr()) };
+    pcap::Error::PcapError(msg.to_string_lossy().into_owned()).into()
+}
+
+/// Waits up to `timeout` for packets to become readable on a non-blocking
+/// capture, returning `true` if they did.
+///
+/// Devices without a selectable descriptor, and failed polls, fall back to
+/// sleeping for `timeout` and return `false`.
+#[cfg(unix)]
+pub(crate) fn wait_readable(cap: &mut Capture<Active>, timeout: Duration) -> bool {
+    // SAFETY: the handle is valid while `cap` is borrowed
+    let fd = unsafe { pcap_get_selectable_fd(cap.as_ptr().cast()) };
+    if fd < 0 {
+        std::thread::sleep(timeout);
+        return false;
+    }
+    let mut fd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let ms = c_int::try_from(timeout.as_millis()).unwrap_or(c_int::MAX);
+    // SAFETY: `fd` is a single valid pollfd for the duration of the call
+    match unsafe { libc::poll(&mut fd, 1, ms) } {
+        ..0 => {
+            std::thread::sleep(timeout);
+            false
+        }
+        0 => false,
+        _ => true,
+    }
+}
+
+/// Waits up to `timeout` for packets to become readable on a non-blocking
+/// capture, returning `true` if they did.
+///
+/// Without a selectable descriptor this sleeps for `timeout` before the
+/// next read is tried, and returns `false`.
+#[cfg(not(unix))]
+pub(crate) fn wait_readable(_cap: &mut Capture<Active>, timeout: Duration) -> bool {
+    std::thread::sleep(timeout);
+    false
+}
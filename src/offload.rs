@@ -0,0 +1,89 @@
+use pcap::Linktype;
+
+use crate::async_pcap::Packet;
+use crate::logging::warn;
+use crate::parse;
+
+/// MTU assumed when the interface's cannot be read.
+pub(crate) const DEFAULT_MTU: u32 = 1500;
+
+impl Packet {
+    /// Whether the packet is larger than the link MTU allows, which means
+    /// the NIC or the kernel coalesced several segments into it (LRO/GRO)
+    /// before it was captured.
+    ///
+    /// Compares the original length of the IP packet, without the
+    /// link-layer header, against `mtu`. Packets whose link type cannot be
+    /// decoded are never reported.
+    ///
+    /// # Notes
+    ///
+    /// - Coalesced packets do not exist on the wire: their IP headers are
+    ///   rewritten and their sizes and timing differ from the segments
+    ///   actually sent. Disable the offloads for accurate captures, on
+    ///   Linux with `ethtool -K <dev> gro off lro off`.
+    /// - Outgoing packets are coalesced the same way by segmentation
+    ///   offload (TSO/GSO), which `ethtool -K <dev> tso off gso off`
+    ///   disables.
+    pub fn is_likely_coalesced(&self, datalink: Linktype, mtu: u32) -> bool {
+        let Some(network) = parse::network_payload(datalink, &self.data) else {
+            return false;
+        };
+        let link_header = (self.data.len() - network.len()) as u32;
+        self.header.len.saturating_sub(link_header) > mtu
+    }
+}
+
+/// Flags coalesced packets on the capture thread and warns about the
+/// first one.
+#[derive(Debug)]
+pub(crate) struct OffloadDetector {
+    datalink: Linktype,
+    mtu: u32,
+    device: Option<String>,
+    warned: bool,
+}
+
+impl OffloadDetector {
+    pub(crate) fn new(datalink: Linktype, mtu: u32, device: Option<String>) -> Self {
+        Self {
+            datalink,
+            mtu,
+            device,
+            warned: false,
+        }
+    }
+
+    /// Whether `packet` is coalesced, logging a warning the first time.
+    pub(crate) fn check(&mut self, packet: &Packet) -> bool {
+        if !packet.is_likely_coalesced(self.datalink, self.mtu) {
+            return false;
+        }
+        if !self.warned {
+            self.warned = true;
+            let device = self.device.as_deref().unwrap_or("<dev>");
+            warn!(
+                "AsyncCapture captured a {}-byte packet over an MTU of {}; receive offload \
+                 is likely on. Disable it with `ethtool -K {device} gro off lro off` for \
+                 accurate capture.",
+                packet.header.len, self.mtu
+            );
+        }
+        true
+    }
+}
+
+/// Reads the MTU of an interface from sysfs.
+#[cfg(target_os = "linux")]
+pub(crate) fn link_mtu(name: &str) -> Option<u32> {
+    if name.contains('/') {
+        return None;
+    }
+    let text = std::fs::read_to_string(format!("/sys/class/net/{name}/mtu")).ok()?;
+    text.trim().parse().ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn link_mtu(_name: &str) -> Option<u32> {
+    None
+}
@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use pcap::Device;
+
+use crate::async_pcap::Packet;
+use crate::builder::AsyncCaptureBuilder;
+use crate::error::Error;
+
+/// Read timeout of the one-shot capture, which bounds how long closing it
+/// takes.
+const ONE_SHOT_READ_TIMEOUT_MS: i32 = 100;
+
+/// Opens `device`, waits up to `timeout` for the first packet matching the
+/// BPF `filter`, and closes the device again.
+///
+/// Returns `None` if no packet matched in time. The capture is opened in
+/// immediate mode with an otherwise default [`AsyncCaptureBuilder`]; use
+/// the builder directly for other settings.
+///
+/// # Notes
+///
+/// - The capture thread has exited and the device is closed by the time
+///   this returns, whatever the outcome, so the device can be reopened
+///   right away. Closing waits for the pending read, at most 100 ms.
+/// - Fails with the pcap error if the device cannot be opened or the
+///   filter does not compile, and with the first read error other than a
+///   timeout.
+pub async fn capture_one<D, S>(
+    device: D,
+    filter: S,
+    timeout: Duration,
+) -> Result<Option<Packet>, Error>
+where
+    D: Into<Device>,
+    S: Into<String>,
+{
+    let (capture, handle) = AsyncCaptureBuilder::new(device)
+        .filter(filter)
+        .immediate_mode(true)
+        .timeout(ONE_SHOT_READ_TIMEOUT_MS)
+        .open()?;
+    let first = async {
+        loop {
+            match capture.next_packet().await {
+                Some(Ok(packet)) => return Ok(Some(packet)),
+                Some(Err(Error::Timeout)) => continue,
+                Some(Err(e)) => return Err(e),
+                None => return Ok(None),
+            }
+        }
+    };
+    let result = tokio::time::timeout(timeout, first)
+        .await
+        .unwrap_or(Ok(None));
+    handle.stop();
+    drop(capture);
+    handle.wait_stopped().await;
+    result
+}
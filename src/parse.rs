@@ -0,0 +1,693 @@
+// Some parsers are only used by optional features
+#![cfg_attr(
+    not(all(feature = "tcp-reassembly", feature = "checksums")),
+    allow(dead_code)
+)]
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use pcap::Linktype;
+
+use crate::flow::FlowKey;
+
+/// IP protocol number for ICMP.
+pub(crate) const IPPROTO_ICMP: u8 = 1;
+/// IP protocol number for TCP.
+pub(crate) const IPPROTO_TCP: u8 = 6;
+/// IP protocol number for UDP.
+pub(crate) const IPPROTO_UDP: u8 = 17;
+/// IP protocol number for ICMPv6.
+pub(crate) const IPPROTO_ICMPV6: u8 = 58;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+
+/// Longest chain of IPv6 extension headers walked to find the transport
+/// header; packets with longer chains are not parsed, which bounds the
+/// work spent on crafted packets.
+pub(crate) const MAX_EXTENSION_HEADERS: usize = 8;
+
+/// `DLT_RAW` as used by most platforms.
+const DLT_RAW: i32 = 12;
+/// `DLT_RAW` as used by OpenBSD.
+const DLT_RAW_OPENBSD: i32 = 14;
+
+/// Length of the Linux cooked capture header.
+const SLL_HEADER_LEN: usize = 16;
+/// Length of the Linux cooked capture v2 header.
+const SLL2_HEADER_LEN: usize = 20;
+
+/// PPP protocol numbers of IPv4 and IPv6.
+const PPP_IPV4: u16 = 0x0021;
+const PPP_IPV6: u16 = 0x0057;
+
+/// `AF_INET`, the same on every platform.
+const BSD_AF_INET: u32 = 2;
+/// `AF_INET6` of Linux, NetBSD and OpenBSD, FreeBSD, and Darwin, all of
+/// which can appear in loopback captures.
+const BSD_AF_INET6: [u32; 4] = [10, 24, 28, 30];
+
+/// Network-layer view of a packet.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct IpLayer<'a> {
+    pub(crate) src: IpAddr,
+    pub(crate) dst: IpAddr,
+    pub(crate) protocol: u8,
+    /// IPv4 time to live or IPv6 hop limit
+    pub(crate) ttl: u8,
+    /// Whether this is a fragment other than a complete datagram
+    pub(crate) fragmented: bool,
+    /// Whether the snaplen cut the datagram short
+    pub(crate) truncated: bool,
+    /// The IP header, options and extension headers included
+    pub(crate) header: &'a [u8],
+    /// Transport header and payload, trimmed to the IP length
+    pub(crate) payload: &'a [u8],
+}
+
+/// TCP segment view.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TcpSegment<'a> {
+    pub(crate) src_port: u16,
+    pub(crate) dst_port: u16,
+    pub(crate) seq: u32,
+    pub(crate) flags: u8,
+    pub(crate) payload: &'a [u8],
+}
+
+pub(crate) const TCP_FIN: u8 = 0x01;
+pub(crate) const TCP_SYN: u8 = 0x02;
+pub(crate) const TCP_RST: u8 = 0x04;
+
+pub(crate) fn be16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+pub(crate) fn be32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Strips the link-layer header and returns the IP packet it carries.
+///
+/// Handles Ethernet, raw IP, the BSD loopback headers, Linux cooked
+/// captures, PPP and Cisco HDLC, which covers loopback, `tun`/`utun` and
+/// most tunnel interfaces.
+pub(crate) fn network_payload(datalink: Linktype, data: &[u8]) -> Option<&[u8]> {
+    match datalink {
+        Linktype::ETHERNET => after_ethertype(data, 12),
+        Linktype::RAW
+        | Linktype(DLT_RAW)
+        | Linktype(DLT_RAW_OPENBSD)
+        | Linktype::IPV4
+        | Linktype::IPV6 => Some(data),
+        // The address family, in the byte order of the capturing host
+        Linktype::NULL => {
+            let family = u32::from_ne_bytes(data.get(..4)?.try_into().ok()?);
+            ip_family(family)
+                .or_else(|| ip_family(family.swap_bytes()))
+                .and(data.get(4..))
+        }
+        // The address family, in network byte order
+        Linktype::LOOP => ip_family(be32(data, 0)?).and(data.get(4..)),
+        // The protocol is the last field of the header
+        Linktype::LINUX_SLL => after_ethertype(data, SLL_HEADER_LEN - 2),
+        Linktype::LINUX_SLL2 => match be16(data, 0)? {
+            ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => data.get(SLL2_HEADER_LEN..),
+            _ => None,
+        },
+        Linktype::PPP | Linktype::PPP_HDLC => ppp(data),
+        // A direction byte, then the PPP header
+        Linktype::PPP_WITH_DIR => ppp(data.get(1..)?),
+        // Address and control bytes, then an EtherType
+        Linktype::C_HDLC => after_ethertype(data, 2),
+        _ => None,
+    }
+}
+
+/// Returns the EtherType at `offset`, past any VLAN tags, and where it
+/// is.
+pub(crate) fn inner_ethertype(data: &[u8], mut offset: usize) -> Option<(u16, usize)> {
+    let mut ethertype = be16(data, offset)?;
+    while ethertype == ETHERTYPE_VLAN || ethertype == ETHERTYPE_QINQ {
+        offset += 4;
+        ethertype = be16(data, offset)?;
+    }
+    Some((ethertype, offset))
+}
+
+/// Returns what follows the EtherType at `offset`, skipping VLAN tags,
+/// if it is IPv4 or IPv6.
+fn after_ethertype(data: &[u8], offset: usize) -> Option<&[u8]> {
+    match inner_ethertype(data, offset)? {
+        (ETHERTYPE_IPV4 | ETHERTYPE_IPV6, offset) => data.get(offset + 2..),
+        _ => None,
+    }
+}
+
+/// `Some` if `family` is a BSD loopback address family of IP.
+fn ip_family(family: u32) -> Option<()> {
+    (family == BSD_AF_INET || BSD_AF_INET6.contains(&family)).then_some(())
+}
+
+/// Strips a PPP header, with or without the HDLC address and control
+/// bytes, if it carries IPv4 or IPv6.
+fn ppp(data: &[u8]) -> Option<&[u8]> {
+    let data = data.strip_prefix(&[0xff, 0x03]).unwrap_or(data);
+    // A compressed protocol field is a single, odd byte
+    let (protocol, len) = if data.first()? & 1 == 1 {
+        (u16::from(data[0]), 1)
+    } else {
+        (be16(data, 0)?, 2)
+    };
+    match protocol {
+        PPP_IPV4 | PPP_IPV6 => data.get(len..),
+        _ => None,
+    }
+}
+
+/// Parses an IPv4 or IPv6 header.
+pub(crate) fn ip_layer(data: &[u8]) -> Option<IpLayer<'_>> {
+    match data.first()? >> 4 {
+        4 => ipv4(data),
+        6 => ipv6(data),
+        _ => None,
+    }
+}
+
+fn ipv4(data: &[u8]) -> Option<IpLayer<'_>> {
+    let ihl = usize::from(data.first()? & 0x0f) * 4;
+    let total = usize::from(be16(data, 2)?);
+    if ihl < 20 || total < ihl {
+        return None;
+    }
+    let frag = be16(data, 6)?;
+    let src: [u8; 4] = data.get(12..16)?.try_into().ok()?;
+    let dst: [u8; 4] = data.get(16..20)?.try_into().ok()?;
+    Some(IpLayer {
+        src: IpAddr::V4(Ipv4Addr::from(src)),
+        dst: IpAddr::V4(Ipv4Addr::from(dst)),
+        protocol: *data.get(9)?,
+        ttl: *data.get(8)?,
+        fragmented: frag & 0x3fff != 0,
+        truncated: data.len() < total,
+        header: data.get(..ihl)?,
+        payload: data.get(ihl..total.min(data.len()))?,
+    })
+}
+
+/// Parses an IPv6 header, walking up to [`MAX_EXTENSION_HEADERS`]
+/// extension headers to find the transport protocol.
+fn ipv6(data: &[u8]) -> Option<IpLayer<'_>> {
+    let payload_len = usize::from(be16(data, 4)?);
+    let src: [u8; 16] = data.get(8..24)?.try_into().ok()?;
+    let dst: [u8; 16] = data.get(24..40)?.try_into().ok()?;
+    let total = 40 + payload_len;
+    let end = total.min(data.len());
+    let mut next = *data.get(6)?;
+    let mut offset = 40;
+    let mut fragmented = false;
+    for _ in 0..MAX_EXTENSION_HEADERS {
+        match next {
+            // Hop-by-hop, routing, destination options, mobility, HIP and
+            // shim6, all of which give their length in 8-byte units
+            0 | 43 | 60 | 135 | 139 | 140 => {
+                next = *data.get(offset)?;
+                offset += (usize::from(*data.get(offset + 1)?) + 1) * 8;
+            }
+            // Fragment header
+            44 => {
+                let frag = be16(data, offset + 2)?;
+                fragmented |= frag & 0xfff9 != 0;
+                next = *data.get(offset)?;
+                offset += 8;
+            }
+            // Authentication header, whose length is in 4-byte units
+            51 => {
+                next = *data.get(offset)?;
+                offset += (usize::from(*data.get(offset + 1)?) + 2) * 4;
+            }
+            // Anything else, ESP included, ends the chain
+            _ => break,
+        }
+    }
+    if is_extension_header(next) {
+        // The chain is longer than we are willing to walk
+        return None;
+    }
+    Some(IpLayer {
+        src: IpAddr::V6(Ipv6Addr::from(src)),
+        dst: IpAddr::V6(Ipv6Addr::from(dst)),
+        protocol: next,
+        ttl: *data.get(7)?,
+        fragmented,
+        truncated: data.len() < total,
+        header: data.get(..offset)?,
+        payload: data.get(offset..end)?,
+    })
+}
+
+/// Whether `next` is an IPv6 extension header walked by [`ipv6`].
+fn is_extension_header(next: u8) -> bool {
+    matches!(next, 0 | 43 | 44 | 51 | 60 | 135 | 139 | 140)
+}
+
+/// Length of the link, network and transport headers at the start of a
+/// packet, i.e. the offset of its transport payload.
+///
+/// Fragments end at the IP header, as do protocols whose header is not
+/// known. Returns `None` if the packet is not IP.
+pub(crate) fn headers_len(datalink: Linktype, data: &[u8]) -> Option<usize> {
+    let network = network_payload(datalink, data)?;
+    let ip = ip_layer(network)?;
+    let transport = if ip.fragmented {
+        0
+    } else {
+        match ip.protocol {
+            IPPROTO_TCP => {
+                tcp(ip.payload).map_or(0, |segment| ip.payload.len() - segment.payload.len())
+            }
+            // Type, code, checksum and the rest of the header
+            IPPROTO_UDP | IPPROTO_ICMP | IPPROTO_ICMPV6 => 8,
+            _ => 0,
+        }
+    };
+    let len = data.len() - network.len() + ip.header.len() + transport;
+    Some(len.min(data.len()))
+}
+
+/// Parses a TCP header.
+pub(crate) fn tcp(data: &[u8]) -> Option<TcpSegment<'_>> {
+    let data_offset = usize::from(data.get(12)? >> 4) * 4;
+    if data_offset < 20 {
+        return None;
+    }
+    Some(TcpSegment {
+        src_port: be16(data, 0)?,
+        dst_port: be16(data, 2)?,
+        seq: be32(data, 4)?,
+        flags: *data.get(13)?,
+        payload: data.get(data_offset..)?,
+    })
+}
+
+/// Parses a packet down to its TCP segment and flow key.
+pub(crate) fn tcp_packet(datalink: Linktype, data: &[u8]) -> Option<(FlowKey, TcpSegment<'_>)> {
+    let ip = ip_layer(network_payload(datalink, data)?)?;
+    if ip.protocol != IPPROTO_TCP || ip.fragmented {
+        return None;
+    }
+    let segment = tcp(ip.payload)?;
+    let key = FlowKey {
+        src_ip: ip.src,
+        dst_ip: ip.dst,
+        src_port: segment.src_port,
+        dst_port: segment.dst_port,
+        protocol: ip.protocol,
+    };
+    Some((key, segment))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOP_BY_HOP: u8 = 0;
+    const ROUTING: u8 = 43;
+    const FRAGMENT: u8 = 44;
+    const ESP: u8 = 50;
+    const AH: u8 = 51;
+    const DEST_OPTS: u8 = 60;
+
+    /// An IPv6 header from ::1 to ::2 followed by `rest`, which holds the
+    /// extension headers and the transport layer.
+    fn ipv6_packet(next: u8, rest: &[u8]) -> Vec<u8> {
+        let mut data = vec![0x60, 0, 0, 0];
+        data.extend_from_slice(&(rest.len() as u16).to_be_bytes());
+        data.extend_from_slice(&[next, 64]);
+        data.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        data.extend_from_slice(&Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 2).octets());
+        data.extend_from_slice(rest);
+        data
+    }
+
+    /// An extension header in 8-byte units: next header, length, padding.
+    fn extension(next: u8, units: u8) -> Vec<u8> {
+        let mut header = vec![0; (usize::from(units) + 1) * 8];
+        header[0] = next;
+        header[1] = units;
+        header
+    }
+
+    /// A fragment header at `offset` bytes, a multiple of 8, with the
+    /// more-fragments flag.
+    fn fragment_header(next: u8, offset: u16, more: bool) -> Vec<u8> {
+        let field = offset | u16::from(more);
+        let mut header = vec![next, 0];
+        header.extend_from_slice(&field.to_be_bytes());
+        header.extend_from_slice(&0x1234_5678u32.to_be_bytes());
+        header
+    }
+
+    /// A UDP header from port 53 to port 4000 and a 4-byte payload.
+    fn udp() -> Vec<u8> {
+        vec![0, 53, 0x0f, 0xa0, 0, 12, 0, 0, 1, 2, 3, 4]
+    }
+
+    /// A TCP header without options from port 80 to port 81 and "hi".
+    fn tcp_segment() -> Vec<u8> {
+        let mut segment = vec![0, 80, 0, 81, 0, 0, 0, 1, 0, 0, 0, 0, 0x50, 0x18, 0, 0];
+        segment.extend_from_slice(&[0, 0, 0, 0]);
+        segment.extend_from_slice(b"hi");
+        segment
+    }
+
+    fn chain(headers: &[Vec<u8>]) -> Vec<u8> {
+        headers.concat()
+    }
+
+    #[test]
+    fn ipv6_without_extension_headers() {
+        let data = ipv6_packet(IPPROTO_UDP, &udp());
+        let ip = ip_layer(&data).unwrap();
+        assert_eq!(ip.protocol, IPPROTO_UDP);
+        assert_eq!(ip.ttl, 64);
+        assert_eq!(ip.src, IpAddr::V6(Ipv6Addr::LOCALHOST));
+        assert_eq!(ip.header.len(), 40);
+        assert_eq!(ip.payload, udp());
+        assert!(!ip.fragmented && !ip.truncated);
+    }
+
+    #[test]
+    fn hop_by_hop_header_is_skipped() {
+        let rest = chain(&[extension(IPPROTO_TCP, 0), tcp_segment()]);
+        let data = ipv6_packet(HOP_BY_HOP, &rest);
+        let ip = ip_layer(&data).unwrap();
+        assert_eq!(ip.protocol, IPPROTO_TCP);
+        assert_eq!(ip.header.len(), 48);
+        assert_eq!(tcp(ip.payload).unwrap().payload, b"hi");
+
+        let (key, segment) = tcp_packet(Linktype::RAW, &data).unwrap();
+        assert_eq!((key.src_port, key.dst_port), (80, 81));
+        assert_eq!(segment.payload, b"hi");
+    }
+
+    #[test]
+    fn chain_of_hop_by_hop_routing_and_destination_options() {
+        let rest = chain(&[
+            extension(ROUTING, 0),
+            extension(DEST_OPTS, 1),
+            extension(IPPROTO_UDP, 0),
+            udp(),
+        ]);
+        let data = ipv6_packet(HOP_BY_HOP, &rest);
+        let ip = ip_layer(&data).unwrap();
+        assert_eq!(ip.protocol, IPPROTO_UDP);
+        assert_eq!(ip.header.len(), 40 + 8 + 16 + 8);
+        assert_eq!(ip.payload, udp());
+        assert_eq!(headers_len(Linktype::RAW, &data), Some(40 + 32 + 8));
+    }
+
+    #[test]
+    fn authentication_header_length_is_in_4_byte_units() {
+        // Next header, length 1 i.e. (1 + 2) * 4 = 12 bytes, reserved,
+        // SPI and sequence number
+        let mut ah = vec![IPPROTO_TCP, 1, 0, 0];
+        ah.extend_from_slice(&[0; 8]);
+        let rest = chain(&[extension(AH, 0), ah, tcp_segment()]);
+        let data = ipv6_packet(HOP_BY_HOP, &rest);
+        let ip = ip_layer(&data).unwrap();
+        assert_eq!(ip.protocol, IPPROTO_TCP);
+        assert_eq!(ip.header.len(), 40 + 8 + 12);
+        assert_eq!(tcp(ip.payload).unwrap().dst_port, 81);
+    }
+
+    #[test]
+    fn esp_ends_the_chain() {
+        let rest = chain(&[extension(ESP, 0), vec![0xaa; 16]]);
+        let data = ipv6_packet(DEST_OPTS, &rest);
+        let ip = ip_layer(&data).unwrap();
+        assert_eq!(ip.protocol, ESP);
+        assert_eq!(ip.header.len(), 48);
+    }
+
+    #[test]
+    fn first_fragment_keeps_its_transport_header() {
+        let rest = chain(&[fragment_header(IPPROTO_UDP, 0, true), udp()]);
+        let data = ipv6_packet(FRAGMENT, &rest);
+        let ip = ip_layer(&data).unwrap();
+        assert!(ip.fragmented);
+        assert_eq!(ip.protocol, IPPROTO_UDP);
+        assert_eq!(ip.payload, udp());
+    }
+
+    #[test]
+    fn non_first_fragment_has_no_transport_header() {
+        let rest = chain(&[
+            extension(FRAGMENT, 0),
+            fragment_header(IPPROTO_TCP, 1448, false),
+            b"rest of the segment".to_vec(),
+        ]);
+        let data = ipv6_packet(HOP_BY_HOP, &rest);
+        let ip = ip_layer(&data).unwrap();
+        assert!(ip.fragmented);
+        assert_eq!(ip.protocol, IPPROTO_TCP);
+        assert_eq!(ip.payload, b"rest of the segment");
+        // No ports are read from the payload, and the headers end at IP
+        assert!(tcp_packet(Linktype::RAW, &data).is_none());
+        assert_eq!(headers_len(Linktype::RAW, &data), Some(40 + 16));
+    }
+
+    #[test]
+    fn atomic_fragment_is_not_fragmented() {
+        let rest = chain(&[fragment_header(IPPROTO_UDP, 0, false), udp()]);
+        let data = ipv6_packet(FRAGMENT, &rest);
+        assert!(!ip_layer(&data).unwrap().fragmented);
+    }
+
+    #[test]
+    fn truncated_extension_header_is_rejected() {
+        // The hop-by-hop header claims 24 bytes, 8 are captured
+        let mut rest = extension(IPPROTO_UDP, 2);
+        rest.truncate(8);
+        let mut data = ipv6_packet(HOP_BY_HOP, &rest);
+        assert!(ip_layer(&data).is_none());
+
+        // The routing header announced by the hop-by-hop header is missing
+        data = ipv6_packet(HOP_BY_HOP, &extension(ROUTING, 0));
+        assert!(ip_layer(&data).is_none());
+
+        // Cut inside the fragment header
+        data = ipv6_packet(FRAGMENT, &fragment_header(IPPROTO_UDP, 8, false)[..3]);
+        assert!(ip_layer(&data).is_none());
+    }
+
+    #[test]
+    fn truncated_fixed_header_is_rejected() {
+        let data = ipv6_packet(IPPROTO_UDP, &udp());
+        assert!(ip_layer(&data[..39]).is_none());
+        assert!(ip_layer(&data[..7]).is_none());
+    }
+
+    #[test]
+    fn snaplen_cut_payload_is_reported_as_truncated() {
+        let data = ipv6_packet(IPPROTO_UDP, &udp());
+        let ip = ip_layer(&data[..44]).unwrap();
+        assert!(ip.truncated);
+        assert_eq!(ip.payload.len(), 4);
+    }
+
+    #[test]
+    fn overlong_chain_is_rejected() {
+        let mut headers: Vec<_> = (0..MAX_EXTENSION_HEADERS)
+            .map(|_| extension(DEST_OPTS, 0))
+            .collect();
+        headers.push(extension(IPPROTO_UDP, 0));
+        headers.push(udp());
+        let data = ipv6_packet(DEST_OPTS, &chain(&headers));
+        assert!(ip_layer(&data).is_none());
+
+        let mut headers: Vec<_> = (1..MAX_EXTENSION_HEADERS)
+            .map(|_| extension(DEST_OPTS, 0))
+            .collect();
+        headers.push(extension(IPPROTO_UDP, 0));
+        headers.push(udp());
+        let data = ipv6_packet(DEST_OPTS, &chain(&headers));
+        assert_eq!(ip_layer(&data).unwrap().protocol, IPPROTO_UDP);
+    }
+
+    /// An IPv4 header from 10.0.0.1 to 10.0.0.2 carrying `protocol`,
+    /// followed by `payload`.
+    fn ipv4_packet(protocol: u8, payload: &[u8]) -> Vec<u8> {
+        let total = (20 + payload.len()) as u16;
+        let mut data = vec![0x45, 0];
+        data.extend_from_slice(&total.to_be_bytes());
+        data.extend_from_slice(&[0, 0, 0, 0, 64, protocol, 0, 0]);
+        data.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    /// Prepends `link` to `ip`.
+    fn framed(link: &[u8], ip: &[u8]) -> Vec<u8> {
+        [link, ip].concat()
+    }
+
+    /// Checks that `data` of `datalink` carries exactly `ip`.
+    fn assert_carries(datalink: Linktype, data: &[u8], ip: &[u8]) {
+        assert_eq!(network_payload(datalink, data), Some(ip), "{datalink:?}");
+    }
+
+    #[test]
+    fn ethernet_with_and_without_vlan_tags() {
+        let ip = ipv4_packet(IPPROTO_UDP, &udp());
+        let macs = [0u8; 12];
+        let plain = framed(&[&macs[..], &[0x08, 0x00]].concat(), &ip);
+        assert_carries(Linktype::ETHERNET, &plain, &ip);
+
+        let tagged = framed(&[&macs[..], &[0x81, 0x00, 0, 42, 0x08, 0x00]].concat(), &ip);
+        assert_carries(Linktype::ETHERNET, &tagged, &ip);
+
+        let qinq = [
+            &macs[..],
+            &[0x88, 0xa8, 0, 1, 0x81, 0x00, 0, 42, 0x86, 0xdd],
+        ]
+        .concat();
+        let ip6 = ipv6_packet(IPPROTO_UDP, &udp());
+        assert_carries(Linktype::ETHERNET, &framed(&qinq, &ip6), &ip6);
+
+        // ARP is not IP
+        let arp = framed(&[&macs[..], &[0x08, 0x06]].concat(), &[0; 28]);
+        assert_eq!(network_payload(Linktype::ETHERNET, &arp), None);
+    }
+
+    #[test]
+    fn raw_ip_link_types() {
+        let ip = ipv4_packet(IPPROTO_TCP, &tcp_segment());
+        let ip6 = ipv6_packet(IPPROTO_TCP, &tcp_segment());
+        for datalink in [
+            Linktype::RAW,
+            Linktype(DLT_RAW),
+            Linktype(DLT_RAW_OPENBSD),
+            Linktype::IPV4,
+        ] {
+            assert_carries(datalink, &ip, &ip);
+        }
+        assert_carries(Linktype::IPV6, &ip6, &ip6);
+        assert_carries(Linktype::RAW, &ip6, &ip6);
+
+        let (key, _) = tcp_packet(Linktype::RAW, &ip).unwrap();
+        assert_eq!(key.src_ip, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(key.dst_port, 81);
+    }
+
+    #[test]
+    fn null_loopback_in_either_byte_order() {
+        let ip = ipv4_packet(IPPROTO_UDP, &udp());
+        let ip6 = ipv6_packet(IPPROTO_UDP, &udp());
+        assert_carries(Linktype::NULL, &framed(&2u32.to_ne_bytes(), &ip), &ip);
+        assert_carries(Linktype::NULL, &framed(&2u32.to_be_bytes(), &ip), &ip);
+        assert_carries(Linktype::NULL, &framed(&2u32.to_le_bytes(), &ip), &ip);
+        // AF_INET6 differs between the BSDs
+        for family in BSD_AF_INET6 {
+            assert_carries(Linktype::NULL, &framed(&family.to_ne_bytes(), &ip6), &ip6);
+            assert_carries(
+                Linktype::NULL,
+                &framed(&family.swap_bytes().to_ne_bytes(), &ip6),
+                &ip6,
+            );
+        }
+        let unknown = framed(&7u32.to_ne_bytes(), &ip);
+        assert_eq!(network_payload(Linktype::NULL, &unknown), None);
+        assert_eq!(network_payload(Linktype::NULL, &[2, 0]), None);
+    }
+
+    #[test]
+    fn openbsd_loop_is_in_network_byte_order() {
+        let ip = ipv4_packet(IPPROTO_UDP, &udp());
+        assert_carries(Linktype::LOOP, &framed(&2u32.to_be_bytes(), &ip), &ip);
+        let ip6 = ipv6_packet(IPPROTO_UDP, &udp());
+        assert_carries(Linktype::LOOP, &framed(&24u32.to_be_bytes(), &ip6), &ip6);
+    }
+
+    #[test]
+    fn linux_cooked_captures() {
+        let ip = ipv4_packet(IPPROTO_UDP, &udp());
+        // Packet type, ARPHRD type, address length, address, protocol
+        let mut sll = vec![0, 0, 0, 1, 0, 6];
+        sll.extend_from_slice(&[0; 8]);
+        sll.extend_from_slice(&[0x08, 0x00]);
+        assert_carries(Linktype::LINUX_SLL, &framed(&sll, &ip), &ip);
+
+        // Protocol, reserved, interface, ARPHRD type, packet type,
+        // address length, address
+        let mut sll2 = vec![0x86, 0xdd, 0, 0, 0, 0, 0, 2, 0, 1, 0, 6];
+        sll2.extend_from_slice(&[0; 8]);
+        let ip6 = ipv6_packet(IPPROTO_UDP, &udp());
+        assert_carries(Linktype::LINUX_SLL2, &framed(&sll2, &ip6), &ip6);
+
+        sll2[..2].copy_from_slice(&[0x08, 0x06]);
+        assert_eq!(
+            network_payload(Linktype::LINUX_SLL2, &framed(&sll2, &ip6)),
+            None
+        );
+    }
+
+    #[test]
+    fn ppp_framings() {
+        let ip = ipv4_packet(IPPROTO_UDP, &udp());
+        let ip6 = ipv6_packet(IPPROTO_UDP, &udp());
+        assert_carries(Linktype::PPP, &framed(&[0x00, 0x21], &ip), &ip);
+        assert_carries(
+            Linktype::PPP,
+            &framed(&[0xff, 0x03, 0x00, 0x57], &ip6),
+            &ip6,
+        );
+        // Protocol field compression
+        assert_carries(Linktype::PPP, &framed(&[0x21], &ip), &ip);
+        assert_carries(
+            Linktype::PPP_HDLC,
+            &framed(&[0xff, 0x03, 0x00, 0x21], &ip),
+            &ip,
+        );
+        // Direction byte first
+        assert_carries(Linktype::PPP_WITH_DIR, &framed(&[1, 0x00, 0x21], &ip), &ip);
+        // LCP is not IP
+        assert_eq!(
+            network_payload(Linktype::PPP, &framed(&[0xc0, 0x21], &ip)),
+            None
+        );
+    }
+
+    #[test]
+    fn cisco_hdlc() {
+        let ip = ipv4_packet(IPPROTO_UDP, &udp());
+        assert_carries(
+            Linktype::C_HDLC,
+            &framed(&[0x0f, 0x00, 0x08, 0x00], &ip),
+            &ip,
+        );
+    }
+
+    #[test]
+    fn gre_tunnel_ends_at_the_outer_ip_header() {
+        // GRE has no link type of its own: it is IP protocol 47, whose
+        // payload is not decoded
+        const IPPROTO_GRE: u8 = 47;
+        let inner = ipv4_packet(IPPROTO_UDP, &udp());
+        let gre = framed(&[0, 0, 0x08, 0x00], &inner);
+        let outer = ipv4_packet(IPPROTO_GRE, &gre);
+        let ip = ip_layer(network_payload(Linktype::RAW, &outer).unwrap()).unwrap();
+        assert_eq!(ip.protocol, IPPROTO_GRE);
+        assert_eq!(ip.payload, gre);
+        assert_eq!(headers_len(Linktype::RAW, &outer), Some(20));
+    }
+
+    #[test]
+    fn unsupported_link_type_yields_nothing() {
+        let ip = ipv4_packet(IPPROTO_UDP, &udp());
+        assert_eq!(network_payload(Linktype::IEEE802_11, &ip), None);
+        assert_eq!(headers_len(Linktype::IEEE802_11, &ip), None);
+    }
+}
@@ -0,0 +1,61 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Pause requested through
+/// [`AsyncCaptureHandle::pause()`](crate::AsyncCaptureHandle::pause).
+#[derive(Debug, Default)]
+pub(crate) struct Pause {
+    /// Mirrors `state` so that the capture thread can check it without
+    /// taking the lock
+    paused: AtomicBool,
+    state: Mutex<bool>,
+    resumed: Condvar,
+    /// Packets dropped because they arrived during a pause
+    pub(crate) dropped: AtomicU64,
+}
+
+impl Pause {
+    pub(crate) fn set(&self, paused: bool) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        *state = paused;
+        self.paused.store(paused, Ordering::Release);
+        if !paused {
+            self.resumed.notify_all();
+        }
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+
+    /// Waits up to `timeout` for the capture to be resumed.
+    pub(crate) fn wait(&self, timeout: Duration) {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = self
+            .resumed
+            .wait_timeout_while(state, timeout, |paused| *paused);
+    }
+}
+
+/// Period during which the capture thread did not read, whose packets are
+/// dropped once it reads again.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PausedWindow {
+    pub(crate) from: Duration,
+    pub(crate) until: Duration,
+}
+
+impl PausedWindow {
+    /// Covers the time between `from` and `until`, as wall clock times.
+    pub(crate) fn new(from: SystemTime, until: SystemTime) -> Self {
+        let since_epoch = |time: SystemTime| {
+            time.duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+        };
+        Self {
+            from: since_epoch(from),
+            until: since_epoch(until),
+        }
+    }
+}
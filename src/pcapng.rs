@@ -0,0 +1,495 @@
+use std::io::{self, Write};
+use std::net::IpAddr;
+
+use pcap::{Linktype, Precision};
+
+use crate::async_pcap::Packet;
+use crate::info::CaptureInfo;
+#[cfg(feature = "rt-tokio")]
+use crate::merge::MergedCapture;
+
+const BLOCK_SECTION_HEADER: u32 = 0x0a0d_0d0a;
+const BLOCK_INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+const BLOCK_NAME_RESOLUTION: u32 = 0x0000_0004;
+const BLOCK_ENHANCED_PACKET: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+
+const OPT_END: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const OPT_SHB_USERAPPL: u16 = 4;
+const OPT_IF_NAME: u16 = 2;
+const OPT_IF_DESCRIPTION: u16 = 3;
+const OPT_IF_TSRESOL: u16 = 9;
+
+const NRB_RECORD_END: u16 = 0;
+const NRB_RECORD_IPV4: u16 = 1;
+const NRB_RECORD_IPV6: u16 = 2;
+
+/// Describes one capture interface recorded in a pcapng file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceDescription {
+    /// Link-layer header type of packets captured on the interface
+    pub linktype: Linktype,
+    /// Snapshot length the interface was captured with
+    pub snaplen: u32,
+    /// Interface name, e.g. `eth0`
+    pub name: Option<String>,
+    /// Human readable interface description
+    pub description: Option<String>,
+}
+
+impl InterfaceDescription {
+    /// Creates a description with no name or description.
+    pub fn new(linktype: Linktype, snaplen: u32) -> Self {
+        Self {
+            linktype,
+            snaplen,
+            name: None,
+            description: None,
+        }
+    }
+
+    /// Describes the device `name`, captured with the parameters in
+    /// `info`, e.g. from [`AsyncCapture::info()`](crate::AsyncCapture::info).
+    pub fn from_info(name: &str, info: &CaptureInfo) -> Self {
+        Self {
+            name: Some(name.to_string()),
+            ..Self::new(
+                info.datalink,
+                u32::try_from(info.snaplen).unwrap_or(u32::MAX),
+            )
+        }
+    }
+}
+
+/// Writes packets in the pcapng format.
+///
+/// Unlike the classic pcap format, pcapng records which interface each
+/// packet was captured on, carries interface names and descriptions, and
+/// stores timestamps at the precision the packets were captured with.
+/// Reading pcapng files needs no extra support: libpcap opens them like
+/// classic pcap files.
+///
+/// A section header is written on creation, optionally with a comment on
+/// the capture as a whole. Each interface must be registered with
+/// [`add_interface`](Self::add_interface) before packets referencing it are
+/// written; [`write_names`](Self::write_names) records host names that
+/// Wireshark shows in place of the addresses.
+pub struct PcapNgWriter<W: Write> {
+    out: W,
+    precision: Precision,
+    interfaces: Vec<InterfaceDescription>,
+}
+
+impl<W: Write> PcapNgWriter<W> {
+    /// Starts a pcapng section on `out` for packets with microsecond
+    /// timestamps.
+    pub fn new(out: W) -> io::Result<Self> {
+        Self::with_precision(out, Precision::Micro)
+    }
+
+    /// Starts a pcapng section on `out`.
+    ///
+    /// `precision` must match the capture the packets come from: with
+    /// [`Precision::Nano`] libpcap stores nanoseconds in `ts.tv_usec`.
+    pub fn with_precision(out: W, precision: Precision) -> io::Result<Self> {
+        Self::start(out, precision, None)
+    }
+
+    /// Starts a pcapng section on `out` whose header carries `comment`,
+    /// e.g. what was captured and why.
+    pub fn with_comment(out: W, precision: Precision, comment: &str) -> io::Result<Self> {
+        Self::start(out, precision, Some(comment))
+    }
+
+    fn start(mut out: W, precision: Precision, comment: Option<&str>) -> io::Result<Self> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        // Section length is not known in advance
+        body.extend_from_slice(&(-1i64).to_le_bytes());
+        if let Some(comment) = comment {
+            push_option(&mut body, OPT_COMMENT, comment.as_bytes());
+        }
+        push_option(
+            &mut body,
+            OPT_SHB_USERAPPL,
+            env!("CARGO_PKG_NAME").as_bytes(),
+        );
+        push_option(&mut body, OPT_END, &[]);
+        write_block(&mut out, BLOCK_SECTION_HEADER, &body)?;
+
+        Ok(Self {
+            out,
+            precision,
+            interfaces: Vec::new(),
+        })
+    }
+
+    /// Records an interface and returns the id to pass to
+    /// [`write_packet`](Self::write_packet).
+    pub fn add_interface(&mut self, interface: InterfaceDescription) -> io::Result<u32> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&(interface.linktype.0 as u16).to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&interface.snaplen.to_le_bytes());
+        if let Some(name) = &interface.name {
+            push_option(&mut body, OPT_IF_NAME, name.as_bytes());
+        }
+        if let Some(description) = &interface.description {
+            push_option(&mut body, OPT_IF_DESCRIPTION, description.as_bytes());
+        }
+        let tsresol = match self.precision {
+            Precision::Micro => 6,
+            Precision::Nano => 9,
+        };
+        push_option(&mut body, OPT_IF_TSRESOL, &[tsresol]);
+        push_option(&mut body, OPT_END, &[]);
+        write_block(&mut self.out, BLOCK_INTERFACE_DESCRIPTION, &body)?;
+
+        self.interfaces.push(interface);
+        Ok(self.interfaces.len() as u32 - 1)
+    }
+
+    /// Records an interface for each capture of `merged`, in order, so that
+    /// the index a merged packet is tagged with is also its interface id.
+    ///
+    /// Requires the `rt-tokio` feature.
+    #[cfg(feature = "rt-tokio")]
+    pub fn add_merged(&mut self, merged: &MergedCapture) -> io::Result<()> {
+        for index in 0..merged.len() {
+            if let (Some(device), Some(info)) = (merged.device(index), merged.info(index)) {
+                self.add_interface(InterfaceDescription::from_info(device, info))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the interfaces recorded so far, indexed by id.
+    pub fn interfaces(&self) -> &[InterfaceDescription] {
+        &self.interfaces
+    }
+
+    /// Writes a packet captured on the given interface.
+    pub fn write_packet(&mut self, interface: u32, packet: &Packet) -> io::Result<()> {
+        self.write_packet_with_comment(interface, packet, None)
+    }
+
+    /// Writes a packet with an optional comment attached to it.
+    pub fn write_packet_with_comment(
+        &mut self,
+        interface: u32,
+        packet: &Packet,
+        comment: Option<&str>,
+    ) -> io::Result<()> {
+        if interface as usize >= self.interfaces.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown pcapng interface id {interface}"),
+            ));
+        }
+
+        let units_per_sec: u64 = match self.precision {
+            Precision::Micro => 1_000_000,
+            Precision::Nano => 1_000_000_000,
+        };
+        let ts = (packet.header.ts.tv_sec as u64)
+            .wrapping_mul(units_per_sec)
+            .wrapping_add(packet.header.ts.tv_usec as u64);
+
+        let mut body = Vec::with_capacity(20 + packet.data.len() + 4);
+        body.extend_from_slice(&interface.to_le_bytes());
+        body.extend_from_slice(&((ts >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(ts as u32).to_le_bytes());
+        body.extend_from_slice(&(packet.data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&packet.header.len.to_le_bytes());
+        body.extend_from_slice(&packet.data);
+        pad(&mut body);
+        if let Some(comment) = comment {
+            push_option(&mut body, OPT_COMMENT, comment.as_bytes());
+            push_option(&mut body, OPT_END, &[]);
+        }
+        write_block(&mut self.out, BLOCK_ENHANCED_PACKET, &body)
+    }
+
+    /// Writes a Name Resolution Block mapping each address to a host name.
+    ///
+    /// An address given several times gets each of its names. Nothing is
+    /// written if `names` is empty.
+    pub fn write_names<'a, I>(&mut self, names: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = (IpAddr, &'a str)>,
+    {
+        let mut body = Vec::new();
+        for (addr, name) in names {
+            let (record, mut value) = match addr {
+                IpAddr::V4(v4) => (NRB_RECORD_IPV4, v4.octets().to_vec()),
+                IpAddr::V6(v6) => (NRB_RECORD_IPV6, v6.octets().to_vec()),
+            };
+            value.extend_from_slice(name.as_bytes());
+            value.push(0);
+            // Records are laid out like options
+            push_option(&mut body, record, &value);
+        }
+        if body.is_empty() {
+            return Ok(());
+        }
+        push_option(&mut body, NRB_RECORD_END, &[]);
+        write_block(&mut self.out, BLOCK_NAME_RESOLUTION, &body)
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+fn pad(buf: &mut Vec<u8>) {
+    buf.resize(buf.len().next_multiple_of(4), 0);
+}
+
+fn push_option(buf: &mut Vec<u8>, code: u16, value: &[u8]) {
+    let value = &value[..value.len().min(usize::from(u16::MAX))];
+    buf.extend_from_slice(&code.to_le_bytes());
+    buf.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    buf.extend_from_slice(value);
+    pad(buf);
+}
+
+fn write_block<W: Write>(out: &mut W, block_type: u32, body: &[u8]) -> io::Result<()> {
+    let total = (body.len() + 12) as u32;
+    out.write_all(&block_type.to_le_bytes())?;
+    out.write_all(&total.to_le_bytes())?;
+    out.write_all(body)?;
+    out.write_all(&total.to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+
+    fn le16(buf: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes(buf[at..at + 2].try_into().unwrap())
+    }
+
+    fn le32(buf: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(buf[at..at + 4].try_into().unwrap())
+    }
+
+    /// Splits a file into its blocks' types and bodies, checking that the
+    /// leading and trailing total lengths agree and are 32-bit aligned.
+    fn blocks(file: &[u8]) -> Vec<(u32, &[u8])> {
+        let mut blocks = Vec::new();
+        let mut at = 0;
+        while at < file.len() {
+            let total = le32(file, at + 4) as usize;
+            assert_eq!(total % 4, 0, "block at {at} is not padded");
+            assert_eq!(le32(file, at + total - 4) as usize, total);
+            blocks.push((le32(file, at), &file[at + 8..at + total - 4]));
+            at += total;
+        }
+        assert_eq!(at, file.len());
+        blocks
+    }
+
+    /// Options of a block, from `body[at..]` up to the end-of-options.
+    fn options(body: &[u8], mut at: usize) -> Vec<(u16, &[u8])> {
+        let mut options = Vec::new();
+        loop {
+            let code = le16(body, at);
+            let len = usize::from(le16(body, at + 2));
+            if code == OPT_END {
+                assert_eq!(len, 0);
+                return options;
+            }
+            options.push((code, &body[at + 4..at + 4 + len]));
+            at += (4 + len).next_multiple_of(4);
+        }
+    }
+
+    fn packet(secs: u64, fraction: u32, data: Vec<u8>) -> Packet {
+        let mut packet = Packet::new(data, UNIX_EPOCH + Duration::from_secs(secs));
+        packet.header.ts.tv_usec = fraction as _;
+        packet
+    }
+
+    fn write(precision: Precision, packet: &Packet) -> Vec<u8> {
+        let mut writer = PcapNgWriter::with_precision(Vec::new(), precision).unwrap();
+        let mut interface = InterfaceDescription::new(Linktype::ETHERNET, 1500);
+        interface.name = Some("eth0".to_string());
+        let id = writer.add_interface(interface).unwrap();
+        writer.write_packet(id, packet).unwrap();
+        writer.into_inner().unwrap()
+    }
+
+    #[test]
+    fn blocks_are_framed_and_padded() {
+        let file = write(Precision::Micro, &packet(1, 0, vec![0xaa; 5]));
+        let blocks = blocks(&file);
+        let types: Vec<_> = blocks.iter().map(|(block_type, _)| *block_type).collect();
+        assert_eq!(
+            types,
+            [
+                BLOCK_SECTION_HEADER,
+                BLOCK_INTERFACE_DESCRIPTION,
+                BLOCK_ENHANCED_PACKET
+            ]
+        );
+
+        let shb = blocks[0].1;
+        assert_eq!(le32(shb, 0), BYTE_ORDER_MAGIC);
+        assert_eq!((le16(shb, 4), le16(shb, 6)), (1, 0));
+        assert_eq!(
+            options(shb, 16),
+            [(OPT_SHB_USERAPPL, env!("CARGO_PKG_NAME").as_bytes())]
+        );
+
+        let idb = blocks[1].1;
+        assert_eq!(le16(idb, 0), 1);
+        assert_eq!(le32(idb, 4), 1500);
+        assert_eq!(
+            options(idb, 8),
+            [(OPT_IF_NAME, &b"eth0"[..]), (OPT_IF_TSRESOL, &[6][..])]
+        );
+
+        // 20 bytes of fields, then the 5 packet bytes padded to 8
+        let epb = blocks[2].1;
+        assert_eq!(epb.len(), 28);
+        assert_eq!((le32(epb, 12), le32(epb, 16)), (5, 5));
+        assert_eq!(&epb[20..], [0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0, 0, 0]);
+    }
+
+    #[test]
+    fn timestamps_are_split_into_high_and_low_words() {
+        for (precision, tsresol, units) in [
+            (Precision::Micro, 6, 1_000_000u64),
+            (Precision::Nano, 9, 1_000_000_000),
+        ] {
+            let file = write(precision, &packet(5_000, 123_456, vec![0; 4]));
+            let blocks = blocks(&file);
+            assert!(options(blocks[1].1, 8).contains(&(OPT_IF_TSRESOL, &[tsresol][..])));
+            let epb = blocks[2].1;
+            let ts = 5_000 * units + 123_456;
+            assert_eq!(le32(epb, 0), 0);
+            assert_eq!(le32(epb, 4), (ts >> 32) as u32);
+            assert_eq!(le32(epb, 8), ts as u32);
+        }
+    }
+
+    #[test]
+    fn packets_of_unknown_interfaces_are_rejected() {
+        let mut writer = PcapNgWriter::new(Vec::new()).unwrap();
+        let error = writer.write_packet(0, &packet(0, 0, vec![0])).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn section_comments_come_first_and_are_padded() {
+        let writer = PcapNgWriter::with_comment(Vec::new(), Precision::Micro, "lab").unwrap();
+        let file = writer.into_inner().unwrap();
+        let blocks = blocks(&file);
+        let shb = blocks[0].1;
+        // Three bytes of comment padded to four
+        assert_eq!(&shb[16..24], [1, 0, 3, 0, b'l', b'a', b'b', 0]);
+        assert_eq!(
+            options(shb, 16),
+            [
+                (OPT_COMMENT, &b"lab"[..]),
+                (OPT_SHB_USERAPPL, env!("CARGO_PKG_NAME").as_bytes())
+            ]
+        );
+    }
+
+    #[test]
+    fn packet_comments_follow_the_padded_data() {
+        let mut writer = PcapNgWriter::new(Vec::new()).unwrap();
+        let id = writer
+            .add_interface(InterfaceDescription::new(Linktype::ETHERNET, 1500))
+            .unwrap();
+        writer
+            .write_packet_with_comment(id, &packet(0, 0, vec![1; 2]), Some("retransmit"))
+            .unwrap();
+        let file = writer.into_inner().unwrap();
+        let epb = blocks(&file)[2].1;
+        assert_eq!(options(epb, 24), [(OPT_COMMENT, &b"retransmit"[..])]);
+    }
+
+    #[test]
+    fn name_records_are_terminated() {
+        let mut writer = PcapNgWriter::new(Vec::new()).unwrap();
+        writer
+            .write_names([
+                (IpAddr::from([192, 0, 2, 1]), "a"),
+                (IpAddr::from([0x2001, 0xdb8, 0, 0, 0, 0, 0, 1]), "bc"),
+            ])
+            .unwrap();
+        let file = writer.into_inner().unwrap();
+        let blocks = blocks(&file);
+        assert_eq!(blocks.len(), 2);
+        let (block_type, nrb) = blocks[1];
+        assert_eq!(block_type, BLOCK_NAME_RESOLUTION);
+
+        // 4 address bytes, "a" and its NUL, padded to 8
+        assert_eq!((le16(nrb, 0), le16(nrb, 2)), (NRB_RECORD_IPV4, 6));
+        assert_eq!(&nrb[4..12], [192, 0, 2, 1, b'a', 0, 0, 0]);
+        // 16 address bytes, "bc" and its NUL, padded to 20
+        assert_eq!((le16(nrb, 12), le16(nrb, 14)), (NRB_RECORD_IPV6, 19));
+        assert_eq!(nrb[16..18], [0x20, 0x01]);
+        assert_eq!(&nrb[32..36], [b'b', b'c', 0, 0]);
+        // nrb_record_end, with no value
+        assert_eq!(&nrb[36..], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn no_name_block_is_written_without_names() {
+        let mut writer = PcapNgWriter::new(Vec::new()).unwrap();
+        writer.write_names([]).unwrap();
+        let file = writer.into_inner().unwrap();
+        assert_eq!(blocks(&file).len(), 1);
+    }
+
+    #[cfg(feature = "rt-tokio")]
+    #[test]
+    fn merged_inputs_get_interface_ids_in_order() {
+        use crate::tstamp::TimestampSource;
+
+        let info = |datalink| CaptureInfo {
+            snaplen: 9000,
+            datalink,
+            timestamp_source: TimestampSource::Host,
+            precision: Precision::Micro,
+        };
+        let merged = MergedCapture::idle(vec![
+            ("eth0".to_string(), info(Linktype::ETHERNET)),
+            ("wlan0".to_string(), info(Linktype::IEEE802_11_RADIOTAP)),
+        ]);
+        let mut writer = PcapNgWriter::new(Vec::new()).unwrap();
+        writer.add_merged(&merged).unwrap();
+        assert_eq!(
+            writer.interfaces(),
+            [
+                InterfaceDescription::from_info("eth0", &info(Linktype::ETHERNET)),
+                InterfaceDescription::from_info("wlan0", &info(Linktype::IEEE802_11_RADIOTAP)),
+            ]
+        );
+        writer.write_packet(1, &packet(0, 0, vec![0])).unwrap();
+        assert!(writer.write_packet(2, &packet(0, 0, vec![0])).is_err());
+
+        let file = writer.into_inner().unwrap();
+        let blocks = blocks(&file);
+        assert_eq!(le16(blocks[1].1, 0), 1);
+        assert_eq!(le16(blocks[2].1, 0), 127);
+        assert_eq!(options(blocks[2].1, 8)[0], (OPT_IF_NAME, &b"wlan0"[..]));
+        // The packet references the second interface
+        assert_eq!(le32(blocks[3].1, 0), 1);
+    }
+}
@@ -0,0 +1,228 @@
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+#[cfg(feature = "bytes")]
+use pcap::PacketHeader;
+
+use crate::async_pcap::{AsyncCapture, AsyncCaptureHandle, Packet};
+use crate::error::Error;
+use crate::source::PacketSource;
+use crate::worker::CaptureOptions;
+
+/// Free packet buffers shared by the capture thread and the consumer.
+pub(crate) struct BufferPool {
+    free: Mutex<Vec<Vec<u8>>>,
+    capacity: usize,
+    /// Size new buffers are allocated with, the effective snaplen
+    buffer_len: usize,
+}
+
+impl BufferPool {
+    pub(crate) fn new(capacity: usize, buffer_len: usize) -> Arc<Self> {
+        Arc::new(Self {
+            free: Mutex::new(Vec::with_capacity(capacity)),
+            capacity,
+            buffer_len,
+        })
+    }
+
+    /// Takes a free buffer, or allocates one that fits any packet of the
+    /// capture if none is left.
+    pub(crate) fn take(&self) -> Vec<u8> {
+        self.lock()
+            .pop()
+            .unwrap_or_else(|| Vec::with_capacity(self.buffer_len))
+    }
+
+    /// Returns a buffer to the pool, freeing it if the pool is full.
+    pub(crate) fn put(&self, mut buf: Vec<u8>) {
+        if buf.capacity() == 0 {
+            return;
+        }
+        let mut free = self.lock();
+        if free.len() < self.capacity {
+            buf.clear();
+            free.push(buf);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Vec<u8>>> {
+        self.free.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for BufferPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferPool")
+            .field("capacity", &self.capacity)
+            .field("buffer_len", &self.buffer_len)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A packet whose buffer goes back to the capture's pool when dropped.
+///
+/// Returned by [`AsyncCapture::next_pooled_packet()`]; dereferences to
+/// the [`Packet`].
+pub struct PooledPacket {
+    packet: Option<Packet>,
+    pool: Option<Arc<BufferPool>>,
+}
+
+impl PooledPacket {
+    /// Detaches the packet from the pool; its buffer is then freed
+    /// normally.
+    pub fn into_packet(mut self) -> Packet {
+        self.packet.take().unwrap()
+    }
+}
+
+impl Deref for PooledPacket {
+    type Target = Packet;
+
+    fn deref(&self) -> &Packet {
+        self.packet.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledPacket {
+    fn drop(&mut self) {
+        if let (Some(packet), Some(pool)) = (self.packet.take(), &self.pool) {
+            pool.put(packet.data);
+        }
+    }
+}
+
+impl fmt::Debug for PooledPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PooledPacket").field(&**self).finish()
+    }
+}
+
+/// A packet whose data is held as [`Bytes`], with its buffer going back to
+/// the capture's pool once every clone of the data is dropped.
+///
+/// Returned by [`AsyncCapture::next_bytes_packet()`] and
+/// [`PooledPacket::into_bytes()`]. Unlike a [`PooledPacket`], the data
+/// can be cloned and sliced cheaply, e.g. to hand the payload of one
+/// packet to several tasks, without copying it out of the pooled buffer.
+/// Requires the `bytes` feature.
+#[cfg(feature = "bytes")]
+#[derive(Debug, Clone)]
+pub struct BytesPacket {
+    /// Packet header information provided by pcap
+    pub header: PacketHeader,
+    /// Raw packet data
+    pub data: Bytes,
+}
+
+/// Owner of a pooled buffer shared by [`Bytes`] handles.
+#[cfg(feature = "bytes")]
+struct SharedBuffer {
+    data: Vec<u8>,
+    pool: Option<Arc<BufferPool>>,
+}
+
+#[cfg(feature = "bytes")]
+impl AsRef<[u8]> for SharedBuffer {
+    fn as_ref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Drop for SharedBuffer {
+    fn drop(&mut self) {
+        if let Some(pool) = &self.pool {
+            pool.put(std::mem::take(&mut self.data));
+        }
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl PooledPacket {
+    /// Turns the packet into a [`BytesPacket`], whose data keeps the
+    /// pooled buffer until its last clone is dropped.
+    pub fn into_bytes(mut self) -> BytesPacket {
+        let packet = self.packet.take().unwrap();
+        let owner = SharedBuffer {
+            data: packet.data,
+            pool: self.pool.take(),
+        };
+        BytesPacket {
+            header: packet.header,
+            data: Bytes::from_owner(owner),
+        }
+    }
+}
+
+impl AsyncCapture {
+    /// Creates a capture that recycles packet buffers through a pool of up
+    /// to `pool_size` free buffers.
+    ///
+    /// The capture thread copies each packet into a buffer taken from the
+    /// pool, and a [`PooledPacket`] returned by
+    /// [`next_pooled_packet()`](Self::next_pooled_packet) puts it back when
+    /// dropped, so at a steady rate packets are captured without
+    /// allocating. Packets dropped on the capture thread, e.g. by a filter
+    /// pipeline, return their buffer immediately.
+    ///
+    /// # Notes
+    ///
+    /// - The pool bounds the buffers kept for reuse, not the packets in
+    ///   flight: when it is empty, a new buffer is allocated rather than
+    ///   stalling the capture, and surplus buffers are freed on return.
+    /// - Buffers are allocated with the effective snaplen of the source, so
+    ///   copying a packet into one never reallocates, and the pool holds up
+    ///   to `pool_size` times the snaplen. A source reporting no snaplen
+    ///   gets buffers that grow to the largest packet copied into them.
+    /// - A packet keeps its full-size buffer until it is dropped, however
+    ///   short it is, so queued packets hold about the snaplen each: with a
+    ///   large snaplen, bound the queue with
+    ///   [`AsyncCaptureBuilder::bounded()`](crate::AsyncCaptureBuilder::bounded)
+    ///   or lower the snaplen to keep memory in check.
+    /// - Only sources that implement
+    ///   [`PacketSource::next_packet_into()`] reuse buffers; live captures
+    ///   and savefiles do.
+    /// - Packets received with [`next_packet()`](Self::next_packet) are
+    ///   not returned to the pool.
+    pub fn with_buffer_pool<S: PacketSource>(
+        source: S,
+        pool_size: usize,
+    ) -> (Self, AsyncCaptureHandle) {
+        let buffer_len = usize::try_from(source.info().snaplen).unwrap_or(0);
+        let options = CaptureOptions {
+            buffer_pool: Some(BufferPool::new(pool_size, buffer_len)),
+            ..CaptureOptions::default()
+        };
+        let (capture, handle, _) = Self::with_options(source, options);
+        (capture, handle)
+    }
+
+    /// Waits for the next packet, wrapped so that its buffer is recycled
+    /// when dropped.
+    ///
+    /// On a capture without a buffer pool the buffer is simply freed.
+    pub async fn next_pooled_packet(&self) -> Option<Result<PooledPacket, Error>> {
+        let item = self.next_packet().await?;
+        Some(item.map(|packet| PooledPacket {
+            packet: Some(packet),
+            pool: self.shared.buffer_pool.clone(),
+        }))
+    }
+
+    /// Waits for the next packet, with its data as [`Bytes`] backed by the
+    /// pooled buffer. Requires the `bytes` feature.
+    ///
+    /// The buffer goes back to the pool once the last clone of the data is
+    /// dropped; on a capture without a buffer pool it is simply freed.
+    /// Converting the packet does not copy its data.
+    #[cfg(feature = "bytes")]
+    pub async fn next_bytes_packet(&self) -> Option<Result<BytesPacket, Error>> {
+        let item = self.next_pooled_packet().await?;
+        Some(item.map(PooledPacket::into_bytes))
+    }
+}
@@ -0,0 +1,112 @@
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::logging::warn;
+
+/// Slack allowed on top of twice the requested timeout before a read is
+/// considered to have ignored it.
+const TIMEOUT_SLACK: Duration = Duration::from_millis(50);
+
+const UNKNOWN: u8 = 0;
+const HONORED: u8 = 1;
+const IGNORED: u8 = 2;
+
+/// Read settings requested when the device was opened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ReadRequest {
+    pub(crate) timeout: Duration,
+    pub(crate) immediate_mode: bool,
+}
+
+/// How the capture's reads behave compared to what was requested.
+///
+/// libpcap has no call to query the read timeout or immediate mode of an
+/// activated handle, and some platforms accept them without effect. The
+/// requested values are therefore reported as-is, and whether the timeout
+/// is honored is inferred by timing the reads of the capture thread.
+/// Whether immediate mode took effect cannot be observed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadBehavior {
+    /// Read timeout requested when opening, `None` if unknown (for
+    /// captures not opened by [`AsyncCaptureBuilder`](crate::AsyncCaptureBuilder))
+    pub requested_timeout: Option<Duration>,
+    /// Whether immediate mode was requested, `None` if unknown
+    pub requested_immediate_mode: Option<bool>,
+    /// Longest time a single read has blocked; reads are only timed when
+    /// the requested settings are known
+    pub longest_read: Duration,
+    /// Best-effort verdict on the read timeout
+    ///
+    /// `Some(true)` once a read timed out close to the requested timeout,
+    /// `Some(false)` once a read blocked for more than twice the requested
+    /// timeout, and `None` until either was observed or if no timeout was
+    /// requested.
+    pub timeout_honored: Option<bool>,
+}
+
+/// Times reads on the capture thread to judge whether the requested
+/// timeout is honored.
+#[derive(Debug, Default)]
+pub(crate) struct ReadProbe {
+    request: Option<ReadRequest>,
+    longest_nanos: AtomicU64,
+    verdict: AtomicU8,
+    warned: AtomicBool,
+}
+
+impl ReadProbe {
+    pub(crate) fn new(request: Option<ReadRequest>) -> Self {
+        Self {
+            request,
+            ..Self::default()
+        }
+    }
+
+    /// Returns `true` if reads should be timed.
+    pub(crate) fn enabled(&self) -> bool {
+        self.request.is_some()
+    }
+
+    /// Records a read that blocked for `elapsed` and whether it returned
+    /// because the timeout expired.
+    pub(crate) fn record(&self, elapsed: Duration, timed_out: bool) {
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.longest_nanos.fetch_max(nanos, Ordering::Relaxed);
+
+        let Some(request) = self.request.filter(|r| !r.timeout.is_zero()) else {
+            return;
+        };
+        if elapsed > request.timeout * 2 + TIMEOUT_SLACK {
+            self.verdict.store(IGNORED, Ordering::Relaxed);
+            if !self.warned.swap(true, Ordering::Relaxed) {
+                warn!(
+                    "AsyncCapture read blocked for {elapsed:?} although a {:?} timeout was requested; \
+                     the platform may not honor read timeouts.",
+                    request.timeout
+                );
+            }
+        } else if timed_out {
+            let _ = self.verdict.compare_exchange(
+                UNKNOWN,
+                HONORED,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            );
+        }
+    }
+
+    pub(crate) fn current(&self) -> ReadBehavior {
+        let timeout = self.request.map(|r| r.timeout);
+        let timeout_honored = match self.verdict.load(Ordering::Relaxed) {
+            HONORED => Some(true),
+            IGNORED => Some(false),
+            _ => None,
+        };
+        ReadBehavior {
+            requested_timeout: timeout,
+            requested_immediate_mode: self.request.map(|r| r.immediate_mode),
+            longest_read: Duration::from_nanos(self.longest_nanos.load(Ordering::Relaxed)),
+            timeout_honored,
+        }
+    }
+}
@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use pcap::Linktype;
+
+use crate::async_pcap::Packet;
+use crate::parse::{IPPROTO_TCP, IPPROTO_UDP};
+
+/// Minimum time between two rescans of the socket and process tables.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Local process owning the socket a packet was sent from or received on.
+///
+/// Attached to [`PacketMeta::process`](crate::PacketMeta::process) with
+/// [`AsyncCaptureBuilder::annotate_process()`](crate::AsyncCaptureBuilder::annotate_process).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessInfo {
+    /// Process ID of a process holding the socket
+    pub pid: u32,
+    /// Command name of the process, as in `/proc/<pid>/comm`
+    pub name: String,
+    /// Inode number of the socket
+    pub inode: u64,
+}
+
+/// A TCP or UDP socket of the local host.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+struct Socket {
+    local: IpAddr,
+    /// Peer address and port; unspecified for listening and unconnected
+    /// sockets
+    remote: (IpAddr, u16),
+    inode: u64,
+}
+
+/// Socket tables and the processes holding them, as read from `/proc`.
+#[derive(Debug, Default)]
+struct Tables {
+    /// Sockets by protocol and local port
+    sockets: HashMap<(u8, u16), Vec<Socket>>,
+    /// Owning process of each socket inode
+    owners: HashMap<u64, (u32, String)>,
+}
+
+impl Tables {
+    fn read() -> Self {
+        let mut sockets: HashMap<_, Vec<_>> = HashMap::new();
+        for (protocol, port, socket) in read_sockets() {
+            sockets.entry((protocol, port)).or_default().push(socket);
+        }
+        Self {
+            sockets,
+            owners: read_owners(),
+        }
+    }
+
+    /// Finds the socket of a packet sent from `src` to `dst` or the other
+    /// way round, preferring a connected socket over a listening one.
+    fn find(&self, protocol: u8, src: (IpAddr, u16), dst: (IpAddr, u16)) -> Option<u64> {
+        let mut fallback = None;
+        for (local, remote) in [(src, dst), (dst, src)] {
+            let Some(sockets) = self.sockets.get(&(protocol, local.1)) else {
+                continue;
+            };
+            for socket in sockets {
+                if !socket.local.is_unspecified() && socket.local != local.0 {
+                    continue;
+                }
+                if socket.remote == remote {
+                    return Some(socket.inode);
+                }
+                if socket.remote.0.is_unspecified() {
+                    fallback = fallback.or(Some(socket.inode));
+                }
+            }
+        }
+        fallback
+    }
+}
+
+/// State shared with the thread rescanning the tables.
+#[derive(Debug, Default)]
+struct Scan {
+    /// Set when a lookup missed since the last scan started
+    wanted: bool,
+    /// Set once the table is dropped
+    closed: bool,
+    /// Tables read since the capture thread last took them
+    latest: Option<Tables>,
+}
+
+/// Thread rescanning the tables, woken by the lookups that miss.
+#[derive(Debug, Default)]
+struct Scanner {
+    scan: Mutex<Scan>,
+    wake: Condvar,
+}
+
+impl Scanner {
+    fn lock(&self) -> MutexGuard<'_, Scan> {
+        self.scan.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Rescans the tables whenever asked to, at most once per
+    /// [`REFRESH_INTERVAL`], until the table is dropped.
+    fn run(&self) {
+        let mut last: Option<Instant> = None;
+        loop {
+            let mut scan = self
+                .wake
+                .wait_while(self.lock(), |scan| !scan.wanted && !scan.closed)
+                .unwrap_or_else(|e| e.into_inner());
+            if let Some(last) = last {
+                let pause = REFRESH_INTERVAL.saturating_sub(last.elapsed());
+                scan = self
+                    .wake
+                    .wait_timeout_while(scan, pause, |scan| !scan.closed)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+            }
+            if scan.closed {
+                return;
+            }
+            // Misses during the scan ask for the next one
+            scan.wanted = false;
+            drop(scan);
+            last = Some(Instant::now());
+            let tables = Tables::read();
+            self.lock().latest = Some(tables);
+        }
+    }
+}
+
+/// Cached copy of the socket tables and of the processes holding them.
+///
+/// A lookup that misses asks a thread of its own to rescan `/proc`, at most
+/// once per [`REFRESH_INTERVAL`], so the capture thread never waits for the
+/// scan. Packets of sockets opened since the last scan go unattributed
+/// until the next one completes.
+#[derive(Debug)]
+pub(crate) struct ProcessTable {
+    tables: Tables,
+    scanner: Arc<Scanner>,
+}
+
+impl ProcessTable {
+    /// Starts the scanning thread, which reads the tables right away.
+    pub(crate) fn new() -> Self {
+        let scanner = Arc::new(Scanner::default());
+        scanner.lock().wanted = true;
+        let thread = scanner.clone();
+        std::thread::spawn(move || thread.run());
+        Self {
+            tables: Tables::default(),
+            scanner,
+        }
+    }
+
+    /// Finds the local process a TCP or UDP packet belongs to.
+    pub(crate) fn lookup(&mut self, datalink: Linktype, packet: &Packet) -> Option<ProcessInfo> {
+        let headers = packet.headers(datalink);
+        let protocol = headers.protocol()?;
+        if protocol != IPPROTO_TCP && protocol != IPPROTO_UDP {
+            return None;
+        }
+        let src = (headers.src_ip()?, headers.src_port()?);
+        let dst = (headers.dst_ip()?, headers.dst_port()?);
+        if let Ok(mut scan) = self.scanner.scan.try_lock()
+            && let Some(tables) = scan.latest.take()
+        {
+            self.tables = tables;
+        }
+        let found = self.tables.find(protocol, src, dst).and_then(|inode| {
+            let (pid, name) = self.tables.owners.get(&inode)?;
+            Some(ProcessInfo {
+                pid: *pid,
+                name: name.clone(),
+                inode,
+            })
+        });
+        if found.is_none() {
+            self.scanner.lock().wanted = true;
+            self.scanner.wake.notify_one();
+        }
+        found
+    }
+}
+
+impl Drop for ProcessTable {
+    fn drop(&mut self) {
+        self.scanner.lock().closed = true;
+        self.scanner.wake.notify_one();
+    }
+}
+
+/// Reads the TCP and UDP sockets of the local host from `/proc/net`, as
+/// protocol, local port and socket.
+#[cfg(target_os = "linux")]
+fn read_sockets() -> Vec<(u8, u16, Socket)> {
+    let tables = [
+        ("tcp", IPPROTO_TCP),
+        ("tcp6", IPPROTO_TCP),
+        ("udp", IPPROTO_UDP),
+        ("udp6", IPPROTO_UDP),
+    ];
+    let mut sockets = Vec::new();
+    for (table, protocol) in tables {
+        let Ok(text) = std::fs::read_to_string(format!("/proc/net/{table}")) else {
+            continue;
+        };
+        // The first line holds the column names
+        for line in text.lines().skip(1) {
+            if let Some((port, socket)) = parse_socket_line(line) {
+                sockets.push((protocol, port, socket));
+            }
+        }
+    }
+    sockets
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_sockets() -> Vec<(u8, u16, Socket)> {
+    Vec::new()
+}
+
+/// Parses a line such as
+/// `0: 0100007F:0035 00000000:0000 0A 00000000:00000000 00:00000000 00000000 0 0 12345 ...`.
+#[cfg(target_os = "linux")]
+fn parse_socket_line(line: &str) -> Option<(u16, Socket)> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let (local, port) = parse_endpoint(fields.get(1)?)?;
+    let remote = parse_endpoint(fields.get(2)?)?;
+    let inode = fields.get(9)?.parse().ok()?;
+    // Sockets in TIME_WAIT no longer belong to anyone
+    if inode == 0 {
+        return None;
+    }
+    Some((
+        port,
+        Socket {
+            local,
+            remote,
+            inode,
+        },
+    ))
+}
+
+/// Parses an `ADDRESS:PORT` column, where the address is written as 32-bit
+/// words in host byte order and the port in hex.
+#[cfg(target_os = "linux")]
+fn parse_endpoint(text: &str) -> Option<(IpAddr, u16)> {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    let (addr, port) = text.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    if addr.len() % 8 != 0 {
+        return None;
+    }
+    let words = (0..addr.len() / 8)
+        .map(|i| u32::from_str_radix(addr.get(i * 8..i * 8 + 8)?, 16).ok())
+        .collect::<Option<Vec<u32>>>()?;
+    let addr = match words[..] {
+        [word] => IpAddr::V4(Ipv4Addr::from(word.to_ne_bytes())),
+        [a, b, c, d] => {
+            let mut bytes = [0; 16];
+            for (chunk, word) in bytes.chunks_exact_mut(4).zip([a, b, c, d]) {
+                chunk.copy_from_slice(&word.to_ne_bytes());
+            }
+            // Dual-stack sockets see IPv4 peers as mapped addresses
+            Ipv6Addr::from(bytes).to_canonical()
+        }
+        _ => return None,
+    };
+    Some((addr, port))
+}
+
+/// Maps socket inodes to the first process found holding them, by reading
+/// the descriptors under `/proc/<pid>/fd`.
+///
+/// Only the processes whose descriptors are readable are seen, i.e. the
+/// capturing user's own unless it runs as root.
+#[cfg(target_os = "linux")]
+fn read_owners() -> HashMap<u64, (u32, String)> {
+    let mut owners = HashMap::new();
+    let Ok(procs) = std::fs::read_dir("/proc") else {
+        return owners;
+    };
+    for entry in procs.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        let mut name = None;
+        for fd in fds.flatten() {
+            let Ok(target) = std::fs::read_link(fd.path()) else {
+                continue;
+            };
+            let Some(inode) = target
+                .to_str()
+                .and_then(|t| t.strip_prefix("socket:["))
+                .and_then(|t| t.strip_suffix(']'))
+                .and_then(|t| t.parse::<u64>().ok())
+            else {
+                continue;
+            };
+            let name = name.get_or_insert_with(|| {
+                std::fs::read_to_string(entry.path().join("comm"))
+                    .map(|comm| comm.trim_end().to_owned())
+                    .unwrap_or_default()
+            });
+            owners.entry(inode).or_insert_with(|| (pid, name.clone()));
+        }
+    }
+    owners
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_owners() -> HashMap<u64, (u32, String)> {
+    HashMap::new()
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    /// An address column, as 32-bit words in host byte order.
+    fn words(octets: &[u8]) -> String {
+        octets
+            .chunks_exact(4)
+            .map(|word| format!("{:08X}", u32::from_ne_bytes(word.try_into().unwrap())))
+            .collect()
+    }
+
+    fn line(local: &[u8], local_port: u16, remote: &[u8], remote_port: u16, inode: u64) -> String {
+        format!(
+            "   3: {}:{local_port:04X} {}:{remote_port:04X} 01 00000000:00000000 00:00000000 \
+             00000000  1000        0 {inode} 1 0000000000000000 20 4 30 10 -1",
+            words(local),
+            words(remote),
+        )
+    }
+
+    #[test]
+    fn ipv4_lines_are_parsed() {
+        let text = line(&[127, 0, 0, 1], 8080, &[192, 0, 2, 7], 443, 4242);
+        let (port, socket) = parse_socket_line(&text).unwrap();
+        assert_eq!(port, 8080);
+        assert_eq!(socket.local, Ipv4Addr::new(127, 0, 0, 1));
+        assert_eq!(socket.remote, (Ipv4Addr::new(192, 0, 2, 7).into(), 443));
+        assert_eq!(socket.inode, 4242);
+    }
+
+    #[test]
+    fn ipv6_lines_are_parsed() {
+        let local = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+        let text = line(&local.octets(), 53, &[0; 16], 0, 7);
+        let (port, socket) = parse_socket_line(&text).unwrap();
+        assert_eq!(port, 53);
+        assert_eq!(socket.local, local);
+        assert_eq!(socket.remote, (Ipv6Addr::UNSPECIFIED.into(), 0));
+    }
+
+    #[test]
+    fn mapped_ipv4_addresses_become_ipv4() {
+        let mapped = Ipv4Addr::new(192, 0, 2, 1).to_ipv6_mapped();
+        let (addr, port) = parse_endpoint(&format!("{}:01BB", words(&mapped.octets()))).unwrap();
+        assert_eq!(addr, Ipv4Addr::new(192, 0, 2, 1));
+        assert_eq!(port, 443);
+    }
+
+    #[test]
+    fn time_wait_sockets_are_skipped() {
+        let text = line(&[127, 0, 0, 1], 8080, &[127, 0, 0, 1], 9090, 0);
+        assert!(parse_socket_line(&text).is_none());
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped() {
+        let header = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when \
+                      retrnsmt   uid  timeout inode";
+        assert!(parse_socket_line(header).is_none());
+        assert!(parse_socket_line("   3: 0100007F:1F90 00000000:0000 0A").is_none());
+        assert!(parse_endpoint("0100007F").is_none());
+        assert!(parse_endpoint("0100007F:XYZ").is_none());
+        assert!(parse_endpoint("0100007G:0050").is_none());
+        assert!(parse_endpoint("0100007F0:0050").is_none());
+        // Neither one nor four words
+        assert!(parse_endpoint("0100007F0100007F:0050").is_none());
+    }
+}
@@ -0,0 +1,65 @@
+use crate::builder::AsyncCaptureBuilder;
+
+/// Kernel buffer size used by [`CaptureProfile::Balanced`], in bytes.
+const BALANCED_BUFFER_SIZE: i32 = 8 * 1024 * 1024;
+/// Read timeout used by [`CaptureProfile::Balanced`], in milliseconds.
+const BALANCED_TIMEOUT_MS: i32 = 500;
+/// Kernel buffer size used by [`CaptureProfile::HighThroughput`], in bytes.
+const HIGH_THROUGHPUT_BUFFER_SIZE: i32 = 64 * 1024 * 1024;
+/// Read timeout used by [`CaptureProfile::HighThroughput`], in milliseconds.
+const HIGH_THROUGHPUT_TIMEOUT_MS: i32 = 100;
+/// Packets read between two checks of the stop flag under
+/// [`CaptureProfile::HighThroughput`].
+const HIGH_THROUGHPUT_READ_BATCH: usize = 64;
+
+/// Preset of the kernel buffer and read settings, applied by
+/// [`AsyncCaptureBuilder::profile()`].
+///
+/// Undersized kernel buffers are the most common cause of kernel drops: the
+/// buffer has to hold every packet that arrives while the capture thread is
+/// busy, so its size caps the burst the capture survives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureProfile {
+    /// Immediate mode, a 64 KiB buffer and a 1 ms read timeout, as
+    /// [`AsyncCaptureBuilder::low_latency()`]; for interactive tools and
+    /// request tracing at modest rates
+    LowLatency,
+    /// Immediate mode, an 8 MiB buffer and a 500 ms read timeout; a
+    /// general-purpose setting for busy hosts
+    Balanced,
+    /// Immediate mode off, a 64 MiB buffer, a 100 ms read timeout and reads
+    /// in batches of 64 between stop checks; for sustained high packet
+    /// rates, where packets are delivered in bursts as the kernel fills
+    /// its buffer
+    HighThroughput,
+}
+
+impl AsyncCaptureBuilder {
+    /// Applies the buffer and read settings of `profile`.
+    ///
+    /// # Notes
+    ///
+    /// - Options set after this call override the preset, e.g. a
+    ///   [`buffer_size()`](Self::buffer_size) for a host with less memory.
+    /// - On Linux the buffer is the memory-mapped ring of libpcap, which
+    ///   `net.core.rmem_max` does not cap; when the kernel cannot allocate
+    ///   it, libpcap retries with a smaller ring without reporting it. On
+    ///   the BSDs and macOS the size is capped by the `bpf` buffer limit
+    ///   of the kernel.
+    /// - [`AsyncCaptureHandle::config()`](crate::AsyncCaptureHandle::config)
+    ///   reports the values the capture was opened with.
+    pub fn profile(self, profile: CaptureProfile) -> Self {
+        match profile {
+            CaptureProfile::LowLatency => self.low_latency(),
+            CaptureProfile::Balanced => self
+                .immediate_mode(true)
+                .buffer_size(BALANCED_BUFFER_SIZE)
+                .timeout(BALANCED_TIMEOUT_MS),
+            CaptureProfile::HighThroughput => self
+                .immediate_mode(false)
+                .buffer_size(HIGH_THROUGHPUT_BUFFER_SIZE)
+                .timeout(HIGH_THROUGHPUT_TIMEOUT_MS)
+                .read_batch(HIGH_THROUGHPUT_READ_BATCH),
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock, Weak};
+use std::time::Duration;
+
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::worker::{PacketOrStop, Queued};
+
+/// What the capture thread does with a packet when the queue of a bounded
+/// capture is full.
+///
+/// Set with [`AsyncCaptureBuilder::bounded()`](crate::AsyncCaptureBuilder::bounded).
+/// Packets lost to a full queue are counted by
+/// [`AsyncCaptureHandle::overflow_drops()`](crate::AsyncCaptureHandle::overflow_drops).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// The capture thread waits until the consumer makes room; on a live
+    /// capture the kernel then drops packets once its own buffer fills
+    Block,
+    /// The packet just read is dropped
+    DropNewest,
+    /// The oldest queued packet is dropped to make room, so the consumer
+    /// sees the most recent traffic; statistics, events and errors queued
+    /// are kept
+    DropOldest,
+}
+
+/// Limit on the packets queued between the capture thread and the
+/// consumer.
+#[derive(Debug)]
+pub(crate) struct QueueBound {
+    pub(crate) capacity: u64,
+    pub(crate) policy: OverflowPolicy,
+    /// Packets lost to a full queue
+    pub(crate) dropped: AtomicU64,
+    /// Items taken off the channel to make room, counted as dequeued too
+    pub(crate) evicted: AtomicU64,
+    /// Receiving end of the channel, from which the oldest packets are
+    /// evicted; weak so that dropping the capture still closes the channel
+    receiver: OnceLock<Weak<Mutex<UnboundedReceiver<PacketOrStop>>>>,
+    /// Messages taken off the channel while evicting the packets behind
+    /// them, handed out ahead of the rest of the channel
+    kept: Mutex<VecDeque<PacketOrStop>>,
+    /// Set while the capture thread waits for room
+    waiting: AtomicBool,
+    lock: Mutex<()>,
+    space: Condvar,
+}
+
+impl QueueBound {
+    pub(crate) fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            capacity: capacity.max(1) as u64,
+            policy,
+            dropped: AtomicU64::new(0),
+            evicted: AtomicU64::new(0),
+            receiver: OnceLock::new(),
+            kept: Mutex::new(VecDeque::new()),
+            waiting: AtomicBool::new(false),
+            lock: Mutex::new(()),
+            space: Condvar::new(),
+        }
+    }
+
+    /// Records the receiving end packets are evicted from.
+    pub(crate) fn attach(&self, receiver: Weak<Mutex<UnboundedReceiver<PacketOrStop>>>) {
+        let _ = self.receiver.set(receiver);
+    }
+
+    /// Waits up to `timeout` for the consumer to take a packet.
+    pub(crate) fn wait_for_space(&self, timeout: Duration, full: impl FnOnce() -> bool) {
+        let guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.waiting.store(true, Ordering::SeqCst);
+        // Checked under the lock, so that a release in between is not missed
+        if full() {
+            let _ = self.space.wait_timeout(guard, timeout);
+        }
+        self.waiting.store(false, Ordering::SeqCst);
+    }
+
+    /// Wakes the capture thread once the consumer has taken packets.
+    pub(crate) fn release(&self) {
+        if self.waiting.load(Ordering::SeqCst) {
+            let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+            self.space.notify_one();
+        }
+    }
+
+    /// Removes the oldest queued packets and returns them: a single
+    /// packet, or the packets of the oldest batch.
+    ///
+    /// Statistics, events and errors queued in front of them are kept in
+    /// order, to be handed out by [`take_kept()`](Self::take_kept) ahead of
+    /// the rest of the channel. Returns `None` if the consumer is receiving
+    /// at that moment, or no packet is queued in the channel.
+    pub(crate) fn evict_oldest(&self) -> Option<Vec<Queued>> {
+        let receiver = self.receiver.get()?.upgrade()?;
+        let mut rx = receiver.try_lock().ok()?;
+        let mut kept = lock(&self.kept);
+        while let Ok(message) = rx.try_recv() {
+            match message {
+                PacketOrStop::Packet(Ok(packet), annotations) => {
+                    return Some(vec![(Ok(packet), annotations)]);
+                }
+                PacketOrStop::Batch(items) => {
+                    let (packets, errors): (Vec<_>, Vec<_>) =
+                        items.into_iter().partition(|(item, _)| item.is_ok());
+                    if !errors.is_empty() {
+                        kept.push_back(PacketOrStop::Batch(errors));
+                    }
+                    if !packets.is_empty() {
+                        return Some(packets);
+                    }
+                }
+                other => kept.push_back(other),
+            }
+        }
+        None
+    }
+
+    /// Takes the oldest message kept by [`evict_oldest()`](Self::evict_oldest).
+    ///
+    /// Called with the channel's receiver locked, so that no message
+    /// received from the channel overtakes it.
+    pub(crate) fn take_kept(&self) -> Option<PacketOrStop> {
+        lock(&self.kept).pop_front()
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::UNIX_EPOCH;
+
+    use pcap::Stat;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use super::*;
+    use crate::async_pcap::Packet;
+    use crate::error::Error;
+    use crate::meta::Annotations;
+    use crate::reconnect::LinkEvent;
+
+    fn packet(byte: u8) -> PacketOrStop {
+        let packet = Packet::new(vec![byte], UNIX_EPOCH);
+        PacketOrStop::Packet(Ok(packet), Annotations::default())
+    }
+
+    #[test]
+    fn drop_oldest_keeps_what_is_not_a_packet() {
+        let bound = QueueBound::new(2, OverflowPolicy::DropOldest);
+        let (tx, rx) = unbounded_channel();
+        let rx = Arc::new(Mutex::new(rx));
+        bound.attach(Arc::downgrade(&rx));
+
+        let stat = Stat {
+            received: 1,
+            dropped: 0,
+            if_dropped: 0,
+        };
+        tx.send(PacketOrStop::Stats(stat)).unwrap();
+        tx.send(PacketOrStop::Event(LinkEvent::Paused)).unwrap();
+        tx.send(PacketOrStop::Batch(vec![
+            (
+                Err(Error::CapturePanicked("boom".into())),
+                Annotations::default(),
+            ),
+            (Ok(Packet::new(vec![1], UNIX_EPOCH)), Annotations::default()),
+        ]))
+        .unwrap();
+        tx.send(packet(2)).unwrap();
+
+        let evicted = bound.evict_oldest().expect("a packet is queued");
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].0.as_ref().unwrap().data, [1]);
+
+        assert!(matches!(bound.take_kept(), Some(PacketOrStop::Stats(s)) if s == stat));
+        assert!(matches!(
+            bound.take_kept(),
+            Some(PacketOrStop::Event(LinkEvent::Paused))
+        ));
+        match bound.take_kept() {
+            Some(PacketOrStop::Batch(items)) => {
+                assert!(matches!(items[..], [(Err(Error::CapturePanicked(_)), _)]));
+            }
+            other => panic!("expected the error batch, got {other:?}"),
+        }
+        assert!(bound.take_kept().is_none());
+
+        let evicted = bound.evict_oldest().expect("a packet is queued");
+        assert_eq!(evicted[0].0.as_ref().unwrap().data, [2]);
+        assert!(bound.evict_oldest().is_none());
+    }
+
+    #[test]
+    fn nothing_is_evicted_while_the_consumer_receives() {
+        let bound = QueueBound::new(1, OverflowPolicy::DropOldest);
+        let (tx, rx) = unbounded_channel();
+        let rx = Arc::new(Mutex::new(rx));
+        bound.attach(Arc::downgrade(&rx));
+        tx.send(packet(1)).unwrap();
+
+        let guard = rx.lock().unwrap();
+        assert!(bound.evict_oldest().is_none());
+        drop(guard);
+        assert!(bound.evict_oldest().is_some());
+    }
+}
@@ -0,0 +1,304 @@
+use pcap::Linktype;
+
+use crate::async_pcap::Packet;
+
+/// Bit of the present word announcing another present word.
+const PRESENT_EXT: u32 = 1 << 31;
+/// Flags bit set when the frame ends with its FCS.
+const FLAG_FCS: u8 = 0x10;
+
+/// Fields of a radiotap header, as prepended to 802.11 frames captured in
+/// monitor mode.
+///
+/// Only fields of the standard radiotap namespace in the first present
+/// word are decoded. Parsing stops at the first field whose layout is not
+/// known, so fields after it are `None` even if present; vendor namespaces
+/// and extended present words are skipped over.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct Radiotap {
+    /// Length of the whole radiotap header; the 802.11 frame starts here
+    pub len: u16,
+    /// First present word, telling which fields the sender included
+    pub present: u32,
+    /// Value of the MAC's 64-bit timer when the first bit arrived, in
+    /// microseconds
+    pub tsft: Option<u64>,
+    /// Radiotap flags, such as [`has_fcs()`](Self::has_fcs)
+    pub flags: Option<u8>,
+    /// Legacy data rate in units of 500 kbit/s
+    pub rate: Option<u8>,
+    /// Channel the frame was received or sent on
+    pub channel: Option<RadiotapChannel>,
+    /// Signal strength at the antenna, in dBm
+    pub antenna_signal: Option<i8>,
+    /// Noise at the antenna, in dBm
+    pub antenna_noise: Option<i8>,
+    /// Transmit power, in dBm
+    pub tx_power: Option<i8>,
+    /// Index of the antenna the frame was received on
+    pub antenna: Option<u8>,
+    /// 802.11n MCS index, when the sender reports it
+    pub mcs_index: Option<u8>,
+}
+
+/// Channel field of a radiotap header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RadiotapChannel {
+    /// Center frequency in MHz
+    pub frequency: u16,
+    /// Channel flags, such as 2 GHz or 5 GHz spectrum
+    pub flags: u16,
+}
+
+impl RadiotapChannel {
+    /// Returns the IEEE channel number of the frequency, if it is in the
+    /// 2.4, 5 or 6 GHz band.
+    pub fn number(&self) -> Option<u16> {
+        match self.frequency {
+            2484 => Some(14),
+            2412..=2472 => Some((self.frequency - 2407) / 5),
+            5955..=7115 => Some((self.frequency - 5950) / 5),
+            5000..=5925 => Some((self.frequency - 5000) / 5),
+            _ => None,
+        }
+    }
+}
+
+impl Radiotap {
+    /// Parses the radiotap header at the start of `data`.
+    ///
+    /// Returns `None` if the header is truncated or not version 0.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if *data.first()? != 0 {
+            return None;
+        }
+        let len = u16::from_le_bytes(data.get(2..4)?.try_into().ok()?);
+        let header = data.get(..usize::from(len))?;
+        let present = le32(header, 4)?;
+
+        // Field data follows the last present word
+        let mut offset = 8;
+        let mut word = present;
+        while word & PRESENT_EXT != 0 {
+            word = le32(header, offset)?;
+            offset += 4;
+        }
+
+        let mut radiotap = Self {
+            len,
+            present,
+            ..Self::default()
+        };
+        for bit in 0..29 {
+            if present & (1 << bit) == 0 {
+                continue;
+            }
+            let Some((align, size)) = field_layout(bit) else {
+                break;
+            };
+            offset = offset.next_multiple_of(align);
+            let Some(field) = header.get(offset..offset + size) else {
+                break;
+            };
+            match bit {
+                0 => radiotap.tsft = Some(u64::from_le_bytes(field.try_into().ok()?)),
+                1 => radiotap.flags = Some(field[0]),
+                2 => radiotap.rate = Some(field[0]),
+                3 => {
+                    radiotap.channel = Some(RadiotapChannel {
+                        frequency: u16::from_le_bytes([field[0], field[1]]),
+                        flags: u16::from_le_bytes([field[2], field[3]]),
+                    })
+                }
+                5 => radiotap.antenna_signal = Some(field[0] as i8),
+                6 => radiotap.antenna_noise = Some(field[0] as i8),
+                10 => radiotap.tx_power = Some(field[0] as i8),
+                11 => radiotap.antenna = Some(field[0]),
+                // Known bits tell whether the MCS index is valid
+                19 if field[0] & 0x02 != 0 => radiotap.mcs_index = Some(field[2]),
+                _ => {}
+            }
+            offset += size;
+        }
+        Some(radiotap)
+    }
+
+    /// Whether the 802.11 frame ends with its 4-byte frame check sequence.
+    pub fn has_fcs(&self) -> bool {
+        self.flags.is_some_and(|flags| flags & FLAG_FCS != 0)
+    }
+
+    /// Returns the legacy data rate in Mbit/s.
+    pub fn rate_mbps(&self) -> Option<f32> {
+        self.rate.map(|rate| f32::from(rate) / 2.0)
+    }
+}
+
+/// Alignment and size of the standard radiotap fields.
+fn field_layout(bit: u32) -> Option<(usize, usize)> {
+    Some(match bit {
+        0 => (8, 8),        // TSFT
+        1 | 2 => (1, 1),    // flags, rate
+        3 => (2, 4),        // channel
+        4 => (1, 2),        // FHSS
+        5 | 6 => (1, 1),    // antenna signal and noise, dBm
+        7..=9 => (2, 2),    // lock quality, TX attenuation
+        10..=13 => (1, 1),  // TX power, antenna, dB signal and noise
+        14 | 15 => (2, 2),  // RX and TX flags
+        16 | 17 => (1, 1),  // RTS and data retries
+        18 => (4, 8),       // extended channel
+        19 => (1, 3),       // MCS
+        20 => (4, 8),       // A-MPDU status
+        21 | 23 => (2, 12), // VHT, HE
+        22 => (8, 12),      // timestamp
+        24 => (2, 12),      // HE-MU
+        25 => (2, 6),       // HE-MU other user
+        26 => (1, 1),       // zero-length PSDU
+        27 => (2, 4),       // L-SIG
+        _ => return None,
+    })
+}
+
+/// Type of an 802.11 frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameType {
+    /// Beacons, probes, authentication and association
+    Management,
+    /// RTS, CTS, acknowledgements and the like
+    Control,
+    /// Frames carrying data
+    Data,
+    /// Extension frames, such as DMG beacons
+    Extension,
+}
+
+/// The MAC header of an 802.11 frame.
+///
+/// Addresses that the frame type does not carry, such as the second
+/// address of an acknowledgement, are `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ieee80211Header {
+    /// Frame type, from the frame control field
+    pub frame_type: FrameType,
+    /// Frame subtype, from the frame control field
+    pub subtype: u8,
+    /// Flags byte of the frame control field
+    pub flags: u8,
+    /// Duration or association ID
+    pub duration: u16,
+    /// Receiver address
+    pub addr1: [u8; 6],
+    /// Transmitter address
+    pub addr2: Option<[u8; 6]>,
+    /// BSSID, source or destination address, depending on the DS bits
+    pub addr3: Option<[u8; 6]>,
+    /// Sequence number, without the fragment number
+    pub sequence: Option<u16>,
+    /// Fourth address, only in data frames between distribution systems
+    pub addr4: Option<[u8; 6]>,
+}
+
+impl Ieee80211Header {
+    /// Parses the MAC header at the start of `data`.
+    ///
+    /// Returns `None` if the frame is too short for its type.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let fc = data.get(..2)?;
+        let frame_type = match (fc[0] >> 2) & 0x03 {
+            0 => FrameType::Management,
+            1 => FrameType::Control,
+            2 => FrameType::Data,
+            _ => FrameType::Extension,
+        };
+        let subtype = fc[0] >> 4;
+        let flags = fc[1];
+        let duration = u16::from_le_bytes(data.get(2..4)?.try_into().ok()?);
+        let addr1 = mac(data, 4)?;
+
+        let mut header = Self {
+            frame_type,
+            subtype,
+            flags,
+            duration,
+            addr1,
+            addr2: None,
+            addr3: None,
+            sequence: None,
+            addr4: None,
+        };
+        match frame_type {
+            FrameType::Control => {
+                // CTS (12) and ACK (13) carry the receiver address only
+                if !matches!(subtype, 12 | 13) {
+                    header.addr2 = mac(data, 10);
+                }
+            }
+            FrameType::Management | FrameType::Data => {
+                header.addr2 = Some(mac(data, 10)?);
+                header.addr3 = Some(mac(data, 16)?);
+                let seq = u16::from_le_bytes(data.get(22..24)?.try_into().ok()?);
+                header.sequence = Some(seq >> 4);
+                if frame_type == FrameType::Data && header.to_ds() && header.from_ds() {
+                    header.addr4 = Some(mac(data, 24)?);
+                }
+            }
+            FrameType::Extension => {}
+        }
+        Some(header)
+    }
+
+    /// Whether the frame is going to the distribution system.
+    pub fn to_ds(&self) -> bool {
+        self.flags & 0x01 != 0
+    }
+
+    /// Whether the frame is coming from the distribution system.
+    pub fn from_ds(&self) -> bool {
+        self.flags & 0x02 != 0
+    }
+
+    /// Whether the frame body is encrypted.
+    pub fn is_protected(&self) -> bool {
+        self.flags & 0x40 != 0
+    }
+}
+
+impl Packet {
+    /// Parses the radiotap header of the packet.
+    ///
+    /// Requires the `radiotap` feature. Returns `None` unless `datalink`
+    /// is [`Linktype::IEEE802_11_RADIOTAP`].
+    pub fn radiotap(&self, datalink: Linktype) -> Option<Radiotap> {
+        if datalink != Linktype::IEEE802_11_RADIOTAP {
+            return None;
+        }
+        Radiotap::parse(&self.data)
+    }
+
+    /// Parses the 802.11 MAC header of the packet.
+    ///
+    /// Requires the `radiotap` feature. Works with
+    /// [`Linktype::IEEE802_11_RADIOTAP`], skipping the radiotap header,
+    /// and with [`Linktype::IEEE802_11`]; returns `None` for any other
+    /// link type.
+    pub fn ieee80211(&self, datalink: Linktype) -> Option<Ieee80211Header> {
+        let frame = match datalink {
+            Linktype::IEEE802_11_RADIOTAP => {
+                let radiotap = Radiotap::parse(&self.data)?;
+                self.data.get(usize::from(radiotap.len)..)?
+            }
+            Linktype::IEEE802_11 => &self.data[..],
+            _ => return None,
+        };
+        Ieee80211Header::parse(frame)
+    }
+}
+
+fn le32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn mac(data: &[u8], at: usize) -> Option<[u8; 6]> {
+    data.get(at..at + 6)?.try_into().ok()
+}
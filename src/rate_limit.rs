@@ -0,0 +1,223 @@
+use std::time::{Duration, Instant};
+
+use pcap::PacketHeader;
+
+use crate::async_pcap::Packet;
+
+/// Upper bound on the rate at which packets are forwarded to the consumer.
+///
+/// The limit is enforced on the capture thread, which sleeps before
+/// forwarding a packet that would exceed it. It is a fixed ceiling and is
+/// independent of the original packet timing. On a live capture, packets
+/// that arrive while the thread is throttled queue up in the kernel buffer
+/// and are dropped once it fills. A rate of zero forwards nothing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateLimit {
+    /// At most this many packets per second
+    Packets(u64),
+    /// At most this many captured bytes (`caplen`) per second
+    Bytes(u64),
+}
+
+impl RateLimit {
+    fn per_second(self) -> f64 {
+        match self {
+            RateLimit::Packets(n) | RateLimit::Bytes(n) => n as f64,
+        }
+    }
+
+    fn cost(self, header: &PacketHeader) -> f64 {
+        match self {
+            RateLimit::Packets(_) => 1.0,
+            RateLimit::Bytes(_) => f64::from(header.caplen),
+        }
+    }
+}
+
+/// Token bucket enforcing a [`RateLimit`].
+///
+/// The bucket holds at most 100 ms worth of tokens, so short bursts are
+/// smoothed without exceeding the configured ceiling by more than that.
+/// [`delay_for()`](Self::delay_for) still admits a packet that costs more
+/// than the available tokens, leaving the bucket in debt; the returned
+/// delay pays that debt back. Callers that discard packets over the rate
+/// instead check [`has_room()`](Self::has_room) and only
+/// [`charge()`](Self::charge) the packets they keep.
+#[derive(Debug)]
+pub(crate) struct TokenBucket {
+    limit: RateLimit,
+    capacity: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(limit: RateLimit, now: Instant) -> Self {
+        let capacity = (limit.per_second() / 10.0).max(1.0);
+        Self {
+            limit,
+            capacity,
+            tokens: capacity,
+            last: now,
+        }
+    }
+
+    /// Charges the bucket for `packet`, read at `now`, and returns how
+    /// long to wait before forwarding it.
+    pub(crate) fn delay_for(&mut self, packet: &Packet, now: Instant) -> Duration {
+        let rate = self.limit.per_second();
+        if rate <= 0.0 {
+            return Duration::MAX;
+        }
+
+        self.refill(now);
+        self.charge(&packet.header);
+
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+
+    /// Refills the bucket up to `now` and tells whether a packet with
+    /// `header` fits without debt. A single packet larger than the whole
+    /// bucket fits a full one.
+    pub(crate) fn has_room(&mut self, header: &PacketHeader, now: Instant) -> bool {
+        if self.limit.per_second() <= 0.0 {
+            return false;
+        }
+        self.refill(now);
+        self.tokens >= self.limit.cost(header).min(self.capacity)
+    }
+
+    /// Takes the tokens of a packet with `header` out of the bucket.
+    pub(crate) fn charge(&mut self, header: &PacketHeader) {
+        self.tokens -= self.limit.cost(header);
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * self.limit.per_second()).min(self.capacity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+    use crate::clock::{Clock, ManualClock};
+
+    fn packet(caplen: usize) -> Packet {
+        Packet::new(vec![0; caplen], UNIX_EPOCH)
+    }
+
+    #[test]
+    fn burst_of_100_ms_is_admitted_at_once() {
+        let clock = ManualClock::new();
+        let mut bucket = TokenBucket::new(RateLimit::Packets(100), clock.now());
+        for _ in 0..10 {
+            assert_eq!(bucket.delay_for(&packet(1), clock.now()), Duration::ZERO);
+        }
+        assert_eq!(
+            bucket.delay_for(&packet(1), clock.now()),
+            Duration::from_millis(10)
+        );
+    }
+
+    #[test]
+    fn tokens_refill_with_time() {
+        let clock = ManualClock::new();
+        let mut bucket = TokenBucket::new(RateLimit::Packets(100), clock.now());
+        for _ in 0..10 {
+            bucket.delay_for(&packet(1), clock.now());
+        }
+        clock.advance(Duration::from_millis(50));
+        for _ in 0..5 {
+            assert_eq!(bucket.delay_for(&packet(1), clock.now()), Duration::ZERO);
+        }
+        assert!(bucket.delay_for(&packet(1), clock.now()) > Duration::ZERO);
+    }
+
+    #[test]
+    fn refill_is_capped_at_the_burst_size() {
+        let clock = ManualClock::new();
+        let mut bucket = TokenBucket::new(RateLimit::Packets(100), clock.now());
+        clock.advance(Duration::from_secs(10));
+        for _ in 0..10 {
+            assert_eq!(bucket.delay_for(&packet(1), clock.now()), Duration::ZERO);
+        }
+        assert!(bucket.delay_for(&packet(1), clock.now()) > Duration::ZERO);
+    }
+
+    #[test]
+    fn debt_is_paid_back_by_the_delay() {
+        let clock = ManualClock::new();
+        let mut bucket = TokenBucket::new(RateLimit::Packets(100), clock.now());
+        for _ in 0..10 {
+            bucket.delay_for(&packet(1), clock.now());
+        }
+        // Three packets over the burst wait 10, 20 and 30 ms
+        for wait in [10, 20, 30] {
+            assert_eq!(
+                bucket.delay_for(&packet(1), clock.now()),
+                Duration::from_millis(wait)
+            );
+        }
+        // Once the debt is paid, the next packet waits one interval
+        clock.advance(Duration::from_millis(30));
+        assert_eq!(
+            bucket.delay_for(&packet(1), clock.now()),
+            Duration::from_millis(10)
+        );
+    }
+
+    #[test]
+    fn byte_rate_charges_the_captured_length() {
+        let clock = ManualClock::new();
+        let mut bucket = TokenBucket::new(RateLimit::Bytes(1000), clock.now());
+        // The burst is 100 bytes; a 150-byte packet leaves a 50-byte debt
+        assert_eq!(
+            bucket.delay_for(&packet(150), clock.now()),
+            Duration::from_millis(50)
+        );
+        clock.advance(Duration::from_millis(50));
+        assert_eq!(
+            bucket.delay_for(&packet(100), clock.now()),
+            Duration::from_millis(100)
+        );
+    }
+
+    #[test]
+    fn low_rates_admit_one_packet_at_a_time() {
+        let clock = ManualClock::new();
+        let mut bucket = TokenBucket::new(RateLimit::Packets(5), clock.now());
+        assert_eq!(bucket.delay_for(&packet(1), clock.now()), Duration::ZERO);
+        assert_eq!(
+            bucket.delay_for(&packet(1), clock.now()),
+            Duration::from_millis(200)
+        );
+    }
+
+    #[test]
+    fn zero_rate_forwards_nothing() {
+        let clock = ManualClock::new();
+        let mut bucket = TokenBucket::new(RateLimit::Packets(0), clock.now());
+        assert_eq!(bucket.delay_for(&packet(1), clock.now()), Duration::MAX);
+    }
+
+    #[test]
+    fn packets_over_the_rate_find_no_room_and_leave_no_debt() {
+        let clock = ManualClock::new();
+        let mut bucket = TokenBucket::new(RateLimit::Bytes(1000), clock.now());
+        let header = packet(150).header;
+        // Larger than the 100-byte burst, but a full bucket takes it
+        assert!(bucket.has_room(&header, clock.now()));
+        bucket.charge(&header);
+        assert!(!bucket.has_room(&header, clock.now()));
+        clock.advance(Duration::from_millis(150));
+        assert!(bucket.has_room(&header, clock.now()));
+    }
+}
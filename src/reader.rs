@@ -0,0 +1,135 @@
+use std::fmt;
+use std::sync::atomic::Ordering;
+#[cfg(feature = "rt-tokio")]
+use std::time::Duration;
+
+use pcap::Linktype;
+
+use crate::async_pcap::{AsyncCapture, AsyncCaptureHandle, Packet, StreamItem};
+use crate::error::Error;
+use crate::info::CaptureInfo;
+use crate::local::LocalAddresses;
+use crate::meta::PacketMeta;
+
+/// The receiving half of an [`AsyncCapture`], owned by a single consumer.
+///
+/// Created by [`AsyncCapture::into_split()`]. Unlike `AsyncCapture`, whose
+/// methods take `&self` and share one queue between every caller, the
+/// reader receives through `&mut self` without an internal lock, so it
+/// can be moved into a task as is and no other caller can take its
+/// packets.
+///
+/// # Notes
+///
+/// - The reader is not `Clone`. To share the packets between several
+///   consumers, keep the `AsyncCapture` itself behind an `Arc`, where each
+///   packet goes to one caller, or use
+///   [`AsyncCapture::broadcast()`], where every subscriber sees every
+///   packet.
+/// - The receiving methods are cancellation safe, as with
+///   [`AsyncCapture::next_packet()`].
+/// - No Tokio runtime is needed.
+pub struct AsyncCaptureReader {
+    /// Only reached through `&mut self`, so its queue is never locked
+    capture: AsyncCapture,
+}
+
+impl AsyncCaptureReader {
+    /// Returns the parameters the capture is actually running with.
+    pub fn info(&self) -> &CaptureInfo {
+        self.capture.info()
+    }
+
+    /// Returns the link-layer header type of the captured packets.
+    pub fn datalink(&self) -> Linktype {
+        self.capture.datalink()
+    }
+
+    /// Returns the addresses of the capturing interface, recorded when it
+    /// was opened by [`AsyncCaptureBuilder`](crate::AsyncCaptureBuilder).
+    ///
+    /// Empty for captures created from any other source.
+    pub fn local_addresses(&self) -> &LocalAddresses {
+        self.capture.local_addresses()
+    }
+
+    /// Waits for the next packet.
+    ///
+    /// Returns `None` once the capture has stopped, as
+    /// [`AsyncCapture::next_packet()`] does.
+    pub async fn next_packet(&mut self) -> Option<Result<Packet, Error>> {
+        self.next_packet_meta().await.map(|(pkt, _)| pkt)
+    }
+
+    /// Waits for the next packet along with the information gathered for
+    /// it on the capture thread.
+    ///
+    /// See [`AsyncCapture::next_packet_meta()`].
+    pub async fn next_packet_meta(&mut self) -> Option<(Result<Packet, Error>, PacketMeta)> {
+        self.capture.rx.get_mut().next_meta().await
+    }
+
+    /// Waits for packets and appends up to `limit` of them to `buf`.
+    ///
+    /// See [`AsyncCapture::next_packets()`].
+    pub async fn next_packets(
+        &mut self,
+        buf: &mut Vec<Result<Packet, Error>>,
+        limit: usize,
+    ) -> usize {
+        self.capture.rx.get_mut().next_packets(buf, limit).await
+    }
+
+    /// Collects up to `max` packets, waiting at most `timeout` for them.
+    ///
+    /// See [`AsyncCapture::next_batch()`]. Requires the `rt-tokio` feature.
+    #[cfg(feature = "rt-tokio")]
+    pub async fn next_batch(
+        &mut self,
+        max: usize,
+        timeout: Duration,
+    ) -> Vec<Result<Packet, Error>> {
+        self.capture.rx.get_mut().next_batch(max, timeout).await
+    }
+
+    /// Waits for the next packet, statistics or device event.
+    ///
+    /// See [`AsyncCapture::next_item()`].
+    pub async fn next_item(&mut self) -> Option<StreamItem> {
+        self.capture.rx.get_mut().next_item().await
+    }
+
+    /// Waits for the next packet or change of the capture's state.
+    ///
+    /// See [`AsyncCapture::next_event()`].
+    pub async fn next_event(&mut self) -> Option<StreamItem> {
+        self.capture
+            .shared
+            .events_wanted
+            .store(true, Ordering::Relaxed);
+        self.next_item().await
+    }
+}
+
+impl AsyncCapture {
+    /// Splits the capture into an owned reader and a handle.
+    ///
+    /// The [`AsyncCaptureReader`] receives the remaining packets through
+    /// `&mut self`, so it can be moved into the task that consumes them
+    /// while the [`AsyncCaptureHandle`] stays with the code that controls
+    /// the capture.
+    pub fn into_split(self) -> (AsyncCaptureReader, AsyncCaptureHandle) {
+        let handle = AsyncCaptureHandle {
+            shared: self.shared.clone(),
+        };
+        (AsyncCaptureReader { capture: self }, handle)
+    }
+}
+
+impl fmt::Debug for AsyncCaptureReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncCaptureReader")
+            .field("capture", &self.capture)
+            .finish()
+    }
+}
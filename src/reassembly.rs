@@ -0,0 +1,702 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::Duration;
+
+use bytes::Bytes;
+use pcap::{Linktype, Precision};
+
+use crate::async_pcap::{AsyncCapture, Packet};
+use crate::error::Error;
+use crate::flow::FlowKey;
+use crate::parse::{self, TCP_FIN, TCP_RST, TCP_SYN};
+
+/// How often idle flows are swept, in capture time.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Capacity kept by internal collections when they are shrunk after a
+/// burst.
+const BASELINE_CAPACITY: usize = 64;
+
+/// Limits applied by a [`TcpReassembler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReassemblyConfig {
+    /// Maximum out-of-order bytes buffered per flow direction
+    pub max_buffered_per_flow: usize,
+    /// Flows without traffic for this long (in capture time) are dropped
+    pub flow_timeout: Duration,
+}
+
+impl Default for ReassemblyConfig {
+    fn default() -> Self {
+        Self {
+            max_buffered_per_flow: 1024 * 1024,
+            flow_timeout: Duration::from_secs(120),
+        }
+    }
+}
+
+/// Change in a flow direction tracked by a [`TcpReassembler`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FlowEvent {
+    /// The first segment of the flow direction was seen
+    Opened {
+        /// The flow direction
+        key: FlowKey,
+        /// Whether the flow started with a SYN, rather than being picked
+        /// up mid-connection
+        syn: bool,
+    },
+    /// Payload that became available in order
+    Data {
+        /// The flow direction
+        key: FlowKey,
+        /// The bytes following those of the previous `Data` event
+        data: Bytes,
+    },
+    /// A missing range was given up on because the buffer limit was
+    /// exceeded; the next `Data` event follows the gap
+    Gap {
+        /// The flow direction
+        key: FlowKey,
+        /// Number of bytes skipped
+        bytes: u64,
+    },
+    /// The flow direction is no longer tracked
+    Closed {
+        /// The flow direction
+        key: FlowKey,
+        /// Why the flow ended
+        reason: CloseReason,
+    },
+}
+
+/// Why a [`FlowEvent::Closed`] was reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    /// Every byte up to the FIN was delivered
+    Fin,
+    /// An RST was seen; buffered data was discarded
+    Reset,
+    /// No segment was seen for [`ReassemblyConfig::flow_timeout`]
+    Timeout,
+    /// A SYN with another initial sequence number started a new
+    /// connection with the same 5-tuple
+    Reused,
+}
+
+/// Reassembles TCP payload into in-order byte streams per flow direction.
+///
+/// Feed it packets with [`push`](Self::push) or let it pull from a capture
+/// with [`next`](Self::next). Each direction of a connection is tracked
+/// separately under its own [`FlowKey`]. To also learn when flows start
+/// and end, use [`push_events`](Self::push_events) and
+/// [`next_event`](Self::next_event) instead, which report each flow's
+/// [`FlowEvent::Opened`], its data and its [`FlowEvent::Closed`] in
+/// order.
+///
+/// # Policy
+///
+/// - Bytes that were already delivered are never replaced; retransmitted
+///   copies are discarded.
+/// - Overlapping out-of-order segments are resolved in favour of the one
+///   with the lower sequence number; between segments starting at the
+///   same sequence number the longer one is kept, and the first arrival
+///   wins if they are as long.
+/// - Out-of-order segments are buffered up to
+///   [`ReassemblyConfig::max_buffered_per_flow`]. When that limit is
+///   exceeded the missing range is given up on: the stream skips ahead to
+///   the earliest buffered segment and [`gaps`](Self::gaps) is incremented.
+/// - A stream picked up mid-connection starts at the first segment seen.
+/// - A SYN on a tracked flow whose sequence number differs from the one
+///   that opened it starts a new connection, closing the flow as
+///   [`CloseReason::Reused`]; a retransmitted SYN is ignored.
+/// - A flow is forgotten once its FIN has been reached, on RST (buffered
+///   data is discarded), or after [`ReassemblyConfig::flow_timeout`].
+/// - Malformed, non-TCP and IP-fragmented packets are ignored.
+/// - The flow table and output queue grow with bursts of traffic and are
+///   shrunk back during the periodic idle-flow sweep once most of their
+///   capacity is unused.
+pub struct TcpReassembler {
+    datalink: Linktype,
+    /// Precision the packet timestamps are read with
+    precision: Precision,
+    config: ReassemblyConfig,
+    flows: HashMap<FlowKey, Stream>,
+    ready: VecDeque<FlowEvent>,
+    last_sweep: Duration,
+    gaps: u64,
+}
+
+/// Reassembly state of one flow direction.
+struct Stream {
+    /// Sequence number of the SYN, if the flow started with one
+    isn: Option<u32>,
+    /// Sequence number of the next byte to deliver
+    next_seq: u32,
+    /// Stream offset of `next_seq`
+    delivered: u64,
+    /// Out-of-order segments keyed by stream offset
+    pending: BTreeMap<u64, Vec<u8>>,
+    buffered: usize,
+    fin_at: Option<u64>,
+    last_seen: Duration,
+}
+
+impl TcpReassembler {
+    /// Creates a reassembler for packets of the given datalink with the
+    /// default limits.
+    pub fn new(datalink: Linktype) -> Self {
+        Self::with_config(datalink, ReassemblyConfig::default())
+    }
+
+    /// Creates a reassembler with custom limits.
+    pub fn with_config(datalink: Linktype, config: ReassemblyConfig) -> Self {
+        Self {
+            datalink,
+            precision: Precision::Micro,
+            config,
+            flows: HashMap::new(),
+            ready: VecDeque::new(),
+            last_sweep: Duration::ZERO,
+            gaps: 0,
+        }
+    }
+
+    /// Reads the packet timestamps, which drive the flow timeout, with
+    /// `precision`; microseconds by default.
+    ///
+    /// Set it to the [`CaptureInfo::precision`](crate::CaptureInfo::precision)
+    /// of a capture opened with nanosecond timestamps.
+    pub fn precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Number of flow directions currently tracked.
+    pub fn active_flows(&self) -> usize {
+        self.flows.len()
+    }
+
+    /// Number of times a stream skipped a missing range because its
+    /// buffer limit was exceeded.
+    pub fn gaps(&self) -> u64 {
+        self.gaps
+    }
+
+    /// Processes one packet and returns the payload chunks that became
+    /// available in order.
+    pub fn push(&mut self, packet: &Packet) -> impl Iterator<Item = (FlowKey, Bytes)> + '_ {
+        self.process(packet);
+        self.ready.drain(..).filter_map(FlowEvent::into_data)
+    }
+
+    /// Processes one packet and returns the events it caused, in order.
+    pub fn push_events(&mut self, packet: &Packet) -> impl Iterator<Item = FlowEvent> + '_ {
+        self.process(packet);
+        self.ready.drain(..)
+    }
+
+    /// Pulls packets from `capture` until a reassembled chunk is available.
+    ///
+    /// Returns `None` once the capture has stopped; errors from the capture
+    /// are passed through.
+    pub async fn next(
+        &mut self,
+        capture: &AsyncCapture,
+    ) -> Option<Result<(FlowKey, Bytes), Error>> {
+        loop {
+            if let Some(event) = self.ready.pop_front() {
+                match event.into_data() {
+                    Some(chunk) => return Some(Ok(chunk)),
+                    None => continue,
+                }
+            }
+            match capture.next_packet().await? {
+                Ok(packet) => self.process(&packet),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+
+    /// Pulls packets from `capture` until a flow event is available.
+    ///
+    /// Returns `None` once the capture has stopped; errors from the capture
+    /// are passed through. Flows still tracked at that point get no
+    /// `Closed` event.
+    pub async fn next_event(&mut self, capture: &AsyncCapture) -> Option<Result<FlowEvent, Error>> {
+        loop {
+            if let Some(event) = self.ready.pop_front() {
+                return Some(Ok(event));
+            }
+            match capture.next_packet().await? {
+                Ok(packet) => self.process(&packet),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+
+    fn process(&mut self, packet: &Packet) {
+        let ts = packet.since_epoch_with(self.precision);
+        self.sweep(ts);
+
+        let Some((key, segment)) = parse::tcp_packet(self.datalink, &packet.data) else {
+            return;
+        };
+        if segment.flags & TCP_RST != 0 {
+            if self.flows.remove(&key).is_some() {
+                self.close(key, CloseReason::Reset);
+            }
+            return;
+        }
+
+        let syn = segment.flags & TCP_SYN != 0;
+        let seq = if syn {
+            segment.seq.wrapping_add(1)
+        } else {
+            segment.seq
+        };
+        if syn
+            && self
+                .flows
+                .get(&key)
+                .is_some_and(|s| s.isn != Some(segment.seq))
+        {
+            // A new connection reusing the same 5-tuple, rather than a
+            // retransmission of the SYN that opened the flow
+            self.flows.remove(&key);
+            self.close(key, CloseReason::Reused);
+        }
+        let stream = self.flows.entry(key).or_insert_with(|| {
+            self.ready.push_back(FlowEvent::Opened { key, syn });
+            Stream {
+                isn: syn.then_some(segment.seq),
+                next_seq: seq,
+                delivered: 0,
+                pending: BTreeMap::new(),
+                buffered: 0,
+                fin_at: None,
+                last_seen: ts,
+            }
+        });
+        stream.last_seen = ts;
+
+        let rel = i64::from(seq.wrapping_sub(stream.next_seq) as i32);
+        let start = stream.delivered as i64 + rel;
+        let end = start + segment.payload.len() as i64;
+        if segment.flags & TCP_FIN != 0 && end >= 0 {
+            stream.fin_at.get_or_insert(end as u64);
+        }
+        if end > stream.delivered as i64 {
+            let offset = start.max(stream.delivered as i64);
+            let skip = (offset - start) as usize;
+            let offset = offset as u64;
+            let bytes = &segment.payload[skip..];
+            // A retransmission at the same offset may carry more data
+            let held = stream.pending.get(&offset).map_or(0, Vec::len);
+            if bytes.len() > held {
+                stream.buffered += bytes.len() - held;
+                stream.pending.insert(offset, bytes.to_vec());
+            }
+        }
+
+        self.gaps += stream.drain(key, &mut self.ready, self.config.max_buffered_per_flow);
+        if stream.fin_at.is_some_and(|fin| stream.delivered >= fin) {
+            self.flows.remove(&key);
+            self.close(key, CloseReason::Fin);
+        }
+    }
+
+    fn close(&mut self, key: FlowKey, reason: CloseReason) {
+        self.ready.push_back(FlowEvent::Closed { key, reason });
+    }
+
+    fn sweep(&mut self, now: Duration) {
+        if now.saturating_sub(self.last_sweep) < SWEEP_INTERVAL {
+            return;
+        }
+        self.last_sweep = now;
+        let timeout = self.config.flow_timeout;
+        let ready = &mut self.ready;
+        self.flows.retain(|&key, stream| {
+            let live = now.saturating_sub(stream.last_seen) <= timeout;
+            if !live {
+                ready.push_back(FlowEvent::Closed {
+                    key,
+                    reason: CloseReason::Timeout,
+                });
+            }
+            live
+        });
+
+        // Give back memory held since a burst once utilization drops
+        let floor = self.flows.len().max(BASELINE_CAPACITY);
+        if self.flows.capacity() > floor * 4 {
+            self.flows.shrink_to(floor * 2);
+        }
+        if self.ready.is_empty() && self.ready.capacity() > BASELINE_CAPACITY * 4 {
+            self.ready.shrink_to(BASELINE_CAPACITY);
+        }
+    }
+}
+
+impl FlowEvent {
+    fn into_data(self) -> Option<(FlowKey, Bytes)> {
+        match self {
+            FlowEvent::Data { key, data } => Some((key, data)),
+            _ => None,
+        }
+    }
+}
+
+impl Stream {
+    /// Delivers every buffered segment that is now contiguous, skipping
+    /// missing ranges while the buffer is over `limit`. Returns the number
+    /// of skipped ranges.
+    fn drain(&mut self, key: FlowKey, ready: &mut VecDeque<FlowEvent>, limit: usize) -> u64 {
+        let mut gaps = 0;
+        while let Some(mut entry) = self.pending.first_entry() {
+            let offset = *entry.key();
+            if offset > self.delivered {
+                if self.buffered <= limit {
+                    break;
+                }
+                // Give up on the missing range
+                let gap = offset - self.delivered;
+                self.next_seq = self.next_seq.wrapping_add(gap as u32);
+                self.delivered = offset;
+                gaps += 1;
+                ready.push_back(FlowEvent::Gap { key, bytes: gap });
+            }
+            let overlap = (self.delivered - offset) as usize;
+            let bytes = std::mem::take(entry.get_mut());
+            entry.remove();
+            self.buffered -= bytes.len();
+            if overlap < bytes.len() {
+                let fresh = bytes.len() - overlap;
+                self.next_seq = self.next_seq.wrapping_add(fresh as u32);
+                self.delivered += fresh as u64;
+                ready.push_back(FlowEvent::Data {
+                    key,
+                    data: Bytes::from(bytes).slice(overlap..),
+                });
+            }
+        }
+        gaps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+
+    const TCP_ACK: u8 = 0x10;
+
+    /// An Ethernet frame holding an IPv4 TCP segment from 10.0.0.1:80 to
+    /// 10.0.0.2:81, captured `millis` after the epoch.
+    fn segment(seq: u32, flags: u8, payload: &[u8], millis: u64) -> Packet {
+        segment_from(80, seq, flags, payload, millis)
+    }
+
+    /// Like [`segment`], from another source port.
+    fn segment_from(port: u16, seq: u32, flags: u8, payload: &[u8], millis: u64) -> Packet {
+        let mut data = vec![0; 12];
+        data.extend_from_slice(&[0x08, 0x00]);
+        let total = (20 + 20 + payload.len()) as u16;
+        data.extend_from_slice(&[0x45, 0, 0, 0, 0, 0, 0, 0, 64, 6, 0, 0]);
+        data[16..18].copy_from_slice(&total.to_be_bytes());
+        data.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        data.extend_from_slice(&port.to_be_bytes());
+        data.extend_from_slice(&[0, 81]);
+        data.extend_from_slice(&seq.to_be_bytes());
+        data.extend_from_slice(&[0, 0, 0, 0, 0x50, flags, 0, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(payload);
+        Packet::new(data, UNIX_EPOCH + Duration::from_millis(millis))
+    }
+
+    fn key(port: u16) -> FlowKey {
+        FlowKey {
+            src_ip: [10, 0, 0, 1].into(),
+            dst_ip: [10, 0, 0, 2].into(),
+            src_port: port,
+            dst_port: 81,
+            protocol: 6,
+        }
+    }
+
+    fn data(port: u16, bytes: &[u8]) -> FlowEvent {
+        FlowEvent::Data {
+            key: key(port),
+            data: Bytes::copy_from_slice(bytes),
+        }
+    }
+
+    fn closed(port: u16, reason: CloseReason) -> FlowEvent {
+        FlowEvent::Closed {
+            key: key(port),
+            reason,
+        }
+    }
+
+    fn events(reassembler: &mut TcpReassembler, packets: &[Packet]) -> Vec<FlowEvent> {
+        packets
+            .iter()
+            .flat_map(|packet| reassembler.push_events(packet).collect::<Vec<_>>())
+            .collect()
+    }
+
+    fn payload(reassembler: &mut TcpReassembler, packets: &[Packet]) -> Vec<u8> {
+        packets
+            .iter()
+            .flat_map(|packet| reassembler.push(packet).collect::<Vec<_>>())
+            .flat_map(|(_, data)| data.to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn longer_retransmission_at_the_same_offset_is_kept() {
+        let mut reassembler = TcpReassembler::new(Linktype::ETHERNET);
+        let packets = [
+            segment(100, TCP_SYN, b"", 0),
+            // Out of order: bytes 5.. arrive before 0..5
+            segment(106, TCP_ACK, b"wo", 1),
+            segment(106, TCP_ACK, b"world", 2),
+            segment(101, TCP_ACK, b"hello", 3),
+        ];
+        assert_eq!(payload(&mut reassembler, &packets), b"helloworld");
+    }
+
+    #[test]
+    fn shorter_retransmission_at_the_same_offset_is_ignored() {
+        let mut reassembler = TcpReassembler::new(Linktype::ETHERNET);
+        let packets = [
+            segment(100, TCP_SYN, b"", 0),
+            segment(106, TCP_ACK, b"world", 1),
+            segment(106, TCP_ACK, b"wo", 2),
+            segment(101, TCP_ACK, b"hello", 3),
+        ];
+        assert_eq!(payload(&mut reassembler, &packets), b"helloworld");
+    }
+
+    #[test]
+    fn connection_is_opened_delivered_and_closed_by_fin() {
+        let mut reassembler = TcpReassembler::new(Linktype::ETHERNET);
+        let packets = [
+            segment(100, TCP_SYN, b"", 0),
+            segment(101, TCP_ACK, b"hello", 1),
+            segment(106, TCP_ACK | TCP_FIN, b"!", 2),
+        ];
+        assert_eq!(
+            events(&mut reassembler, &packets),
+            [
+                FlowEvent::Opened {
+                    key: key(80),
+                    syn: true
+                },
+                data(80, b"hello"),
+                data(80, b"!"),
+                closed(80, CloseReason::Fin),
+            ]
+        );
+        assert_eq!(reassembler.active_flows(), 0);
+    }
+
+    #[test]
+    fn out_of_order_segments_are_delivered_in_order() {
+        let mut reassembler = TcpReassembler::new(Linktype::ETHERNET);
+        let packets = [
+            segment(100, TCP_SYN, b"", 0),
+            segment(111, TCP_ACK, b"!", 1),
+            segment(106, TCP_ACK, b"world", 2),
+            segment(101, TCP_ACK, b"hello", 3),
+        ];
+        assert_eq!(
+            events(&mut reassembler, &packets)[1..],
+            [data(80, b"hello"), data(80, b"world"), data(80, b"!")]
+        );
+    }
+
+    #[test]
+    fn delivered_bytes_are_never_replaced() {
+        let mut reassembler = TcpReassembler::new(Linktype::ETHERNET);
+        let packets = [
+            segment(100, TCP_SYN, b"", 0),
+            segment(101, TCP_ACK, b"hello", 1),
+            // A plain retransmission, then one that overlaps new data
+            segment(101, TCP_ACK, b"HELLO", 2),
+            segment(104, TCP_ACK, b"LO world", 3),
+        ];
+        assert_eq!(payload(&mut reassembler, &packets), b"hello world");
+    }
+
+    #[test]
+    fn overlapping_out_of_order_segments_favour_the_lower_sequence() {
+        let mut reassembler = TcpReassembler::new(Linktype::ETHERNET);
+        let packets = [
+            segment(100, TCP_SYN, b"", 0),
+            segment(108, TCP_ACK, b"WXYZ", 1),
+            segment(106, TCP_ACK, b"abcd", 2),
+            segment(101, TCP_ACK, b"hello", 3),
+        ];
+        assert_eq!(payload(&mut reassembler, &packets), b"helloabcdYZ");
+    }
+
+    #[test]
+    fn missing_range_is_given_up_past_the_buffer_limit() {
+        let config = ReassemblyConfig {
+            max_buffered_per_flow: 4,
+            ..ReassemblyConfig::default()
+        };
+        let mut reassembler = TcpReassembler::with_config(Linktype::ETHERNET, config);
+        let packets = [
+            segment(100, TCP_SYN, b"", 0),
+            segment(106, TCP_ACK, b"wo", 1),
+            segment(108, TCP_ACK, b"rld", 2),
+            // Too late: the stream has moved past it
+            segment(101, TCP_ACK, b"hello", 3),
+        ];
+        assert_eq!(
+            events(&mut reassembler, &packets)[1..],
+            [
+                FlowEvent::Gap {
+                    key: key(80),
+                    bytes: 5
+                },
+                data(80, b"wo"),
+                data(80, b"rld"),
+            ]
+        );
+        assert_eq!(reassembler.gaps(), 1);
+    }
+
+    #[test]
+    fn reset_discards_buffered_data() {
+        let mut reassembler = TcpReassembler::new(Linktype::ETHERNET);
+        let packets = [
+            segment(100, TCP_SYN, b"", 0),
+            segment(106, TCP_ACK, b"world", 1),
+            segment(101, TCP_RST, b"", 2),
+        ];
+        assert_eq!(
+            events(&mut reassembler, &packets)[1..],
+            [closed(80, CloseReason::Reset)]
+        );
+        assert_eq!(reassembler.active_flows(), 0);
+    }
+
+    #[test]
+    fn idle_flow_times_out() {
+        let config = ReassemblyConfig {
+            flow_timeout: Duration::from_secs(2),
+            ..ReassemblyConfig::default()
+        };
+        let mut reassembler = TcpReassembler::with_config(Linktype::ETHERNET, config);
+        let packets = [
+            segment(100, TCP_SYN, b"", 0),
+            segment(101, TCP_ACK, b"hi", 1_000),
+            segment_from(90, 500, TCP_SYN, b"", 2_500),
+        ];
+        let events = events(&mut reassembler, &packets);
+        assert!(!events.contains(&closed(80, CloseReason::Timeout)));
+
+        // Idle for 2.6 s at the next sweep
+        let later = segment_from(90, 501, TCP_ACK, b"later", 3_600);
+        let events: Vec<_> = reassembler.push_events(&later).collect();
+        assert_eq!(
+            events,
+            [closed(80, CloseReason::Timeout), data(90, b"later")]
+        );
+        assert_eq!(reassembler.active_flows(), 1);
+    }
+
+    #[test]
+    fn stream_picked_up_mid_connection_starts_at_the_first_segment() {
+        let mut reassembler = TcpReassembler::new(Linktype::ETHERNET);
+        let packets = [
+            segment(5_000, TCP_ACK, b"mid", 0),
+            segment(5_003, TCP_ACK, b"stream", 1),
+        ];
+        assert_eq!(
+            events(&mut reassembler, &packets),
+            [
+                FlowEvent::Opened {
+                    key: key(80),
+                    syn: false
+                },
+                data(80, b"mid"),
+                data(80, b"stream"),
+            ]
+        );
+    }
+
+    #[test]
+    fn new_syn_on_the_same_tuple_reopens_the_flow() {
+        let mut reassembler = TcpReassembler::new(Linktype::ETHERNET);
+        let packets = [
+            segment(100, TCP_SYN, b"", 0),
+            segment(101, TCP_ACK, b"one", 1),
+            segment(9_000, TCP_SYN, b"", 2),
+            segment(9_001, TCP_ACK, b"two", 3),
+        ];
+        assert_eq!(
+            events(&mut reassembler, &packets)[2..],
+            [
+                closed(80, CloseReason::Reused),
+                FlowEvent::Opened {
+                    key: key(80),
+                    syn: true
+                },
+                data(80, b"two"),
+            ]
+        );
+    }
+
+    #[test]
+    fn retransmitted_syn_keeps_the_flow() {
+        let mut reassembler = TcpReassembler::new(Linktype::ETHERNET);
+        let packets = [
+            segment(100, TCP_SYN, b"", 0),
+            segment(101, TCP_ACK, b"one", 1),
+            segment(100, TCP_SYN, b"", 2),
+            segment(104, TCP_FIN | TCP_ACK, b"two", 3),
+        ];
+        assert_eq!(
+            events(&mut reassembler, &packets)[1..],
+            [
+                data(80, b"one"),
+                data(80, b"two"),
+                closed(80, CloseReason::Fin),
+            ]
+        );
+    }
+
+    #[test]
+    fn syn_of_a_flow_picked_up_mid_connection_reopens_it() {
+        let mut reassembler = TcpReassembler::new(Linktype::ETHERNET);
+        let packets = [
+            segment(5_000, TCP_ACK, b"mid", 0),
+            segment(5_002, TCP_SYN, b"", 1),
+        ];
+        assert_eq!(
+            events(&mut reassembler, &packets)[2..],
+            [
+                closed(80, CloseReason::Reused),
+                FlowEvent::Opened {
+                    key: key(80),
+                    syn: true
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn sequence_numbers_wrap_around() {
+        let mut reassembler = TcpReassembler::new(Linktype::ETHERNET);
+        let packets = [
+            segment(u32::MAX - 2, TCP_SYN, b"", 0),
+            segment(u32::MAX - 1, TCP_ACK, b"ab", 1),
+            segment(0, TCP_ACK, b"cd", 2),
+        ];
+        assert_eq!(payload(&mut reassembler, &packets), b"abcd");
+    }
+}
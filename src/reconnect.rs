@@ -0,0 +1,89 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use pcap::{Active, Capture};
+
+use crate::error::Error;
+
+/// Reopening of the device when a live capture loses it, e.g. when a USB
+/// adapter is unplugged or a VPN tunnel is torn down.
+///
+/// With a policy set on
+/// [`AsyncCaptureBuilder::reconnect()`](crate::AsyncCaptureBuilder::reconnect),
+/// a read error of kind [`ErrorKind::DeviceGone`](crate::ErrorKind::DeviceGone),
+/// or one the [`RetryPolicy`](crate::RetryPolicy) gave up on, no longer ends
+/// the capture:
+///
+/// - [`StreamItem::Disconnected`](crate::StreamItem::Disconnected) is queued
+///   with the error, in place of the error itself.
+/// - The capture thread reopens the device with the builder's settings,
+///   re-applying the filter and datalink, first after `initial_backoff`
+///   and then with the delay doubling up to `max_backoff`.
+/// - Once the device is open again,
+///   [`StreamItem::Reconnected`](crate::StreamItem::Reconnected) is queued
+///   and packets flow as before.
+/// - After `max_attempts` failed attempts, if set, the last error is
+///   delivered and ends the capture.
+///
+/// A stop request ends the capture while it waits to reconnect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// Delay before the first attempt to reopen the device
+    pub initial_backoff: Duration,
+    /// Longest delay between two attempts
+    pub max_backoff: Duration,
+    /// Attempts before giving up, or `None` to keep trying until the
+    /// capture is stopped
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            max_attempts: None,
+        }
+    }
+}
+
+/// Reopens the device with the settings of the builder it came from.
+pub(crate) type Reopen = dyn Fn() -> Result<Capture<Active>, Error> + Send + Sync;
+
+/// Reconnection as configured on the capture thread.
+#[derive(Clone)]
+pub(crate) struct Reconnect {
+    pub(crate) policy: ReconnectPolicy,
+    pub(crate) reopen: Arc<Reopen>,
+}
+
+impl fmt::Debug for Reconnect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reconnect")
+            .field("policy", &self.policy)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Change of the device's or the capture's state, queued in order with
+/// the packets.
+#[derive(Debug)]
+pub(crate) enum LinkEvent {
+    Disconnected(Error),
+    Reconnected,
+    /// The variants below are only queued once lifecycle events are wanted
+    Paused,
+    Resumed,
+    FilterChanged(String),
+    KernelDrops(u64),
+}
+
+/// How an attempt to reconnect ended.
+pub(crate) enum Reconnection {
+    Reconnected,
+    Stopped,
+    ReceiverDropped,
+    /// The policy ran out of attempts; the last error is attached
+    GaveUp(Error),
+}
@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use pcap::{Linktype, Precision};
+
+use crate::async_pcap::Packet;
+use crate::dumper;
+use crate::info::CaptureInfo;
+
+/// Snaplen recorded when none is known.
+const DEFAULT_SNAPLEN: u32 = 65535;
+
+/// In-memory flight recorder that keeps the most recent packets of a
+/// capture, to be written out when something worth investigating happens.
+///
+/// Packets are added with [`record()`](Self::record), or by the capture
+/// thread once the recorder is passed to
+/// [`AsyncCaptureBuilder::record_to()`](crate::AsyncCaptureBuilder::record_to).
+/// The oldest packets are evicted once they are older than the window
+/// relative to the newest one, or once the captured bytes held exceed the
+/// byte limit; both limits may be set. [`dump_to()`](Self::dump_to)
+/// writes what is held to a pcap file and [`drain()`](Self::drain) takes
+/// it out.
+///
+/// # Notes
+///
+/// - Clones share the same packets, so one clone can be given to the
+///   capture and another kept to dump on demand.
+/// - The window is measured on the packet timestamps, not the wall clock,
+///   so a quiet link keeps its last packets however old they are.
+/// - Recording copies each packet on the capture thread; the limits bound
+///   the memory held, which is about the captured bytes plus a small
+///   constant per packet.
+#[derive(Clone)]
+pub struct RingRecorder {
+    ring: Arc<Mutex<Ring>>,
+}
+
+/// Packets held by a [`RingRecorder`], oldest first.
+#[derive(Debug)]
+struct Ring {
+    packets: VecDeque<Packet>,
+    /// Captured bytes of the packets held
+    bytes: u64,
+    window: Option<Duration>,
+    max_bytes: Option<u64>,
+    linktype: Linktype,
+    snaplen: u32,
+    precision: Precision,
+}
+
+impl RingRecorder {
+    /// Keeps the packets captured within `window` of the newest one.
+    pub fn for_duration(window: Duration) -> Self {
+        Self::with_limits(Some(window), None)
+    }
+
+    /// Keeps the most recent packets whose captured bytes add up to at
+    /// most `max_bytes`.
+    pub fn for_bytes(max_bytes: u64) -> Self {
+        Self::with_limits(None, Some(max_bytes))
+    }
+
+    fn with_limits(window: Option<Duration>, max_bytes: Option<u64>) -> Self {
+        Self {
+            ring: Arc::new(Mutex::new(Ring {
+                packets: VecDeque::new(),
+                bytes: 0,
+                window,
+                max_bytes,
+                linktype: Linktype::ETHERNET,
+                snaplen: DEFAULT_SNAPLEN,
+                precision: Precision::Micro,
+            })),
+        }
+    }
+
+    /// Also evicts packets older than `window` relative to the newest one.
+    pub fn window(self, window: Duration) -> Self {
+        self.lock().window = Some(window);
+        self
+    }
+
+    /// Also caps the captured bytes held at `max_bytes`.
+    pub fn max_bytes(self, max_bytes: u64) -> Self {
+        self.lock().max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Takes the link-layer type, snaplen and timestamp precision written
+    /// by [`dump_to()`](Self::dump_to) from `info`; Ethernet, 65535 and
+    /// microseconds by default.
+    ///
+    /// [`AsyncCaptureBuilder::record_to()`](crate::AsyncCaptureBuilder::record_to)
+    /// sets them from the opened capture.
+    pub fn capture_info(self, info: &CaptureInfo) -> Self {
+        self.set_info(info);
+        self
+    }
+
+    pub(crate) fn set_info(&self, info: &CaptureInfo) {
+        let mut ring = self.lock();
+        ring.linktype = info.datalink;
+        ring.snaplen = u32::try_from(info.snaplen).unwrap_or(DEFAULT_SNAPLEN);
+        ring.precision = info.precision;
+    }
+
+    /// Adds a copy of `packet`, evicting the packets that fall outside the
+    /// limits.
+    pub fn record(&self, packet: &Packet) {
+        let mut ring = self.lock();
+        ring.bytes += packet.data.len() as u64;
+        ring.packets.push_back(packet.clone());
+        ring.evict();
+    }
+
+    /// Returns a copy of the packets held, oldest first.
+    pub fn snapshot(&self) -> Vec<Packet> {
+        self.lock().packets.iter().cloned().collect()
+    }
+
+    /// Takes the packets held, oldest first, leaving the recorder empty.
+    pub fn drain(&self) -> Vec<Packet> {
+        let mut ring = self.lock();
+        ring.bytes = 0;
+        ring.packets.drain(..).collect()
+    }
+
+    /// Writes the packets held to a new pcap file at `path`, oldest first,
+    /// and returns how many were written.
+    ///
+    /// The packets stay in the recorder. They are copied out first, so
+    /// recording goes on while the file is written.
+    ///
+    /// # Notes
+    ///
+    /// - The file is written on the calling thread; in asynchronous code,
+    ///   call it through `spawn_blocking` or a thread of its own.
+    /// - An existing file at `path` is truncated.
+    pub fn dump_to<P: AsRef<Path>>(&self, path: P) -> io::Result<usize> {
+        let (packets, linktype, snaplen, precision) = {
+            let ring = self.lock();
+            (
+                ring.packets.iter().cloned().collect::<Vec<_>>(),
+                ring.linktype,
+                ring.snaplen,
+                ring.precision,
+            )
+        };
+        dumper::write_savefile(path.as_ref(), linktype, snaplen, precision, &packets)?;
+        Ok(packets.len())
+    }
+
+    /// Number of packets held.
+    pub fn len(&self) -> usize {
+        self.lock().packets.len()
+    }
+
+    /// Whether no packet is held.
+    pub fn is_empty(&self) -> bool {
+        self.lock().packets.is_empty()
+    }
+
+    /// Captured bytes of the packets held.
+    pub fn bytes(&self) -> u64 {
+        self.lock().bytes
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Ring> {
+        self.ring.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Ring {
+    fn evict(&mut self) {
+        let newest = match self.packets.back() {
+            Some(packet) => packet.since_epoch_with(self.precision),
+            None => return,
+        };
+        while let Some(oldest) = self.packets.front() {
+            let too_old = self.window.is_some_and(|window| {
+                newest.saturating_sub(oldest.since_epoch_with(self.precision)) > window
+            });
+            let too_big = self.max_bytes.is_some_and(|max| self.bytes > max);
+            if !too_old && !too_big {
+                break;
+            }
+            self.bytes -= oldest.data.len() as u64;
+            self.packets.pop_front();
+        }
+    }
+}
+
+impl fmt::Debug for RingRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ring = self.lock();
+        f.debug_struct("RingRecorder")
+            .field("packets", &ring.packets.len())
+            .field("bytes", &ring.bytes)
+            .field("window", &ring.window)
+            .field("max_bytes", &ring.max_bytes)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,166 @@
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::fmt;
+use std::ptr::{self, NonNull};
+
+use pcap::{Active, Capture};
+
+use crate::async_pcap::AsyncCapture;
+use crate::builder::AsyncCaptureBuilder;
+use crate::error::Error;
+
+unsafe extern "C" {
+    fn pcap_open(
+        source: *const c_char,
+        snaplen: c_int,
+        flags: c_int,
+        read_timeout: c_int,
+        auth: *mut RmtAuth,
+        errbuf: *mut c_char,
+    ) -> *mut c_void;
+}
+
+/// `PCAP_ERRBUF_SIZE`
+const ERRBUF_SIZE: usize = 256;
+/// `PCAP_OPENFLAG_PROMISCUOUS`
+const OPENFLAG_PROMISCUOUS: c_int = 1;
+/// `RPCAP_RMTAUTH_NULL`
+const RMTAUTH_NULL: c_int = 0;
+/// `RPCAP_RMTAUTH_PWD`
+const RMTAUTH_PWD: c_int = 1;
+
+/// `struct pcap_rmtauth`
+#[repr(C)]
+struct RmtAuth {
+    kind: c_int,
+    username: *mut c_char,
+    password: *mut c_char,
+}
+
+/// How to authenticate to an `rpcapd` server.
+#[derive(Clone, PartialEq, Eq)]
+pub enum RemoteAuth {
+    /// No authentication, for servers started with `rpcapd -n`
+    Null,
+    /// A user account of the remote host
+    Password {
+        /// User name
+        username: String,
+        /// Password of the user
+        password: String,
+    },
+}
+
+impl fmt::Debug for RemoteAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoteAuth::Null => f.write_str("Null"),
+            RemoteAuth::Password { username, .. } => f
+                .debug_struct("Password")
+                .field("username", username)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// Settings of a remote capture, applied by `pcap_open`.
+#[derive(Debug, Clone)]
+pub(crate) struct RemoteSource {
+    pub(crate) url: String,
+    pub(crate) auth: RemoteAuth,
+}
+
+impl RemoteSource {
+    /// Opens the remote interface with the given settings.
+    pub(crate) fn open(
+        &self,
+        snaplen: i32,
+        promisc: bool,
+        timeout_ms: i32,
+    ) -> Result<Capture<Active>, Error> {
+        let setup = |e: std::ffi::NulError| Error::Setup(format!("invalid remote capture: {e}"));
+        let url = CString::new(self.url.as_str()).map_err(setup)?;
+        // Kept alive until `pcap_open` returns
+        let credentials = match &self.auth {
+            RemoteAuth::Null => None,
+            RemoteAuth::Password { username, password } => Some((
+                CString::new(username.as_str()).map_err(setup)?,
+                CString::new(password.as_str()).map_err(setup)?,
+            )),
+        };
+        let mut auth = match &credentials {
+            None => RmtAuth {
+                kind: RMTAUTH_NULL,
+                username: ptr::null_mut(),
+                password: ptr::null_mut(),
+            },
+            Some((username, password)) => RmtAuth {
+                kind: RMTAUTH_PWD,
+                username: username.as_ptr().cast_mut(),
+                password: password.as_ptr().cast_mut(),
+            },
+        };
+        let flags = if promisc { OPENFLAG_PROMISCUOUS } else { 0 };
+        let mut errbuf = [0 as c_char; ERRBUF_SIZE];
+        // SAFETY: the strings outlive the call, libpcap only reads the
+        // credentials, and the error buffer has the size libpcap expects.
+        let handle = unsafe {
+            pcap_open(
+                url.as_ptr(),
+                snaplen,
+                flags,
+                timeout_ms,
+                &mut auth,
+                errbuf.as_mut_ptr(),
+            )
+        };
+        let Some(handle) = NonNull::new(handle) else {
+            // SAFETY: libpcap leaves a NUL-terminated message in the buffer
+            let message = unsafe { CStr::from_ptr(errbuf.as_ptr()) };
+            return Err(pcap::Error::PcapError(message.to_string_lossy().into_owned()).into());
+        };
+        // The capture takes ownership of the handle and closes it on drop
+        Ok(Capture::from(handle.cast()))
+    }
+}
+
+impl AsyncCaptureBuilder {
+    /// Creates a builder for the interface `iface` of a host running
+    /// `rpcapd`, captured through libpcap's remote capture protocol.
+    ///
+    /// `host` may carry a port, e.g. `appliance:2002`; the default is 2002.
+    /// The capture is opened with `pcap_open` and the URL
+    /// `rpcap://host/iface`, which is also the device name of the capture.
+    /// Requires the `remote` feature and a libpcap built with remote
+    /// capture support.
+    ///
+    /// # Notes
+    ///
+    /// - Only the promiscuous mode, snaplen, read timeout and the settings
+    ///   applied after opening, such as the filter and datalink, reach the
+    ///   remote interface. The kernel buffer size, immediate mode, monitor
+    ///   mode, adapter timestamps and timestamp precision are ignored.
+    /// - The packets travel over the network, so exclude the `rpcapd`
+    ///   connection from the filter when capturing on the interface that
+    ///   carries it.
+    /// - The credentials are sent in clear text unless `rpcapd` is set up
+    ///   for TLS.
+    /// - [`config()`](Self::config) records the URL as the device, which
+    ///   [`CaptureConfig::builder()`](crate::CaptureConfig::builder)
+    ///   cannot open.
+    pub fn remote(host: &str, iface: &str, auth: RemoteAuth) -> Self {
+        let url = format!("rpcap://{host}/{iface}");
+        let mut builder = Self::new(url.as_str());
+        builder.remote = Some(RemoteSource { url, auth });
+        builder
+    }
+}
+
+impl AsyncCapture {
+    /// Returns an [`AsyncCaptureBuilder`] for the interface `iface` of a
+    /// remote host running `rpcapd`.
+    ///
+    /// See [`AsyncCaptureBuilder::remote()`]. Requires the `remote` feature.
+    pub fn remote(host: &str, iface: &str, auth: RemoteAuth) -> AsyncCaptureBuilder {
+        AsyncCaptureBuilder::remote(host, iface, auth)
+    }
+}
@@ -0,0 +1,138 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use pcap::{Active, Capture, PacketHeader, Precision, Stat};
+
+use crate::async_pcap::{self, Packet};
+use crate::error::Error;
+use crate::info::CaptureInfo;
+use crate::source::PacketSource;
+
+/// A [`PacketSource`] that delivers the packets of another source with
+/// their recorded inter-packet gaps, scaled by a speed factor.
+///
+/// Created by [`PacketSource::replay_speed()`]. The gap between two
+/// packets is taken from their header timestamps and divided by the
+/// speed, so 2.0 replays twice as fast and 0.5 half as fast; the capture
+/// thread sleeps for it before returning the later packet. A speed of
+/// `f64::INFINITY`, zero, or any value that is not positive replays as
+/// fast as possible.
+///
+/// # Notes
+///
+/// - The first packet is returned without delay.
+/// - Packets are paced against the replay start rather than one after the
+///   other, so time spent by the consumer does not accumulate as drift.
+/// - A timestamp earlier than the one before it, e.g. after a clock jump
+///   in the recording, is replayed without delay and becomes the new
+///   reference.
+/// - The sleep does not watch the stop flag, so a stop request waits for
+///   the gap in progress; use [`max_gap()`](Self::max_gap) to bound it.
+pub struct Paced<S> {
+    source: S,
+    /// `None` when not pacing at all
+    speed: Option<f64>,
+    max_gap: Option<Duration>,
+    /// Precision of the source's timestamps
+    precision: Precision,
+    /// Timestamp of the previous packet and when it was due
+    last: Option<(Duration, Instant)>,
+}
+
+impl<S: PacketSource> Paced<S> {
+    pub(crate) fn new(source: S, speed: f64) -> Self {
+        let precision = source.info().precision;
+        Self {
+            source,
+            speed: (speed > 0.0 && speed.is_finite()).then_some(speed),
+            max_gap: None,
+            precision,
+            last: None,
+        }
+    }
+}
+
+impl<S> Paced<S> {
+    /// Caps the scaled gap between two packets, compressing long idle
+    /// periods of the recording.
+    pub fn max_gap(mut self, gap: Duration) -> Self {
+        self.max_gap = Some(gap);
+        self
+    }
+
+    /// Returns the wrapped source.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+
+    /// Sleeps until the packet stamped `ts` is due.
+    fn pace(&mut self, ts: Duration) {
+        let Some(speed) = self.speed else {
+            return;
+        };
+        let now = Instant::now();
+        let due = match self.last {
+            Some((last_ts, last_due)) if ts >= last_ts => {
+                let mut gap = (ts - last_ts).div_f64(speed);
+                if let Some(max) = self.max_gap {
+                    gap = gap.min(max);
+                }
+                last_due.checked_add(gap).unwrap_or(now)
+            }
+            // The first packet, or the clock went backwards
+            _ => now,
+        };
+        if let Some(wait) = due.checked_duration_since(now) {
+            std::thread::sleep(wait);
+        }
+        self.last = Some((ts, due));
+    }
+}
+
+impl<S: PacketSource> PacketSource for Paced<S> {
+    fn next_packet(&mut self) -> Option<Result<Packet, Error>> {
+        let res = self.source.next_packet();
+        if let Some(Ok(packet)) = &res {
+            self.pace(packet.since_epoch_with(self.precision));
+        }
+        res
+    }
+
+    fn next_packet_into(&mut self, buf: Vec<u8>) -> Option<Result<Packet, Error>> {
+        let res = self.source.next_packet_into(buf);
+        if let Some(Ok(packet)) = &res {
+            self.pace(packet.since_epoch_with(self.precision));
+        }
+        res
+    }
+
+    fn next_packet_if(
+        &mut self,
+        buf: Vec<u8>,
+        accept: &dyn Fn(&PacketHeader, &[u8]) -> bool,
+    ) -> Option<Result<Option<Packet>, Error>> {
+        // Skipped packets still take up their slot in the timeline
+        let seen = Cell::new(None);
+        let precision = self.precision;
+        let res = self.source.next_packet_if(buf, &|header, data| {
+            seen.set(Some(async_pcap::header_since_epoch(header, precision)));
+            accept(header, data)
+        });
+        if let (Some(Ok(_)), Some(ts)) = (&res, seen.get()) {
+            self.pace(ts);
+        }
+        res
+    }
+
+    fn info(&self) -> CaptureInfo {
+        self.source.info()
+    }
+
+    fn stats(&mut self) -> Option<Stat> {
+        self.source.stats()
+    }
+
+    fn as_active(&mut self) -> Option<&mut Capture<Active>> {
+        self.source.as_active()
+    }
+}
@@ -0,0 +1,306 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use pcap::{Active, Capture, Device, Offline, Precision};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+use crate::error::Error;
+use crate::logging::{debug, warn};
+use crate::sender::open_send_only;
+
+/// Packets sent between two [`ReplayEvent::Progress`] events by default.
+const DEFAULT_PROGRESS_EVERY: u64 = 1000;
+
+/// Settings of a replay, which sends the packets of a capture file on a
+/// device with their recorded timing.
+///
+/// The gap between two packets is taken from their timestamps and divided
+/// by the [`speed()`](Self::speed), as for [`Paced`](crate::Paced), and the
+/// file is sent [`loops()`](Self::loops) times in a row. [`start()`](Self::start)
+/// runs the replay on a thread of its own and returns a [`Replay`] that
+/// reports its progress.
+///
+/// # Notes
+///
+/// - Packets are sent as recorded, with `pcap_sendpacket`; nothing is
+///   rewritten, so addresses and checksums are those of the recording.
+/// - The file's link-layer type should match the device's; a mismatch is
+///   logged and the packets are sent anyway.
+/// - Sending usually requires the same privileges as capturing.
+#[derive(Debug, Clone)]
+pub struct Replayer {
+    path: PathBuf,
+    device: Device,
+    speed: f64,
+    loops: u32,
+    max_gap: Option<Duration>,
+    progress_every: u64,
+}
+
+impl Replayer {
+    /// Prepares to send the packets of the capture file at `path` on
+    /// `device`, once and at the recorded speed.
+    pub fn new<P: Into<PathBuf>, D: Into<Device>>(path: P, device: D) -> Self {
+        Self {
+            path: path.into(),
+            device: device.into(),
+            speed: 1.0,
+            loops: 1,
+            max_gap: None,
+            progress_every: DEFAULT_PROGRESS_EVERY,
+        }
+    }
+
+    /// Divides the recorded gaps by `speed`, so 2.0 replays twice as fast
+    /// and 0.5 half as fast; 1.0 by default. `f64::INFINITY`, zero, or any
+    /// value that is not positive sends as fast as possible.
+    pub fn speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Sends the file `count` times in a row; once by default. A count of
+    /// 0 repeats it until the replay is stopped.
+    ///
+    /// Each pass starts right after the previous one, without a gap.
+    pub fn loops(mut self, count: u32) -> Self {
+        self.loops = count;
+        self
+    }
+
+    /// Caps the scaled gap between two packets, compressing long idle
+    /// periods of the recording.
+    pub fn max_gap(mut self, gap: Duration) -> Self {
+        self.max_gap = Some(gap);
+        self
+    }
+
+    /// Reports a [`ReplayEvent::Progress`] every `packets` packets sent;
+    /// every 1000 by default.
+    pub fn progress_every(mut self, packets: u64) -> Self {
+        self.progress_every = packets.max(1);
+        self
+    }
+
+    /// Opens the file and the device and starts sending.
+    ///
+    /// Fails if either cannot be opened; later errors are reported as
+    /// events.
+    pub fn start(self) -> Result<Replay, Error> {
+        let file = self.open_file()?;
+        let cap = open_send_only(self.device.clone())?;
+        if file.get_datalink() != cap.get_datalink() {
+            warn!(
+                "Replayer sending {:?} packets from {} on {}, whose datalink is {:?}",
+                file.get_datalink(),
+                self.path.display(),
+                self.device.name,
+                cap.get_datalink()
+            );
+        }
+        let (tx, events) = unbounded_channel();
+        let (stop, stopped) = mpsc::channel();
+        let job = Job {
+            config: self,
+            file,
+            cap,
+            events: tx,
+            stopped,
+        };
+        std::thread::spawn(move || job.run());
+        Ok(Replay { events, stop })
+    }
+
+    /// Opens the file with nanosecond timestamps, so that gaps are replayed
+    /// at the precision they were recorded with.
+    fn open_file(&self) -> Result<Capture<Offline>, Error> {
+        Ok(Capture::from_file_with_precision(
+            &self.path,
+            Precision::Nano,
+        )?)
+    }
+}
+
+/// A running replay, started by [`Replayer::start()`].
+///
+/// Dropping it stops the replay.
+pub struct Replay {
+    events: UnboundedReceiver<ReplayEvent>,
+    stop: mpsc::Sender<()>,
+}
+
+impl Replay {
+    /// Waits for the next event of the replay.
+    ///
+    /// The last event is [`Finished`](ReplayEvent::Finished),
+    /// [`Stopped`](ReplayEvent::Stopped) or [`Failed`](ReplayEvent::Failed),
+    /// after which `None` is returned. No Tokio runtime is needed.
+    pub async fn next_event(&mut self) -> Option<ReplayEvent> {
+        self.events.recv().await
+    }
+
+    /// Asks the replay to stop. A gap in progress is cut short.
+    pub fn stop(&self) {
+        let _ = self.stop.send(());
+    }
+}
+
+impl fmt::Debug for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Replay")
+            .field("pending_events", &self.events.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// What a replay has done so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayProgress {
+    /// Pass over the file in progress, counting from 0
+    pub pass: u32,
+    /// Packets sent, over all passes
+    pub packets: u64,
+    /// Bytes sent, over all passes
+    pub bytes: u64,
+    /// Packets that could not be sent
+    pub send_failures: u64,
+    /// Time since the replay started
+    pub elapsed: Duration,
+}
+
+/// Event of a [`Replay`].
+#[derive(Debug)]
+pub enum ReplayEvent {
+    /// A pass over the file started; passes count from 0
+    PassStarted(u32),
+    /// Reported every [`progress_every`](Replayer::progress_every) packets
+    Progress(ReplayProgress),
+    /// A packet could not be sent, e.g. because it is larger than the
+    /// device's MTU; the replay goes on
+    SendFailed {
+        /// Position of the packet in the file, counting from 0
+        index: u64,
+        /// The pcap error
+        error: Error,
+    },
+    /// Every pass was sent
+    Finished(ReplayProgress),
+    /// The replay was stopped, or dropped, before the end
+    Stopped(ReplayProgress),
+    /// The file could not be read any further
+    Failed(Error),
+}
+
+/// How a pass over the file ended.
+enum PassEnd {
+    Complete,
+    Stopped,
+}
+
+/// State of the replay thread.
+struct Job {
+    config: Replayer,
+    file: Capture<Offline>,
+    cap: Capture<Active>,
+    events: UnboundedSender<ReplayEvent>,
+    stopped: mpsc::Receiver<()>,
+}
+
+impl Job {
+    fn run(mut self) {
+        let mut progress = ReplayProgress {
+            pass: 0,
+            packets: 0,
+            bytes: 0,
+            send_failures: 0,
+            elapsed: Duration::ZERO,
+        };
+        let started = Instant::now();
+        let last = loop {
+            let _ = self.events.send(ReplayEvent::PassStarted(progress.pass));
+            let end = self.pass(&mut progress, started);
+            progress.elapsed = started.elapsed();
+            match end {
+                Ok(PassEnd::Complete) => {}
+                Ok(PassEnd::Stopped) => break ReplayEvent::Stopped(progress),
+                Err(e) => break ReplayEvent::Failed(e),
+            }
+            if self.config.loops != 0 && progress.pass + 1 >= self.config.loops {
+                break ReplayEvent::Finished(progress);
+            }
+            progress.pass += 1;
+            match self.config.open_file() {
+                Ok(file) => self.file = file,
+                Err(e) => break ReplayEvent::Failed(e),
+            }
+        };
+        debug!(
+            "Replayer done with {}: {last:?}",
+            self.config.path.display()
+        );
+        let _ = self.events.send(last);
+    }
+
+    /// Sends the packets of the open file with their recorded gaps.
+    fn pass(&mut self, progress: &mut ReplayProgress, started: Instant) -> Result<PassEnd, Error> {
+        let speed =
+            (self.config.speed > 0.0 && self.config.speed.is_finite()).then_some(self.config.speed);
+        // Timestamp of the previous packet and when it was due
+        let mut last: Option<(Duration, Instant)> = None;
+        let mut index = 0;
+        loop {
+            let packet = match self.file.next_packet() {
+                Ok(packet) => packet,
+                Err(pcap::Error::NoMorePackets) => return Ok(PassEnd::Complete),
+                Err(e) => return Err(e.into()),
+            };
+            let ts = Duration::new(
+                packet.header.ts.tv_sec as u64,
+                packet.header.ts.tv_usec as u32,
+            );
+            let now = Instant::now();
+            let due = match (speed, last) {
+                (Some(speed), Some((last_ts, last_due))) if ts >= last_ts => {
+                    let mut gap = (ts - last_ts).div_f64(speed);
+                    if let Some(max) = self.config.max_gap {
+                        gap = gap.min(max);
+                    }
+                    last_due.checked_add(gap).unwrap_or(now)
+                }
+                // The first packet, as fast as possible, or the clock went
+                // backwards in the recording
+                _ => now,
+            };
+            // Waiting on the stop channel doubles as the sleep
+            match self
+                .stopped
+                .recv_timeout(due.saturating_duration_since(now))
+            {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => return Ok(PassEnd::Stopped),
+            }
+            last = Some((ts, due));
+
+            match self.cap.sendpacket(packet.data) {
+                Ok(()) => {
+                    progress.packets += 1;
+                    progress.bytes += packet.data.len() as u64;
+                    if progress.packets.is_multiple_of(self.config.progress_every) {
+                        progress.elapsed = started.elapsed();
+                        let _ = self.events.send(ReplayEvent::Progress(*progress));
+                    }
+                }
+                Err(error) => {
+                    progress.send_failures += 1;
+                    let _ = self.events.send(ReplayEvent::SendFailed {
+                        index,
+                        error: error.into(),
+                    });
+                }
+            }
+            index += 1;
+        }
+    }
+}
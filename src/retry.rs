@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+/// In-loop retry of failed reads, for long-running captures that should
+/// ride out transient errors.
+///
+/// With a policy set on
+/// [`AsyncCaptureBuilder::retry_policy()`](crate::AsyncCaptureBuilder::retry_policy),
+/// the capture thread classifies every read error with
+/// [`Error::is_transient()`](crate::Error::is_transient):
+///
+/// - Read timeouts are expected on an idle live capture; they are dropped
+///   and never count as a retry.
+/// - Other transient errors are retried after `backoff`, up to
+///   `max_retries` consecutive times. Each retry is logged and counted by
+///   [`AsyncCaptureHandle::read_retries()`](crate::AsyncCaptureHandle::read_retries).
+///   A successful read resets the count.
+/// - A fatal error, or a transient one once the retries are exhausted, is
+///   delivered to the consumer and ends the capture.
+///
+/// The device is not reopened; the same handle is read again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Consecutive transient errors retried before giving up
+    pub max_retries: u32,
+    /// Delay before each retry
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            backoff: Duration::from_millis(10),
+        }
+    }
+}
@@ -0,0 +1,453 @@
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use memmap2::MmapMut;
+use pcap::{Linktype, Precision};
+
+use crate::async_pcap::Packet;
+
+/// Identifies a ring file, at offset 0.
+pub const RING_FILE_MAGIC: [u8; 8] = *b"APCAPRNG";
+/// Layout version written at offset 8.
+pub const RING_FILE_VERSION: u32 = 1;
+/// Size of the file header; the data region starts right after it.
+pub const RING_FILE_HEADER_LEN: usize = 64;
+/// Size of the header preceding each record's packet bytes.
+pub const RING_RECORD_HEADER_LEN: usize = 32;
+/// Set in the header flags once the writer has finalized the file.
+pub const RING_FLAG_FINALIZED: u32 = 1;
+/// Set in the header flags when the record timestamps are in nanoseconds.
+pub const RING_FLAG_NANO: u32 = 2;
+
+/// How often dirty pages are flushed when none is configured.
+const DEFAULT_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+// Header field offsets
+const OFF_VERSION: usize = 8;
+const OFF_LINKTYPE: usize = 12;
+const OFF_SNAPLEN: usize = 16;
+const OFF_FLAGS: usize = 20;
+const OFF_CAPACITY: usize = 24;
+const OFF_HEAD: usize = 32;
+const OFF_TAIL: usize = 40;
+const OFF_RECORDS: usize = 48;
+const OFF_WRAPS: usize = 56;
+
+/// Settings for recording a capture into a [`RingFile`].
+///
+/// Passed to
+/// [`AsyncCaptureBuilder::ring_file()`](crate::AsyncCaptureBuilder::ring_file).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RingFileConfig {
+    /// Path of the file, created or truncated on open
+    pub path: PathBuf,
+    /// Size of the data region in bytes; the oldest records are overwritten
+    /// once it is full
+    pub capacity: u64,
+    /// How often dirty pages are flushed to disk with an asynchronous
+    /// `msync`
+    pub sync_interval: Duration,
+}
+
+impl RingFileConfig {
+    /// Creates a configuration with a one second sync interval.
+    pub fn new<P: Into<PathBuf>>(path: P, capacity: u64) -> Self {
+        Self {
+            path: path.into(),
+            capacity,
+            sync_interval: DEFAULT_SYNC_INTERVAL,
+        }
+    }
+}
+
+/// Packet recorder that writes into a memory-mapped ring file.
+///
+/// Records are copied straight into a shared file mapping, so writing a
+/// packet costs no system call; dirty pages are flushed with an
+/// asynchronous `msync` every [`RingFileConfig::sync_interval`] and
+/// synchronously by [`finalize`](Self::finalize). When the data region is
+/// full the oldest records are overwritten.
+///
+/// # File layout
+///
+/// All integers are little-endian. The file is a 64-byte header followed
+/// by the data region of `capacity` bytes:
+///
+/// | Offset | Size | Field                                             |
+/// |--------|------|---------------------------------------------------|
+/// | 0      | 8    | magic, `APCAPRNG`                                 |
+/// | 8      | 4    | layout version, currently 1                       |
+/// | 12     | 4    | link-layer header type                            |
+/// | 16     | 4    | snaplen                                           |
+/// | 20     | 4    | flags, see below                                  |
+/// | 24     | 8    | capacity of the data region                       |
+/// | 32     | 8    | head: offset of the oldest record                 |
+/// | 40     | 8    | tail: offset at which the next record is written  |
+/// | 48     | 8    | number of records in the ring                     |
+/// | 56     | 8    | number of times the writer wrapped around         |
+///
+/// Bit 0 of the flags ([`RING_FLAG_FINALIZED`]) is set once the file is
+/// finalized, and bit 1 ([`RING_FLAG_NANO`]) when the packets were captured
+/// with [`Precision::Nano`]. Offsets of head and tail are relative to the
+/// start of the data region.
+/// Each record starts on an 8-byte boundary with a 32-byte header:
+///
+/// | Offset | Size | Field                                             |
+/// |--------|------|---------------------------------------------------|
+/// | 0      | 4    | record length, header and padding included        |
+/// | 4      | 4    | captured length                                   |
+/// | 8      | 4    | original length                                   |
+/// | 12     | 4    | reserved, zero                                    |
+/// | 16     | 8    | timestamp seconds                                 |
+/// | 24     | 4    | timestamp fraction, in micro- or nanoseconds      |
+/// | 28     | 4    | reserved, zero                                    |
+///
+/// followed by the captured bytes, zero-padded to a multiple of 8. To read
+/// the ring, start at head and follow the record lengths for the given
+/// number of records. When a record would not fit before the end of the
+/// data region the writer wraps to offset 0; a record length of 0, or
+/// fewer than 4 bytes left before the end, marks the wrap point.
+///
+/// The header is updated after each record is complete, but a reader that
+/// maps the file while it is being written can still observe a record
+/// being overwritten; only a finalized file is guaranteed consistent.
+pub struct RingFile {
+    map: MmapMut,
+    capacity: u64,
+    head: u64,
+    tail: u64,
+    records: u64,
+    wraps: u64,
+    flags: u32,
+    sync_interval: Duration,
+    last_sync: Instant,
+}
+
+impl RingFile {
+    /// Creates the ring file described by `config` for packets of the given
+    /// link type and snaplen, whose timestamps have `precision`.
+    pub fn create(
+        config: &RingFileConfig,
+        linktype: Linktype,
+        snaplen: u32,
+        precision: Precision,
+    ) -> io::Result<Self> {
+        Self::create_at(&config.path, config.capacity, linktype, snaplen, precision)
+            .map(|ring| ring.with_sync_interval(config.sync_interval))
+    }
+
+    fn create_at(
+        path: &Path,
+        capacity: u64,
+        linktype: Linktype,
+        snaplen: u32,
+        precision: Precision,
+    ) -> io::Result<Self> {
+        let capacity = capacity - capacity % 8;
+        if capacity < RING_RECORD_HEADER_LEN as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "ring file capacity is smaller than a record header",
+            ));
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(RING_FILE_HEADER_LEN as u64 + capacity)?;
+        // SAFETY: the file was just created by us; concurrent modification
+        // by other processes is outside of our control, as with any mapping.
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+        map[..8].copy_from_slice(&RING_FILE_MAGIC);
+        put_u32(&mut map, OFF_VERSION, RING_FILE_VERSION);
+        put_u32(&mut map, OFF_LINKTYPE, linktype.0 as u32);
+        put_u32(&mut map, OFF_SNAPLEN, snaplen);
+        put_u64(&mut map, OFF_CAPACITY, capacity);
+        let flags = match precision {
+            Precision::Micro => 0,
+            Precision::Nano => RING_FLAG_NANO,
+        };
+        put_u32(&mut map, OFF_FLAGS, flags);
+
+        let mut ring = Self {
+            map,
+            capacity,
+            head: 0,
+            tail: 0,
+            records: 0,
+            wraps: 0,
+            flags,
+            sync_interval: DEFAULT_SYNC_INTERVAL,
+            last_sync: Instant::now(),
+        };
+        ring.write_header();
+        Ok(ring)
+    }
+
+    fn with_sync_interval(mut self, interval: Duration) -> Self {
+        self.sync_interval = interval;
+        self
+    }
+
+    /// Number of records currently held by the ring.
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// Number of times the writer wrapped around to the start of the ring.
+    pub fn wraps(&self) -> u64 {
+        self.wraps
+    }
+
+    /// Appends a packet, overwriting the oldest records if needed.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if the record is larger
+    /// than the whole data region.
+    // `time_t` is not 64 bits on every platform
+    #[allow(clippy::unnecessary_cast)]
+    pub fn write_packet(&mut self, packet: &Packet) -> io::Result<()> {
+        let need = (RING_RECORD_HEADER_LEN + packet.data.len()).next_multiple_of(8) as u64;
+        if need > self.capacity {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "packet does not fit in the ring file",
+            ));
+        }
+
+        if self.tail + need > self.capacity {
+            // Mark the wrap point and drop the records past it
+            if self.capacity - self.tail >= 4 {
+                self.put_data_u32(self.tail, 0);
+            }
+            while self.records > 0 && self.head >= self.tail {
+                self.evict();
+            }
+            self.tail = 0;
+            self.wraps += 1;
+            if self.records == 0 {
+                self.head = 0;
+            }
+        }
+        while self.records > 0 && self.head >= self.tail && self.head < self.tail + need {
+            self.evict();
+        }
+        if self.records == 0 {
+            self.head = self.tail;
+        }
+
+        let start = RING_FILE_HEADER_LEN + self.tail as usize;
+        let record = &mut self.map[start..start + need as usize];
+        record[..4].copy_from_slice(&(need as u32).to_le_bytes());
+        record[4..8].copy_from_slice(&packet.header.caplen.to_le_bytes());
+        record[8..12].copy_from_slice(&packet.header.len.to_le_bytes());
+        record[12..16].fill(0);
+        record[16..24].copy_from_slice(&(packet.header.ts.tv_sec as i64).to_le_bytes());
+        record[24..28].copy_from_slice(&(packet.header.ts.tv_usec as u32).to_le_bytes());
+        record[28..32].fill(0);
+        let data_end = RING_RECORD_HEADER_LEN + packet.data.len();
+        record[RING_RECORD_HEADER_LEN..data_end].copy_from_slice(&packet.data);
+        record[data_end..].fill(0);
+
+        self.tail += need;
+        self.records += 1;
+        self.write_header();
+
+        if self.last_sync.elapsed() >= self.sync_interval {
+            self.last_sync = Instant::now();
+            self.map.flush_async()?;
+        }
+        Ok(())
+    }
+
+    /// Marks the file finalized and flushes it to disk.
+    pub fn finalize(mut self) -> io::Result<()> {
+        self.mark_finalized()
+    }
+
+    pub(crate) fn mark_finalized(&mut self) -> io::Result<()> {
+        self.flags |= RING_FLAG_FINALIZED;
+        put_u32(&mut self.map, OFF_FLAGS, self.flags);
+        self.map.flush()
+    }
+
+    /// Drops the oldest record.
+    fn evict(&mut self) {
+        let len = u64::from(self.data_u32(self.head));
+        self.head += len;
+        self.records -= 1;
+        if self.head + 4 > self.capacity || self.data_u32(self.head) == 0 {
+            self.head = 0;
+        }
+    }
+
+    fn write_header(&mut self) {
+        put_u64(&mut self.map, OFF_HEAD, self.head);
+        put_u64(&mut self.map, OFF_TAIL, self.tail);
+        put_u64(&mut self.map, OFF_RECORDS, self.records);
+        put_u64(&mut self.map, OFF_WRAPS, self.wraps);
+    }
+
+    fn data_u32(&self, offset: u64) -> u32 {
+        let at = RING_FILE_HEADER_LEN + offset as usize;
+        u32::from_le_bytes(self.map[at..at + 4].try_into().unwrap())
+    }
+
+    fn put_data_u32(&mut self, offset: u64, value: u32) {
+        put_u32(&mut self.map, RING_FILE_HEADER_LEN + offset as usize, value);
+    }
+}
+
+fn put_u32(buf: &mut [u8], at: usize, value: u32) {
+    buf[at..at + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buf: &mut [u8], at: usize, value: u64) {
+    buf[at..at + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+impl fmt::Debug for RingFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RingFile")
+            .field("capacity", &self.capacity)
+            .field("head", &self.head)
+            .field("tail", &self.tail)
+            .field("records", &self.records)
+            .field("wraps", &self.wraps)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+
+    /// Ring file of `capacity` bytes in a path unique to the test.
+    fn ring(name: &str, capacity: u64, precision: Precision) -> (PathBuf, RingFile) {
+        let path =
+            std::env::temp_dir().join(format!("async-pcap-ring-{name}-{}", std::process::id()));
+        let config = RingFileConfig::new(&path, capacity);
+        let ring = RingFile::create(&config, Linktype::ETHERNET, 65535, precision).unwrap();
+        (path, ring)
+    }
+
+    fn le32(file: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(file[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn nanosecond_timestamps_are_flagged() {
+        let (path, mut ring) = ring("nano", 1024, Precision::Nano);
+        let mut packet = Packet::new(vec![1; 4], UNIX_EPOCH + Duration::from_secs(1));
+        packet.header.ts.tv_usec = 123_456_789;
+        ring.write_packet(&packet).unwrap();
+        ring.finalize().unwrap();
+
+        let file = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(le32(&file, OFF_FLAGS), RING_FLAG_FINALIZED | RING_FLAG_NANO);
+        assert_eq!(le32(&file, RING_FILE_HEADER_LEN + 24), 123_456_789);
+    }
+
+    fn le64(file: &[u8], at: usize) -> u64 {
+        u64::from_le_bytes(file[at..at + 8].try_into().unwrap())
+    }
+
+    fn numbered(n: u8) -> Packet {
+        Packet::new(vec![n; 8], UNIX_EPOCH)
+    }
+
+    /// Reads the packet bytes of the records of a ring file, oldest first,
+    /// as described in the layout of [`RingFile`].
+    fn read_records(file: &[u8]) -> Vec<Vec<u8>> {
+        let data = &file[RING_FILE_HEADER_LEN..];
+        let capacity = le64(file, OFF_CAPACITY) as usize;
+        let mut at = le64(file, OFF_HEAD) as usize;
+        let mut records = Vec::new();
+        for _ in 0..le64(file, OFF_RECORDS) {
+            if at + 4 > capacity || le32(data, at) == 0 {
+                at = 0;
+            }
+            let len = le32(data, at) as usize;
+            let caplen = le32(data, at + 4) as usize;
+            let start = at + RING_RECORD_HEADER_LEN;
+            records.push(data[start..start + caplen].to_vec());
+            at += len;
+        }
+        records
+    }
+
+    #[test]
+    fn oldest_records_are_evicted_when_the_writer_wraps() {
+        // Records of 8 bytes take 40, so six fit and 16 bytes are left
+        let (path, mut ring) = ring("wrap", 256, Precision::Micro);
+        for n in 0..7 {
+            ring.write_packet(&numbered(n)).unwrap();
+        }
+        assert_eq!((ring.records(), ring.wraps()), (6, 1));
+        assert_eq!((ring.head, ring.tail), (40, 40));
+        // The wrap point is marked where the seventh record did not fit
+        assert_eq!(ring.data_u32(240), 0);
+
+        for n in 7..12 {
+            ring.write_packet(&numbered(n)).unwrap();
+        }
+        // Evicting the record before the marker moved head back to 0
+        assert_eq!((ring.records(), ring.wraps()), (6, 1));
+        assert_eq!((ring.head, ring.tail), (0, 240));
+        ring.finalize().unwrap();
+
+        let file = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((le64(&file, OFF_HEAD), le64(&file, OFF_TAIL)), (0, 240));
+        assert_eq!((le64(&file, OFF_RECORDS), le64(&file, OFF_WRAPS)), (6, 1));
+        let expected: Vec<_> = (6..12).map(|n| vec![n; 8]).collect();
+        assert_eq!(read_records(&file), expected);
+    }
+
+    #[test]
+    fn records_are_read_across_the_wrap_point() {
+        let (path, mut ring) = ring("across", 256, Precision::Micro);
+        for n in 0..9 {
+            ring.write_packet(&numbered(n)).unwrap();
+        }
+        ring.finalize().unwrap();
+
+        let file = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let expected: Vec<_> = (3..9).map(|n| vec![n; 8]).collect();
+        assert_eq!(read_records(&file), expected);
+    }
+
+    #[test]
+    fn packets_larger_than_the_ring_are_rejected() {
+        let (path, mut ring) = ring("large", 64, Precision::Micro);
+        let error = ring
+            .write_packet(&Packet::new(vec![0; 64], UNIX_EPOCH))
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(ring.records(), 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn finalizing_sets_the_flag() {
+        let (path, mut ring) = ring("finalized", 256, Precision::Micro);
+        ring.write_packet(&numbered(0)).unwrap();
+        let file = std::fs::read(&path).unwrap();
+        assert_eq!(le32(&file, OFF_FLAGS), 0);
+        ring.finalize().unwrap();
+
+        let file = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(&file[..8], RING_FILE_MAGIC);
+        assert_eq!(le32(&file, OFF_FLAGS), RING_FLAG_FINALIZED);
+        assert_eq!(read_records(&file), [vec![0; 8]]);
+    }
+}
@@ -0,0 +1,81 @@
+use std::cell::{Cell, RefCell};
+use std::time::Instant;
+
+use pcap::PacketHeader;
+
+use crate::clock::Clock;
+use crate::rate_limit::{RateLimit, TokenBucket};
+
+/// Which packets the capture thread keeps when sampling is configured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Sampling {
+    /// Keep one packet in every `n`
+    pub(crate) one_in: Option<u64>,
+    /// Keep at most this many packets per second
+    pub(crate) packets_per_sec: Option<u64>,
+    /// Keep at most this many captured bytes per second
+    pub(crate) bytes_per_sec: Option<u64>,
+}
+
+impl Sampling {
+    pub(crate) fn is_active(&self) -> bool {
+        self.one_in.is_some_and(|n| n > 1)
+            || self.packets_per_sec.is_some()
+            || self.bytes_per_sec.is_some()
+    }
+}
+
+/// Sampling state of the capture thread.
+///
+/// Unlike a [`RateLimit`](crate::RateLimit), which delays packets over the
+/// ceiling, the rate caps discard them, so the capture thread keeps up with
+/// the link.
+#[derive(Debug)]
+pub(crate) struct Sampler {
+    one_in: u64,
+    seen: Cell<u64>,
+    packets: Option<RefCell<TokenBucket>>,
+    bytes: Option<RefCell<TokenBucket>>,
+}
+
+impl Sampler {
+    pub(crate) fn new(sampling: Sampling, now: Instant) -> Self {
+        Self {
+            one_in: sampling.one_in.unwrap_or(1).max(1),
+            seen: Cell::new(0),
+            packets: sampling
+                .packets_per_sec
+                .map(|rate| RefCell::new(TokenBucket::new(RateLimit::Packets(rate), now))),
+            bytes: sampling
+                .bytes_per_sec
+                .map(|rate| RefCell::new(TokenBucket::new(RateLimit::Bytes(rate), now))),
+        }
+    }
+
+    /// Whether the packet with `header` is kept. The time is only read
+    /// from `clock` when a rate cap needs it.
+    pub(crate) fn keeps(&self, header: &PacketHeader, clock: &dyn Clock) -> bool {
+        let seen = self.seen.get();
+        self.seen.set(seen.wrapping_add(1));
+        if !seen.is_multiple_of(self.one_in) {
+            return false;
+        }
+        if self.packets.is_none() && self.bytes.is_none() {
+            return true;
+        }
+        let now = clock.now();
+        // Both caps must have room before either is charged
+        let caps = [&self.packets, &self.bytes];
+        let fits = caps
+            .iter()
+            .flat_map(|cap| cap.as_ref())
+            .all(|bucket| bucket.borrow_mut().has_room(header, now));
+        if !fits {
+            return false;
+        }
+        for bucket in caps.iter().flat_map(|cap| cap.as_ref()) {
+            bucket.borrow_mut().charge(header);
+        }
+        true
+    }
+}
@@ -0,0 +1,62 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Daily time windows during which packets are forwarded.
+///
+/// Set with
+/// [`AsyncCaptureBuilder::schedule()`](crate::AsyncCaptureBuilder::schedule).
+/// Outside every window the capture thread keeps reading but drops the
+/// packets, so that the kernel buffer does not fill up with stale traffic
+/// that would be delivered when the next window opens. Dropped packets
+/// are counted by
+/// [`AsyncCaptureHandle::unscheduled_drops()`](crate::AsyncCaptureHandle::unscheduled_drops).
+///
+/// Times of day are given as durations since midnight and evaluated
+/// against the system clock in UTC, shifted by
+/// [`utc_offset()`](Self::utc_offset); daylight saving changes are not
+/// followed. A schedule without windows forwards nothing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CaptureSchedule {
+    windows: Vec<(Duration, Duration)>,
+    offset_secs: i64,
+}
+
+impl CaptureSchedule {
+    /// Creates a schedule without windows.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a window from `start` to `end` every day.
+    ///
+    /// A window whose end is before its start spans midnight, e.g. 22:00
+    /// to 06:00; one whose end equals its start covers the whole day.
+    pub fn daily(mut self, start: Duration, end: Duration) -> Self {
+        self.windows.push((start, end));
+        self
+    }
+
+    /// Sets the offset of local time from UTC, in seconds east of UTC.
+    pub fn utc_offset(mut self, seconds: i32) -> Self {
+        self.offset_secs = i64::from(seconds);
+        self
+    }
+
+    /// Whether `now` falls in one of the windows.
+    pub(crate) fn is_active(&self, now: SystemTime) -> bool {
+        let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let local = since_epoch.as_secs() as i64 + self.offset_secs;
+        let secs = local.rem_euclid(SECS_PER_DAY as i64) as u64;
+        let time = Duration::new(secs, since_epoch.subsec_nanos());
+        let day = Duration::from_secs(SECS_PER_DAY);
+        self.windows.iter().any(|&(start, end)| {
+            let (start, end) = (start.min(day), end.min(day));
+            match start.cmp(&end) {
+                std::cmp::Ordering::Less => start <= time && time < end,
+                std::cmp::Ordering::Greater => time >= start || time < end,
+                std::cmp::Ordering::Equal => true,
+            }
+        })
+    }
+}
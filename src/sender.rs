@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+use std::fmt;
+#[cfg(feature = "sink")]
+use std::future::Future;
+#[cfg(feature = "sink")]
+use std::pin::Pin;
+#[cfg(feature = "sink")]
+use std::task::{Context, Poll};
+
+use pcap::{Active, Capture, Device};
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tokio::sync::oneshot;
+
+use crate::error::Error;
+use crate::logging::debug;
+
+/// Sends that may be pending at once through the [`Sink`](futures_sink::Sink)
+/// implementation before it stops accepting frames.
+#[cfg(feature = "sink")]
+const MAX_IN_FLIGHT: usize = 64;
+
+/// Snaplen of the handles opened only to send, which never read.
+const SEND_ONLY_SNAPLEN: i32 = 64;
+
+type Request = (Vec<u8>, oneshot::Sender<Result<(), Error>>);
+
+/// Transmits raw frames on an interface without blocking the runtime.
+///
+/// Frames are handed to a background thread that owns a pcap handle and
+/// calls `pcap_sendpacket` for each, in order; the async methods resolve
+/// once the frame has been passed to the kernel. The thread exits when the
+/// last clone of the sender is dropped.
+///
+/// With the `sink` feature the sender also implements
+/// `futures::Sink<Vec<u8>>`, so a stream of crafted frames can be piped
+/// out with `StreamExt::forward`.
+///
+/// # Notes
+///
+/// - Frames must include the link-layer header of the interface, e.g. the
+///   Ethernet header.
+/// - To send on the interface a capture reads from, open the sender on the
+///   same device, e.g. the name in
+///   [`AsyncCapture::local_addresses()`](crate::AsyncCapture::local_addresses).
+///   Depending on the platform, the capture may or may not see the frames
+///   sent.
+/// - Sending usually requires the same privileges as capturing.
+pub struct AsyncSender {
+    tx: UnboundedSender<Request>,
+    /// Sends started through the sink and not yet completed
+    in_flight: VecDeque<oneshot::Receiver<Result<(), Error>>>,
+}
+
+impl AsyncSender {
+    /// Opens `device` for sending.
+    ///
+    /// The handle is opened with a small snaplen and a filter matching no
+    /// packet, so that it does not buffer the traffic it never reads.
+    pub fn open<D: Into<Device>>(device: D) -> Result<Self, Error> {
+        Ok(Self::new(open_send_only(device)?))
+    }
+
+    /// Sends through an already opened capture.
+    ///
+    /// The capture is moved to the sender thread and is not read from;
+    /// packets it captures in the meantime are buffered and dropped by the
+    /// kernel.
+    pub fn new(cap: Capture<Active>) -> Self {
+        let (tx, mut rx) = unbounded_channel::<Request>();
+        std::thread::spawn(move || {
+            let mut cap = cap;
+            while let Some((frame, done)) = rx.blocking_recv() {
+                let _ = done.send(cap.sendpacket(frame).map_err(Error::from));
+            }
+        });
+        Self {
+            tx,
+            in_flight: VecDeque::new(),
+        }
+    }
+
+    /// Sends one frame, resolving once it has been handed to the kernel.
+    ///
+    /// Fails with the pcap error if the frame could not be sent, e.g.
+    /// because it is larger than the interface MTU.
+    pub async fn send<B: Into<Vec<u8>>>(&self, frame: B) -> Result<(), Error> {
+        self.start(frame.into())?
+            .await
+            .unwrap_or(Err(Error::CaptureStopped))
+    }
+
+    /// Queues a frame for the sender thread.
+    fn start(&self, frame: Vec<u8>) -> Result<oneshot::Receiver<Result<(), Error>>, Error> {
+        let (done, result) = oneshot::channel();
+        self.tx
+            .send((frame, done))
+            .map_err(|_| Error::CaptureStopped)?;
+        Ok(result)
+    }
+
+    /// Completes the pending sends while more than `max` remain, returning
+    /// the first error.
+    #[cfg(feature = "sink")]
+    fn poll_pending(&mut self, cx: &mut Context<'_>, max: usize) -> Poll<Result<(), Error>> {
+        while self.in_flight.len() > max {
+            let front = self.in_flight.front_mut().expect("checked non-empty");
+            let result = std::task::ready!(Pin::new(front).poll(cx));
+            self.in_flight.pop_front();
+            result.unwrap_or(Err(Error::CaptureStopped))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Clone for AsyncSender {
+    /// Returns a sender sharing the same thread, with no pending sends.
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            in_flight: VecDeque::new(),
+        }
+    }
+}
+
+impl fmt::Debug for AsyncSender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncSender")
+            .field("in_flight", &self.in_flight.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Opens `device` with a small snaplen and a filter matching no packet,
+/// for a handle that only sends.
+pub(crate) fn open_send_only<D: Into<Device>>(device: D) -> Result<Capture<Active>, Error> {
+    let mut cap = Capture::from_device(device)?
+        .snaplen(SEND_ONLY_SNAPLEN)
+        .open()?;
+    if let Err(e) = cap.filter("less 0", true) {
+        debug!("AsyncSender could not install its drop-all filter: {e}");
+    }
+    Ok(cap)
+}
+
+/// Frames are queued without waiting for them to be sent, up to 64 at a
+/// time; flushing waits until all of them have been, and reports the
+/// first failure.
+#[cfg(feature = "sink")]
+impl futures_sink::Sink<Vec<u8>> for AsyncSender {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.get_mut().poll_pending(cx, MAX_IN_FLIGHT - 1)
+    }
+
+    fn start_send(self: Pin<&mut Self>, frame: Vec<u8>) -> Result<(), Error> {
+        let this = self.get_mut();
+        let result = this.start(frame)?;
+        this.in_flight.push_back(result);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.get_mut().poll_pending(cx, 0)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.get_mut().poll_pending(cx, 0)
+    }
+}
@@ -0,0 +1,105 @@
+use std::fmt;
+use std::future::{Future, poll_fn};
+use std::pin::pin;
+use std::task::Poll;
+
+use futures_sink::Sink;
+
+use crate::async_pcap::{AsyncCapture, Packet};
+use crate::error::Error;
+
+/// Why [`AsyncCapture::forward_to()`] stopped before the capture ended.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ForwardError<E> {
+    /// The capture delivered an error other than a read timeout
+    Capture(Error),
+    /// The sink failed to accept, flush or close
+    Sink(E),
+}
+
+impl<E: fmt::Display> fmt::Display for ForwardError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ForwardError::Capture(e) => write!(f, "capture failed: {e}"),
+            ForwardError::Sink(e) => write!(f, "sink failed: {e}"),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for ForwardError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ForwardError::Capture(e) => Some(e),
+            ForwardError::Sink(e) => Some(e),
+        }
+    }
+}
+
+impl AsyncCapture {
+    /// Sends every packet into `sink` until the capture stops, then closes
+    /// the sink and returns the number of packets forwarded.
+    ///
+    /// Requires the `sink` feature. Each packet waits for the sink to be
+    /// ready, so a slow sink slows the consumption down and packets queue
+    /// up in the capture instead. The sink is flushed whenever no further
+    /// packet is immediately available.
+    ///
+    /// # Notes
+    ///
+    /// - Read timeouts are skipped; any other capture error ends the
+    ///   forwarding with [`ForwardError::Capture`], without closing the
+    ///   sink.
+    /// - A sink error ends it with [`ForwardError::Sink`]; the packet being
+    ///   sent is lost.
+    /// - The capture keeps running after an error; call
+    ///   [`AsyncCaptureHandle::stop()`](crate::AsyncCaptureHandle::stop)
+    ///   to end it.
+    pub async fn forward_to<S>(&self, sink: S) -> Result<u64, ForwardError<S::Error>>
+    where
+        S: Sink<Packet>,
+    {
+        let mut sink = pin!(sink);
+        let mut forwarded = 0;
+        let mut unflushed = false;
+        loop {
+            let mut next = pin!(self.next_packet());
+            let ready = poll_fn(|cx| {
+                Poll::Ready(match next.as_mut().poll(cx) {
+                    Poll::Ready(item) => Some(item),
+                    Poll::Pending => None,
+                })
+            })
+            .await;
+            let item = match ready {
+                Some(item) => item,
+                None => {
+                    if unflushed {
+                        poll_fn(|cx| sink.as_mut().poll_flush(cx))
+                            .await
+                            .map_err(ForwardError::Sink)?;
+                        unflushed = false;
+                    }
+                    next.await
+                }
+            };
+            let packet = match item {
+                Some(Ok(packet)) => packet,
+                Some(Err(Error::Timeout)) => continue,
+                Some(Err(e)) => return Err(ForwardError::Capture(e)),
+                None => break,
+            };
+            poll_fn(|cx| sink.as_mut().poll_ready(cx))
+                .await
+                .map_err(ForwardError::Sink)?;
+            sink.as_mut()
+                .start_send(packet)
+                .map_err(ForwardError::Sink)?;
+            forwarded += 1;
+            unflushed = true;
+        }
+        poll_fn(|cx| sink.as_mut().poll_close(cx))
+            .await
+            .map_err(ForwardError::Sink)?;
+        Ok(forwarded)
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::atomic::Ordering;
+
+use crate::async_pcap::AsyncCaptureHandle;
+
+/// Number of metrics exported for each capture.
+pub(crate) const METRICS: usize = 13;
+
+/// Unit of an exported metric, which the Prometheus exporter spells out
+/// in the metric names instead.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum MetricUnit {
+    Count,
+    Bytes,
+    CountPerSecond,
+    BitsPerSecond,
+    Seconds,
+}
+
+/// Value of an exported metric at the time of the snapshot.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum MetricValue {
+    /// Total that only goes up
+    Counter(u64),
+    /// Value that goes up and down
+    Gauge(f64),
+}
+
+/// One metric of a capture, as exported by every metrics backend.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Metric {
+    pub(crate) name: &'static str,
+    pub(crate) help: &'static str,
+    #[cfg(feature = "metrics")]
+    pub(crate) unit: MetricUnit,
+    pub(crate) value: MetricValue,
+}
+
+/// Reads the exported metrics of the capture, always in the same order.
+pub(crate) fn snapshot(handle: &AsyncCaptureHandle) -> [Metric; METRICS] {
+    #[cfg(feature = "metrics")]
+    use MetricUnit::*;
+    use MetricValue::*;
+
+    let shared = &handle.shared;
+    let kernel = *shared
+        .kernel_stats
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let depth = shared
+        .enqueued
+        .load(Ordering::Relaxed)
+        .saturating_sub(shared.dequeued.load(Ordering::Relaxed));
+    let throughput = handle.throughput();
+    let uptime = match &*shared.summary.lock().unwrap_or_else(|e| e.into_inner()) {
+        Some(summary) => Some(summary.duration),
+        None => shared
+            .started
+            .get()
+            .map(|(started, _)| shared.clock.now().saturating_duration_since(*started)),
+    };
+
+    [
+        Metric {
+            name: "pcap_packets_received_total",
+            help: "Packets read from the capture source",
+            #[cfg(feature = "metrics")]
+            unit: Count,
+            value: Counter(shared.received.load(Ordering::Relaxed)),
+        },
+        Metric {
+            name: "pcap_bytes_received_total",
+            help: "Original length of the packets read from the capture source",
+            #[cfg(feature = "metrics")]
+            unit: Bytes,
+            value: Counter(shared.received_bytes.load(Ordering::Relaxed)),
+        },
+        Metric {
+            name: "pcap_packets_dropped_total",
+            help: "Packets dropped by the kernel because the capture buffer was full",
+            #[cfg(feature = "metrics")]
+            unit: Count,
+            value: Counter(kernel.map_or(0, |stats| u64::from(stats.dropped))),
+        },
+        Metric {
+            name: "pcap_packets_if_dropped_total",
+            help: "Packets dropped by the interface or its driver",
+            #[cfg(feature = "metrics")]
+            unit: Count,
+            value: Counter(kernel.map_or(0, |stats| u64::from(stats.if_dropped))),
+        },
+        Metric {
+            name: "pcap_packets_overflow_dropped_total",
+            help: "Packets dropped because the queue towards the consumer was full",
+            #[cfg(feature = "metrics")]
+            unit: Count,
+            value: Counter(handle.overflow_drops()),
+        },
+        Metric {
+            name: "pcap_packets_filtered_total",
+            help: "Packets discarded by the filters of the capture thread",
+            #[cfg(feature = "metrics")]
+            unit: Count,
+            value: Counter(handle.filtered()),
+        },
+        Metric {
+            name: "pcap_read_retries_total",
+            help: "Reads retried after a transient error",
+            #[cfg(feature = "metrics")]
+            unit: Count,
+            value: Counter(handle.read_retries()),
+        },
+        Metric {
+            name: "pcap_reconnects_total",
+            help: "Times the device was reopened after it was lost",
+            #[cfg(feature = "metrics")]
+            unit: Count,
+            value: Counter(handle.reconnects()),
+        },
+        Metric {
+            name: "pcap_soft_errors_total",
+            help: "Errors the capture thread recovered from",
+            #[cfg(feature = "metrics")]
+            unit: Count,
+            value: Counter(handle.soft_errors()),
+        },
+        Metric {
+            name: "pcap_queue_depth",
+            help: "Packets waiting for the consumer",
+            #[cfg(feature = "metrics")]
+            unit: Count,
+            value: Gauge(depth as f64),
+        },
+        Metric {
+            name: "pcap_packets_per_second",
+            help: "Packets read per second over the throughput window",
+            #[cfg(feature = "metrics")]
+            unit: CountPerSecond,
+            value: Gauge(throughput.pps),
+        },
+        Metric {
+            name: "pcap_bits_per_second",
+            help: "Bits read per second over the throughput window",
+            #[cfg(feature = "metrics")]
+            unit: BitsPerSecond,
+            value: Gauge(throughput.bps),
+        },
+        Metric {
+            name: "pcap_uptime_seconds",
+            help: "Time the capture has been reading for",
+            #[cfg(feature = "metrics")]
+            unit: Seconds,
+            value: Gauge(uptime.unwrap_or_default().as_secs_f64()),
+        },
+    ]
+}
@@ -0,0 +1,339 @@
+use std::fmt::{self, Write};
+use std::net::IpAddr;
+
+use pcap::{Linktype, Precision};
+
+use crate::async_pcap::{AsyncCapture, AsyncCaptureHandle, Packet};
+use crate::builder::AsyncCaptureBuilder;
+use crate::error::Error;
+use crate::parse::{
+    self, IPPROTO_ICMP, IPPROTO_ICMPV6, IPPROTO_TCP, IPPROTO_UDP, TCP_FIN, TCP_RST, TCP_SYN,
+};
+
+const TCP_PSH: u8 = 0x08;
+const TCP_ACK: u8 = 0x10;
+
+/// How much detail [`summarize()`] puts in a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    /// Addresses, protocol and length
+    Brief,
+    /// Adds ports, TCP flags and ICMP types
+    #[default]
+    Normal,
+    /// Adds TCP sequence numbers and the captured length
+    Detailed,
+}
+
+/// Settings for [`sniff()`].
+#[derive(Debug, Clone)]
+pub struct SniffOptions {
+    /// Device to capture on; the default device if `None`
+    pub device: Option<String>,
+    /// BPF program in `tcpdump` syntax
+    pub filter: Option<String>,
+    /// Detail of each line
+    pub verbosity: Verbosity,
+    /// Whether the device is put in promiscuous mode
+    pub promisc: bool,
+    /// Snapshot length in bytes
+    pub snaplen: i32,
+}
+
+impl Default for SniffOptions {
+    fn default() -> Self {
+        Self {
+            device: None,
+            filter: None,
+            verbosity: Verbosity::Normal,
+            promisc: false,
+            snaplen: 65535,
+        }
+    }
+}
+
+/// Capture returning one summary line per packet.
+///
+/// Created by [`sniff()`].
+pub struct Sniffer {
+    capture: AsyncCapture,
+    handle: AsyncCaptureHandle,
+    verbosity: Verbosity,
+}
+
+impl Sniffer {
+    /// Waits for the next packet and returns its summary line.
+    ///
+    /// Read timeouts are skipped; other capture errors are returned.
+    /// Returns `None` once the capture has stopped.
+    pub async fn next_line(&mut self) -> Option<Result<String, Error>> {
+        loop {
+            match self.capture.next_packet().await? {
+                Ok(packet) => {
+                    let info = self.capture.info();
+                    let line = summarize(info.datalink, info.precision, &packet, self.verbosity);
+                    return Some(Ok(line));
+                }
+                Err(Error::Timeout) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+
+    /// Returns the handle that stops the capture.
+    pub fn handle(&self) -> &AsyncCaptureHandle {
+        &self.handle
+    }
+
+    /// Returns the underlying capture.
+    pub fn capture(&self) -> &AsyncCapture {
+        &self.capture
+    }
+}
+
+impl fmt::Debug for Sniffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sniffer")
+            .field("capture", &self.capture)
+            .field("verbosity", &self.verbosity)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Opens a device and summarizes its packets in `tcpdump`-like lines.
+///
+/// A ready-made building block for small capture tools: it opens the
+/// configured or default device through [`AsyncCaptureBuilder`], applies
+/// the filter, and formats each packet with [`summarize()`]:
+///
+/// ```text
+/// 12:34:56.789012 IP 192.168.1.10.51000 > 93.184.216.34.443: TCP [S], length 74
+/// ```
+///
+/// Fails with [`Error::NoDefaultDevice`] if no device is given and none
+/// can be found.
+pub fn sniff(options: SniffOptions) -> Result<Sniffer, Error> {
+    let builder = match options.device {
+        Some(name) => AsyncCaptureBuilder::new(name.as_str()),
+        None => AsyncCaptureBuilder::default_device()?,
+    };
+    let mut builder = builder.promisc(options.promisc).snaplen(options.snaplen);
+    if let Some(filter) = options.filter {
+        builder = builder.filter(filter);
+    }
+    let (capture, handle) = builder.open()?;
+    Ok(Sniffer {
+        capture,
+        handle,
+        verbosity: options.verbosity,
+    })
+}
+
+/// Formats a one-line summary of a packet of the given link type, whose
+/// timestamp has `precision`.
+///
+/// The time is the capture timestamp in UTC, with six or nine fractional
+/// digits depending on the precision. IPv4 and IPv6 packets are
+/// decoded down to TCP, UDP and ICMP; other packets are reported by their
+/// EtherType or link type.
+pub fn summarize(
+    datalink: Linktype,
+    precision: Precision,
+    packet: &Packet,
+    verbosity: Verbosity,
+) -> String {
+    let mut line = timestamp(packet, precision);
+    let ip = parse::network_payload(datalink, &packet.data).and_then(parse::ip_layer);
+    let Some(ip) = ip else {
+        match ethertype(datalink, &packet.data) {
+            Some(ethertype) => {
+                let _ = write!(line, " ethertype 0x{ethertype:04x}");
+            }
+            None => {
+                let _ = write!(line, " linktype {}", datalink.0);
+            }
+        }
+        push_length(&mut line, packet, verbosity);
+        return line;
+    };
+
+    let family = if ip.src.is_ipv4() { "IP" } else { "IP6" };
+    let ports = match ip.protocol {
+        IPPROTO_TCP | IPPROTO_UDP if verbosity != Verbosity::Brief && !ip.fragmented => {
+            ports(ip.payload)
+        }
+        _ => None,
+    };
+    let _ = write!(
+        line,
+        " {family} {} > {}: {}",
+        endpoint(ip.src, ports.map(|(src, _)| src)),
+        endpoint(ip.dst, ports.map(|(_, dst)| dst)),
+        protocol_name(ip.protocol),
+    );
+    if verbosity != Verbosity::Brief && !ip.fragmented {
+        match ip.protocol {
+            IPPROTO_TCP => {
+                if let Some(segment) = parse::tcp(ip.payload) {
+                    let _ = write!(line, " [{}]", tcp_flags(segment.flags));
+                    if verbosity == Verbosity::Detailed {
+                        let _ = write!(line, ", seq {}", segment.seq);
+                    }
+                }
+            }
+            IPPROTO_ICMP | IPPROTO_ICMPV6 => {
+                if let Some(&kind) = ip.payload.first() {
+                    let _ = write!(line, " type {kind}");
+                }
+            }
+            _ => {}
+        }
+    }
+    if ip.fragmented {
+        line.push_str(" (fragment)");
+    }
+    push_length(&mut line, packet, verbosity);
+    line
+}
+
+// `time_t` is not 64 bits on every platform
+#[allow(clippy::unnecessary_cast)]
+fn timestamp(packet: &Packet, precision: Precision) -> String {
+    let secs = packet.header.ts.tv_sec as i64;
+    let day = secs.rem_euclid(86_400);
+    let digits = match precision {
+        Precision::Micro => 6,
+        Precision::Nano => 9,
+    };
+    format!(
+        "{:02}:{:02}:{:02}.{:0digits$}",
+        day / 3600,
+        day / 60 % 60,
+        day % 60,
+        packet.header.ts.tv_usec
+    )
+}
+
+fn push_length(line: &mut String, packet: &Packet, verbosity: Verbosity) {
+    let _ = write!(line, ", length {}", packet.header.len);
+    if verbosity == Verbosity::Detailed {
+        let _ = write!(line, ", caplen {}", packet.header.caplen);
+    }
+}
+
+fn ethertype(datalink: Linktype, data: &[u8]) -> Option<u16> {
+    if datalink != Linktype::ETHERNET {
+        return None;
+    }
+    parse::inner_ethertype(data, 12).map(|(ethertype, _)| ethertype)
+}
+
+fn ports(segment: &[u8]) -> Option<(u16, u16)> {
+    let bytes = segment.get(..4)?;
+    Some((
+        u16::from_be_bytes([bytes[0], bytes[1]]),
+        u16::from_be_bytes([bytes[2], bytes[3]]),
+    ))
+}
+
+fn endpoint(addr: IpAddr, port: Option<u16>) -> String {
+    match port {
+        Some(port) => format!("{addr}.{port}"),
+        None => addr.to_string(),
+    }
+}
+
+fn protocol_name(protocol: u8) -> String {
+    match protocol {
+        IPPROTO_TCP => "TCP".into(),
+        IPPROTO_UDP => "UDP".into(),
+        IPPROTO_ICMP => "ICMP".into(),
+        IPPROTO_ICMPV6 => "ICMP6".into(),
+        other => format!("proto {other}"),
+    }
+}
+
+/// Formats TCP flags the way `tcpdump` does, e.g. `S.` for SYN-ACK.
+fn tcp_flags(flags: u8) -> String {
+    let mut out = String::new();
+    for (bit, name) in [
+        (TCP_SYN, 'S'),
+        (TCP_FIN, 'F'),
+        (TCP_PSH, 'P'),
+        (TCP_RST, 'R'),
+    ] {
+        if flags & bit != 0 {
+            out.push(name);
+        }
+    }
+    if flags & TCP_ACK != 0 {
+        out.push('.');
+    }
+    if out.is_empty() {
+        out.push_str("none");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+
+    fn at(secs: u64, fraction: u32) -> Packet {
+        let mut packet = Packet::new(vec![0; 14], UNIX_EPOCH + Duration::from_secs(secs));
+        packet.header.ts.tv_usec = fraction as _;
+        packet
+    }
+
+    #[test]
+    fn timestamps_have_the_digits_of_their_precision() {
+        let micro = summarize(
+            Linktype::ETHERNET,
+            Precision::Micro,
+            &at(3_723, 4_567),
+            Verbosity::Brief,
+        );
+        assert!(micro.starts_with("01:02:03.004567 "), "{micro}");
+        let nano = summarize(
+            Linktype::ETHERNET,
+            Precision::Nano,
+            &at(3_723, 4_567),
+            Verbosity::Brief,
+        );
+        assert!(nano.starts_with("01:02:03.000004567 "), "{nano}");
+    }
+
+    #[test]
+    fn vlan_tags_are_skipped() {
+        // 802.1ad and 802.1Q tags, then ARP
+        let mut arp = vec![0; 12];
+        arp.extend_from_slice(&[0x88, 0xa8, 0, 10, 0x81, 0x00, 0, 20, 0x08, 0x06]);
+        arp.extend_from_slice(&[0; 28]);
+        let line = summarize(
+            Linktype::ETHERNET,
+            Precision::Micro,
+            &Packet::new(arp, UNIX_EPOCH),
+            Verbosity::Brief,
+        );
+        assert!(line.ends_with(" ethertype 0x0806, length 50"), "{line}");
+
+        // An 802.1Q tag, then IPv4 UDP
+        let mut udp = vec![0; 12];
+        udp.extend_from_slice(&[0x81, 0x00, 0, 10, 0x08, 0x00]);
+        udp.extend_from_slice(&[0x45, 0, 0, 28, 0, 0, 0, 0, 64, 17, 0, 0]);
+        udp.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        udp.extend_from_slice(&[0x30, 0x39, 0, 53, 0, 8, 0, 0]);
+        let line = summarize(
+            Linktype::ETHERNET,
+            Precision::Micro,
+            &Packet::new(udp, UNIX_EPOCH),
+            Verbosity::Normal,
+        );
+        assert!(
+            line.ends_with(" IP 10.0.0.1.12345 > 10.0.0.2.53: UDP, length 46"),
+            "{line}"
+        );
+    }
+}
@@ -0,0 +1,241 @@
+use pcap::{Active, Capture, Linktype, Offline, PacketHeader, Precision, Stat};
+
+use crate::async_pcap::Packet;
+use crate::error::Error;
+use crate::info::CaptureInfo;
+use crate::replay::Paced;
+use crate::tstamp::TimestampSource;
+
+/// A blocking source of packets driven by the background capture thread.
+///
+/// [`AsyncCapture`](crate::AsyncCapture) moves the source onto its own thread
+/// and calls [`next_packet`](Self::next_packet) in a loop, so the async
+/// machinery does not depend on libpcap directly. `pcap::Capture<Active>`
+/// implements it for live captures; [`IterSource`] replays packets from an
+/// iterator, which makes it easy to unit test code that consumes an
+/// `AsyncCapture` without a real interface.
+pub trait PacketSource: Send + 'static {
+    /// Reads the next packet, blocking until one is available.
+    ///
+    /// Returns `None` once the source has no more packets to produce.
+    fn next_packet(&mut self) -> Option<Result<Packet, Error>>;
+
+    /// Reads the next packet into `buf`, reusing its allocation.
+    ///
+    /// Called instead of [`next_packet`](Self::next_packet) by captures
+    /// with a buffer pool. The default implementation drops `buf` and calls
+    /// `next_packet`.
+    fn next_packet_into(&mut self, buf: Vec<u8>) -> Option<Result<Packet, Error>> {
+        drop(buf);
+        self.next_packet()
+    }
+
+    /// Reads the next packet, but copies it only if `accept` returns `true`
+    /// for its header and data.
+    ///
+    /// Returns `Some(Ok(None))` for a packet that was skipped. Called by
+    /// captures with length bounds or a prefilter. The default
+    /// implementation reads the packet with
+    /// [`next_packet_into`](Self::next_packet_into) and checks it
+    /// afterwards.
+    fn next_packet_if(
+        &mut self,
+        buf: Vec<u8>,
+        accept: &dyn Fn(&PacketHeader, &[u8]) -> bool,
+    ) -> Option<Result<Option<Packet>, Error>> {
+        self.next_packet_into(buf)
+            .map(|res| res.map(|packet| accept(&packet.header, &packet.data).then_some(packet)))
+    }
+
+    /// Returns the parameters the source is running with.
+    fn info(&self) -> CaptureInfo;
+
+    /// Returns the packet statistics kept by the source.
+    ///
+    /// Returns `None` if the source keeps no statistics or they could not
+    /// be read. The default implementation returns `None`.
+    fn stats(&mut self) -> Option<Stat> {
+        None
+    }
+
+    /// Returns the underlying live capture, if the source is one.
+    ///
+    /// Used by [`AsyncCaptureHandle::with_capture()`](crate::AsyncCaptureHandle::with_capture).
+    /// The default implementation returns `None`.
+    fn as_active(&mut self) -> Option<&mut Capture<Active>> {
+        None
+    }
+
+    /// Wraps the source so that its packets are delivered with their
+    /// recorded inter-packet gaps divided by `speed`.
+    ///
+    /// Meant for savefiles and other recordings; see [`Paced`] for how the
+    /// gaps are computed. `f64::INFINITY` or 0 replays as fast as possible.
+    fn replay_speed(self, speed: f64) -> Paced<Self>
+    where
+        Self: Sized,
+    {
+        Paced::new(self, speed)
+    }
+}
+
+impl PacketSource for Capture<Active> {
+    fn next_packet(&mut self) -> Option<Result<Packet, Error>> {
+        let res = Capture::next_packet(self)
+            .map(|packet| Packet {
+                header: *packet.header,
+                data: packet.data.to_vec(),
+            })
+            .map_err(Error::from);
+        Some(res)
+    }
+
+    fn next_packet_if(
+        &mut self,
+        mut buf: Vec<u8>,
+        accept: &dyn Fn(&PacketHeader, &[u8]) -> bool,
+    ) -> Option<Result<Option<Packet>, Error>> {
+        let res = Capture::next_packet(self)
+            .map(|packet| {
+                accept(packet.header, packet.data).then(|| {
+                    buf.clear();
+                    buf.extend_from_slice(packet.data);
+                    Packet {
+                        header: *packet.header,
+                        data: buf,
+                    }
+                })
+            })
+            .map_err(Error::from);
+        Some(res)
+    }
+
+    fn next_packet_into(&mut self, mut buf: Vec<u8>) -> Option<Result<Packet, Error>> {
+        let res = Capture::next_packet(self)
+            .map(|packet| {
+                buf.clear();
+                buf.extend_from_slice(packet.data);
+                Packet {
+                    header: *packet.header,
+                    data: buf,
+                }
+            })
+            .map_err(Error::from);
+        Some(res)
+    }
+
+    fn info(&self) -> CaptureInfo {
+        CaptureInfo::query(self)
+    }
+
+    fn stats(&mut self) -> Option<Stat> {
+        Capture::stats(self).ok()
+    }
+
+    fn as_active(&mut self) -> Option<&mut Capture<Active>> {
+        Some(self)
+    }
+}
+
+/// Reads a savefile; both classic pcap and pcapng files are supported by
+/// libpcap. The source ends at the end of the file.
+impl PacketSource for Capture<Offline> {
+    fn next_packet(&mut self) -> Option<Result<Packet, Error>> {
+        match Capture::next_packet(self) {
+            Ok(packet) => Some(Ok(Packet {
+                header: *packet.header,
+                data: packet.data.to_vec(),
+            })),
+            Err(pcap::Error::NoMorePackets) => None,
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+
+    fn next_packet_if(
+        &mut self,
+        mut buf: Vec<u8>,
+        accept: &dyn Fn(&PacketHeader, &[u8]) -> bool,
+    ) -> Option<Result<Option<Packet>, Error>> {
+        match Capture::next_packet(self) {
+            Ok(packet) if !accept(packet.header, packet.data) => Some(Ok(None)),
+            Ok(packet) => {
+                buf.clear();
+                buf.extend_from_slice(packet.data);
+                Some(Ok(Some(Packet {
+                    header: *packet.header,
+                    data: buf,
+                })))
+            }
+            Err(pcap::Error::NoMorePackets) => None,
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+
+    fn next_packet_into(&mut self, mut buf: Vec<u8>) -> Option<Result<Packet, Error>> {
+        match Capture::next_packet(self) {
+            Ok(packet) => {
+                buf.clear();
+                buf.extend_from_slice(packet.data);
+                Some(Ok(Packet {
+                    header: *packet.header,
+                    data: buf,
+                }))
+            }
+            Err(pcap::Error::NoMorePackets) => None,
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+
+    fn info(&self) -> CaptureInfo {
+        CaptureInfo::query(self)
+    }
+}
+
+/// A [`PacketSource`] that yields packets from an iterator.
+///
+/// The source ends when the iterator is exhausted. By default it reports an
+/// Ethernet datalink and a snaplen of 65535 bytes.
+pub struct IterSource<I> {
+    iter: I,
+    info: CaptureInfo,
+}
+
+impl<I> IterSource<I>
+where
+    I: Iterator<Item = Result<Packet, Error>> + Send + 'static,
+{
+    /// Creates a source that yields the items of `iter`.
+    pub fn new<T>(iter: T) -> Self
+    where
+        T: IntoIterator<IntoIter = I>,
+    {
+        Self {
+            iter: iter.into_iter(),
+            info: CaptureInfo {
+                snaplen: 65535,
+                datalink: Linktype::ETHERNET,
+                timestamp_source: TimestampSource::Host,
+                precision: Precision::Micro,
+            },
+        }
+    }
+
+    /// Overrides the parameters the source reports.
+    pub fn with_info(mut self, info: CaptureInfo) -> Self {
+        self.info = info;
+        self
+    }
+}
+
+impl<I> PacketSource for IterSource<I>
+where
+    I: Iterator<Item = Result<Packet, Error>> + Send + 'static,
+{
+    fn next_packet(&mut self) -> Option<Result<Packet, Error>> {
+        self.iter.next()
+    }
+
+    fn info(&self) -> CaptureInfo {
+        self.info.clone()
+    }
+}
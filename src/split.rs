@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::ffi::CStr;
+
+use pcap::Linktype;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+use crate::async_pcap::{AsyncCapture, Packet};
+use crate::error::Error;
+
+/// Packets of one interface split off an `any` capture.
+#[derive(Debug)]
+pub struct InterfaceStream {
+    name: String,
+    rx: UnboundedReceiver<Packet>,
+}
+
+impl InterfaceStream {
+    /// Name of the interface, or `if<index>` if it could not be resolved.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Waits for the next packet of this interface.
+    ///
+    /// Returns `None` once the underlying capture has stopped.
+    pub async fn next_packet(&mut self) -> Option<Packet> {
+        self.rx.recv().await
+    }
+}
+
+/// Interfaces discovered while splitting an `any` capture.
+///
+/// Returned by [`AsyncCapture::split_by_interface()`].
+#[derive(Debug)]
+pub struct InterfaceSplit {
+    rx: UnboundedReceiver<Result<InterfaceStream, Error>>,
+}
+
+impl InterfaceSplit {
+    /// Waits until traffic is seen on a new interface and returns its
+    /// stream.
+    ///
+    /// Capture errors are delivered here rather than on the interface
+    /// streams. Returns `None` once the capture has stopped.
+    pub async fn next_interface(&mut self) -> Option<Result<InterfaceStream, Error>> {
+        self.rx.recv().await
+    }
+}
+
+impl AsyncCapture {
+    /// Splits a capture on the Linux `any` device into one stream per
+    /// interface.
+    ///
+    /// Spawns a Tokio task that reads the interface index from each
+    /// packet's `LINUX_SLL2` header and routes the packet to that
+    /// interface's [`InterfaceStream`]. A new stream is announced through
+    /// [`InterfaceSplit::next_interface()`] the first time an interface is
+    /// seen, so the set grows as new interfaces carry traffic.
+    ///
+    /// The task ends, and with it the capture, once the [`InterfaceSplit`]
+    /// and every stream it returned are dropped; while the split is kept,
+    /// it keeps running to announce new interfaces.
+    ///
+    /// Fails with [`Error::Setup`] unless the capture's datalink is
+    /// [`Linktype::LINUX_SLL2`]; select it with
+    /// [`AsyncCaptureBuilder::datalink()`](crate::AsyncCaptureBuilder::datalink).
+    /// Must be called from within a Tokio runtime.
+    pub fn split_by_interface(self) -> Result<InterfaceSplit, Error> {
+        if self.datalink() != Linktype::LINUX_SLL2 {
+            return Err(Error::Setup(format!(
+                "splitting by interface requires the LINUX_SLL2 datalink, got {:?}",
+                self.datalink()
+            )));
+        }
+
+        let (tx, rx) = unbounded_channel();
+        tokio::spawn(demux(self, tx));
+        Ok(InterfaceSplit { rx })
+    }
+}
+
+async fn demux(capture: AsyncCapture, split: UnboundedSender<Result<InterfaceStream, Error>>) {
+    let mut streams: HashMap<u32, UnboundedSender<Packet>> = HashMap::new();
+    loop {
+        let item = tokio::select! {
+            item = capture.next_packet() => item,
+            () = abandoned(&split, &streams) => break,
+        };
+        let Some(item) = item else {
+            break;
+        };
+        let packet = match item {
+            Ok(packet) => packet,
+            Err(e) => {
+                let _ = split.send(Err(e));
+                continue;
+            }
+        };
+        // The SLL2 header carries the interface index at bytes 4..8
+        let Some(index) = packet
+            .data
+            .get(4..8)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        else {
+            continue;
+        };
+        let tx = streams.entry(index).or_insert_with(|| {
+            let (tx, rx) = unbounded_channel();
+            let name = interface_name(index);
+            let _ = split.send(Ok(InterfaceStream { name, rx }));
+            tx
+        });
+        let _ = tx.send(packet);
+    }
+}
+
+/// Completes once no receiver is left for the packets: the split and every
+/// announced stream were dropped.
+async fn abandoned(
+    split: &UnboundedSender<Result<InterfaceStream, Error>>,
+    streams: &HashMap<u32, UnboundedSender<Packet>>,
+) {
+    split.closed().await;
+    for tx in streams.values() {
+        tx.closed().await;
+    }
+}
+
+fn interface_name(index: u32) -> String {
+    let mut buf = [0; libc::IF_NAMESIZE];
+    // SAFETY: `buf` is IF_NAMESIZE bytes long, as if_indextoname requires.
+    let ret = unsafe { libc::if_indextoname(index, buf.as_mut_ptr()) };
+    if ret.is_null() {
+        return format!("if{index}");
+    }
+    // SAFETY: on success the buffer holds a NUL-terminated name.
+    unsafe { CStr::from_ptr(buf.as_ptr()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use pcap::Precision;
+
+    use super::*;
+    use crate::info::CaptureInfo;
+    use crate::source::IterSource;
+    use crate::summary::EndReason;
+    use crate::tstamp::TimestampSource;
+
+    #[tokio::test]
+    async fn capture_stops_once_the_split_and_its_streams_are_dropped() {
+        let info = CaptureInfo {
+            snaplen: 65535,
+            datalink: Linktype::LINUX_SLL2,
+            timestamp_source: TimestampSource::Host,
+            precision: Precision::Micro,
+        };
+        // SLL2 headers of interface 1, one every millisecond, without end
+        let source = IterSource::new(std::iter::repeat_with(|| {
+            std::thread::sleep(Duration::from_millis(1));
+            let mut header = vec![0; 20];
+            header[4..8].copy_from_slice(&1u32.to_be_bytes());
+            Ok(Packet::new(header, UNIX_EPOCH))
+        }))
+        .with_info(info);
+        let (capture, handle) = AsyncCapture::new(source);
+        let mut split = capture.split_by_interface().unwrap();
+        let mut stream = split.next_interface().await.unwrap().unwrap();
+        assert!(stream.next_packet().await.is_some());
+
+        drop(split);
+        drop(stream);
+        let summary = tokio::time::timeout(Duration::from_secs(5), handle.wait_stopped())
+            .await
+            .expect("the capture stops");
+        assert_eq!(summary.reason, EndReason::ReceiverDropped);
+    }
+}
@@ -0,0 +1,51 @@
+use pcap::Stat;
+
+/// Counters of a capture, from the kernel and from the capture thread.
+///
+/// Returned by
+/// [`AsyncCaptureHandle::stats()`](crate::AsyncCaptureHandle::stats). The
+/// kernel counters are those of `pcap_stats` and are `None` unless the
+/// source is a live capture; see [`DropReasons`](crate::DropReasons) for
+/// what each kind of drop means. The other counters are kept by this crate
+/// and count from the start of the capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureStats {
+    /// Packets that passed the kernel filter, as counted by libpcap. Its
+    /// meaning differs between platforms; on Linux it includes the packets
+    /// dropped for lack of buffer space
+    pub received: Option<u32>,
+    /// Packets the kernel dropped because the capture buffer was full
+    pub dropped: Option<u32>,
+    /// Packets dropped by the interface or its driver; only reported on
+    /// Linux
+    pub if_dropped: Option<u32>,
+    /// Packets read from the source by the capture thread
+    pub read: u64,
+    /// Packets put on the queue towards the consumer
+    pub queued: u64,
+    /// Packets taken off the queue by the consumer
+    pub delivered: u64,
+    /// Packets discarded on the capture thread by the length bounds,
+    /// prefilter, sampling, deduplication, schedule, filter pipeline or a
+    /// pause
+    pub filtered: u64,
+    /// Packets dropped because the bounded queue towards the consumer was
+    /// full
+    pub overflow_dropped: u64,
+}
+
+impl CaptureStats {
+    /// Number of packets waiting in the queue towards the consumer.
+    pub fn backlog(&self) -> u64 {
+        self.queued.saturating_sub(self.delivered)
+    }
+
+    pub(crate) fn with_kernel(mut self, stat: Option<Stat>) -> Self {
+        self.received = stat.map(|stat| stat.received);
+        self.dropped = stat.map(|stat| stat.dropped);
+        self.if_dropped = stat
+            .filter(|_| cfg!(target_os = "linux"))
+            .map(|stat| stat.if_dropped);
+        self
+    }
+}
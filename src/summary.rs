@@ -0,0 +1,82 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Why a capture's background thread exited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndReason {
+    /// [`AsyncCaptureHandle::stop()`](crate::AsyncCaptureHandle::stop) was called
+    Stopped,
+    /// The source had no more packets, e.g. the end of a savefile
+    SourceEnded,
+    /// The receiving side was dropped
+    ReceiverDropped,
+    /// A read error was reported as a startup failure
+    StartupFailed,
+    /// A read failed with an error the [`RetryPolicy`](crate::RetryPolicy)
+    /// does not retry, or after its retries ran out, or
+    /// [`AsyncCaptureBuilder::reconnect()`](crate::AsyncCaptureBuilder::reconnect)
+    /// gave up reopening the device; that final error is delivered to the
+    /// consumer as the last item before the end
+    ReadFailed,
+    /// A capture limit such as
+    /// [`AsyncCaptureBuilder::max_bytes()`](crate::AsyncCaptureBuilder::max_bytes)
+    /// was reached
+    LimitReached,
+    /// The capture thread panicked; the message is in
+    /// [`CaptureSummary::last_error`]
+    Panicked,
+}
+
+/// Final report of a capture, produced just before its thread exits.
+///
+/// Returned by
+/// [`AsyncCaptureHandle::wait_stopped()`](crate::AsyncCaptureHandle::wait_stopped)
+/// and [`AsyncCaptureHandle::summary()`](crate::AsyncCaptureHandle::summary).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureSummary {
+    /// Why the capture ended
+    pub reason: EndReason,
+    /// Packets read from the source, including ones that were filtered out
+    /// on the capture thread
+    pub received: u64,
+    /// Packets dropped by the kernel, if the source reports statistics
+    pub dropped: Option<u32>,
+    /// Time from the start of the read loop until it ended
+    pub duration: Duration,
+    /// Message of the last error read from the source
+    pub last_error: Option<String>,
+}
+
+type Callback = Box<dyn FnOnce(CaptureSummary) + Send>;
+
+/// Callback run with the final report when the capture ends.
+///
+/// Shared between clones of the builder it was registered on, so that it
+/// still runs only once.
+#[derive(Clone)]
+pub(crate) struct StopHook(Arc<Mutex<Option<Callback>>>);
+
+impl StopHook {
+    pub(crate) fn new(callback: Callback) -> Self {
+        Self(Arc::new(Mutex::new(Some(callback))))
+    }
+
+    /// Runs the callback, unless it already ran.
+    pub(crate) fn run(&self, summary: CaptureSummary) {
+        let callback = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take();
+        if let Some(callback) = callback {
+            callback(summary);
+        }
+    }
+}
+
+impl fmt::Debug for StopHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StopHook").finish_non_exhaustive()
+    }
+}
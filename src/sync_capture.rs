@@ -0,0 +1,124 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use pcap::Linktype;
+
+use crate::async_pcap::{AsyncCapture, AsyncCaptureHandle, Packet};
+use crate::error::Error;
+use crate::info::CaptureInfo;
+use crate::local::LocalAddresses;
+use crate::source::PacketSource;
+use crate::worker::{PacketReceiver, Shared};
+
+/// A blocking counterpart of [`AsyncCapture`] for code that does not use
+/// an async runtime.
+///
+/// Packets are read by the same background thread and delivered in the
+/// same order as with `AsyncCapture`; only the receiving side blocks the
+/// calling thread instead of awaiting. The capture is stopped with the
+/// usual [`AsyncCaptureHandle`], and every capture option of
+/// [`AsyncCaptureBuilder`](crate::AsyncCaptureBuilder) applies; open one
+/// with [`AsyncCaptureBuilder::open_sync()`](crate::AsyncCaptureBuilder::open_sync).
+///
+/// `SyncCapture` is also an [`Iterator`] over the packets, which ends when
+/// the capture stops.
+///
+/// # Panics
+///
+/// [`next_packet()`](Self::next_packet) panics if called from within an
+/// asynchronous execution context; use `AsyncCapture` there.
+pub struct SyncCapture {
+    rx: Mutex<PacketReceiver>,
+    info: CaptureInfo,
+    shared: Arc<Shared>,
+}
+
+impl SyncCapture {
+    /// Creates a new blocking capture from a `pcap::Capture<Active>` or any
+    /// other [`PacketSource`].
+    ///
+    /// Spawns a background thread that reads packets, like
+    /// [`AsyncCapture::new()`].
+    pub fn new<S: PacketSource>(source: S) -> (Self, AsyncCaptureHandle) {
+        let (capture, handle) = AsyncCapture::new(source);
+        (capture.into_sync(), handle)
+    }
+
+    pub(crate) fn from_parts(rx: PacketReceiver, info: CaptureInfo, shared: Arc<Shared>) -> Self {
+        Self {
+            rx: Mutex::new(rx),
+            info,
+            shared,
+        }
+    }
+
+    /// Returns the parameters the capture is actually running with.
+    pub fn info(&self) -> &CaptureInfo {
+        &self.info
+    }
+
+    /// Returns the snaplen granted by libpcap, which may be lower than
+    /// the one that was requested.
+    pub fn effective_snaplen(&self) -> i32 {
+        self.info.snaplen
+    }
+
+    /// Returns the link-layer header type of the captured packets.
+    pub fn datalink(&self) -> Linktype {
+        self.info.datalink
+    }
+
+    /// Returns the addresses of the capturing interface, recorded when it
+    /// was opened by [`AsyncCaptureBuilder`](crate::AsyncCaptureBuilder).
+    ///
+    /// Empty for captures created from any other source.
+    pub fn local_addresses(&self) -> &LocalAddresses {
+        &self.shared.local
+    }
+
+    /// Blocks until the next packet is available.
+    ///
+    /// Returns `Some(Result<Packet, Error>)` if a packet is received,
+    /// or `None` if the capture has stopped. Concurrent callers each get
+    /// distinct packets, as with [`AsyncCapture::next_packet()`].
+    pub fn next_packet(&self) -> Option<Result<Packet, Error>> {
+        let mut rx = self.rx.lock().unwrap();
+        let (pkt, annotations) = rx.blocking_recv()?;
+        self.shared.delivered(annotations);
+        Some(pkt)
+    }
+}
+
+impl AsyncCapture {
+    /// Converts the capture into a [`SyncCapture`] that delivers the
+    /// remaining packets through blocking calls.
+    pub fn into_sync(self) -> SyncCapture {
+        let (rx, info, shared) = self.into_parts();
+        SyncCapture::from_parts(rx, info, shared)
+    }
+}
+
+impl Iterator for SyncCapture {
+    type Item = Result<Packet, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_packet()
+    }
+}
+
+impl Iterator for &SyncCapture {
+    type Item = Result<Packet, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_packet()
+    }
+}
+
+impl fmt::Debug for SyncCapture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SyncCapture")
+            .field("snaplen", &self.info.snaplen)
+            .field("datalink", &self.info.datalink)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::clock::Clock;
+
+/// Number of buckets the rolling window is divided into.
+const BUCKETS: u32 = 10;
+
+/// Current capture throughput over the rolling window.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Throughput {
+    /// Packets per second
+    pub pps: f64,
+    /// Bits per second, from the original (on the wire) packet lengths
+    pub bps: f64,
+}
+
+/// Rolling-window packet and byte rate tracker.
+///
+/// The window is split into buckets keyed by the monotonic time at which
+/// packets were captured. Rates are computed over the buckets that fall in
+/// the window ending now, so an idle capture decays to zero. Until a full
+/// window has elapsed the rate is computed over the elapsed time (but never
+/// less than one bucket), which avoids a spike on the first packets.
+#[derive(Debug)]
+pub(crate) struct ThroughputMeter {
+    clock: Arc<dyn Clock>,
+    bucket_len: Duration,
+    start: Instant,
+    buckets: Mutex<Vec<Bucket>>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Bucket {
+    index: u64,
+    packets: u64,
+    bytes: u64,
+}
+
+impl ThroughputMeter {
+    pub(crate) fn new(window: Duration, clock: Arc<dyn Clock>) -> Self {
+        let bucket_len = (window / BUCKETS).max(Duration::from_millis(1));
+        Self {
+            start: clock.now(),
+            clock,
+            bucket_len,
+            buckets: Mutex::new(vec![Bucket::default(); BUCKETS as usize]),
+        }
+    }
+
+    fn index(&self, now: Instant) -> u64 {
+        (now.duration_since(self.start).as_nanos() / self.bucket_len.as_nanos()) as u64
+    }
+
+    /// Records one packet of `len` bytes.
+    pub(crate) fn record(&self, len: u32) {
+        let index = self.index(self.clock.now());
+        let mut buckets = self.lock();
+        let bucket = &mut buckets[(index % u64::from(BUCKETS)) as usize];
+        if bucket.index != index {
+            *bucket = Bucket {
+                index,
+                ..Bucket::default()
+            };
+        }
+        bucket.packets += 1;
+        bucket.bytes += u64::from(len);
+    }
+
+    /// Returns the rates over the window ending now.
+    pub(crate) fn current(&self) -> Throughput {
+        let now = self.clock.now();
+        let current = self.index(now);
+        let oldest = current.saturating_sub(u64::from(BUCKETS) - 1);
+        let (packets, bytes) = self
+            .lock()
+            .iter()
+            .filter(|b| b.index >= oldest && b.index <= current && b.packets > 0)
+            .fold((0u64, 0u64), |(p, b), bucket| {
+                (p + bucket.packets, b + bucket.bytes)
+            });
+
+        let elapsed = now.duration_since(self.start);
+        let into_bucket =
+            Duration::from_nanos((elapsed.as_nanos() % self.bucket_len.as_nanos()) as u64);
+        let span = if current < u64::from(BUCKETS) {
+            elapsed
+        } else {
+            self.bucket_len * (BUCKETS - 1) + into_bucket
+        };
+        let secs = span.max(self.bucket_len).as_secs_f64();
+        Throughput {
+            pps: packets as f64 / secs,
+            bps: bytes as f64 * 8.0 / secs,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Bucket>> {
+        self.buckets.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
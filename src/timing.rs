@@ -0,0 +1,77 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Monotonic timestamps of one packet's trip through the capture channel,
+/// read from the capture's [`Clock`](crate::Clock).
+///
+/// Only recorded when
+/// [`AsyncCaptureBuilder::timing_instrumentation()`](crate::AsyncCaptureBuilder::timing_instrumentation)
+/// is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketTiming {
+    /// When the capture thread queued the packet
+    pub enqueued_at: Instant,
+    /// When the consumer received the packet
+    pub delivered_at: Instant,
+}
+
+impl PacketTiming {
+    /// Time the packet spent waiting in the channel.
+    pub fn dwell(&self) -> Duration {
+        self.delivered_at
+            .saturating_duration_since(self.enqueued_at)
+    }
+}
+
+/// Aggregate queue dwell time of the packets delivered so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QueueDwell {
+    /// Number of packets measured
+    pub packets: u64,
+    /// Dwell time of the most recently delivered packet
+    pub last: Duration,
+    /// Mean dwell time
+    pub mean: Duration,
+    /// Longest dwell time seen
+    pub max: Duration,
+}
+
+/// Accumulates [`PacketTiming`]s into a [`QueueDwell`].
+#[derive(Debug, Default)]
+pub(crate) struct DwellRecorder {
+    inner: Mutex<Totals>,
+}
+
+#[derive(Debug, Default)]
+struct Totals {
+    packets: u64,
+    total: Duration,
+    last: Duration,
+    max: Duration,
+}
+
+impl DwellRecorder {
+    pub(crate) fn record(&self, timing: &PacketTiming) {
+        let dwell = timing.dwell();
+        let mut totals = self.inner.lock().unwrap();
+        totals.packets += 1;
+        totals.total = totals.total.saturating_add(dwell);
+        totals.last = dwell;
+        totals.max = totals.max.max(dwell);
+    }
+
+    pub(crate) fn current(&self) -> QueueDwell {
+        let totals = self.inner.lock().unwrap();
+        let mean = match u32::try_from(totals.packets) {
+            Ok(0) => Duration::ZERO,
+            Ok(n) => totals.total / n,
+            Err(_) => Duration::from_secs_f64(totals.total.as_secs_f64() / totals.packets as f64),
+        };
+        QueueDwell {
+            packets: totals.packets,
+            last: totals.last,
+            mean,
+            max: totals.max,
+        }
+    }
+}
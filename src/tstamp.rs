@@ -0,0 +1,72 @@
+use std::ffi::{c_int, c_void};
+use std::ptr;
+
+use pcap::{Capture, Inactive, Precision};
+
+unsafe extern "C" {
+    fn pcap_list_tstamp_types(p: *mut c_void, types: *mut *mut c_int) -> c_int;
+    fn pcap_free_tstamp_types(types: *mut c_int);
+    fn pcap_set_tstamp_type(p: *mut c_void, tstamp_type: c_int) -> c_int;
+    fn pcap_set_tstamp_precision(p: *mut c_void, tstamp_precision: c_int) -> c_int;
+}
+
+/// `PCAP_TSTAMP_ADAPTER`
+const TSTAMP_ADAPTER: c_int = 3;
+/// `PCAP_TSTAMP_ADAPTER_UNSYNCED`
+const TSTAMP_ADAPTER_UNSYNCED: c_int = 4;
+
+/// Clock that stamped the packets of a capture.
+///
+/// Reported by [`CaptureInfo::timestamp_source`](crate::CaptureInfo::timestamp_source);
+/// it applies to the `header.ts` of every packet of the capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TimestampSource {
+    /// The host stamped packets when the kernel processed them, some
+    /// unknown time after they arrived at the adapter
+    #[default]
+    Host,
+    /// The network adapter stamped packets on arrival, with its clock
+    /// synchronized to the system clock
+    Adapter,
+    /// The network adapter stamped packets on arrival with its own
+    /// free-running clock, which is not synchronized to the system clock
+    AdapterUnsynced,
+}
+
+/// Asks for adapter timestamps if the device offers them, preferring ones
+/// synchronized with the system clock.
+///
+/// Returns the source requested, or `None` if the device only offers host
+/// timestamps.
+pub(crate) fn request_adapter(cap: &Capture<Inactive>) -> Option<TimestampSource> {
+    let handle = cap.as_ptr().cast::<c_void>();
+    let mut types = ptr::null_mut();
+    // SAFETY: the handle is valid while `cap` is borrowed, and the list
+    // allocated by libpcap is freed once it has been copied.
+    let supported = unsafe {
+        let count = pcap_list_tstamp_types(handle, &mut types);
+        if count <= 0 || types.is_null() {
+            return None;
+        }
+        let list = std::slice::from_raw_parts(types, count as usize).to_vec();
+        pcap_free_tstamp_types(types);
+        list
+    };
+    let (value, source) = [
+        (TSTAMP_ADAPTER, TimestampSource::Adapter),
+        (TSTAMP_ADAPTER_UNSYNCED, TimestampSource::AdapterUnsynced),
+    ]
+    .into_iter()
+    .find(|(value, _)| supported.contains(value))?;
+    // SAFETY: as above
+    let status = unsafe { pcap_set_tstamp_type(handle, value) };
+    (status == 0).then_some(source)
+}
+
+/// Asks for timestamps with `precision`, returning `false` if the device
+/// does not offer it.
+pub(crate) fn request_precision(cap: &Capture<Inactive>, precision: Precision) -> bool {
+    // SAFETY: the handle is valid while `cap` is borrowed.
+    let status = unsafe { pcap_set_tstamp_precision(cap.as_ptr().cast(), precision as c_int) };
+    status == 0
+}
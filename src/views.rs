@@ -0,0 +1,306 @@
+use std::fmt;
+use std::net::IpAddr;
+
+use pcap::Linktype;
+
+use crate::async_pcap::Packet;
+use crate::parse::{self, IPPROTO_TCP, IPPROTO_UDP, IpLayer, be16, be32};
+
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+
+/// Length of an Ethernet header without VLAN tags.
+const ETHERNET_HEADER_LEN: usize = 14;
+
+/// Ethernet header of a packet, borrowed from its data.
+///
+/// Returned by [`Packet::ethernet()`]. Requires the `parse` feature.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct EthernetView<'a> {
+    data: &'a [u8],
+    /// Offset of the innermost EtherType, past any VLAN tags
+    ethertype_at: usize,
+}
+
+impl<'a> EthernetView<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut ethertype_at = 12;
+        while matches!(be16(data, ethertype_at)?, ETHERTYPE_VLAN | ETHERTYPE_QINQ) {
+            ethertype_at += 4;
+        }
+        Some(Self { data, ethertype_at })
+    }
+
+    /// Destination MAC address.
+    pub fn dst(&self) -> [u8; 6] {
+        self.data[..6].try_into().unwrap()
+    }
+
+    /// Source MAC address.
+    pub fn src(&self) -> [u8; 6] {
+        self.data[6..12].try_into().unwrap()
+    }
+
+    /// EtherType of the payload, past any VLAN tags.
+    pub fn ethertype(&self) -> u16 {
+        be16(self.data, self.ethertype_at).unwrap_or_default()
+    }
+
+    /// VLAN identifier of the outermost 802.1Q or 802.1ad tag, if the frame
+    /// is tagged.
+    pub fn vlan(&self) -> Option<u16> {
+        (self.ethertype_at > 12).then(|| be16(self.data, 14).unwrap_or_default() & 0x0fff)
+    }
+
+    /// The header, VLAN tags included.
+    pub fn header(&self) -> &'a [u8] {
+        &self.data[..self.ethertype_at + 2]
+    }
+
+    /// What follows the header.
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[self.ethertype_at + 2..]
+    }
+}
+
+impl fmt::Debug for EthernetView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EthernetView")
+            .field("dst", &self.dst())
+            .field("src", &self.src())
+            .field("ethertype", &format_args!("{:#06x}", self.ethertype()))
+            .field("vlan", &self.vlan())
+            .finish_non_exhaustive()
+    }
+}
+
+/// IPv4 or IPv6 header of a packet, borrowed from its data.
+///
+/// Returned by [`Packet::ip()`]. Requires the `parse` feature.
+#[derive(Clone, Copy)]
+pub struct IpView<'a> {
+    layer: IpLayer<'a>,
+}
+
+impl<'a> IpView<'a> {
+    /// IP version, 4 or 6.
+    pub fn version(&self) -> u8 {
+        self.layer.header[0] >> 4
+    }
+
+    /// Source address.
+    pub fn src(&self) -> IpAddr {
+        self.layer.src
+    }
+
+    /// Destination address.
+    pub fn dst(&self) -> IpAddr {
+        self.layer.dst
+    }
+
+    /// IP protocol number of the transport layer, found past any IPv6
+    /// extension headers.
+    pub fn protocol(&self) -> u8 {
+        self.layer.protocol
+    }
+
+    /// IPv4 time to live, or IPv6 hop limit.
+    pub fn ttl(&self) -> u8 {
+        self.layer.ttl
+    }
+
+    /// Whether the packet is a fragment rather than a complete datagram.
+    pub fn is_fragment(&self) -> bool {
+        self.layer.fragmented
+    }
+
+    /// Whether the snaplen cut the datagram short.
+    pub fn is_truncated(&self) -> bool {
+        self.layer.truncated
+    }
+
+    /// The header, IPv4 options and IPv6 extension headers included.
+    pub fn header(&self) -> &'a [u8] {
+        self.layer.header
+    }
+
+    /// The transport header and payload, without any link-layer padding.
+    pub fn payload(&self) -> &'a [u8] {
+        self.layer.payload
+    }
+}
+
+impl fmt::Debug for IpView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IpView")
+            .field("src", &self.src())
+            .field("dst", &self.dst())
+            .field("protocol", &self.protocol())
+            .field("ttl", &self.ttl())
+            .field("fragment", &self.is_fragment())
+            .field("payload_len", &self.payload().len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// TCP header of a packet, borrowed from its data.
+///
+/// Returned by [`Packet::tcp()`]. Requires the `parse` feature.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct TcpView<'a> {
+    data: &'a [u8],
+    header_len: usize,
+}
+
+impl<'a> TcpView<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let segment = parse::tcp(data)?;
+        Some(Self {
+            data,
+            header_len: data.len() - segment.payload.len(),
+        })
+    }
+
+    /// Source port.
+    pub fn src_port(&self) -> u16 {
+        be16(self.data, 0).unwrap_or_default()
+    }
+
+    /// Destination port.
+    pub fn dst_port(&self) -> u16 {
+        be16(self.data, 2).unwrap_or_default()
+    }
+
+    /// Sequence number.
+    pub fn seq(&self) -> u32 {
+        be32(self.data, 4).unwrap_or_default()
+    }
+
+    /// Acknowledgment number, meaningful if the ACK flag is set.
+    pub fn ack(&self) -> u32 {
+        be32(self.data, 8).unwrap_or_default()
+    }
+
+    /// Flag bits: FIN is `0x01`, SYN `0x02`, RST `0x04`, PSH `0x08`, ACK
+    /// `0x10` and so on.
+    pub fn flags(&self) -> u8 {
+        self.data[13]
+    }
+
+    /// Receive window, before any window scaling.
+    pub fn window(&self) -> u16 {
+        be16(self.data, 14).unwrap_or_default()
+    }
+
+    /// The header, options included.
+    pub fn header(&self) -> &'a [u8] {
+        &self.data[..self.header_len]
+    }
+
+    /// The segment's payload.
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[self.header_len..]
+    }
+}
+
+impl fmt::Debug for TcpView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TcpView")
+            .field("src_port", &self.src_port())
+            .field("dst_port", &self.dst_port())
+            .field("seq", &self.seq())
+            .field("flags", &format_args!("{:#04x}", self.flags()))
+            .field("payload_len", &self.payload().len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// UDP header of a packet, borrowed from its data.
+///
+/// Returned by [`Packet::udp()`]. Requires the `parse` feature.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct UdpView<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> UdpView<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        (data.len() >= 8).then_some(Self { data })
+    }
+
+    /// Source port.
+    pub fn src_port(&self) -> u16 {
+        be16(self.data, 0).unwrap_or_default()
+    }
+
+    /// Destination port.
+    pub fn dst_port(&self) -> u16 {
+        be16(self.data, 2).unwrap_or_default()
+    }
+
+    /// Length of the header and payload, as given by the header.
+    pub fn length(&self) -> u16 {
+        be16(self.data, 4).unwrap_or_default()
+    }
+
+    /// The datagram's payload, trimmed to the header's length and to what
+    /// was captured.
+    pub fn payload(&self) -> &'a [u8] {
+        let end = usize::from(self.length()).clamp(8, self.data.len());
+        &self.data[8..end]
+    }
+}
+
+impl fmt::Debug for UdpView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpView")
+            .field("src_port", &self.src_port())
+            .field("dst_port", &self.dst_port())
+            .field("length", &self.length())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Packet {
+    /// Returns the Ethernet header of the packet, or `None` unless
+    /// `datalink` is [`Linktype::ETHERNET`] and the header was captured.
+    ///
+    /// Requires the `parse` feature.
+    pub fn ethernet(&self, datalink: Linktype) -> Option<EthernetView<'_>> {
+        if datalink != Linktype::ETHERNET || self.data.len() < ETHERNET_HEADER_LEN {
+            return None;
+        }
+        EthernetView::parse(&self.data)
+    }
+
+    /// Returns the IPv4 or IPv6 header of the packet.
+    ///
+    /// The link-layer header is stripped as by
+    /// [`headers()`](Self::headers), for the same link types. Returns
+    /// `None` if the packet is not IP. Requires the `parse` feature.
+    pub fn ip(&self, datalink: Linktype) -> Option<IpView<'_>> {
+        let layer = parse::ip_layer(parse::network_payload(datalink, &self.data)?)?;
+        Some(IpView { layer })
+    }
+
+    /// Returns the TCP header of the packet, or `None` if it is not TCP or
+    /// is a fragment. Requires the `parse` feature.
+    pub fn tcp(&self, datalink: Linktype) -> Option<TcpView<'_>> {
+        self.transport(datalink, IPPROTO_TCP)
+            .and_then(TcpView::parse)
+    }
+
+    /// Returns the UDP header of the packet, or `None` if it is not UDP or
+    /// is a fragment. Requires the `parse` feature.
+    pub fn udp(&self, datalink: Linktype) -> Option<UdpView<'_>> {
+        self.transport(datalink, IPPROTO_UDP)
+            .and_then(UdpView::parse)
+    }
+
+    /// Returns the transport header and payload if the packet carries
+    /// `protocol` in a complete datagram.
+    fn transport(&self, datalink: Linktype, protocol: u8) -> Option<&[u8]> {
+        let ip = self.ip(datalink)?;
+        (ip.protocol() == protocol && !ip.is_fragment()).then(|| ip.payload())
+    }
+}
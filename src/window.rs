@@ -0,0 +1,135 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::async_pcap::{AsyncCapture, Packet};
+
+/// Packets whose capture timestamps fall in one time window.
+#[derive(Debug, Clone)]
+pub struct TimeWindow {
+    /// Start of the window, as a duration since the Unix epoch
+    pub start: Duration,
+    /// Length of the window
+    pub length: Duration,
+    /// Packets of the window, in arrival order
+    pub packets: Vec<Packet>,
+}
+
+/// Groups the packets of a capture into fixed windows of capture time.
+///
+/// Returned by [`AsyncCapture::window_by_time()`]. Windows are aligned to
+/// multiples of their length since the Unix epoch, so with a one second
+/// window every batch covers one whole second of timestamps.
+///
+/// # Notes
+///
+/// - A window is complete once a packet from a later window arrives, so
+///   on a quiet link it is returned only when traffic resumes, or when the
+///   capture stops, which flushes the last partial window.
+/// - Packets stamped earlier than the current window (out-of-order
+///   timestamps, e.g. from several capture queues) are added to the
+///   current window rather than to one already returned.
+/// - Windows without packets are skipped unless
+///   [`emit_empty()`](Self::emit_empty) is set.
+/// - Capture errors are discarded.
+#[derive(Debug)]
+pub struct TimeWindows {
+    capture: AsyncCapture,
+    length: Duration,
+    emit_empty: bool,
+    current: Option<TimeWindow>,
+    /// Windows closed but not yet returned
+    ready: VecDeque<TimeWindow>,
+}
+
+impl TimeWindows {
+    /// Also returns a window with no packets for every window without
+    /// traffic between two packets.
+    ///
+    /// A long idle gap yields as many empty windows as it spans, so keep
+    /// the window length in proportion to the expected gaps.
+    pub fn emit_empty(mut self, enabled: bool) -> Self {
+        self.emit_empty = enabled;
+        self
+    }
+
+    /// Returns the underlying capture.
+    pub fn capture(&self) -> &AsyncCapture {
+        &self.capture
+    }
+
+    /// Waits for the next complete window.
+    ///
+    /// Returns `None` once the capture has stopped and every window has
+    /// been returned.
+    pub async fn next_window(&mut self) -> Option<TimeWindow> {
+        loop {
+            if let Some(window) = self.ready.pop_front() {
+                return Some(window);
+            }
+            let Some(item) = self.capture.next_packet().await else {
+                return self.current.take();
+            };
+            if let Ok(packet) = item {
+                self.add(packet);
+            }
+        }
+    }
+
+    fn add(&mut self, packet: Packet) {
+        let start = self.window_start(packet.since_epoch_with(self.capture.info().precision));
+        let current = match &mut self.current {
+            Some(current) if start <= current.start => current,
+            _ => {
+                let next = TimeWindow {
+                    start,
+                    length: self.length,
+                    packets: Vec::new(),
+                };
+                if let Some(closed) = self.current.replace(next) {
+                    let mut gap = closed.start + self.length;
+                    self.ready.push_back(closed);
+                    while self.emit_empty && gap < start {
+                        self.ready.push_back(TimeWindow {
+                            start: gap,
+                            length: self.length,
+                            packets: Vec::new(),
+                        });
+                        gap += self.length;
+                    }
+                }
+                self.current.as_mut().unwrap()
+            }
+        };
+        current.packets.push(packet);
+    }
+
+    fn window_start(&self, ts: Duration) -> Duration {
+        let length = self.length.as_nanos();
+        let start = ts.as_nanos() / length * length;
+        Duration::new(
+            (start / 1_000_000_000) as u64,
+            (start % 1_000_000_000) as u32,
+        )
+    }
+}
+
+impl AsyncCapture {
+    /// Groups the packets into windows of `length` by their capture
+    /// timestamps.
+    ///
+    /// See [`TimeWindows`] for how windows are formed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `length` is zero.
+    pub fn window_by_time(self, length: Duration) -> TimeWindows {
+        assert!(!length.is_zero(), "time window length must be non-zero");
+        TimeWindows {
+            capture: self,
+            length,
+            emit_empty: false,
+            current: None,
+            ready: VecDeque::new(),
+        }
+    }
+}
@@ -0,0 +1,2125 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::poll_fn;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError, sync_channel};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::task::Poll;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+
+use pcap::{Active, BreakLoop, Capture, PacketHeader, Stat};
+use tokio::sync::Notify;
+use tokio::sync::mpsc::{Sender, UnboundedReceiver, UnboundedSender};
+
+use crate::async_pcap::{Packet, StreamItem};
+use crate::backoff::{Backoff, IdleBackoff};
+#[cfg(feature = "checksums")]
+use crate::checksum::Checksums;
+use crate::clock::{Clock, SystemClock};
+use crate::config::CaptureConfig;
+use crate::dedup::{DedupMode, Deduplicator};
+use crate::drop_monitor::{DROP_SAMPLE_INTERVAL, DropMonitor, DropThreshold};
+use crate::error::{Error, ErrorKind};
+use crate::filter::{FilterPipeline, LengthBounds, Prefilter};
+use crate::gate::StartGate;
+use crate::headers::DEFAULT_HEADERS_FALLBACK;
+use crate::local::LocalAddresses;
+use crate::log_limit::LogLimiter;
+use crate::logging::{ErrorHook, debug, error, info, warn};
+use crate::meta::{Annotations, PacketMeta};
+use crate::nonblock;
+use crate::offload::OffloadDetector;
+use crate::pause::{Pause, PausedWindow};
+use crate::pool::BufferPool;
+use crate::probe::{ReadProbe, ReadRequest};
+#[cfg(feature = "process-info")]
+use crate::process::ProcessTable;
+use crate::queue::{OverflowPolicy, QueueBound};
+use crate::rate_limit::{RateLimit, TokenBucket};
+use crate::reconnect::{LinkEvent, Reconnect, Reconnection};
+use crate::recorder::RingRecorder;
+use crate::retry::RetryPolicy;
+#[cfg(feature = "ring-file")]
+use crate::ring_file::RingFile;
+use crate::sample::{Sampler, Sampling};
+use crate::schedule::CaptureSchedule;
+use crate::source::PacketSource;
+use crate::summary::{CaptureSummary, EndReason, StopHook};
+use crate::throughput::ThroughputMeter;
+use crate::timing::DwellRecorder;
+use crate::tstamp::TimestampSource;
+
+/// Packets [`AsyncCapture::next_batch()`](crate::AsyncCapture::next_batch)
+/// reserves room for up front, so that a large `max` does not allocate for
+/// packets that never arrive.
+#[cfg(feature = "rt-tokio")]
+const MAX_BATCH_RESERVE: usize = 1024;
+
+/// Rolling window used for throughput when none is configured.
+const DEFAULT_THROUGHPUT_WINDOW: Duration = Duration::from_secs(1);
+
+/// Longest single sleep taken by the capture thread, so that a stop request
+/// is noticed promptly even while throttling.
+const MAX_SLEEP_SLICE: Duration = Duration::from_millis(50);
+
+/// A packet queued by the capture thread, with its side information.
+pub(crate) type Queued = (Result<Packet, Error>, Annotations);
+
+/// Enum used internally to represent either a captured packet
+/// or a stop signal to terminate the capture.
+#[derive(Debug)]
+pub(crate) enum PacketOrStop {
+    /// A captured packet, with the side information gathered for it
+    Packet(Result<Packet, Error>, Annotations),
+    /// Several packets sent in one channel operation
+    Batch(Vec<Queued>),
+    /// Periodic capture statistics
+    Stats(Stat),
+    /// The device was lost or reopened, or the capture's state changed
+    Event(LinkEvent),
+    /// Signal that capture has stopped
+    Stop,
+}
+
+/// How the capture thread groups packets before sending them.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SendBatch {
+    /// Number of packets at which a batch is sent
+    pub(crate) max_packets: usize,
+    /// Longest time the first packet of a batch waits to be sent
+    pub(crate) max_delay: Duration,
+}
+
+/// Packets accumulated for the next batch.
+pub(crate) struct Batcher {
+    clock: Arc<dyn Clock>,
+    config: SendBatch,
+    items: Vec<Queued>,
+    opened: Instant,
+}
+
+impl Batcher {
+    fn new(config: SendBatch, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            opened: clock.now(),
+            clock,
+            config,
+            items: Vec::with_capacity(config.max_packets),
+        }
+    }
+
+    /// Adds an item, returning `true` once the batch should be sent.
+    fn push(&mut self, item: Queued) -> bool {
+        if self.items.is_empty() {
+            self.opened = self.clock.now();
+        }
+        self.items.push(item);
+        self.items.len() >= self.config.max_packets || self.is_due()
+    }
+
+    /// Whether a non-empty batch has waited for its maximum delay.
+    fn is_due(&self) -> bool {
+        !self.items.is_empty()
+            && self.clock.now().saturating_duration_since(self.opened) >= self.config.max_delay
+    }
+
+    fn take(&mut self) -> Vec<Queued> {
+        std::mem::replace(&mut self.items, Vec::with_capacity(self.config.max_packets))
+    }
+}
+
+/// Where the capture thread delivers packets.
+pub(crate) enum Output {
+    /// The channel owned by an [`AsyncCapture`](crate::AsyncCapture),
+    /// optionally batching packets
+    Internal(UnboundedSender<PacketOrStop>, Option<Batcher>),
+    /// A channel supplied by the user; closing it signals the end
+    External(Sender<Result<Packet, Error>>),
+}
+
+impl Output {
+    /// Delivers packets through the internal channel, in batches if
+    /// `batch` is set.
+    pub(crate) fn internal(
+        tx: UnboundedSender<PacketOrStop>,
+        batch: Option<SendBatch>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        let batcher = batch
+            .filter(|config| config.max_packets > 1)
+            .map(|config| Batcher::new(config, clock));
+        Output::Internal(tx, batcher)
+    }
+
+    /// Delivers one item, returning `false` once the receiver is gone.
+    ///
+    /// A full external channel blocks the capture thread until there is room.
+    /// Annotations are only delivered through the internal channel, stamped
+    /// with `enqueued_at`, the clock time the item is queued at, so a
+    /// batched packet's dwell includes the time spent in its batch.
+    fn send(
+        &mut self,
+        item: Result<Packet, Error>,
+        mut annotations: Annotations,
+        enqueued_at: Option<Instant>,
+    ) -> bool {
+        match self {
+            Output::Internal(tx, None) => {
+                annotations.enqueued_at = enqueued_at;
+                tx.send(PacketOrStop::Packet(item, annotations)).is_ok()
+            }
+            Output::Internal(tx, Some(batch)) => {
+                annotations.enqueued_at = enqueued_at;
+                if batch.push((item, annotations)) {
+                    tx.send(PacketOrStop::Batch(batch.take())).is_ok()
+                } else {
+                    !tx.is_closed()
+                }
+            }
+            Output::External(tx) => tx.blocking_send(item).is_ok(),
+        }
+    }
+
+    /// Sends any partial batch, returning `false` once the receiver is
+    /// gone.
+    fn flush(&mut self) -> bool {
+        match self {
+            Output::Internal(tx, Some(batch)) if !batch.items.is_empty() => {
+                tx.send(PacketOrStop::Batch(batch.take())).is_ok()
+            }
+            _ => !self.is_closed(),
+        }
+    }
+
+    /// Whether the receiver is gone.
+    fn is_closed(&self) -> bool {
+        match self {
+            Output::Internal(tx, _) => tx.is_closed(),
+            Output::External(tx) => tx.is_closed(),
+        }
+    }
+
+    /// Sends a partial batch whose maximum delay has elapsed, returning
+    /// `false` once the receiver is gone.
+    fn flush_due(&mut self) -> bool {
+        match self {
+            Output::Internal(tx, Some(batch)) if batch.is_due() => {
+                tx.send(PacketOrStop::Batch(batch.take())).is_ok()
+            }
+            _ => true,
+        }
+    }
+
+    /// Sends any partial batch, then the statistics, so that they follow
+    /// the packets read before them. Returns `false` once the receiver is
+    /// gone; only the internal channel carries statistics.
+    fn send_stats(&mut self, stat: Stat) -> bool {
+        match self {
+            Output::Internal(tx, batch) => {
+                if let Some(batch) = batch
+                    && !batch.items.is_empty()
+                    && tx.send(PacketOrStop::Batch(batch.take())).is_err()
+                {
+                    return false;
+                }
+                tx.send(PacketOrStop::Stats(stat)).is_ok()
+            }
+            Output::External(_) => true,
+        }
+    }
+
+    /// Sends any partial batch, then `event`, so that it follows the packets
+    /// read before it. Returns `false` once the receiver is gone; only the
+    /// internal channel carries events.
+    fn send_event(&mut self, event: LinkEvent) -> bool {
+        match self {
+            Output::Internal(tx, batch) => {
+                if let Some(batch) = batch
+                    && !batch.items.is_empty()
+                    && tx.send(PacketOrStop::Batch(batch.take())).is_err()
+                {
+                    return false;
+                }
+                tx.send(PacketOrStop::Event(event)).is_ok()
+            }
+            Output::External(tx) => !tx.is_closed(),
+        }
+    }
+
+    /// Sends any partial batch, then signals the end of the capture.
+    fn finish(self) {
+        if let Output::Internal(tx, batch) = self {
+            if let Some(mut batch) = batch
+                && !batch.items.is_empty()
+            {
+                let _ = tx.send(PacketOrStop::Batch(batch.take()));
+            }
+            let _ = tx.send(PacketOrStop::Stop);
+        }
+    }
+}
+
+/// One item taken from the internal channel.
+pub(crate) enum Delivery {
+    Packet(Queued),
+    Stats(Stat),
+    Event(LinkEvent),
+}
+
+/// Receiving end of the internal channel, which unpacks batches so that
+/// packets are still handed out one at a time.
+pub(crate) struct PacketReceiver {
+    /// Shared with the capture thread only to evict packets from a full
+    /// bounded queue; never locked across an await
+    rx: Arc<Mutex<UnboundedReceiver<PacketOrStop>>>,
+    shared: Arc<Shared>,
+    /// Items received but not yet handed out
+    pending: VecDeque<Delivery>,
+    /// Set once the end of the capture was received; the channel is not
+    /// polled again
+    ended: bool,
+    /// Set once the end of the capture was handed out as
+    /// [`StreamItem::Stopped`]
+    stop_reported: bool,
+}
+
+impl PacketReceiver {
+    pub(crate) fn new(rx: UnboundedReceiver<PacketOrStop>, shared: Arc<Shared>) -> Self {
+        let rx = Arc::new(Mutex::new(rx));
+        if let Some(bound) = &shared.queue_bound {
+            bound.attach(Arc::downgrade(&rx));
+        }
+        Self {
+            rx,
+            shared,
+            pending: VecDeque::new(),
+            ended: false,
+            stop_reported: false,
+        }
+    }
+
+    /// Waits for the next packet along with its completed annotations, or
+    /// `None` once the capture has stopped.
+    pub(crate) async fn next_meta(&mut self) -> Option<(Result<Packet, Error>, PacketMeta)> {
+        let (pkt, annotations) = self.recv().await?;
+        let meta = self.shared.delivered(annotations);
+        Some((pkt, meta))
+    }
+
+    /// Waits for the next packet, statistics or event.
+    ///
+    /// After the end of the capture, whichever method received it, returns
+    /// [`StreamItem::Stopped`] once, then `None`.
+    pub(crate) async fn next_item(&mut self) -> Option<StreamItem> {
+        let Some(delivery) = self.recv_item().await else {
+            if std::mem::replace(&mut self.stop_reported, true) {
+                return None;
+            }
+            // Recorded before the end of the capture is signalled
+            let summary = self
+                .shared
+                .summary
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone();
+            return summary.map(StreamItem::Stopped);
+        };
+        Some(match delivery {
+            Delivery::Packet((pkt, annotations)) => {
+                self.shared.delivered(annotations);
+                StreamItem::Packet(pkt)
+            }
+            Delivery::Stats(stat) => StreamItem::Stats(stat),
+            Delivery::Event(LinkEvent::Disconnected(e)) => StreamItem::Disconnected(e),
+            Delivery::Event(LinkEvent::Reconnected) => StreamItem::Reconnected,
+            Delivery::Event(LinkEvent::Paused) => StreamItem::Paused,
+            Delivery::Event(LinkEvent::Resumed) => StreamItem::Resumed,
+            Delivery::Event(LinkEvent::FilterChanged(filter)) => StreamItem::FilterChanged(filter),
+            Delivery::Event(LinkEvent::KernelDrops(drops)) => StreamItem::KernelDropIncrease(drops),
+        })
+    }
+
+    /// Waits for packets and appends up to `limit` of them to `buf`,
+    /// returning the number appended, `0` once the capture has stopped.
+    pub(crate) async fn next_packets(
+        &mut self,
+        buf: &mut Vec<Result<Packet, Error>>,
+        limit: usize,
+    ) -> usize {
+        if limit == 0 {
+            return 0;
+        }
+        let mut batch = Vec::with_capacity(limit);
+        self.recv_many(&mut batch, limit).await;
+        let before = buf.len();
+        for (pkt, annotations) in batch {
+            self.shared.delivered(annotations);
+            buf.push(pkt);
+        }
+        buf.len() - before
+    }
+
+    /// Collects up to `max` packets, waiting at most `timeout` for them.
+    #[cfg(feature = "rt-tokio")]
+    pub(crate) async fn next_batch(
+        &mut self,
+        max: usize,
+        timeout: Duration,
+    ) -> Vec<Result<Packet, Error>> {
+        let mut batch = Vec::with_capacity(max.min(MAX_BATCH_RESERVE));
+        let deadline = tokio::time::Instant::now() + timeout;
+        while batch.len() < max {
+            let limit = max - batch.len();
+            match tokio::time::timeout_at(deadline, self.next_packets(&mut batch, limit)).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+        }
+        batch
+    }
+
+    /// Waits for the next packet, or `None` once the capture has stopped.
+    /// Statistics and events received on the way are discarded.
+    pub(crate) async fn recv(&mut self) -> Option<Queued> {
+        loop {
+            if let Delivery::Packet(item) = self.recv_item().await? {
+                return Some(item);
+            }
+        }
+    }
+
+    /// Waits for the next packet, statistics or event, or `None` once the
+    /// capture has stopped.
+    pub(crate) async fn recv_item(&mut self) -> Option<Delivery> {
+        loop {
+            if let Some(item) = self.pending.pop_front() {
+                return Some(item);
+            }
+            if self.ended {
+                return None;
+            }
+            let message = poll_fn(|cx| {
+                let mut rx = lock(&self.rx);
+                match self.take_kept() {
+                    Some(message) => Poll::Ready(Some(message)),
+                    None => rx.poll_recv(cx),
+                }
+            })
+            .await;
+            if !self.unpack(message) {
+                return None;
+            }
+        }
+    }
+
+    /// Blocking variant of [`recv()`](Self::recv).
+    pub(crate) fn blocking_recv(&mut self) -> Option<Queued> {
+        loop {
+            match self.pending.pop_front() {
+                Some(Delivery::Packet(item)) => return Some(item),
+                Some(_) => continue,
+                None if self.ended => return None,
+                None => {}
+            }
+            let mut rx = lock(&self.rx);
+            let message = self.take_kept().or_else(|| rx.blocking_recv());
+            drop(rx);
+            if !self.unpack(message) {
+                return None;
+            }
+        }
+    }
+
+    /// Waits for at least one packet, then appends up to `limit` of those
+    /// already queued to `buf`. Returns the number appended, `0` once the
+    /// capture has stopped.
+    pub(crate) async fn recv_many(&mut self, buf: &mut Vec<Queued>, limit: usize) -> usize {
+        loop {
+            while self
+                .pending
+                .front()
+                .is_some_and(|item| !matches!(item, Delivery::Packet(_)))
+            {
+                self.pending.pop_front();
+            }
+            if !self.pending.is_empty() {
+                break;
+            }
+            if self.ended {
+                return 0;
+            }
+            let mut messages = Vec::with_capacity(limit);
+            let received = poll_fn(|cx| {
+                let mut rx = lock(&self.rx);
+                while messages.len() < limit
+                    && let Some(message) = self.take_kept()
+                {
+                    messages.push(message);
+                }
+                if messages.is_empty() {
+                    rx.poll_recv_many(cx, &mut messages, limit)
+                } else {
+                    Poll::Ready(messages.len())
+                }
+            })
+            .await;
+            if received == 0 {
+                self.ended = true;
+                return 0;
+            }
+            for message in messages {
+                if !self.unpack(Some(message)) {
+                    break;
+                }
+            }
+        }
+        let mut n = 0;
+        while n < limit
+            && let Some(item) = self.pending.pop_front()
+        {
+            if let Delivery::Packet(item) = item {
+                buf.push(item);
+                n += 1;
+            }
+        }
+        n
+    }
+
+    /// Queues the packets of `message`, returning `false` at the end of the
+    /// capture, which is remembered.
+    fn unpack(&mut self, message: Option<PacketOrStop>) -> bool {
+        match message {
+            Some(PacketOrStop::Packet(pkt, annotations)) => {
+                self.shared.dequeued.fetch_add(1, Ordering::Relaxed);
+                self.release();
+                self.pending.push_back(Delivery::Packet((pkt, annotations)));
+                true
+            }
+            Some(PacketOrStop::Batch(items)) => {
+                self.shared
+                    .dequeued
+                    .fetch_add(items.len() as u64, Ordering::Relaxed);
+                self.release();
+                self.pending.extend(items.into_iter().map(Delivery::Packet));
+                true
+            }
+            Some(PacketOrStop::Stats(stat)) => {
+                self.pending.push_back(Delivery::Stats(stat));
+                true
+            }
+            Some(PacketOrStop::Event(event)) => {
+                self.pending.push_back(Delivery::Event(event));
+                true
+            }
+            Some(PacketOrStop::Stop) | None => {
+                self.ended = true;
+                false
+            }
+        }
+    }
+
+    /// Takes a message kept back by a bounded queue's eviction; to be
+    /// called with `rx` locked.
+    fn take_kept(&self) -> Option<PacketOrStop> {
+        self.shared.queue_bound.as_ref()?.take_kept()
+    }
+
+    /// Lets a capture thread blocked on a full queue continue.
+    fn release(&self) {
+        if let Some(bound) = &self.shared.queue_bound {
+            bound.release();
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Closure run on the capture thread with access to the live capture.
+pub(crate) type Command = Box<dyn FnOnce(Option<&mut Capture<Active>>) + Send>;
+
+/// Commands queued for the capture thread, run between reads.
+#[derive(Default)]
+pub(crate) struct CommandQueue {
+    /// Set while `commands` is non-empty, so the thread can skip the lock
+    pending: AtomicBool,
+    inner: Mutex<Commands>,
+}
+
+#[derive(Default)]
+struct Commands {
+    queue: Vec<Command>,
+    closed: bool,
+}
+
+impl CommandQueue {
+    /// Queues `command`, returning `false` once the thread has exited.
+    pub(crate) fn push(&self, command: Command) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.closed {
+            return false;
+        }
+        inner.queue.push(command);
+        self.pending.store(true, Ordering::Release);
+        true
+    }
+
+    fn take(&self) -> Vec<Command> {
+        if !self.pending.swap(false, Ordering::Acquire) {
+            return Vec::new();
+        }
+        std::mem::take(&mut self.inner.lock().unwrap().queue)
+    }
+
+    /// Refuses further commands and drops the queued ones.
+    fn close(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.closed = true;
+        inner.queue.clear();
+    }
+}
+
+impl fmt::Debug for CommandQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommandQueue")
+            .field("pending", &self.pending)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Interrupts a blocking read of the live capture, if the source is one.
+#[derive(Default)]
+pub(crate) struct ReadInterrupt(Mutex<Option<BreakLoop>>);
+
+impl ReadInterrupt {
+    fn set(&self, breakloop: BreakLoop) {
+        *self.0.lock().unwrap() = Some(breakloop);
+    }
+
+    /// Makes the read in progress, or the next one, return early.
+    pub(crate) fn interrupt(&self) {
+        if let Some(breakloop) = &*self.0.lock().unwrap() {
+            breakloop.breakloop();
+        }
+    }
+}
+
+impl fmt::Debug for ReadInterrupt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadInterrupt").finish_non_exhaustive()
+    }
+}
+
+/// Options applied by the background capture thread.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CaptureOptions {
+    /// Ceiling on the forwarding rate
+    pub(crate) rate_limit: Option<RateLimit>,
+    /// Gate the read loop waits on before starting
+    pub(crate) start_gate: Option<StartGate>,
+    /// Window during which read errors fail the startup instead of being
+    /// forwarded
+    pub(crate) startup_grace: Option<Duration>,
+    /// Rolling window over which throughput is computed
+    pub(crate) throughput_window: Option<Duration>,
+    /// Suppression of consecutive duplicate packets
+    pub(crate) dedup: Option<DedupMode>,
+    /// Whether packets are stamped when queued to measure dwell time
+    pub(crate) timing: bool,
+    /// Number of initial packets dropped before forwarding
+    pub(crate) discard_first: u64,
+    /// Period after the read loop starts during which packets are dropped
+    pub(crate) warmup: Option<Duration>,
+    /// In-loop retry of transient read errors
+    pub(crate) retry: Option<RetryPolicy>,
+    /// Reopening of a lost device
+    pub(crate) reconnect: Option<Reconnect>,
+    /// Budget of captured bytes forwarded before the capture stops
+    pub(crate) max_bytes: Option<u64>,
+    /// Predicates applied after the kernel filter
+    pub(crate) pipeline: Option<FilterPipeline>,
+    /// Read settings requested when the device was opened
+    pub(crate) read_request: Option<ReadRequest>,
+    /// Whether checksums are verified and attached to each packet
+    #[cfg(feature = "checksums")]
+    pub(crate) checksums: bool,
+    /// Memory-mapped file every delivered packet is recorded into
+    #[cfg(feature = "ring-file")]
+    pub(crate) ring: Option<Arc<Mutex<RingFile>>>,
+    /// In-memory recorder every delivered packet is copied into
+    pub(crate) recorder: Option<RingRecorder>,
+    /// Grouping of packets into batches on the internal channel
+    pub(crate) send_batch: Option<SendBatch>,
+    /// Addresses of the device the capture was opened on
+    pub(crate) local_addresses: Option<LocalAddresses>,
+    /// Settings the device was opened with, when opened by a builder
+    pub(crate) config: Option<CaptureConfig>,
+    /// Alert raised when the kernel drop rate gets too high
+    pub(crate) drop_threshold: Option<DropThreshold>,
+    /// Callback run with the final report
+    pub(crate) on_stop: Option<StopHook>,
+    /// Callback run with the errors the capture thread recovers from
+    pub(crate) on_error: Option<ErrorHook>,
+    /// Span the capture thread runs in
+    #[cfg(feature = "tracing")]
+    pub(crate) span: Option<tracing::Span>,
+    /// Time source of the timing-dependent features
+    pub(crate) clock: Option<Arc<dyn Clock>>,
+    /// Whether payloads are stripped, keeping only the headers
+    pub(crate) headers_only: bool,
+    /// Bytes kept of packets whose headers cannot be decoded
+    pub(crate) headers_fallback: Option<usize>,
+    /// Clock the device was opened with, when known
+    pub(crate) timestamp_source: Option<TimestampSource>,
+    /// Free buffers packets are copied into
+    pub(crate) buffer_pool: Option<Arc<BufferPool>>,
+    /// Time windows outside of which packets are dropped
+    pub(crate) schedule: Option<CaptureSchedule>,
+    /// Packet lengths outside of which packets are skipped uncopied
+    pub(crate) length: Option<LengthBounds>,
+    /// Predicate run on packets before they are copied
+    pub(crate) prefilter: Option<Prefilter>,
+    /// Subset of the packets kept, decided before they are copied
+    pub(crate) sampling: Sampling,
+    /// Period at which statistics are queued between the packets
+    pub(crate) stats_interval: Option<Duration>,
+    /// Link MTU above which packets are flagged as offload-coalesced
+    pub(crate) offload_mtu: Option<u32>,
+    /// Waits between polls of an idle non-blocking capture
+    pub(crate) idle_backoff: Option<IdleBackoff>,
+    /// Packets read between two checks of the stop flag
+    pub(crate) read_batch: usize,
+    /// Whether packets are attributed to local processes
+    #[cfg(feature = "process-info")]
+    pub(crate) annotate_process: bool,
+    /// Limit on the packets queued for the consumer, and what happens
+    /// beyond it
+    pub(crate) bounded: Option<(usize, OverflowPolicy)>,
+}
+
+/// State shared between the capture thread and its handles.
+#[derive(Debug)]
+pub(crate) struct Shared {
+    /// Raised to ask the capture thread to stop
+    pub(crate) stop_flag: AtomicBool,
+    /// Set while the capture thread is running
+    pub(crate) running: AtomicBool,
+    /// When the read loop started, on the monotonic and the wall clock
+    pub(crate) started: OnceLock<(Instant, SystemTime)>,
+    /// Time source of the timing-dependent features
+    pub(crate) clock: Arc<dyn Clock>,
+    /// Throughput of the packets read from the source
+    pub(crate) throughput: ThroughputMeter,
+    /// Packets dropped as duplicates of their predecessor
+    pub(crate) duplicates: AtomicU64,
+    /// Packets dropped outside the scheduled windows
+    pub(crate) unscheduled: AtomicU64,
+    /// Packets read from the source
+    pub(crate) received: AtomicU64,
+    /// Original length of the packets read, past the length bounds and
+    /// prefilter
+    pub(crate) received_bytes: AtomicU64,
+    /// Packets put on the internal channel
+    pub(crate) enqueued: AtomicU64,
+    /// Packets taken off the internal channel by the consumer, or evicted
+    /// from a full bounded queue
+    pub(crate) dequeued: AtomicU64,
+    /// Set once the source statistics should be published in
+    /// `kernel_stats`
+    pub(crate) publish_stats: AtomicBool,
+    /// Source statistics, refreshed every second while `publish_stats` is
+    /// set
+    pub(crate) kernel_stats: Mutex<Option<Stat>>,
+    /// Packets skipped by the length bounds
+    pub(crate) length_filtered: AtomicU64,
+    /// Packets skipped by the prefilter
+    pub(crate) prefiltered: AtomicU64,
+    /// Packets skipped by sampling
+    pub(crate) sampled_out: AtomicU64,
+    /// Packets flagged as coalesced by receive offload
+    pub(crate) coalesced: AtomicU64,
+    /// Set while the live capture is in non-blocking mode
+    pub(crate) nonblocking: AtomicBool,
+    /// Filter program and optimize flag last installed while running,
+    /// re-applied when the device is reopened
+    pub(crate) live_filter: Mutex<Option<(String, bool)>>,
+    /// Reads retried after a transient error
+    pub(crate) retries: AtomicU64,
+    /// Times the device was reopened after it was lost
+    pub(crate) reconnects: AtomicU64,
+    /// Errors the capture thread recovered from
+    pub(crate) soft_errors: AtomicU64,
+    /// Time packets spent queued before the consumer received them
+    pub(crate) dwell: DwellRecorder,
+    /// Final report, set just before the capture thread exits
+    pub(crate) summary: Mutex<Option<CaptureSummary>>,
+    /// Notified once `summary` is set
+    pub(crate) ended: Notify,
+    /// Closures waiting to run on the capture thread
+    pub(crate) commands: CommandQueue,
+    /// Packets dropped by each filter pipeline predicate
+    pub(crate) rejections: Vec<AtomicU64>,
+    /// Observed read behavior
+    pub(crate) read_probe: ReadProbe,
+    /// Incremented for every filter update, so that superseded updates can
+    /// be skipped
+    pub(crate) filter_generation: AtomicU64,
+    /// Addresses of the capturing interface
+    pub(crate) local: LocalAddresses,
+    /// Settings the device was opened with, when opened by a builder
+    pub(crate) config: Option<CaptureConfig>,
+    /// Free buffers packets are copied into
+    pub(crate) buffer_pool: Option<Arc<BufferPool>>,
+    /// Limit on the packets queued for the consumer
+    pub(crate) queue_bound: Option<QueueBound>,
+    /// Set while reading is suspended
+    pub(crate) pause: Pause,
+    /// Interrupts a blocking read of a live capture
+    pub(crate) interrupt: ReadInterrupt,
+    /// Set once the consumer asked for lifecycle events
+    pub(crate) events_wanted: AtomicBool,
+    /// Lifecycle events raised outside the read loop, e.g. by commands,
+    /// waiting to be queued
+    pub(crate) raised: Mutex<Vec<LinkEvent>>,
+    /// The capture thread, until it is joined
+    pub(crate) thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Shared {
+    pub(crate) fn new(options: &CaptureOptions) -> Arc<Self> {
+        let clock = options
+            .clock
+            .clone()
+            .unwrap_or_else(|| Arc::new(SystemClock));
+        Arc::new(Self {
+            stop_flag: AtomicBool::new(false),
+            running: AtomicBool::new(true),
+            started: OnceLock::new(),
+            throughput: ThroughputMeter::new(
+                options
+                    .throughput_window
+                    .unwrap_or(DEFAULT_THROUGHPUT_WINDOW),
+                clock.clone(),
+            ),
+            clock,
+            duplicates: AtomicU64::new(0),
+            unscheduled: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+            received_bytes: AtomicU64::new(0),
+            enqueued: AtomicU64::new(0),
+            dequeued: AtomicU64::new(0),
+            publish_stats: AtomicBool::new(false),
+            kernel_stats: Mutex::new(None),
+            length_filtered: AtomicU64::new(0),
+            prefiltered: AtomicU64::new(0),
+            sampled_out: AtomicU64::new(0),
+            coalesced: AtomicU64::new(0),
+            nonblocking: AtomicBool::new(false),
+            live_filter: Mutex::new(None),
+            retries: AtomicU64::new(0),
+            reconnects: AtomicU64::new(0),
+            soft_errors: AtomicU64::new(0),
+            dwell: DwellRecorder::default(),
+            summary: Mutex::new(None),
+            ended: Notify::new(),
+            commands: CommandQueue::default(),
+            rejections: options
+                .pipeline
+                .iter()
+                .flat_map(|pipeline| &pipeline.stages)
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+            read_probe: ReadProbe::new(options.read_request),
+            filter_generation: AtomicU64::new(0),
+            local: options.local_addresses.clone().unwrap_or_default(),
+            config: options.config.clone(),
+            buffer_pool: options.buffer_pool.clone(),
+            queue_bound: options
+                .bounded
+                .map(|(capacity, policy)| QueueBound::new(capacity, policy)),
+            pause: Pause::default(),
+            interrupt: ReadInterrupt::default(),
+            events_wanted: AtomicBool::new(false),
+            raised: Mutex::new(Vec::new()),
+            thread: Mutex::new(None),
+        })
+    }
+
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.stop_flag.load(Ordering::Acquire)
+    }
+
+    /// Completes the annotations of a packet the consumer just received,
+    /// timed on the capture's clock, and records its queue dwell.
+    pub(crate) fn delivered(&self, annotations: Annotations) -> PacketMeta {
+        let meta = annotations.into_meta(self.clock.now());
+        if let Some(timing) = &meta.timing {
+            self.dwell.record(timing);
+        }
+        meta
+    }
+
+    /// Queues `event` for the consumer if it asked for lifecycle events.
+    pub(crate) fn raise(&self, event: LinkEvent) {
+        if self.events_wanted.load(Ordering::Relaxed) {
+            self.raised
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(event);
+        }
+    }
+
+    /// Records the thread reading the capture, so that it can be joined.
+    pub(crate) fn set_thread(&self, thread: JoinHandle<()>) {
+        *self.thread.lock().unwrap() = Some(thread);
+    }
+}
+
+/// Counters of the read loop that outlive a panic in it.
+struct Totals {
+    /// When the read loop started
+    start: Instant,
+    /// Packets read from the source
+    received: u64,
+    /// Message of the last error read from the source
+    last_error: Option<String>,
+}
+
+/// Extracts the message of a panic payload.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        (*msg).to_owned()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "non-string panic payload".to_owned()
+    }
+}
+
+/// Startup window during which errors are reported to the constructor.
+struct Startup {
+    tx: SyncSender<Error>,
+    deadline: Instant,
+}
+
+/// State owned by the background capture thread.
+pub(crate) struct Worker<S> {
+    source: S,
+    output: Output,
+    shared: Arc<Shared>,
+    options: CaptureOptions,
+    sampler: Option<Sampler>,
+    startup: Option<Startup>,
+}
+
+impl<S: PacketSource> Worker<S> {
+    /// Creates the worker, along with the channel on which it reports a
+    /// startup failure when a startup grace window is configured.
+    pub(crate) fn new(
+        mut source: S,
+        output: Output,
+        shared: Arc<Shared>,
+        options: CaptureOptions,
+    ) -> (Self, Option<Receiver<Error>>) {
+        if let Some(cap) = source.as_active() {
+            shared.interrupt.set(cap.breakloop_handle());
+        }
+        let (startup, startup_rx) = match options.startup_grace {
+            Some(grace) => {
+                let (tx, rx) = sync_channel(1);
+                let startup = Startup {
+                    tx,
+                    deadline: Instant::now() + grace,
+                };
+                (Some(startup), Some(rx))
+            }
+            None => (None, None),
+        };
+        let sampler = options
+            .sampling
+            .is_active()
+            .then(|| Sampler::new(options.sampling, shared.clock.now()));
+        let worker = Self {
+            source,
+            output,
+            shared,
+            options,
+            sampler,
+            startup,
+        };
+        (worker, startup_rx)
+    }
+
+    /// Runs the read loop until the source ends, the consumer goes away or
+    /// the capture is stopped.
+    ///
+    /// A panic in the loop, e.g. in a user-supplied predicate, is caught
+    /// and delivered to the consumer as [`Error::CapturePanicked`], so the
+    /// capture still ends with a summary.
+    pub(crate) fn run(mut self) {
+        #[cfg(feature = "tracing")]
+        let _span = self
+            .options
+            .span
+            .take()
+            .unwrap_or_else(|| crate::logging::capture_span(None, None))
+            .entered();
+        let mut totals = Totals {
+            start: self.shared.clock.now(),
+            received: 0,
+            last_error: None,
+        };
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| self.read_loop(&mut totals)));
+        let reason = match outcome {
+            Ok(reason) => reason,
+            Err(payload) => {
+                let msg = panic_message(payload.as_ref());
+                error!("AsyncCapture thread panicked: {msg}");
+                totals.last_error = Some(format!("capture thread panicked: {msg}"));
+                let _ = self.output.send(
+                    Err(Error::CapturePanicked(msg)),
+                    Annotations::default(),
+                    None,
+                );
+                EndReason::Panicked
+            }
+        };
+        let Totals {
+            start,
+            received,
+            last_error,
+        } = totals;
+
+        let summary = CaptureSummary {
+            reason,
+            received,
+            dropped: self.source.stats().map(|stats| stats.dropped),
+            duration: self.shared.clock.now().saturating_duration_since(start),
+            last_error,
+        };
+        #[cfg(feature = "ring-file")]
+        if let Some(ring) = &self.options.ring
+            && let Err(e) = ring
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .mark_finalized()
+        {
+            warn!("AsyncCapture failed to finalize ring file: {e}");
+        }
+        // Close the device before the end is signalled, so that it can be
+        // reopened as soon as the capture is reported stopped
+        drop(self.source);
+        if let Some(hook) = self.options.on_stop.take() {
+            let summary = summary.clone();
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| hook.run(summary))) {
+                error!(
+                    "AsyncCapture stop callback panicked: {}",
+                    panic_message(payload.as_ref())
+                );
+            }
+        }
+        self.shared.commands.close();
+        *self.shared.summary.lock().unwrap() = Some(summary);
+        // Mark the thread stopped before the Stop message can be observed
+        self.shared.running.store(false, Ordering::Release);
+        self.shared.ended.notify_waiters();
+        // Send a Stop message when capture thread ends, unless the receiver
+        // is known to be gone; the output is then just dropped
+        if reason != EndReason::ReceiverDropped {
+            self.output.finish();
+        }
+    }
+
+    /// The read loop proper, returning why it ended.
+    fn read_loop(&mut self, totals: &mut Totals) -> EndReason {
+        let clock = self.shared.clock.clone();
+        let mut bucket = self
+            .options
+            .rate_limit
+            .map(|limit| TokenBucket::new(limit, clock.now()));
+        let precision = self.source.info().precision;
+        let adapter_stamped = self
+            .options
+            .timestamp_source
+            .unwrap_or(self.source.info().timestamp_source)
+            != TimestampSource::Host;
+        let mut dedup = self
+            .options
+            .dedup
+            .map(|mode| Deduplicator::new(mode, precision));
+        let started = match &self.options.start_gate {
+            Some(gate) => gate.wait(&self.shared.stop_flag),
+            None => true,
+        };
+        totals.start = clock.now();
+        let start = totals.start;
+        if started {
+            let _ = self.shared.started.set((start, clock.system_now()));
+        }
+        let mut reason = if started {
+            EndReason::SourceEnded
+        } else {
+            EndReason::Stopped
+        };
+        let mut discard = self.options.discard_first;
+        let warmup_end = self
+            .options
+            .warmup
+            .and_then(|warmup| start.checked_add(warmup));
+        let mut retries = 0u32;
+        let datalink = self.source.info().datalink;
+        let mut offload = self
+            .options
+            .offload_mtu
+            .map(|mtu| OffloadDetector::new(datalink, mtu, self.shared.local.device.clone()));
+        let mut forwarded_bytes = 0u64;
+        let mut in_window = None;
+        if let Some(cap) = self.source.as_active()
+            && cap.is_nonblock()
+        {
+            self.shared.nonblocking.store(true, Ordering::Release);
+        }
+        let mut drop_monitor = self
+            .options
+            .drop_threshold
+            .clone()
+            .map(|threshold| DropMonitor::new(threshold, start));
+        if let Some(monitor) = drop_monitor.as_mut() {
+            monitor.sample(self.source.stats(), 0, start);
+        }
+        let mut warnings = LogLimiter::default();
+        let mut idle = Backoff::new(
+            self.options.idle_backoff.unwrap_or_default(),
+            MAX_SLEEP_SLICE,
+        );
+        let mut next_stats = self
+            .options
+            .stats_interval
+            .and_then(|interval| start.checked_add(interval));
+        #[cfg(feature = "process-info")]
+        let mut processes = self.options.annotate_process.then(ProcessTable::new);
+        let mut published_at: Option<Instant> = None;
+        // Kernel drops at the last sample, to report their increase
+        let mut last_dropped: Option<u32> = None;
+        let read_batch = self.options.read_batch.max(1);
+        let mut batch_left = 0;
+        let mut paused_since = None;
+        let mut paused_window: Option<PausedWindow> = None;
+        loop {
+            if !self.output.flush_due() {
+                debug!("AsyncCapture receiver dropped, ending capture.");
+                reason = EndReason::ReceiverDropped;
+                break;
+            }
+            if batch_left == 0 {
+                batch_left = read_batch;
+                warnings.tick();
+                if !started || self.shared.is_stopped() {
+                    warn!("AsyncCapture thread is aborted.");
+                    reason = EndReason::Stopped;
+                    break;
+                }
+                for command in self.shared.commands.take() {
+                    command(self.source.as_active());
+                }
+                if self.shared.pause.is_paused() && paused_since.is_none() {
+                    self.shared.raise(LinkEvent::Paused);
+                } else if !self.shared.pause.is_paused() && paused_since.is_some() {
+                    self.shared.raise(LinkEvent::Resumed);
+                }
+                if !self.send_raised() {
+                    debug!("AsyncCapture receiver dropped, ending capture.");
+                    reason = EndReason::ReceiverDropped;
+                    break;
+                }
+                if self.shared.pause.is_paused() {
+                    if paused_since.is_none() {
+                        paused_since = Some(clock.system_now());
+                        info!("AsyncCapture paused.");
+                        if !self.output.flush() {
+                            debug!("AsyncCapture receiver dropped, ending capture.");
+                            reason = EndReason::ReceiverDropped;
+                            break;
+                        }
+                    }
+                    // Commands and the stop flag are still served while
+                    // the kernel buffer fills up and overflows
+                    self.shared.pause.wait(MAX_SLEEP_SLICE);
+                    batch_left = 0;
+                    continue;
+                }
+                if let Some(since) = paused_since.take() {
+                    paused_window = Some(PausedWindow::new(since, clock.system_now()));
+                    info!("AsyncCapture resumed.");
+                }
+                let now = clock.now();
+                if let Some(monitor) = drop_monitor.as_mut()
+                    && monitor.is_due(now)
+                {
+                    monitor.sample(self.source.stats(), totals.received, now);
+                }
+                let publish = self.shared.publish_stats.load(Ordering::Relaxed);
+                let watch_drops = self.shared.events_wanted.load(Ordering::Relaxed);
+                if (publish || watch_drops)
+                    && published_at
+                        .is_none_or(|at| now.saturating_duration_since(at) >= DROP_SAMPLE_INTERVAL)
+                {
+                    published_at = Some(now);
+                    let stats = self.source.stats();
+                    if let Some(stat) = stats {
+                        let increase = stat.dropped.wrapping_sub(*last_dropped.get_or_insert(0));
+                        last_dropped = Some(stat.dropped);
+                        if watch_drops
+                            && increase > 0
+                            && !self
+                                .output
+                                .send_event(LinkEvent::KernelDrops(u64::from(increase)))
+                        {
+                            debug!("AsyncCapture receiver dropped, ending capture.");
+                            reason = EndReason::ReceiverDropped;
+                            break;
+                        }
+                    }
+                    if publish {
+                        *self.shared.kernel_stats.lock().unwrap() = stats;
+                    }
+                }
+                if let Some(due) = next_stats
+                    && now >= due
+                {
+                    next_stats = self
+                        .options
+                        .stats_interval
+                        .and_then(|interval| now.checked_add(interval));
+                    if let Some(stat) = self.source.stats()
+                        && !self.output.send_stats(stat)
+                    {
+                        debug!("AsyncCapture receiver dropped, ending capture.");
+                        reason = EndReason::ReceiverDropped;
+                        break;
+                    }
+                }
+            }
+            batch_left -= 1;
+            let owned = match self.read() {
+                Some(Ok(Some(packet))) => Ok(packet),
+                Some(Ok(None)) => {
+                    // Counted by the bound or prefilter that skipped it
+                    totals.received += 1;
+                    self.shared.received.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+                Some(Err(Error::NoMorePackets)) if self.shared.is_stopped() => {
+                    // The read was interrupted by a shutdown
+                    reason = EndReason::Stopped;
+                    break;
+                }
+                Some(Err(e)) => Err(e),
+                None => break,
+            };
+            if owned.is_err() {
+                // The source is empty or failing: end the batch
+                batch_left = 0;
+            }
+            if matches!(&owned, Err(Error::Timeout))
+                && self.shared.nonblocking.load(Ordering::Acquire)
+                && let Some(cap) = self.source.as_active()
+            {
+                // Nothing queued: wait for packets instead of spinning, and
+                // wait briefly again once they flow
+                if nonblock::wait_readable(cap, idle.next_wait()) {
+                    idle.reset();
+                }
+                continue;
+            }
+            idle.reset();
+            let host_timestamp = (adapter_stamped && owned.is_ok()).then(|| clock.system_now());
+            if let Ok(packet) = &owned {
+                totals.received += 1;
+                self.shared.received.fetch_add(1, Ordering::Relaxed);
+                retries = 0;
+                self.shared
+                    .received_bytes
+                    .fetch_add(u64::from(packet.header.len), Ordering::Relaxed);
+                self.shared.throughput.record(packet.header.len);
+                if let Some(window) = paused_window {
+                    let ts = packet.since_epoch_with(precision);
+                    if ts >= window.until {
+                        paused_window = None;
+                    } else if ts >= window.from {
+                        // Buffered by the kernel while the capture was paused
+                        self.shared.pause.dropped.fetch_add(1, Ordering::Relaxed);
+                        self.recycle(owned);
+                        continue;
+                    }
+                }
+                if let Some(schedule) = &self.options.schedule {
+                    let active = schedule.is_active(clock.system_now());
+                    if in_window != Some(active) {
+                        in_window = Some(active);
+                        info!(
+                            "AsyncCapture {} forwarding on schedule.",
+                            if active { "resumed" } else { "paused" }
+                        );
+                    }
+                    if !active {
+                        self.shared.unscheduled.fetch_add(1, Ordering::Relaxed);
+                        self.recycle(owned);
+                        continue;
+                    }
+                }
+                let warming_up = warmup_end.is_some_and(|end| clock.now() < end);
+                let duplicate = dedup.as_mut().is_some_and(|d| d.is_duplicate(packet));
+                if duplicate {
+                    self.shared.duplicates.fetch_add(1, Ordering::Relaxed);
+                }
+                if warming_up || duplicate {
+                    self.recycle(owned);
+                    continue;
+                }
+                // Only the packets dropped by this option count towards it
+                if discard > 0 {
+                    discard -= 1;
+                    self.recycle(owned);
+                    continue;
+                }
+                let rejected = self
+                    .options
+                    .pipeline
+                    .as_ref()
+                    .and_then(|p| p.rejected_by(packet));
+                if let Some(stage) = rejected {
+                    self.shared.rejections[stage].fetch_add(1, Ordering::Relaxed);
+                    self.recycle(owned);
+                    continue;
+                }
+            }
+            let mut fatal = false;
+            let mut owned = match owned {
+                Err(e) => {
+                    if let Some(policy) = &self.options.retry {
+                        if e == Error::Timeout {
+                            continue;
+                        }
+                        if e.is_transient() && retries < policy.max_retries {
+                            retries += 1;
+                            self.shared.retries.fetch_add(1, Ordering::Relaxed);
+                            self.soft_error(&e);
+                            warnings.warn(
+                                &format!("retry: {e}"),
+                                format_args!(
+                                    "AsyncCapture retrying read ({retries}/{}) after: {e}",
+                                    policy.max_retries
+                                ),
+                            );
+                            sleep_unless_stopped(policy.backoff, &self.shared);
+                            continue;
+                        }
+                        fatal = true;
+                    }
+                    let e = match self.options.reconnect.clone() {
+                        Some(reconnect) if fatal || e.kind() == ErrorKind::DeviceGone => {
+                            match self.reconnect(&reconnect, e) {
+                                Reconnection::Reconnected => {
+                                    retries = 0;
+                                    continue;
+                                }
+                                Reconnection::Stopped => {
+                                    reason = EndReason::Stopped;
+                                    break;
+                                }
+                                Reconnection::ReceiverDropped => {
+                                    reason = EndReason::ReceiverDropped;
+                                    break;
+                                }
+                                Reconnection::GaveUp(e) => {
+                                    fatal = true;
+                                    e
+                                }
+                            }
+                        }
+                        _ => e,
+                    };
+                    totals.last_error = Some(e.to_string());
+                    match self.fail_startup(e) {
+                        Some(e) => Err(e),
+                        None => {
+                            reason = EndReason::StartupFailed;
+                            break;
+                        }
+                    }
+                }
+                ok => ok,
+            };
+            if let (true, Ok(packet)) = (self.options.headers_only, &mut owned)
+                && !packet.strip_payload(datalink)
+            {
+                packet.truncate(
+                    self.options
+                        .headers_fallback
+                        .unwrap_or(DEFAULT_HEADERS_FALLBACK),
+                );
+            }
+            if let (Some(max), Ok(packet)) = (self.options.max_bytes, &owned) {
+                forwarded_bytes += u64::from(packet.header.caplen);
+                if forwarded_bytes > max {
+                    info!("AsyncCapture byte budget of {max} reached.");
+                    self.shared.stop_flag.store(true, Ordering::Release);
+                    reason = EndReason::LimitReached;
+                    break;
+                }
+            }
+            #[cfg(feature = "ring-file")]
+            if let (Some(ring), Ok(packet)) = (&self.options.ring, &owned)
+                && let Err(e) = ring.lock().unwrap().write_packet(packet)
+            {
+                warnings.warn(
+                    &format!("ring: {e}"),
+                    format_args!("AsyncCapture failed to record packet to ring file: {e}"),
+                );
+                self.soft_error(&Error::from(e));
+            }
+            if let (Some(recorder), Ok(packet)) = (&self.options.recorder, &owned) {
+                recorder.record(packet);
+            }
+            if let (Some(bucket), Ok(packet)) = (bucket.as_mut(), &owned) {
+                sleep_unless_stopped(bucket.delay_for(packet, clock.now()), &self.shared);
+            }
+            let mut annotations = Annotations {
+                host_timestamp,
+                ..Default::default()
+            };
+            if let (Some(detector), Ok(packet)) = (offload.as_mut(), &owned)
+                && detector.check(packet)
+            {
+                annotations.coalesced = true;
+                self.shared.coalesced.fetch_add(1, Ordering::Relaxed);
+            }
+            #[cfg(feature = "checksums")]
+            if let (true, Ok(packet)) = (self.options.checksums, &owned) {
+                annotations.checksums = Some(Checksums::verify(datalink, &packet.data));
+            }
+            #[cfg(feature = "process-info")]
+            if let (Some(table), Ok(packet)) = (processes.as_mut(), &owned) {
+                annotations.process = table.lookup(datalink, packet);
+            }
+            if owned.is_ok() && !self.make_room() {
+                self.recycle(owned);
+                continue;
+            }
+            if owned.is_ok() && matches!(self.output, Output::Internal(..)) {
+                // Counted first, so that the consumer never takes more
+                // packets than were queued
+                self.shared.enqueued.fetch_add(1, Ordering::Relaxed);
+            }
+            let enqueued_at = self.options.timing.then(|| self.shared.clock.now());
+            if !self.output.send(owned, annotations, enqueued_at) {
+                // Receiver dropped, exit thread
+                debug!("AsyncCapture receiver dropped, ending capture.");
+                reason = EndReason::ReceiverDropped;
+                break;
+            }
+            if fatal {
+                warn!("AsyncCapture read failed, ending capture.");
+                reason = EndReason::ReadFailed;
+                break;
+            }
+        }
+        warnings.flush();
+        reason
+    }
+
+    /// Reads the next packet, timing the read if the probe is enabled.
+    ///
+    /// Errors of libpcap the source returned as they are get their own
+    /// variant here.
+    fn read(&mut self) -> Option<Result<Option<Packet>, Error>> {
+        if !self.shared.read_probe.enabled() {
+            return self.read_source().map(|res| res.map_err(Error::classified));
+        }
+        let start = Instant::now();
+        let owned = self.read_source().map(|res| res.map_err(Error::classified));
+        let timed_out = matches!(&owned, Some(Err(Error::Timeout)));
+        self.shared.read_probe.record(start.elapsed(), timed_out);
+        owned
+    }
+
+    /// Reports the loss of the device and reopens it, waiting longer
+    /// between each failed attempt.
+    fn reconnect(&mut self, reconnect: &Reconnect, e: Error) -> Reconnection {
+        warn!("AsyncCapture lost the device ({e}), reconnecting.");
+        self.soft_error(&e);
+        if !self.output.send_event(LinkEvent::Disconnected(e)) {
+            return Reconnection::ReceiverDropped;
+        }
+        let policy = reconnect.policy;
+        let mut backoff = policy.initial_backoff;
+        let mut attempts = 0;
+        loop {
+            sleep_unless_stopped(backoff, &self.shared);
+            if self.shared.is_stopped() {
+                return Reconnection::Stopped;
+            }
+            attempts += 1;
+            let mut cap = match (reconnect.reopen)() {
+                Ok(cap) => cap,
+                Err(e) if policy.max_attempts.is_some_and(|max| attempts >= max) => {
+                    warn!("AsyncCapture gave up reconnecting after {attempts} attempts.");
+                    return Reconnection::GaveUp(e);
+                }
+                Err(e) => {
+                    debug!("AsyncCapture reconnect attempt {attempts} failed: {e}");
+                    self.soft_error(&e);
+                    backoff = backoff.saturating_mul(2).min(policy.max_backoff);
+                    continue;
+                }
+            };
+            if self.shared.nonblocking.load(Ordering::Acquire)
+                && let Err(e) = nonblock::set(&mut cap, true)
+            {
+                warn!("AsyncCapture could not restore non-blocking mode: {e}");
+                self.soft_error(&e);
+            }
+            let live_filter = lock(&self.shared.live_filter).clone();
+            if let Some((program, optimize)) = live_filter
+                && let Err(e) = cap.filter(&program, optimize)
+            {
+                let e = Error::from(e);
+                warn!("AsyncCapture could not restore the filter {program:?}: {e}");
+                self.soft_error(&e);
+            }
+            self.shared.interrupt.set(cap.breakloop_handle());
+            if let Some(active) = self.source.as_active() {
+                *active = cap;
+            }
+            self.shared.reconnects.fetch_add(1, Ordering::Relaxed);
+            info!("AsyncCapture reconnected after {attempts} attempt(s).");
+            return if self.output.send_event(LinkEvent::Reconnected) {
+                Reconnection::Reconnected
+            } else {
+                Reconnection::ReceiverDropped
+            };
+        }
+    }
+
+    /// Reports an error the capture thread recovers from to the
+    /// [`on_error()`](crate::AsyncCaptureBuilder::on_error) callback.
+    fn soft_error(&self, e: &Error) {
+        self.shared.soft_errors.fetch_add(1, Ordering::Relaxed);
+        if let Some(hook) = &self.options.on_error {
+            hook.report(e);
+        }
+    }
+
+    /// Queues the lifecycle events raised since the last call. Returns
+    /// `false` once the receiver is gone.
+    fn send_raised(&mut self) -> bool {
+        let raised =
+            std::mem::take(&mut *self.shared.raised.lock().unwrap_or_else(|e| e.into_inner()));
+        raised
+            .into_iter()
+            .all(|event| self.output.send_event(event))
+    }
+
+    /// Reads from the source, into a pooled buffer if there is a pool.
+    ///
+    /// Yields `Ok(None)` for a packet skipped by the length bounds, the
+    /// prefilter or sampling, having counted it.
+    fn read_source(&mut self) -> Option<Result<Option<Packet>, Error>> {
+        let buf = self.options.buffer_pool.as_ref().map(|pool| pool.take());
+        if self.options.length.is_none()
+            && self.options.prefilter.is_none()
+            && self.sampler.is_none()
+        {
+            return match buf {
+                Some(buf) => self.source.next_packet_into(buf).map(|res| res.map(Some)),
+                None => self.source.next_packet().map(|res| res.map(Some)),
+            };
+        }
+        let (length, prefilter, sampler, shared) = (
+            self.options.length,
+            &self.options.prefilter,
+            &self.sampler,
+            &self.shared,
+        );
+        let accept = |header: &PacketHeader, data: &[u8]| {
+            if let Some(bounds) = length
+                && !bounds.accepts(header)
+            {
+                shared.length_filtered.fetch_add(1, Ordering::Relaxed);
+                return false;
+            }
+            if let Some(prefilter) = prefilter
+                && !prefilter.accepts(header, data)
+            {
+                shared.prefiltered.fetch_add(1, Ordering::Relaxed);
+                return false;
+            }
+            if let Some(sampler) = sampler
+                && !sampler.keeps(header, &*shared.clock)
+            {
+                shared.sampled_out.fetch_add(1, Ordering::Relaxed);
+                return false;
+            }
+            true
+        };
+        self.source.next_packet_if(buf.unwrap_or_default(), &accept)
+    }
+
+    /// Makes room for one more packet in a bounded queue, applying its
+    /// overflow policy. Returns `false`, having counted the drop, if the
+    /// packet must be dropped instead.
+    fn make_room(&self) -> bool {
+        let (Some(bound), Output::Internal(..)) = (&self.shared.queue_bound, &self.output) else {
+            return true;
+        };
+        let full = || {
+            let queued = self
+                .shared
+                .enqueued
+                .load(Ordering::Relaxed)
+                .saturating_sub(self.shared.dequeued.load(Ordering::Relaxed));
+            queued >= bound.capacity
+        };
+        match bound.policy {
+            OverflowPolicy::Block => {
+                while full() && !self.shared.is_stopped() && !self.output.is_closed() {
+                    bound.wait_for_space(MAX_SLEEP_SLICE, full);
+                }
+                true
+            }
+            OverflowPolicy::DropNewest if full() => {
+                bound.dropped.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+            OverflowPolicy::DropNewest => true,
+            OverflowPolicy::DropOldest => {
+                while full() {
+                    // Fails while the consumer is receiving, or when the
+                    // queued packets are still in a partial batch
+                    let Some(items) = bound.evict_oldest() else {
+                        bound.dropped.fetch_add(1, Ordering::Relaxed);
+                        return false;
+                    };
+                    let taken = items.len() as u64;
+                    self.shared.dequeued.fetch_add(taken, Ordering::Relaxed);
+                    bound.evicted.fetch_add(taken, Ordering::Relaxed);
+                    bound.dropped.fetch_add(taken, Ordering::Relaxed);
+                    items.into_iter().for_each(|(item, _)| self.recycle(item));
+                }
+                true
+            }
+        }
+    }
+
+    /// Returns the buffer of a packet dropped on this thread to the pool.
+    fn recycle(&self, owned: Result<Packet, Error>) {
+        if let (Some(pool), Ok(packet)) = (&self.options.buffer_pool, owned) {
+            pool.put(packet.data);
+        }
+    }
+
+    /// Reports `e` as a startup failure if the grace window is still open.
+    ///
+    /// Returns the error back if it should be forwarded as a stream item
+    /// instead. Read timeouts never fail the startup.
+    fn fail_startup(&mut self, e: Error) -> Option<Error> {
+        let Some(startup) = self.startup.take() else {
+            return Some(e);
+        };
+        if Instant::now() >= startup.deadline {
+            return Some(e);
+        }
+        if e == Error::Timeout {
+            self.startup = Some(startup);
+            return Some(e);
+        }
+        match startup.tx.try_send(e) {
+            Ok(()) => None,
+            // The constructor stopped waiting; forward the error instead
+            Err(TrySendError::Disconnected(e) | TrySendError::Full(e)) => Some(e),
+        }
+    }
+}
+
+/// Waits up to `grace` for a startup error from the capture thread.
+///
+/// The wait ends early with success if the thread exits without an error.
+pub(crate) fn wait_startup(rx: Receiver<Error>, grace: Duration) -> Result<(), Error> {
+    match rx.recv_timeout(grace) {
+        Ok(e) => Err(e),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Sleeps for `delay`, waking early if the stop flag is raised.
+fn sleep_unless_stopped(delay: Duration, shared: &Shared) {
+    if delay.is_zero() {
+        return;
+    }
+    let deadline = shared.clock.now().checked_add(delay);
+    while !shared.is_stopped() {
+        let remaining = match deadline {
+            Some(deadline) => deadline.saturating_duration_since(shared.clock.now()),
+            None => MAX_SLEEP_SLICE,
+        };
+        if remaining.is_zero() {
+            break;
+        }
+        shared.clock.sleep(remaining.min(MAX_SLEEP_SLICE));
+    }
+}
+
+#[cfg(all(test, feature = "rt-tokio"))]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+    use crate::async_pcap::AsyncCapture;
+    use crate::clock::ManualClock;
+    use crate::source::IterSource;
+
+    fn packets(n: u8) -> IterSource<impl Iterator<Item = Result<Packet, Error>> + Send + 'static> {
+        IterSource::new((0..n).map(|i| Ok(Packet::new(vec![i], UNIX_EPOCH))))
+    }
+
+    /// Numbered packets, the first `fast` at once and the others every
+    /// millisecond, without end.
+    fn endless(
+        fast: u32,
+    ) -> IterSource<impl Iterator<Item = Result<Packet, Error>> + Send + 'static> {
+        IterSource::new((0u32..).map(move |i| {
+            if i >= fast {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            Ok(Packet::new(i.to_be_bytes().to_vec(), UNIX_EPOCH))
+        }))
+    }
+
+    fn number(packet: &Packet) -> u32 {
+        u32::from_be_bytes(packet.data[..4].try_into().unwrap())
+    }
+
+    /// Error hook counting the errors reported to it.
+    fn error_counter() -> (Arc<AtomicU64>, ErrorHook) {
+        let errors = Arc::new(AtomicU64::new(0));
+        let counter = errors.clone();
+        let hook = ErrorHook::new(Arc::new(move |_: &Error| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }));
+        (errors, hook)
+    }
+
+    #[tokio::test]
+    async fn panicking_predicate_ends_the_stream_with_the_panic() {
+        let pipeline = FilterPipeline::new().then(|packet: &Packet| {
+            assert!(
+                packet.data[0] < 2,
+                "predicate failed on packet {}",
+                packet.data[0]
+            );
+            true
+        });
+        let options = CaptureOptions {
+            pipeline: Some(pipeline),
+            ..Default::default()
+        };
+        let (capture, handle, _) = AsyncCapture::with_options(packets(5), options);
+
+        for i in 0..2 {
+            assert_eq!(capture.next_packet().await.unwrap().unwrap().data, [i]);
+        }
+        match capture.next_packet().await {
+            Some(Err(Error::CapturePanicked(msg))) => {
+                assert!(msg.contains("predicate failed on packet 2"), "{msg}");
+            }
+            other => panic!("expected the panic, got {other:?}"),
+        }
+        assert!(capture.next_packet().await.is_none());
+        assert!(capture.next_packet().await.is_none());
+
+        let summary = handle.wait_stopped().await;
+        assert_eq!(summary.reason, EndReason::Panicked);
+        assert_eq!(summary.received, 3);
+        assert!(summary.last_error.unwrap().contains("predicate failed"));
+    }
+
+    #[tokio::test]
+    async fn dropped_receiver_ends_the_thread_under_every_queue_policy() {
+        let policies = [
+            None,
+            Some(OverflowPolicy::Block),
+            Some(OverflowPolicy::DropNewest),
+            Some(OverflowPolicy::DropOldest),
+        ];
+        for policy in policies {
+            let (errors, hook) = error_counter();
+            let options = CaptureOptions {
+                bounded: policy.map(|policy| (4, policy)),
+                on_error: Some(hook),
+                ..Default::default()
+            };
+            let (capture, handle, _) = AsyncCapture::with_options(endless(100), options);
+            for _ in 0..10 {
+                capture.next_packet().await.unwrap().unwrap();
+            }
+            drop(capture);
+
+            let summary = tokio::time::timeout(Duration::from_secs(5), handle.wait_stopped())
+                .await
+                .expect("the capture thread exits");
+            assert_eq!(summary.reason, EndReason::ReceiverDropped, "{policy:?}");
+            assert_eq!(summary.last_error, None, "{policy:?}");
+            assert_eq!(errors.load(Ordering::Relaxed), 0, "{policy:?}");
+            assert!(!handle.is_running());
+        }
+    }
+
+    #[tokio::test]
+    async fn stopped_capture_keeps_draining_the_queued_packets() {
+        let (capture, handle) = AsyncCapture::new(endless(20));
+        // Lets the capture thread queue the first packets
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        handle.stop();
+        handle.wait_stopped().await;
+
+        let mut expected = 0;
+        while let Some(packet) = capture.next_packet().await {
+            assert_eq!(number(&packet.unwrap()), expected);
+            expected += 1;
+        }
+        assert!(expected >= 20, "only {expected} packets were drained");
+        assert_eq!(handle.summary().unwrap().reason, EndReason::Stopped);
+    }
+
+    #[tokio::test]
+    async fn end_of_the_capture_is_reported_after_any_method_saw_it() {
+        let (capture, _handle) = AsyncCapture::new(packets(2));
+        while capture.next_packet().await.is_some() {}
+
+        match capture.next_item().await {
+            Some(StreamItem::Stopped(summary)) => {
+                assert_eq!(summary.reason, EndReason::SourceEnded);
+            }
+            other => panic!("expected the end, got {other:?}"),
+        }
+        assert!(capture.next_event().await.is_none());
+        assert!(capture.next_packet().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn reader_takes_batches_and_events_from_the_same_queue() {
+        let (mut reader, _handle) = AsyncCapture::new(packets(3)).0.into_split();
+        let batch = reader.next_batch(2, Duration::from_secs(5)).await;
+        assert_eq!(batch.len(), 2);
+
+        match reader.next_event().await {
+            Some(StreamItem::Packet(Ok(packet))) => assert_eq!(packet.data, [2]),
+            other => panic!("expected the last packet, got {other:?}"),
+        }
+        assert!(matches!(
+            reader.next_event().await,
+            Some(StreamItem::Stopped(_))
+        ));
+        assert!(reader.next_item().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn wrapped_libpcap_errors_of_a_source_are_delivered_typed() {
+        let source = IterSource::new([Err(Error::Pcap(pcap::Error::TimeoutExpired))].into_iter());
+        let (capture, _handle) = AsyncCapture::new(source);
+        assert!(matches!(
+            capture.next_packet().await,
+            Some(Err(Error::Timeout))
+        ));
+    }
+
+    #[tokio::test]
+    async fn retried_reads_are_counted_as_soft_errors() {
+        let source = IterSource::new(
+            [
+                Err(Error::Pcap(pcap::Error::PcapError("transient".into()))),
+                Ok(Packet::new(vec![0], UNIX_EPOCH)),
+            ]
+            .into_iter(),
+        );
+        let (errors, hook) = error_counter();
+        let options = CaptureOptions {
+            retry: Some(RetryPolicy {
+                max_retries: 1,
+                backoff: Duration::ZERO,
+            }),
+            on_error: Some(hook),
+            ..Default::default()
+        };
+        let (capture, handle, _) = AsyncCapture::with_options(source, options);
+        assert_eq!(capture.next_packet().await.unwrap().unwrap().data, [0]);
+        assert_eq!(handle.soft_errors(), 1);
+        assert_eq!(errors.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn adapter_stamped_packets_carry_the_host_time_apart() {
+        use crate::info::CaptureInfo;
+
+        let host = UNIX_EPOCH + Duration::from_secs(1_000);
+        let clock = ManualClock::starting_at(host);
+        let info = |timestamp_source| CaptureInfo {
+            snaplen: 65535,
+            datalink: pcap::Linktype::ETHERNET,
+            timestamp_source,
+            precision: pcap::Precision::Micro,
+        };
+        for (source, expected) in [
+            (TimestampSource::Adapter, Some(host)),
+            (TimestampSource::Host, None),
+        ] {
+            let options = CaptureOptions {
+                clock: Some(Arc::new(clock.clone())),
+                ..Default::default()
+            };
+            let (capture, _handle, _) =
+                AsyncCapture::with_options(packets(1).with_info(info(source)), options);
+            let (packet, meta) = capture.next_packet_meta().await.unwrap();
+            assert_eq!(packet.unwrap().timestamp(), UNIX_EPOCH);
+            assert_eq!(meta.host_timestamp, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn duplicates_do_not_count_towards_the_discarded_packets() {
+        let source = IterSource::new(
+            [0u32, 0, 1, 2, 3]
+                .into_iter()
+                .map(|n| Ok(Packet::new(n.to_be_bytes().to_vec(), UNIX_EPOCH))),
+        );
+        let options = CaptureOptions {
+            discard_first: 2,
+            dedup: Some(DedupMode::FullBytes {
+                window: Duration::from_secs(1),
+            }),
+            ..Default::default()
+        };
+        let (capture, handle, _) = AsyncCapture::with_options(source, options);
+        let mut forwarded = Vec::new();
+        while let Some(packet) = capture.next_packet().await {
+            forwarded.push(number(&packet.unwrap()));
+        }
+        assert_eq!(forwarded, [2, 3]);
+        assert_eq!(handle.duplicates_suppressed(), 1);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_is_enforced_in_clock_time() {
+        let clock = ManualClock::new();
+        let options = CaptureOptions {
+            rate_limit: Some(RateLimit::Packets(100)),
+            clock: Some(Arc::new(clock.clone())),
+            ..Default::default()
+        };
+        let (capture, handle, _) = AsyncCapture::with_options(packets(50), options);
+        let mut received = 0;
+        while let Some(packet) = capture.next_packet().await {
+            packet.unwrap();
+            received += 1;
+        }
+        assert_eq!(received, 50);
+        assert_eq!(handle.summary().unwrap().reason, EndReason::SourceEnded);
+        // A burst of 10, then 40 packets 10 ms apart
+        let elapsed = clock.elapsed();
+        assert!(
+            (Duration::from_millis(390)..=Duration::from_millis(410)).contains(&elapsed),
+            "{elapsed:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn queue_timing_is_read_from_the_clock() {
+        let clock = ManualClock::new();
+        let options = CaptureOptions {
+            timing: true,
+            clock: Some(Arc::new(clock.clone())),
+            ..Default::default()
+        };
+        let (capture, handle, _) = AsyncCapture::with_options(packets(2), options);
+        handle.wait_stopped().await;
+        clock.advance(Duration::from_millis(250));
+
+        let (_, timing) = capture.next_packet_timed().await.unwrap();
+        assert_eq!(timing.unwrap().dwell(), Duration::from_millis(250));
+        clock.advance(Duration::from_millis(50));
+        let (_, timing) = capture.next_packet_timed().await.unwrap();
+        assert_eq!(timing.unwrap().dwell(), Duration::from_millis(300));
+        let dwell = handle.queue_dwell();
+        assert_eq!(dwell.max, Duration::from_millis(300));
+        assert_eq!(dwell.packets, 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn waiting_consumer_gets_each_packet_within_a_millisecond() {
+        let options = CaptureOptions {
+            timing: true,
+            ..Default::default()
+        };
+        // Packets 2 ms apart, so the consumer is always waiting
+        let source = IterSource::new((0..100u8).map(|i| {
+            std::thread::sleep(Duration::from_millis(2));
+            Ok(Packet::new(vec![i], UNIX_EPOCH))
+        }));
+        let (capture, _handle, _) = AsyncCapture::with_options(source, options);
+        let mut dwells = Vec::new();
+        while let Some((packet, timing)) = capture.next_packet_timed().await {
+            packet.unwrap();
+            dwells.push(timing.unwrap().dwell());
+        }
+        dwells.sort_unstable();
+        let median = dwells[dwells.len() / 2];
+        assert!(median < Duration::from_millis(1), "{median:?}");
+    }
+
+    /// Ethernet frame carrying an IPv4 packet from `src` to `dst` with a
+    /// valid header checksum.
+    #[cfg(any(feature = "checksums", feature = "process-info"))]
+    fn ipv4_frame(protocol: u8, src: [u8; 4], dst: [u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0x02, 0, 0, 0, 0, 0x02, 0x02, 0, 0, 0, 0, 0x01, 0x08, 0x00];
+        let mut header = vec![0x45, 0];
+        header.extend(((20 + payload.len()) as u16).to_be_bytes());
+        header.extend([0, 1, 0, 0, 64, protocol, 0, 0]);
+        header.extend(src);
+        header.extend(dst);
+        let checksum = internet_checksum(&header);
+        header[10..12].copy_from_slice(&checksum.to_be_bytes());
+        frame.extend(header);
+        frame.extend(payload);
+        frame
+    }
+
+    #[cfg(any(feature = "checksums", feature = "process-info"))]
+    fn internet_checksum(data: &[u8]) -> u16 {
+        let mut sum: u32 = data
+            .chunks(2)
+            .map(|pair| u32::from(u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])))
+            .sum();
+        while sum > 0xffff {
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+        !(sum as u16)
+    }
+
+    #[cfg(feature = "checksums")]
+    #[tokio::test]
+    async fn checksums_are_attached_to_each_packet() {
+        use crate::checksum::ChecksumStatus;
+
+        let mut echo = vec![8, 0, 0, 0, 0, 1, 0, 1, b'p', b'i', b'n', b'g'];
+        let checksum = internet_checksum(&echo);
+        echo[2..4].copy_from_slice(&checksum.to_be_bytes());
+        let valid = ipv4_frame(1, [192, 0, 2, 1], [192, 0, 2, 2], &echo);
+        let mut corrupted = valid.clone();
+        *corrupted.last_mut().unwrap() ^= 0xff;
+        let source = IterSource::new(
+            [valid, corrupted, vec![0; 14]]
+                .into_iter()
+                .map(|data| Ok(Packet::new(data, UNIX_EPOCH))),
+        );
+        let options = CaptureOptions {
+            checksums: true,
+            ..Default::default()
+        };
+        let (capture, _handle, _) = AsyncCapture::with_options(source, options);
+
+        let mut statuses = Vec::new();
+        while let Some((packet, meta)) = capture.next_packet_meta().await {
+            packet.unwrap();
+            let checksums = meta.checksums.unwrap();
+            statuses.push((checksums.ip, checksums.transport));
+        }
+        assert_eq!(
+            statuses,
+            [
+                (ChecksumStatus::Valid, ChecksumStatus::Valid),
+                (ChecksumStatus::Valid, ChecksumStatus::Invalid),
+                (ChecksumStatus::NotApplicable, ChecksumStatus::NotApplicable),
+            ]
+        );
+    }
+
+    #[cfg(all(feature = "process-info", target_os = "linux"))]
+    #[tokio::test]
+    async fn packets_of_a_local_socket_are_attributed_to_its_process() {
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+        let mut datagram = Vec::new();
+        datagram.extend(port.to_be_bytes());
+        datagram.extend([0, 9, 0, 12, 0, 0, b'p', b'i', b'n', b'g']);
+        let ours = ipv4_frame(17, [127, 0, 0, 1], [127, 0, 0, 1], &datagram);
+        let mut foreign = ours.clone();
+        // From address 127.0.0.2, where the socket is not bound
+        foreign[29] = 2;
+        foreign[33] = 2;
+        // The tables are read off the capture thread, so the first packets
+        // may go by before the scan completes
+        let source = IterSource::new([ours, foreign].into_iter().cycle().map(|data| {
+            std::thread::sleep(Duration::from_millis(1));
+            Ok(Packet::new(data, UNIX_EPOCH))
+        }));
+        let options = CaptureOptions {
+            annotate_process: true,
+            ..Default::default()
+        };
+        let (capture, handle, _) = AsyncCapture::with_options(source, options);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let process = loop {
+            assert!(Instant::now() < deadline, "no packet is attributed");
+            let (packet, meta) = capture.next_packet_meta().await.unwrap();
+            if packet.unwrap().data[29] == 1
+                && let Some(process) = meta.process
+            {
+                break process;
+            }
+        };
+        assert_eq!(process.pid, std::process::id());
+        let (packet, meta) = capture.next_packet_meta().await.unwrap();
+        assert_eq!(packet.unwrap().data[29], 2);
+        assert_eq!(meta.process, None);
+        handle.stop();
+        drop(socket);
+    }
+
+    #[cfg(feature = "ring-file")]
+    #[tokio::test]
+    async fn delivered_packets_are_recorded_into_the_ring_file() {
+        use crate::ring_file::{RING_FLAG_FINALIZED, RingFileConfig};
+
+        let path = std::env::temp_dir().join(format!("async-pcap-ring-{}", std::process::id()));
+        let config = RingFileConfig::new(&path, 4096);
+        let ring = RingFile::create(
+            &config,
+            pcap::Linktype::ETHERNET,
+            65535,
+            pcap::Precision::Micro,
+        )
+        .unwrap();
+        let options = CaptureOptions {
+            ring: Some(Arc::new(Mutex::new(ring))),
+            ..Default::default()
+        };
+        let (capture, handle, _) = AsyncCapture::with_options(packets(3), options);
+        while capture.next_packet().await.is_some() {}
+        handle.wait_stopped().await;
+
+        let file = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let le32 = |at: usize| u32::from_le_bytes(file[at..at + 4].try_into().unwrap());
+        let le64 = |at: usize| u64::from_le_bytes(file[at..at + 8].try_into().unwrap());
+        assert_eq!(le32(20) & RING_FLAG_FINALIZED, RING_FLAG_FINALIZED);
+        assert_eq!(le64(48), 3);
+    }
+}
@@ -0,0 +1,104 @@
+//! Several tasks calling `next_packet()` on one shared capture, as
+//! documented under "Concurrent callers".
+#![cfg(feature = "rt-tokio")]
+
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use async_pcap::{AsyncCapture, Packet};
+
+const PACKETS: u32 = 5_000;
+const CALLERS: usize = 4;
+
+fn numbered(n: u32) -> Packet {
+    Packet::new(n.to_be_bytes().to_vec(), UNIX_EPOCH)
+}
+
+fn number(packet: &Packet) -> u32 {
+    u32::from_be_bytes(packet.data[..4].try_into().unwrap())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn each_packet_goes_to_exactly_one_caller_in_capture_order() {
+    let (capture, _handle) = AsyncCapture::from_packets((0..PACKETS).map(numbered));
+    let capture = Arc::new(capture);
+    let callers: Vec<_> = (0..CALLERS)
+        .map(|_| {
+            let capture = capture.clone();
+            tokio::spawn(async move {
+                let mut seen = Vec::new();
+                while let Some(packet) = capture.next_packet().await {
+                    seen.push(number(&packet.unwrap()));
+                }
+                seen
+            })
+        })
+        .collect();
+
+    let mut all = Vec::new();
+    for caller in callers {
+        let seen = caller.await.unwrap();
+        assert!(seen.windows(2).all(|pair| pair[0] < pair[1]));
+        all.extend(seen);
+    }
+    all.sort_unstable();
+    assert_eq!(all, (0..PACKETS).collect::<Vec<_>>());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn every_caller_gets_none_once_stopped() {
+    let (capture, handle) = AsyncCapture::from_packets((0..).map(numbered));
+    let capture = Arc::new(capture);
+    let callers: Vec<_> = (0..CALLERS)
+        .map(|_| {
+            let capture = capture.clone();
+            tokio::spawn(async move { while capture.next_packet().await.is_some() {} })
+        })
+        .collect();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    handle.stop();
+
+    for caller in callers {
+        tokio::time::timeout(Duration::from_secs(5), caller)
+            .await
+            .expect("the caller sees the end")
+            .unwrap();
+    }
+    assert!(capture.next_packet().await.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn cancelled_callers_lose_no_packet() {
+    let source = (0..PACKETS).map(|n| {
+        if n % 500 == 0 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        numbered(n)
+    });
+    let (capture, _handle) = AsyncCapture::from_packets(source);
+    let capture = Arc::new(capture);
+    let callers: Vec<_> = (0..CALLERS)
+        .map(|_| {
+            let capture = capture.clone();
+            tokio::spawn(async move {
+                let mut seen = Vec::new();
+                loop {
+                    // Most waits are cut short while the source sleeps
+                    let wait = Duration::from_micros(50);
+                    match tokio::time::timeout(wait, capture.next_packet()).await {
+                        Ok(Some(packet)) => seen.push(number(&packet.unwrap())),
+                        Ok(None) => return seen,
+                        Err(_) => continue,
+                    }
+                }
+            })
+        })
+        .collect();
+
+    let mut all = Vec::new();
+    for caller in callers {
+        all.extend(caller.await.unwrap());
+    }
+    all.sort_unstable();
+    assert_eq!(all, (0..PACKETS).collect::<Vec<_>>());
+}
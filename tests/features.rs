@@ -0,0 +1,568 @@
+//! End-to-end tests of the optional features, each driving a capture from
+//! an `IterSource` on a `ManualClock`.
+#![cfg(feature = "rt-tokio")]
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use async_pcap::{AsyncCapture, AsyncCaptureHandle, IterSource, ManualClock, Packet};
+
+/// Captured at one second past the epoch, one microsecond apart.
+fn packet(n: u32, data: Vec<u8>) -> Packet {
+    let ts = UNIX_EPOCH + Duration::from_secs(1) + Duration::from_micros(u64::from(n));
+    Packet::new(data, ts)
+}
+
+/// Packet builders; each is used by only some of the features.
+#[allow(dead_code)]
+mod support {
+    use super::*;
+
+    pub const SRC: [u8; 4] = [192, 0, 2, 1];
+    pub const DST: [u8; 4] = [192, 0, 2, 2];
+    pub const IPPROTO_TCP: u8 = 6;
+    pub const IPPROTO_UDP: u8 = 17;
+    /// More-fragments flag of the IPv4 fragment field.
+    pub const MORE_FRAGMENTS: u16 = 0x2000;
+
+    /// Capture of `packets` on a clock that never moves by itself.
+    pub fn capture<I>(packets: I) -> (AsyncCapture, AsyncCaptureHandle)
+    where
+        I: IntoIterator<Item = Packet>,
+        I::IntoIter: Send + 'static,
+    {
+        let source = IterSource::new(packets.into_iter().map(Ok));
+        AsyncCapture::new_with_clock(source, ManualClock::new())
+    }
+
+    /// Ethernet frame carrying an IPv4 packet with a valid header checksum.
+    ///
+    /// `fragment` is the raw flags and fragment offset field.
+    pub fn ipv4(protocol: u8, id: u16, fragment: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0x02, 0, 0, 0, 0, 0x02, 0x02, 0, 0, 0, 0, 0x01, 0x08, 0x00];
+        let total = (20 + payload.len()) as u16;
+        let mut header = vec![0x45, 0];
+        header.extend(total.to_be_bytes());
+        header.extend(id.to_be_bytes());
+        header.extend(fragment.to_be_bytes());
+        header.extend([64, protocol, 0, 0]);
+        header.extend(SRC);
+        header.extend(DST);
+        let checksum = internet_checksum(&header);
+        header[10..12].copy_from_slice(&checksum.to_be_bytes());
+        frame.extend(header);
+        frame.extend(payload);
+        frame
+    }
+
+    /// TCP segment without options; the checksum is left at zero.
+    pub fn tcp(src_port: u16, dst_port: u16, seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let mut segment = Vec::new();
+        segment.extend(src_port.to_be_bytes());
+        segment.extend(dst_port.to_be_bytes());
+        segment.extend(seq.to_be_bytes());
+        segment.extend(0u32.to_be_bytes());
+        segment.extend([5 << 4, flags, 0xff, 0xff, 0, 0, 0, 0]);
+        segment.extend(payload);
+        segment
+    }
+
+    /// UDP datagram without a checksum.
+    pub fn udp(src_port: u16, dst_port: u16, payload: &[u8]) -> Vec<u8> {
+        let mut datagram = Vec::new();
+        datagram.extend(src_port.to_be_bytes());
+        datagram.extend(dst_port.to_be_bytes());
+        datagram.extend(((8 + payload.len()) as u16).to_be_bytes());
+        datagram.extend([0, 0]);
+        datagram.extend(payload);
+        datagram
+    }
+
+    pub fn internet_checksum(data: &[u8]) -> u16 {
+        let mut sum: u32 = data
+            .chunks(2)
+            .map(|pair| u32::from(u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])))
+            .sum();
+        while sum > 0xffff {
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+        !(sum as u16)
+    }
+}
+
+#[tokio::test]
+async fn throughput_is_measured_in_clock_time() {
+    let clock = ManualClock::new();
+    let ticks = clock.clone();
+    // 50 packets of 100 bytes, one every 10 ms of clock time
+    let source = IterSource::new((0..50).map(move |n| {
+        ticks.advance(Duration::from_millis(10));
+        Ok(packet(n, vec![0; 100]))
+    }));
+    let (capture, handle) = AsyncCapture::new_with_clock(source, clock.clone());
+    while capture.next_packet().await.is_some() {}
+
+    let rate = handle.throughput();
+    assert!((rate.pps - 100.0).abs() < 1.0, "{}", rate.pps);
+    assert!((rate.bps - 80_000.0).abs() < 800.0, "{}", rate.bps);
+
+    clock.advance(Duration::from_secs(2));
+    assert_eq!(handle.throughput().pps, 0.0);
+}
+
+#[cfg(feature = "bytes")]
+#[tokio::test]
+async fn bytes_packets_carry_the_captured_data() {
+    let source = IterSource::new((0..3u8).map(|n| Ok(packet(n.into(), vec![n; 4]))));
+    let (capture, _handle) = AsyncCapture::with_buffer_pool(source, 2);
+    for n in 0..3u8 {
+        let packet = capture.next_bytes_packet().await.unwrap().unwrap();
+        assert_eq!(&packet.data[..], [n; 4]);
+        assert_eq!(packet.header.caplen, 4);
+    }
+    assert!(capture.next_bytes_packet().await.is_none());
+}
+
+#[cfg(feature = "cancellation")]
+#[tokio::test]
+async fn cancelling_the_token_stops_the_capture() {
+    use async_pcap::EndReason;
+    use tokio_util::sync::CancellationToken;
+
+    let source = IterSource::new((0..).map(|n| {
+        std::thread::sleep(Duration::from_millis(1));
+        Ok(packet(n, vec![0; 4]))
+    }));
+    let (capture, handle) = AsyncCapture::new_with_clock(source, ManualClock::new());
+    let token = CancellationToken::new();
+    handle.cancel_on(token.child_token());
+
+    capture.next_packet().await.unwrap().unwrap();
+    token.cancel();
+    let drained = tokio::time::timeout(Duration::from_secs(5), async {
+        while capture.next_packet().await.is_some() {}
+    });
+    drained.await.expect("the stream ends once cancelled");
+    assert_eq!(handle.wait_stopped().await.reason, EndReason::Stopped);
+}
+
+#[cfg(feature = "ip-defrag")]
+#[tokio::test]
+async fn fragments_are_reassembled_from_the_capture() {
+    use async_pcap::IpDefragmenter;
+
+    use support::*;
+
+    let datagram = udp(5000, 53, &[0xab; 24]);
+    let (capture, _handle) = capture([
+        // Second half first
+        packet(0, ipv4(IPPROTO_UDP, 7, 2, &datagram[16..])),
+        packet(1, ipv4(IPPROTO_UDP, 7, MORE_FRAGMENTS, &datagram[..16])),
+        packet(2, ipv4(IPPROTO_UDP, 8, 0, &datagram)),
+    ]);
+    let mut defrag = IpDefragmenter::new(capture.datalink());
+
+    let reassembled = defrag.next(&capture).await.unwrap().unwrap();
+    assert_eq!(reassembled.data, ipv4(IPPROTO_UDP, 7, 0, &datagram));
+    let whole = defrag.next(&capture).await.unwrap().unwrap();
+    assert_eq!(whole.data, ipv4(IPPROTO_UDP, 8, 0, &datagram));
+    assert!(defrag.next(&capture).await.is_none());
+    assert_eq!(defrag.reassembled(), 1);
+}
+
+#[cfg(feature = "metrics")]
+#[tokio::test]
+async fn metrics_are_published_to_the_installed_recorder() {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use metrics::{
+        Counter, Gauge, Histogram, Key, KeyName, Label, Metadata, Recorder, SharedString, Unit,
+    };
+
+    use support::*;
+
+    /// Recorder keeping the last value of each counter and gauge by name.
+    #[derive(Default)]
+    struct Values(Mutex<HashMap<String, Arc<AtomicU64>>>);
+
+    impl Values {
+        fn cell(&self, key: &Key) -> Arc<AtomicU64> {
+            let mut values = self.0.lock().unwrap();
+            values.entry(key.name().to_owned()).or_default().clone()
+        }
+
+        fn get(&self, name: &str) -> Option<u64> {
+            let values = self.0.lock().unwrap();
+            values.get(name).map(|cell| cell.load(Ordering::Relaxed))
+        }
+    }
+
+    impl Recorder for Values {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            Counter::from_arc(self.cell(key))
+        }
+
+        fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::from_arc(self.cell(key))
+        }
+
+        fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::noop()
+        }
+    }
+
+    let (capture, handle) = capture((0..3).map(|n| packet(n, vec![0; 10])));
+    while capture.next_packet().await.is_some() {}
+
+    let values = Values::default();
+    let labels = vec![Label::new("device", "test")];
+    metrics::with_local_recorder(&values, || {
+        // Raised to the shortest interval rather than spinning
+        handle.export_metrics(Duration::ZERO, labels)
+    });
+    let published = (0..500).any(|_| {
+        std::thread::sleep(Duration::from_millis(10));
+        values.get("pcap_packets_received_total") == Some(3)
+    });
+    assert!(published);
+    assert_eq!(values.get("pcap_bytes_received_total"), Some(30));
+    assert_eq!(values.get("pcap_packets_overflow_dropped_total"), Some(0));
+    assert_eq!(values.get("pcap_soft_errors_total"), Some(0));
+    assert!(values.get("pcap_uptime_seconds").is_some());
+}
+
+#[cfg(feature = "parse")]
+#[tokio::test]
+async fn views_parse_the_captured_packets() {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use support::*;
+
+    let (capture, _handle) = capture([
+        packet(0, ipv4(IPPROTO_TCP, 1, 0, &tcp(40000, 443, 1, 0x18, b"hi"))),
+        packet(1, ipv4(IPPROTO_UDP, 2, 0, &udp(5000, 53, b"query"))),
+    ]);
+    let datalink = capture.datalink();
+
+    let first = capture.next_packet().await.unwrap().unwrap();
+    let ip = first.ip(datalink).unwrap();
+    assert_eq!(ip.src(), IpAddr::V4(Ipv4Addr::from(SRC)));
+    assert_eq!(ip.protocol(), IPPROTO_TCP);
+    let segment = first.tcp(datalink).unwrap();
+    assert_eq!((segment.src_port(), segment.dst_port()), (40000, 443));
+    assert_eq!(segment.payload(), b"hi");
+    assert!(first.udp(datalink).is_none());
+
+    let second = capture.next_packet().await.unwrap().unwrap();
+    let datagram = second.udp(datalink).unwrap();
+    assert_eq!(datagram.dst_port(), 53);
+    assert_eq!(datagram.payload(), b"query");
+}
+
+#[cfg(feature = "prometheus")]
+#[tokio::test]
+async fn prometheus_registry_reports_the_capture_counters() {
+    use std::collections::HashMap;
+
+    use prometheus::{Encoder, Registry, TextEncoder};
+
+    use support::*;
+
+    let (capture, handle) = capture((0..4).map(|n| packet(n, vec![0; 10])));
+    let registry = Registry::new();
+    let labels = HashMap::from([("device".to_owned(), "test".to_owned())]);
+    handle.register_metrics(&registry, labels).unwrap();
+    while capture.next_packet().await.is_some() {}
+
+    let mut text = Vec::new();
+    TextEncoder::new()
+        .encode(&registry.gather(), &mut text)
+        .unwrap();
+    let text = String::from_utf8(text).unwrap();
+    assert!(
+        text.contains("pcap_packets_received_total{device=\"test\"} 4"),
+        "{text}"
+    );
+    assert!(
+        text.contains("pcap_bytes_received_total{device=\"test\"} 40"),
+        "{text}"
+    );
+    assert!(
+        text.contains("pcap_queue_depth{device=\"test\"} 0"),
+        "{text}"
+    );
+    assert!(
+        text.contains("pcap_soft_errors_total{device=\"test\"} 0"),
+        "{text}"
+    );
+}
+
+#[cfg(feature = "radiotap")]
+#[tokio::test]
+async fn radiotap_headers_are_parsed_on_a_wireless_capture() {
+    use async_pcap::{CaptureInfo, FrameType, Linktype, Precision, TimestampSource};
+
+    // Radiotap carrying the rate (6 Mbit/s) and the channel (2437 MHz)
+    let mut frame = vec![0, 0, 14, 0];
+    frame.extend((1u32 << 2 | 1 << 3).to_le_bytes());
+    frame.extend([12, 0]);
+    frame.extend(2437u16.to_le_bytes());
+    frame.extend(0x00a0u16.to_le_bytes());
+    // Data frame to the distribution system, sequence number 5
+    frame.extend([0x08, 0x01, 0, 0]);
+    frame.extend([0x02, 0, 0, 0, 0, 0x01]);
+    frame.extend([0x02, 0, 0, 0, 0, 0x02]);
+    frame.extend([0x02, 0, 0, 0, 0, 0x03]);
+    frame.extend([0x50, 0x00]);
+    let info = CaptureInfo {
+        snaplen: 65535,
+        datalink: Linktype::IEEE802_11_RADIOTAP,
+        timestamp_source: TimestampSource::Host,
+        precision: Precision::Micro,
+    };
+    let source = IterSource::new([Ok(packet(0, frame))]).with_info(info);
+    let (capture, _handle) = AsyncCapture::new_with_clock(source, ManualClock::new());
+    let datalink = capture.datalink();
+
+    let packet = capture.next_packet().await.unwrap().unwrap();
+    let radiotap = packet.radiotap(datalink).unwrap();
+    assert_eq!(radiotap.len, 14);
+    assert_eq!(radiotap.rate_mbps(), Some(6.0));
+    assert_eq!(radiotap.channel.unwrap().number(), Some(6));
+    let header = packet.ieee80211(datalink).unwrap();
+    assert_eq!(header.frame_type, FrameType::Data);
+    assert!(header.to_ds() && !header.from_ds());
+    assert_eq!(header.addr3, Some([0x02, 0, 0, 0, 0, 0x03]));
+    assert_eq!(header.sequence, Some(5));
+    assert!(packet.radiotap(Linktype::ETHERNET).is_none());
+}
+
+#[cfg(feature = "remote")]
+#[test]
+fn remote_builder_records_the_rpcap_url() {
+    use async_pcap::RemoteAuth;
+
+    let builder = AsyncCapture::remote("appliance:2002", "eth1", RemoteAuth::Null);
+    let config = builder.config();
+    assert_eq!(
+        config.device.as_deref(),
+        Some("rpcap://appliance:2002/eth1")
+    );
+}
+
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn exported_records_deserialize_into_the_captured_packets() {
+    use async_pcap::ExportFormat;
+
+    use support::*;
+
+    let packets: Vec<_> = (0..3u8)
+        .map(|n| packet(u32::from(n) * 250_000, vec![n; 8]))
+        .collect();
+    let (capture, _handle) = capture(packets.clone());
+    let mut out = Vec::new();
+    let written = capture
+        .export(&mut out, ExportFormat::Ndjson)
+        .await
+        .unwrap();
+    assert_eq!(written, 3);
+
+    let text = String::from_utf8(out).unwrap();
+    let decoded: Vec<Packet> = text
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(decoded.len(), packets.len());
+    for (decoded, original) in decoded.iter().zip(&packets) {
+        assert_eq!(decoded.header, original.header);
+        assert_eq!(decoded.data, original.data);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn exported_timestamps_are_nanoseconds_whatever_the_precision() {
+    use async_pcap::{CaptureInfo, ExportFormat, TimestampSource};
+    use pcap::{Linktype, Precision};
+
+    let mut packet = Packet::new(vec![0; 4], UNIX_EPOCH);
+    packet.header.ts.tv_sec = 1;
+    packet.header.ts.tv_usec = 123_456_789;
+    let info = CaptureInfo {
+        snaplen: 65535,
+        datalink: Linktype::ETHERNET,
+        timestamp_source: TimestampSource::Host,
+        precision: Precision::Nano,
+    };
+    let source = IterSource::new([Ok(packet.clone())]).with_info(info);
+    let (capture, _handle) = AsyncCapture::new_with_clock(source, ManualClock::new());
+    let mut out = Vec::new();
+    capture
+        .export(&mut out, ExportFormat::Ndjson)
+        .await
+        .unwrap();
+
+    let record: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(record["seconds"], 1);
+    assert_eq!(record["nanos"], 123_456_789);
+    let decoded: Packet = serde_json::from_slice(&out).unwrap();
+    assert_eq!(
+        decoded.timestamp(),
+        UNIX_EPOCH + Duration::new(1, 123_456_000)
+    );
+    // A packet serialized on its own is read as microseconds
+    packet.header.ts.tv_usec = 250_000;
+    let record = serde_json::to_value(&packet).unwrap();
+    assert_eq!(record["nanos"], 250_000_000);
+}
+
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn jsonl_timestamps_are_microseconds_whatever_the_precision() {
+    use async_pcap::{CaptureInfo, PayloadEncoding, TimestampSource};
+    use pcap::{Linktype, Precision};
+
+    let mut packet = Packet::new(vec![0; 4], UNIX_EPOCH);
+    packet.header.ts.tv_usec = 123_456_789;
+    let info = CaptureInfo {
+        snaplen: 65535,
+        datalink: Linktype::ETHERNET,
+        timestamp_source: TimestampSource::Host,
+        precision: Precision::Nano,
+    };
+    let source = IterSource::new([Ok(packet)]).with_info(info);
+    let (capture, _handle) = AsyncCapture::new_with_clock(source, ManualClock::new());
+    let mut out = Vec::new();
+    capture
+        .into_jsonl_writer(&mut out, PayloadEncoding::Omit)
+        .await
+        .unwrap();
+
+    let record: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(record["ts_usec"], 123_456);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn invalid_configs_are_rejected_on_load() {
+    use async_pcap::CaptureConfig;
+
+    let config = AsyncCapture::builder("eth0").snaplen(1500).config();
+    let text = serde_json::to_string(&config).unwrap();
+    assert_eq!(
+        serde_json::from_str::<CaptureConfig>(&text).unwrap(),
+        config
+    );
+
+    let error = serde_json::from_str::<CaptureConfig>(r#"{"snaplen": 0}"#).unwrap_err();
+    assert!(error.to_string().contains("snaplen 0"), "{error}");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn profile_settings_round_trip_through_a_saved_config() {
+    use async_pcap::{CaptureConfig, CaptureProfile};
+
+    let config = AsyncCapture::builder("eth0")
+        .profile(CaptureProfile::HighThroughput)
+        .config();
+    assert_eq!(config.read_batch, 64);
+    let text = serde_json::to_string(&config).unwrap();
+    assert_eq!(
+        serde_json::from_str::<CaptureConfig>(&text).unwrap(),
+        config
+    );
+}
+
+#[cfg(feature = "sink")]
+#[tokio::test]
+async fn packets_are_forwarded_into_a_sink() {
+    use std::convert::Infallible;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use futures_sink::Sink;
+
+    use support::*;
+
+    /// Sink keeping the first byte of every packet, closed at the end.
+    #[derive(Default)]
+    struct Collect {
+        firsts: Vec<u8>,
+        closed: bool,
+    }
+
+    impl Sink<Packet> for Collect {
+        type Error = Infallible;
+
+        fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, packet: Packet) -> Result<(), Infallible> {
+            self.firsts.push(packet.data[0]);
+            Ok(())
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<(), Infallible>> {
+            self.closed = true;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    let (capture, _handle) = capture((0..5u8).map(|n| packet(n.into(), vec![n])));
+    let mut sink = Collect::default();
+    let forwarded = capture.forward_to(&mut sink).await.unwrap();
+    assert_eq!(forwarded, 5);
+    assert_eq!(sink.firsts, [0, 1, 2, 3, 4]);
+    assert!(sink.closed);
+}
+
+#[cfg(feature = "tcp-reassembly")]
+#[tokio::test]
+async fn tcp_stream_is_reassembled_from_the_capture() {
+    use async_pcap::{FlowEvent, TcpReassembler};
+
+    use support::*;
+
+    const SYN: u8 = 0x02;
+    const ACK: u8 = 0x10;
+    let (capture, _handle) = capture([
+        packet(0, ipv4(IPPROTO_TCP, 1, 0, &tcp(40000, 80, 1000, SYN, b""))),
+        // The second segment overtakes the first
+        packet(
+            1,
+            ipv4(IPPROTO_TCP, 2, 0, &tcp(40000, 80, 1008, ACK, b"world")),
+        ),
+        packet(
+            2,
+            ipv4(IPPROTO_TCP, 3, 0, &tcp(40000, 80, 1001, ACK, b"hello, ")),
+        ),
+    ]);
+    let mut reassembler = TcpReassembler::new(capture.datalink());
+
+    let mut stream = Vec::new();
+    let mut opened = None;
+    while let Some(event) = reassembler.next_event(&capture).await {
+        match event.unwrap() {
+            FlowEvent::Opened { key, syn } => opened = Some((key.src_port, key.dst_port, syn)),
+            FlowEvent::Data { data, .. } => stream.extend_from_slice(&data),
+            _ => {}
+        }
+    }
+    assert_eq!(opened, Some((40000, 80, true)));
+    assert_eq!(stream, b"hello, world");
+}
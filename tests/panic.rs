@@ -0,0 +1,29 @@
+//! A panic on the capture thread reaches the consumer as an error.
+#![cfg(feature = "rt-tokio")]
+
+use std::time::UNIX_EPOCH;
+
+use async_pcap::{AsyncCapture, EndReason, Error, ErrorKind, IterSource, Packet};
+
+#[tokio::test]
+async fn panicking_source_ends_the_stream_with_the_panic() {
+    let source = IterSource::new((0..5u8).map(|i| {
+        if i == 3 {
+            panic!("source failed at {i}");
+        }
+        Ok(Packet::new(vec![i], UNIX_EPOCH))
+    }));
+    let (capture, handle) = AsyncCapture::new(source);
+
+    for i in 0..3 {
+        assert_eq!(capture.next_packet().await.unwrap().unwrap().data, [i]);
+    }
+    let e = capture.next_packet().await.unwrap().unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::Panicked);
+    assert!(matches!(&e, Error::CapturePanicked(msg) if msg == "source failed at 3"));
+    assert!(capture.next_packet().await.is_none());
+
+    let summary = handle.wait_stopped().await;
+    assert_eq!(summary.reason, EndReason::Panicked);
+    assert!(!handle.is_running());
+}
@@ -0,0 +1,77 @@
+//! Stress tests of the ordering and shutdown guarantees documented in the
+//! threading model of `AsyncCapture`.
+#![cfg(feature = "rt-tokio")]
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use async_pcap::{AsyncCapture, EndReason, Packet};
+
+/// Runs of each test, to give races a chance to show.
+const ROUNDS: usize = 50;
+
+fn numbered(n: u32) -> Packet {
+    Packet::new(n.to_be_bytes().to_vec(), UNIX_EPOCH)
+}
+
+fn number(packet: &Packet) -> u32 {
+    u32::from_be_bytes(packet.data[..4].try_into().unwrap())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn packets_arrive_in_read_order() {
+    for _ in 0..ROUNDS {
+        let (capture, _handle) = AsyncCapture::from_packets((0..2_000).map(numbered));
+        let mut expected = 0;
+        while let Some(packet) = capture.next_packet().await {
+            assert_eq!(number(&packet.unwrap()), expected);
+            expected += 1;
+        }
+        assert_eq!(expected, 2_000);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn thread_is_stopped_once_the_stream_ends() {
+    for _ in 0..ROUNDS {
+        let (capture, handle) = AsyncCapture::from_packets((0..10).map(numbered));
+        while capture.next_packet().await.is_some() {}
+        assert!(!handle.is_running());
+        assert_eq!(handle.summary().unwrap().reason, EndReason::SourceEnded);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn stop_from_another_thread_ends_the_stream() {
+    for round in 0..ROUNDS {
+        let (capture, handle) = AsyncCapture::from_packets((0..).map(numbered));
+        let stopper = handle.clone();
+        let delay = Duration::from_micros(100 * (round as u64 % 10));
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(delay);
+            stopper.stop();
+        });
+        let mut expected = 0;
+        while let Some(packet) = capture.next_packet().await {
+            assert_eq!(number(&packet.unwrap()), expected);
+            expected += 1;
+        }
+        thread.join().unwrap();
+        assert!(!handle.is_running());
+        assert_eq!(handle.summary().unwrap().reason, EndReason::Stopped);
+        assert_eq!(capture.next_packet().await.map(|_| ()), None);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn dropping_the_capture_ends_the_thread() {
+    for _ in 0..ROUNDS {
+        let (capture, handle) = AsyncCapture::from_packets((0..).map(numbered));
+        capture.next_packet().await.unwrap().unwrap();
+        drop(capture);
+        let summary = tokio::time::timeout(Duration::from_secs(5), handle.wait_stopped())
+            .await
+            .expect("the capture thread exits");
+        assert_eq!(summary.reason, EndReason::ReceiverDropped);
+        assert!(!handle.is_running());
+    }
+}
@@ -0,0 +1,103 @@
+//! The capture thread logs inside its `capture` span.
+#![cfg(all(feature = "rt-tokio", feature = "tracing"))]
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::UNIX_EPOCH;
+
+use async_pcap::{AsyncCapture, IterSource, ManualClock, Packet};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+/// Subscriber recording each event with the span it was emitted in.
+#[derive(Default)]
+struct Events {
+    next_id: AtomicU64,
+    spans: Mutex<HashMap<u64, &'static str>>,
+    /// Innermost span name and message of every event
+    seen: Mutex<Vec<(Option<&'static str>, String)>>,
+}
+
+thread_local! {
+    /// Spans entered on the current thread, innermost last.
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{value:?}");
+        }
+    }
+}
+
+impl Subscriber for Events {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        self.spans
+            .lock()
+            .unwrap()
+            .insert(id, span.metadata().name());
+        Id::from_u64(id)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut message = Message(String::new());
+        event.record(&mut message);
+        let span = ENTERED.with(|entered| entered.borrow().last().copied());
+        let name = span.and_then(|id| self.spans.lock().unwrap().get(&id).copied());
+        self.seen.lock().unwrap().push((name, message.0));
+    }
+
+    fn enter(&self, span: &Id) {
+        ENTERED.with(|entered| entered.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, _: &Id) {
+        ENTERED.with(|entered| entered.borrow_mut().pop());
+    }
+}
+
+fn events() -> &'static Arc<Events> {
+    static EVENTS: OnceLock<Arc<Events>> = OnceLock::new();
+    EVENTS.get_or_init(|| {
+        let events = Arc::new(Events::default());
+        tracing::subscriber::set_global_default(events.clone()).unwrap();
+        events
+    })
+}
+
+#[tokio::test]
+async fn capture_thread_events_are_emitted_in_the_capture_span() {
+    let events = events();
+    let source = IterSource::new((0..3u8).map(|i| {
+        if i == 2 {
+            panic!("source failed at {i}");
+        }
+        Ok(Packet::new(vec![i], UNIX_EPOCH))
+    }));
+    let (capture, handle) = AsyncCapture::new_with_clock(source, ManualClock::new());
+    while capture.next_packet().await.is_some() {}
+    handle.wait_stopped().await;
+
+    let seen = events.seen.lock().unwrap();
+    let panicked = seen
+        .iter()
+        .find(|(_, message)| message.contains("thread panicked: source failed at 2"))
+        .expect("the panic is logged");
+    assert_eq!(panicked.0, Some("capture"));
+}